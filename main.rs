@@ -1,30 +1,36 @@
 mod modules;
 
-use modules::config::Config;
+use modules::config::{BootBehavior, Config};
 use modules::web;
-use modules::gpio::RelayController;
-use modules::lightControl;
-use modules::ledStrip::{LEDController, update_leds};
+use modules::gpio::{GpioInitError, RelayController, RelayEventLog, RelayType};
+use modules::lightControl::{self, RelayStates};
+use modules::ledStrip::{ChannelCaps, LEDController, LedAlert, PowerModel, update_leds};
 use modules::storage;
+use modules::models::{RelayReason, Schedule};
 use modules::getData::{self, CurrentReadings};
 use modules::logs;
-use modules::cam::CameraService;
+use modules::watchdog;
+use modules::simulation;
+use modules::cam::{self, CameraService};
 use std::error::Error;
 use std::net::SocketAddr;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::task;
 use axum::{
     extract::State,
-    response::{sse::{Sse, Event}, IntoResponse},
+    body::StreamBody,
+    response::IntoResponse,
     routing::get,
     Router,
-    http::StatusCode,
+    http::{header, StatusCode},
 };
 use futures::stream::{self, Stream};
 use std::convert::Infallible;
 use tokio_stream::StreamExt;
 use std::time::Duration;
+use chrono::NaiveDateTime;
 
 /// Main entry point
 ///
@@ -33,11 +39,13 @@ use std::time::Duration;
 /// - Initializes the database connection
 /// - Sets up the relay controller for device control
 /// - Initializes the light and LED controllers
+/// - Brings the loads up one at a time as `[main] boot_behavior` asks (see `staggered_startup`)
 /// - Sets up the camera service
 /// - Starts background tasks for:
 ///   - Sensor data collection
 ///   - Light control based on schedule
 ///   - LED control based on schedule
+///   - Away mode snapshots
 ///   - Camera streaming server
 ///   - Web server for the control interface
 ///
@@ -48,48 +56,97 @@ use std::time::Duration;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Load the configuration from the config.toml file
-    let config = Arc::new(Config::load()?);
+    let config = Arc::new(Config::load("config.toml")?);
     println!("Configuration loaded successfully: {:?}", config);
+    logs::init(&config.logging);
+    storage::set_write_retries(config.db.write_retries());
 
-    // Initialize database connection
-    let db_pool = Arc::new(storage::initialize_db().await?);
+    // Initialize database connection, or run degraded without it as `[db] on_unavailable` asks
+    let (db_pool, degraded) = storage::open_db(&config.db).await?;
+    let db_pool = Arc::new(db_pool);
+    
+    if let Some(reason) = degraded {
+        storage::set_degraded(reason.clone());
+        logs::log_or_warn(&db_pool, "ERROR", &format!("{}, running degraded: schedule from config.toml, logs to file only, nothing is saved", reason)).await;
+    }
     
     // Log system startup
-    logs::log(&db_pool, "INFO", "Terrarium Controller system starting up").await?;
+    logs::log_or_warn(&db_pool, "INFO", "Terrarium Controller system starting up").await;
+    
+    // A fresh database gets the def_* weeks, so the schedule page has weeks to edit
+    if storage::degraded().is_none() && config.db.seed_schedule() {
+        match storage::retry_busy(|| storage::seed_schedule(&db_pool, &config.db)).await {
+            Ok(0) => {}
+            Ok(weeks) => logs::log_or_warn(&db_pool, "INFO", &format!("Schedule was empty, filled {} weeks from the config defaults", weeks)).await,
+            Err(e) => logs::log_or_warn(&db_pool, "ERROR", &format!("Failed to fill the empty schedule: {}", e)).await,
+        }
+    }
+    
+    // Read the last relay states before anything is switched and logged again
+    let boot_behavior = config.main.boot_behavior;
+    let last_states = storage::last_relay_states(&db_pool).await?;
+    let boot_hold = Arc::new(Mutex::new(boot_behavior.holds_control_loops()));
+    
+    // Start the relay audit log; both controllers report their state changes to it
+    let (relay_event_log, relay_events) = RelayEventLog::channel();
+    task::spawn(storage::write_relay_events((*db_pool).clone(), relay_events));
     
-    // Initialize the relay controller
-    let relay_controller = Arc::new(Mutex::new(
-        RelayController::new().expect("Failed to initialize relay controller")
-    ));
+    // Roll readings up into hourly averages and prune old raw rows
+    task::spawn(watchdog::supervise(Arc::clone(&db_pool), "history", config.main.restart_backoff(), {
+        let db_pool = Arc::clone(&db_pool);
+        let storage_days = config.get_data.storage_days;
+        move || storage::maintain_sensor_history((*db_pool).clone(), storage_days)
+    }));
     
-    // Create a light controller
-    let light_controller = Arc::new(Mutex::new(
-        lightControl::LightController::new(config.light_control.clone())
-            .expect("Failed to initialize light controller")
-    ));
+    // Initialize the relay controller. Without working relays the API and logs
+    // still come up, with the relays reported unavailable.
+    let mut relays = hardware_or_unavailable(&db_pool, "relay controller", RelayController::new(), RelayController::unavailable).await;
+    if relays.is_available() {
+        relays.set_event_log(relay_event_log.clone());
+    }
+    let relay_controller = Arc::new(Mutex::new(relays));
+    
+    // Create a light controller, likewise kept running without its pins
+    let mut lights = hardware_or_unavailable(
+        &db_pool,
+        "light controller",
+        lightControl::LightController::new(config.light_control.clone(), &config.gpio),
+        || lightControl::LightController::unavailable(config.light_control.clone()),
+    ).await;
+    if lights.is_available() {
+        lights.set_event_log(relay_event_log);
+    }
+    // A heat watchdog trip that wasn't resumed keeps the lamp cut across the restart
+    if storage::heat_watchdog_tripped(&db_pool).await? {
+        lights.restore_heat_watchdog_trip();
+        logs::log_or_warn(&db_pool, "WARNING", "Heat watchdog still tripped from before the restart, heat cut until resumed").await;
+    }
+    let light_controller = Arc::new(Mutex::new(lights));
     
     // Create an LED controller that uses the relay controller
-    let led_controller = Arc::new(Mutex::new(
-        LEDController::new(Arc::clone(&relay_controller))
-    ));
+    let mut leds = LEDController::new(Arc::clone(&relay_controller));
+    leds.set_power_model(PowerModel::new(&config.led.power, config.gpio.ic_count()));
+    leds.set_color_order(config.led.color_order());
+    leds.set_channel_caps(ChannelCaps(config.led.channel_caps()));
+    leds.set_wiring(config.led.wiring());
+    let led_controller = Arc::new(Mutex::new(leds));
     
-    // Initialize the LED controller
-    {
-        let mut led_ctrl = led_controller.lock().await;
-        if let Err(e) = led_ctrl.initialize().await {
-            eprintln!("Warning: Failed to initialize LED controller: {:?}", e);
-            logs::log(&db_pool, "WARNING", &format!("Failed to initialize LED controller: {:?}", e)).await?;
-        }
+    // Load persisted runtime settings and restore away mode from them
+    let settings = storage::load_settings(&db_pool).await?;
+    let away = settings[storage::AWAY_MODE_KEY] == "true";
+    let away_mode = Arc::new(Mutex::new(away));
+    if away {
+        logs::log_or_warn(&db_pool, "INFO", "Away mode is on").await;
     }
     
     // Create a shared state for current sensor readings
     let current_readings = Arc::new(Mutex::new(CurrentReadings::new()));
 
     // Initialize the camera service
-    let camera_service = Arc::new(CameraService::new());
+    let camera_service = Arc::new(CameraService::with_snapshot_ttl(config.cam.snapshot_ttl()));
     if let Err(e) = camera_service.initialize().await {
         eprintln!("Warning: Failed to initialize camera: {:?}", e);
-        logs::log(&db_pool, "WARNING", &format!("Failed to initialize camera: {:?}", e)).await?;
+        logs::log_or_warn(&db_pool, "WARNING", &format!("Failed to initialize camera: {:?}", e)).await;
     }
 
     // Initialize and start the sensor data collection task
@@ -97,66 +154,169 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Arc::clone(&db_pool),
         Arc::clone(&current_readings),
         Arc::clone(&config),
-        Arc::clone(&light_controller)
+        Arc::clone(&light_controller),
+        Arc::clone(&away_mode)
     ).await;
 
-    // Initialize the light control task
-    let light_control_handle = task::spawn({
+    // Switch the loads on one by one before the control loops take over
+    let resume_hold = ResumeHold::new(&db_pool, &config, boot_behavior, config.main.schedule_now()).await;
+    staggered_startup(&db_pool, &config, &light_controller, &led_controller, away, &last_states).await?;
+    if boot_behavior.holds_control_loops() {
+        logs::log_or_warn(&db_pool, "WARNING", "Boot behavior safe_off: loads held off until POST /api/system/resume").await;
+    }
+    
+    let control_interval = tokio::time::Duration::from_secs(30);
+
+    // Initialize the light control task; a loop that panics is restarted by the watchdog
+    let light_control_handle = task::spawn(watchdog::supervise(Arc::clone(&db_pool), "lights", config.main.restart_backoff(), {
         let config = Arc::clone(&config);
         let light_controller = Arc::clone(&light_controller);
         let db_pool = Arc::clone(&db_pool);
+        let boot_hold = Arc::clone(&boot_hold);
+        let resume_hold = resume_hold.clone();
         
-        async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
-            loop {
-                interval.tick().await;
+        move || {
+            let config = Arc::clone(&config);
+            let light_controller = Arc::clone(&light_controller);
+            let db_pool = Arc::clone(&db_pool);
+            let boot_hold = Arc::clone(&boot_hold);
+            let resume_hold = resume_hold.clone();
+            async move {
+                let mut interval = tokio::time::interval(control_interval);
+                loop {
+                    interval.tick().await;
+                    if *boot_hold.lock().await || simulation::is_running() || resume_hold.holds(&db_pool, &config, config.main.schedule_now()).await {
+                        continue;
+                    }
                 
-                // Update light control based on schedule
-                if let Err(e) = lightControl::update_lights(&db_pool, &light_controller, &config).await {
-                    eprintln!("Error updating lights: {:?}", e);
-                    if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error updating lights: {:?}", e)).await {
-                        eprintln!("Failed to log error: {:?}", log_err);
+                    // Update light control based on schedule
+                    if let Err(e) = lightControl::update_lights(&db_pool, &light_controller, &config).await {
+                        eprintln!("Error updating lights: {:?}", e);
+                        if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error updating lights: {:?}", e)).await {
+                            eprintln!("Failed to log error: {:?}", log_err);
+                        }
                     }
                 }
             }
         }
-    });
+    }));
     
     // Initialize the LED control task
-    let led_control_handle = task::spawn({
+    let led_control_handle = task::spawn(watchdog::supervise(Arc::clone(&db_pool), "leds", config.main.restart_backoff(), {
         let config = Arc::clone(&config);
         let led_controller = Arc::clone(&led_controller);
         let db_pool = Arc::clone(&db_pool);
+        let light_controller = Arc::clone(&light_controller);
+        let away_mode = Arc::clone(&away_mode);
+        let boot_hold = Arc::clone(&boot_hold);
+        let resume_hold = resume_hold.clone();
         
-        async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
-            loop {
-                interval.tick().await;
-                // Update LED control based on schedule or settings
-                if let Err(e) = update_leds(&db_pool, &led_controller, &config).await {
-                    eprintln!("Error updating LEDs: {:?}", e);
-                    if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error updating LEDs: {:?}", e)).await {
-                        eprintln!("Failed to log error: {:?}", log_err);
+        move || {
+            let config = Arc::clone(&config);
+            let led_controller = Arc::clone(&led_controller);
+            let db_pool = Arc::clone(&db_pool);
+            let light_controller = Arc::clone(&light_controller);
+            let away_mode = Arc::clone(&away_mode);
+            let boot_hold = Arc::clone(&boot_hold);
+            let resume_hold = resume_hold.clone();
+            async move {
+                let mut interval = tokio::time::interval(control_interval);
+                // Last error raising an alert, so a strip that can't be opened is reported once, not every tick
+                let mut alert_error: Option<String> = None;
+                loop {
+                    interval.tick().await;
+                
+                    // Alerts pulse even while the loops are held after a safe_off boot
+                    let mut conditions = Vec::new();
+                    if light_controller.lock().await.is_overheating() {
+                        conditions.push(LedAlert::Overheat);
+                    }
+                    if getData::sensor_health().iter().any(|sensor| sensor.faulted) {
+                        conditions.push(LedAlert::SensorFault);
+                    }
+                    let error = LEDController::check_alerts(&led_controller, &conditions, config.led.alert_color()).await
+                        .err()
+                        .map(|e| format!("{:?}", e));
+                    if error != alert_error {
+                        if let Some(e) = &error {
+                            eprintln!("Error raising LED alert: {}", e);
+                        }
+                        alert_error = error;
+                    }
+                
+                    if *boot_hold.lock().await || simulation::is_running() || resume_hold.holds(&db_pool, &config, config.main.schedule_now()).await {
+                        continue;
+                    }
+                    // Update LED control based on schedule or settings
+                    let away = *away_mode.lock().await;
+                    if let Err(e) = update_leds(&db_pool, &led_controller, &config, away).await {
+                        eprintln!("Error updating LEDs: {:?}", e);
+                        if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error updating LEDs: {:?}", e)).await {
+                            eprintln!("Failed to log error: {:?}", log_err);
+                        }
                     }
                 }
             }
         }
-    });
+    }));
+
+    // Save periodic snapshots for remote checking while away mode is on
+    task::spawn(watchdog::supervise(Arc::clone(&db_pool), "snapshots", config.main.restart_backoff(), {
+        let config = Arc::clone(&config);
+        let camera_service = Arc::clone(&camera_service);
+        let db_pool = Arc::clone(&db_pool);
+        let away_mode = Arc::clone(&away_mode);
+        
+        move || {
+            let config = Arc::clone(&config);
+            let camera_service = Arc::clone(&camera_service);
+            let db_pool = Arc::clone(&db_pool);
+            let away_mode = Arc::clone(&away_mode);
+            async move {
+                let mut interval = tokio::time::interval(config.away.snapshot_interval());
+                loop {
+                    interval.tick().await;
+                    if !*away_mode.lock().await || !camera_service.is_initialized().await {
+                        continue;
+                    }
+                    
+                    let result = match camera_service.take_snapshot().await {
+                        Ok(jpeg) => cam::save_snapshot(
+                            std::path::Path::new("snapshots"),
+                            &jpeg,
+                            chrono::Local::now(),
+                            config.away.max_snapshots(),
+                        ).map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Error saving away snapshot: {}", e);
+                        if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error saving away snapshot: {}", e)).await {
+                            eprintln!("Failed to log error: {:?}", log_err);
+                        }
+                    }
+                }
+            }
+        }
+    }));
 
     // Start the camera stream server (separate from main web server)
     let camera_stream_handle = task::spawn({
         let camera_service_clone = Arc::clone(&camera_service);
         let config_clone = Arc::clone(&config);
+        let db_pool = Arc::clone(&db_pool);
         
         async move {
-            if let Err(e) = start_camera_stream_server(camera_service_clone, config_clone).await {
+            if let Err(e) = start_camera_stream_server(camera_service_clone, config_clone, db_pool).await {
                 eprintln!("Error running camera stream server: {:?}", e);
             }
         }
     });
 
     // Log web server startup
-    logs::log(&db_pool, "INFO", "Starting web server").await?;
+    logs::log_or_warn(&db_pool, "INFO", "Starting web server").await;
+
+    let addrs = config.web.socket_addrs()?;
 
     // Initialize the web server
     let web_handle = task::spawn({
@@ -167,6 +327,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
         let current_readings = Arc::clone(&current_readings);
         let config = Arc::clone(&config);
         let camera_service = Arc::clone(&camera_service);
+        let away_mode = Arc::clone(&away_mode);
+        let boot_hold = Arc::clone(&boot_hold);
         
         async move {
             let router = web::create_router(
@@ -176,18 +338,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 led_controller,
                 current_readings,
                 config,
-                camera_service
+                camera_service,
+                away_mode,
+                boot_hold,
+                settings
             ).await;
             
-            let addr: SocketAddr = format!("{}:{}", config.web.address, config.web.port)
-                .parse()
-                .expect("Invalid address");
-                
-            println!("Starting web server at {}", addr);
-            axum::Server::bind(&addr)
-                .serve(router.into_make_service())
-                .await
-                .expect("Failed to start server");
+            // Run one server per configured address, all sharing the same router
+            let servers = addrs.into_iter().map(|addr| {
+                serve_web(addr, router.clone(), Arc::clone(&db_pool))
+            });
+            futures::future::join_all(servers).await;
         }
     });
 
@@ -195,7 +356,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     tokio::try_join!(light_control_handle, led_control_handle, camera_stream_handle, web_handle)?;
 
     // Log system shutdown
-    logs::log(&db_pool, "INFO", "Terrarium Controller shutting down").await?;
+    logs::log_or_warn(&db_pool, "INFO", "Terrarium Controller shutting down").await;
 
     // Perform safe shutdown
     getData::shutdown_safely(&db_pool).await;
@@ -203,19 +364,224 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Runs the web server on a single address until it stops.
+///
+/// Bind and serve errors are printed and logged rather than propagated, so a
+/// failure on one address doesn't take down servers on the others.
+///
+/// # Arguments
+///
+/// * `addr` - The socket address to listen on
+/// * `router` - The application router
+/// * `db_pool` - Database pool used for logging failures
+async fn serve_web(addr: SocketAddr, router: Router, db_pool: Arc<sqlx::SqlitePool>) {
+    println!("Starting web server at {}", addr);
+    let result = match axum::Server::try_bind(&addr) {
+        Ok(server) => server.serve(router.into_make_service()).await,
+        Err(e) => Err(e),
+    };
+    
+    if let Err(e) = result {
+        eprintln!("Web server on {} failed: {}", addr, e);
+        if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Web server on {} failed: {}", addr, e)).await {
+            eprintln!("Failed to log error: {:?}", log_err);
+        }
+    }
+}
+
+/// Unwraps the result of initializing a hardware component, or falls back to
+/// a stand-in that switches nothing.
+///
+/// On failure the error is mapped to a `GpioInitError` with a hint about what
+/// to check and logged to the console and the system log. The controller keeps
+/// serving the API and logs, with the component reported unavailable.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database pool used for logging the failure
+/// * `component` - Human readable name of the component
+/// * `result` - The result of the component's constructor
+/// * `unavailable` - Creates the stand-in, e.g. `RelayController::unavailable`
+async fn hardware_or_unavailable<T>(
+    db_pool: &sqlx::SqlitePool,
+    component: &str,
+    result: Result<T, Box<dyn Error>>,
+    unavailable: impl FnOnce() -> T,
+) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            let err = GpioInitError::new(component, e.as_ref());
+            eprintln!("{}. Running without it, nothing it drives will be switched.", err);
+            if let Err(log_err) = logs::log(db_pool, "ERROR", &format!("{}; running without it", err)).await {
+                eprintln!("Failed to log error: {:?}", log_err);
+            }
+            unavailable()
+        }
+    }
+}
+
+/// Keeps the loads restored by `[main] boot_behavior = "resume"` until the
+/// schedule next changes what it asks of them.
+///
+/// Without it the control loops would apply the schedule at their first tick
+/// and a resume would only last until then. Both loops share one hold, which
+/// ends for good at the first transition. The LED color isn't compared, as
+/// natural light changes it every minute; only whether the strip is lit.
+#[derive(Clone)]
+struct ResumeHold(Arc<Mutex<Option<(RelayStates, bool)>>>);
+
+impl ResumeHold {
+    /// Captures what the schedule asks at `at`; holds nothing unless `behavior` is `Resume`
+    async fn new(db_pool: &sqlx::SqlitePool, config: &Config, behavior: BootBehavior, at: NaiveDateTime) -> Self {
+        let scheduled = match behavior {
+            BootBehavior::Resume => simulation::resolve_at(db_pool, config, at).await.ok().map(|state| (state.relays, state.led.is_some())),
+            _ => None,
+        };
+        Self(Arc::new(Mutex::new(scheduled)))
+    }
+
+    /// Whether the control loops still skip their tick at `at`.
+    ///
+    /// The hold ends once the schedule asks for something else than at boot,
+    /// or when it can't be resolved.
+    async fn holds(&self, db_pool: &sqlx::SqlitePool, config: &Config, at: NaiveDateTime) -> bool {
+        let mut scheduled = self.0.lock().await;
+        let boot = match *scheduled {
+            Some(boot) => boot,
+            None => return false,
+        };
+        let held = matches!(simulation::resolve_at(db_pool, config, at).await, Ok(state) if (state.relays, state.led.is_some()) == boot);
+        if !held {
+            *scheduled = None;
+            logs::log_or_warn(db_pool, "INFO", &format!("Schedule transition at {}: control loops take over from the resumed states", at.format("%H:%M"))).await;
+        }
+        held
+    }
+}
+
+/// Longest wait for the first temperature reading before the heat step of `staggered_startup`
+const STARTUP_READING_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Brings the loads up one at a time, `[main] startup_stagger_ms` apart.
+///
+/// All relays start off, and the control loops would otherwise switch UV,
+/// heat and the LED strip on in the same instant. The order comes from
+/// `[main] startup_order`, the state of each load from `[main] boot_behavior`.
+/// The heat step waits for the first temperature reading so overheat
+/// protection is armed; without one the heat lamp stays off until the light
+/// control loop sees a reading.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database pool for the schedule and logging
+/// * `config` - Application configuration
+/// * `light_controller` - Controller for the UV and heat relays
+/// * `led_controller` - Controller for the LED strip and its relay
+/// * `away` - Whether away mode is on
+/// * `last_states` - Last logged relay states, see `storage::last_relay_states`
+async fn staggered_startup(
+    db_pool: &sqlx::SqlitePool,
+    config: &Config,
+    light_controller: &Arc<Mutex<lightControl::LightController>>,
+    led_controller: &Arc<Mutex<LEDController>>,
+    away: bool,
+    last_states: &HashMap<String, bool>,
+) -> Result<(), Box<dyn Error>> {
+    let now = config.main.schedule_now();
+    let schedule = Schedule::get_week(db_pool, Schedule::week_for(&now)).await?;
+    let mut states = lightControl::resolve_relay_states(&now.format("%H:%M").to_string(), schedule.as_ref(), config);
+    let behavior = config.main.boot_behavior;
+    let last = |relay: RelayType| last_states.get(relay.as_str()).copied();
+    states.uv1 = behavior.initial_state(states.uv1, last(RelayType::UV1));
+    states.uv2 = behavior.initial_state(states.uv2, last(RelayType::UV2));
+    states.heat = behavior.initial_state(states.heat, last(RelayType::Heat));
+    let led_on = behavior.initial_state(true, last(RelayType::LED));
+    let resumed_color = match behavior {
+        BootBehavior::Resume => storage::last_led_color(db_pool).await?,
+        _ => None,
+    };
+    let stagger = config.main.startup_stagger();
+    let order = config.main.startup_order();
+    
+    for (i, relay) in order.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(stagger).await;
+        }
+        
+        let message = match relay {
+            RelayType::LED => {
+                if let Err(e) = led_controller.lock().await.initialize().await {
+                    eprintln!("Warning: Failed to initialize LED controller: {:?}", e);
+                    logs::log_or_warn(db_pool, "WARNING", &format!("Failed to initialize LED controller: {:?}", e)).await;
+                }
+                if !led_on {
+                    match led_controller.lock().await.power_off(RelayReason::Schedule).await {
+                        Ok(()) => "LED strip held off".to_string(),
+                        Err(e) => format!("LED strip not switched off: {:?}", e),
+                    }
+                } else if let Some(color) = resumed_color {
+                    match led_controller.lock().await.update_color(color, RelayReason::Schedule).await {
+                        Ok(()) => "LED strip resumed at its last color".to_string(),
+                        Err(e) => format!("LED strip not resumed, the LED loop will retry: {:?}", e),
+                    }
+                } else {
+                    match update_leds(db_pool, led_controller, config, away).await {
+                        Ok(()) => "LED strip brought up".to_string(),
+                        Err(e) => format!("LED strip not brought up, the LED loop will retry: {:?}", e),
+                    }
+                }
+            }
+            RelayType::UV1 => {
+                light_controller.lock().await.set_uv1(states.uv1, states.uv1_level);
+                format!("UV1 brought up ({})", if states.uv1 { "on" } else { "off" })
+            }
+            RelayType::UV2 => {
+                light_controller.lock().await.set_uv2(states.uv2, states.uv2_level);
+                format!("UV2 brought up ({})", if states.uv2 { "on" } else { "off" })
+            }
+            RelayType::Heat => {
+                let armed = tokio::time::timeout(STARTUP_READING_TIMEOUT, async {
+                    while !light_controller.lock().await.has_temperature() {
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                    }
+                }).await.is_ok();
+                {
+                    let mut controller = light_controller.lock().await;
+                    controller.set_heat_target(states.heat_target);
+                    controller.control_heat(states.heat);
+                }
+                if armed {
+                    format!("Heat brought up ({})", if states.heat { "on" } else { "off" })
+                } else {
+                    "Heat held off: no temperature reading yet".to_string()
+                }
+            }
+            RelayType::Fan | RelayType::Aux => continue,
+        };
+        
+        println!("Startup {}/{}: {}", i + 1, order.len(), message);
+        logs::log_or_warn(db_pool, "INFO", &format!("Startup: {}", message)).await;
+    }
+    
+    Ok(())
+}
+
 /// Starts a separate HTTP server dedicated to streaming camera footage.
 /// 
 /// This function creates an Axum server that provides:
-/// - A `/stream` endpoint that sends camera frames as Server-Sent Events (SSE)
+/// - A `/stream` endpoint that sends camera frames as an MJPEG stream
 /// - Static file serving from the `./static` directory
 /// 
-/// The server runs on the port specified in the configuration (default: 3030)
-/// and accepts connections from any network interface.
+/// The server listens on `[web] address` and `camera_port`, and isn't started
+/// when `camera_port` is unset. With `[web] require_api_key` it checks keys
+/// like the main API, see `web::with_stream_auth`.
 /// 
 /// # Arguments
 /// 
 /// * `camera_service` - A reference-counted pointer to the camera service
 /// * `config` - A reference-counted pointer to the application configuration
+/// * `db_pool` - Database pool the API keys are checked against
 /// 
 /// # Errors
 /// 
@@ -223,8 +589,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
 /// or encounters any other error during operation.
 async fn start_camera_stream_server(
     camera_service: Arc<CameraService>,
-    config: Arc<Config>
+    config: Arc<Config>,
+    db_pool: Arc<sqlx::SqlitePool>,
 ) -> Result<(), Box<dyn Error>> {
+    let addr = match config.web.camera_stream_addr()? {
+        Some(addr) => addr,
+        None => {
+            println!("Camera stream server disabled (no web.camera_port)");
+            return Ok(());
+        }
+    };
+
     // Create app state
     let state = CameraStreamState { camera_service };
 
@@ -233,12 +608,9 @@ async fn start_camera_stream_server(
         .route("/stream", get(handle_camera_stream))
         .nest_service("/", tower_http::services::ServeDir::new("./static"))
         .with_state(state);
+    let router = web::with_stream_auth(router, &db_pool, &config);
 
-    // Get port from config, or use default 3030
-    let port = config.web.camera_port.unwrap_or(3030);
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    
-    println!("Starting camera stream server on port {}", port);
+    println!("Starting camera stream server on {}", addr);
     axum::Server::bind(&addr)
         .serve(router.into_make_service())
         .await?;
@@ -254,8 +626,9 @@ struct CameraStreamState {
 
 /// Handles requests to the camera stream endpoint.
 /// 
-/// This function creates a Server-Sent Events (SSE) stream that sends camera frames
-/// as base64-encoded JPEG images at a rate of approximately 30 frames per second.
+/// This function streams camera frames as `multipart/x-mixed-replace` JPEG
+/// parts (MJPEG) at approximately 30 frames per second, which browsers show
+/// directly in an `<img>` tag.
 /// 
 /// # Arguments
 /// 
@@ -263,36 +636,86 @@ struct CameraStreamState {
 /// 
 /// # Returns
 /// 
-/// Returns an SSE stream that can be consumed by web clients.
+/// Returns an MJPEG stream that runs until the client disconnects.
 async fn handle_camera_stream(
     State(state): State<CameraStreamState>,
 ) -> impl IntoResponse {
-    // Build a SSE stream of camera frames
+    // Build a stream of JPEG parts, skipping frames that fail to capture
     let stream = stream::unfold(state.camera_service.clone(), |camera_service| async move {
-        // Create a 30 FPS stream (33ms per frame)
-        tokio::time::sleep(Duration::from_millis(33)).await;
-        
-        match camera_service.take_snapshot().await {
-            Ok(jpeg_data) => {
-                // Encode the JPEG data as base64
-                let base64_data = base64::encode(&jpeg_data);
-                // Create an SSE event with the base64 data
-                let event = Event::default().data(base64_data);
-                Some((event, camera_service))
-            },
-            Err(e) => {
-                eprintln!("Error capturing frame: {:?}", e);
-                // Continue the stream even if there's an error
-                Some((Event::default().comment("Error capturing frame"), camera_service))
+        loop {
+            // Create a 30 FPS stream (33ms per frame)
+            tokio::time::sleep(Duration::from_millis(33)).await;
+            
+            match camera_service.take_frame().await {
+                Ok(jpeg_data) => {
+                    let part: Result<Vec<u8>, Infallible> = Ok(cam::mjpeg_part(&jpeg_data));
+                    return Some((part, camera_service));
+                },
+                Err(e) => {
+                    eprintln!("Error capturing frame: {:?}", e);
+                    // Keep the stream open and try again after a short pause
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
             }
         }
     });
 
-    // Create an SSE response from the stream
-    Sse::new(stream)
-        .keep_alive(
-            axum::response::sse::KeepAlive::new()
-                .interval(Duration::from_secs(1))
-                .text("keep-alive-text")
-        )
-}
\ No newline at end of file
+    (
+        [(header::CONTENT_TYPE, format!("multipart/x-mixed-replace; boundary={}", cam::MJPEG_BOUNDARY))],
+        StreamBody::new(stream),
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modules::config::test_config;
+    use modules::gpio::RGBWW;
+    use modules::ledStrip::RecordingStrip;
+
+    #[tokio::test]
+    async fn test_resume_holds_restored_states_until_schedule_transition() {
+        let db_pool = storage::temp_db("boot_resume").await;
+        let mut config = test_config();
+        config.main.boot_behavior = BootBehavior::Resume;
+        config.main.startup_stagger_ms = Some(0);
+        config.led.fade_duration = Some(0);
+        let now = config.main.schedule_now();
+        let today = now.date();
+
+        // Heat is scheduled on until 20:00 this week and the strip all day
+        let mut week = Schedule::default_week(Schedule::week_for(&now), &config.db);
+        week.heat_start = "00:00".to_string();
+        week.heat_end = "20:00".to_string();
+        week.led_start = "00:00".to_string();
+        week.led_end = "23:59".to_string();
+        week.upsert(&db_pool).await.unwrap();
+
+        // The LED loop remembers the color it applied before the restart
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let before = Arc::new(Mutex::new(LEDController::with_driver(Arc::clone(&relays), RecordingStrip::new())));
+        update_leds(&db_pool, &before, &config, false).await.unwrap();
+        assert_eq!(storage::last_led_color(&db_pool).await.unwrap(), Some(before.lock().await.current_color()));
+        let manual = RGBWW { r: 10, g: 20, b: 30, ww: 40, cw: 50 };
+        storage::save_led_color(&db_pool, manual).await.unwrap();
+
+        // Heat was off and the strip lit at the manual color when power was lost
+        let light_controller = Arc::new(Mutex::new(lightControl::LightController::unavailable(config.light_control.clone())));
+        light_controller.lock().await.update_temperature(25.0);
+        let led_controller = Arc::new(Mutex::new(LEDController::with_driver(relays, RecordingStrip::new())));
+        let last_states = HashMap::from([("heat".to_string(), false), ("led".to_string(), true)]);
+        let hold = ResumeHold::new(&db_pool, &config, config.main.boot_behavior, today.and_hms_opt(12, 0, 0).unwrap()).await;
+        staggered_startup(&db_pool, &config, &light_controller, &led_controller, false, &last_states).await.unwrap();
+        assert_eq!(light_controller.lock().await.heat_on(), Some(false));
+        assert_eq!(led_controller.lock().await.current_color(), manual);
+
+        // The loops keep skipping until the heat window closes after 20:00, then take over for good
+        assert!(hold.holds(&db_pool, &config, today.and_hms_opt(12, 30, 0).unwrap()).await);
+        assert!(hold.holds(&db_pool, &config, today.and_hms_opt(20, 0, 0).unwrap()).await);
+        assert!(!hold.holds(&db_pool, &config, today.and_hms_opt(20, 1, 0).unwrap()).await);
+        assert!(!hold.holds(&db_pool, &config, today.and_hms_opt(12, 30, 0).unwrap()).await);
+
+        // Other boot behaviors never hold
+        let schedule = ResumeHold::new(&db_pool, &config, BootBehavior::Schedule, today.and_hms_opt(12, 0, 0).unwrap()).await;
+        assert!(!schedule.holds(&db_pool, &config, today.and_hms_opt(12, 30, 0).unwrap()).await);
+    }
+}
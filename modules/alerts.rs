@@ -0,0 +1,160 @@
+// modules/alerts.rs
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Mutex as StdMutex, OnceLock};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use crate::modules::logs;
+
+/// A condition that is logged on every collection cycle while it lasts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub id: String,           // Condition type, e.g. "overheat" or "high_basking"
+    pub level: &'static str,  // Log level, "WARNING" or "ERROR"
+    pub message: String,
+}
+
+impl Alert {
+    pub fn warning(id: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { id: id.into(), level: "WARNING", message: message.into() }
+    }
+
+    pub fn error(id: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { id: id.into(), level: "ERROR", message: message.into() }
+    }
+}
+
+/// An alert whose condition is present, as returned by `GET /api/alerts`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertStatus {
+    pub id: String,
+    pub level: &'static str,
+    pub message: String,        // Latest message, e.g. with the current reading
+    pub since: DateTime<Utc>,
+    pub acknowledged: bool,     // Silenced until the condition clears
+}
+
+/// Active alerts keyed by condition type.
+///
+/// An alert is repeated on every update while its condition lasts, until it
+/// is acknowledged. An acknowledged alert stays silent until its condition
+/// clears; if it comes back afterwards it is a new alert and notifies again.
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    active: HashMap<String, AlertStatus>,
+}
+
+impl AlertTracker {
+    /// Replaces the active alerts with the conditions present now.
+    ///
+    /// # Arguments
+    ///
+    /// * `present` - Every alert condition present now
+    /// * `now` - Time of the update, the start of newly raised alerts
+    ///
+    /// # Returns
+    ///
+    /// The alerts to notify about, i.e. the present ones not acknowledged
+    pub fn update(&mut self, present: Vec<Alert>, now: DateTime<Utc>) -> Vec<Alert> {
+        let mut active = HashMap::with_capacity(present.len());
+        let mut notify = Vec::new();
+        for alert in present {
+            let status = match self.active.remove(&alert.id) {
+                Some(status) => AlertStatus { message: alert.message.clone(), level: alert.level, ..status },
+                None => AlertStatus {
+                    id: alert.id.clone(),
+                    level: alert.level,
+                    message: alert.message.clone(),
+                    since: now,
+                    acknowledged: false,
+                },
+            };
+            if !status.acknowledged {
+                notify.push(alert);
+            }
+            active.insert(status.id.clone(), status);
+        }
+
+        for cleared in self.active.keys() {
+            info!("Alert cleared: {}", cleared);
+        }
+        self.active = active;
+        notify
+    }
+
+    /// Silences an active alert until its condition clears.
+    ///
+    /// # Returns
+    ///
+    /// The alert, or None if no alert with this id is active
+    pub fn acknowledge(&mut self, id: &str) -> Option<AlertStatus> {
+        let status = self.active.get_mut(id)?;
+        status.acknowledged = true;
+        Some(status.clone())
+    }
+
+    /// Active alerts, oldest first
+    pub fn active(&self) -> Vec<AlertStatus> {
+        let mut active: Vec<AlertStatus> = self.active.values().cloned().collect();
+        active.sort_by(|a, b| a.since.cmp(&b.since).then_with(|| a.id.cmp(&b.id)));
+        active
+    }
+}
+
+// Alerts raised by `collect_data`, kept across cycles
+static ALERTS: OnceLock<StdMutex<AlertTracker>> = OnceLock::new();
+
+/// The alerts raised by `collect_data`
+pub fn tracker() -> &'static StdMutex<AlertTracker> {
+    ALERTS.get_or_init(Default::default)
+}
+
+/// Updates `tracker` with the conditions present now and logs the alerts that
+/// aren't acknowledged, see `AlertTracker::update`.
+///
+/// # Returns
+///
+/// The number of alerts logged
+pub async fn notify(pool: &SqlitePool, tracker: &StdMutex<AlertTracker>, present: Vec<Alert>) -> Result<usize, Box<dyn Error>> {
+    let notify = tracker.lock().unwrap_or_else(|e| e.into_inner()).update(present, Utc::now());
+    for alert in &notify {
+        match alert.level {
+            "ERROR" => error!("{}", alert.message),
+            _ => warn!("{}", alert.message),
+        }
+        logs::log(pool, alert.level, &alert.message).await?;
+    }
+    Ok(notify.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acknowledged_alert_silent_until_recovery_and_retrigger() {
+        let mut tracker = AlertTracker::default();
+        let start = Utc::now();
+        let at = |minute: i64| start + chrono::Duration::minutes(minute);
+        let overheat = || Alert::error("overheat", "OVERHEAT CONDITION DETECTED!");
+        let humidity = || Alert::warning("low_humidity", "Low humidity reading: 20.0% (min 30.0%)");
+
+        assert_eq!(tracker.update(vec![overheat(), humidity()], at(0)).len(), 2);
+        assert_eq!(tracker.update(vec![overheat(), humidity()], at(1)).len(), 2);
+
+        // Acknowledged: no longer notified while it lasts, the other alert still is
+        assert!(tracker.acknowledge("overheat").unwrap().acknowledged);
+        assert_eq!(tracker.update(vec![overheat(), humidity()], at(2)), vec![humidity()]);
+        assert_eq!(tracker.update(vec![overheat()], at(3)), vec![]);
+        assert!(tracker.acknowledge("low_humidity").is_none());
+
+        // Recovered, then back: notified again from the new start
+        assert_eq!(tracker.update(vec![], at(4)), vec![]);
+        assert!(tracker.active().is_empty());
+        assert_eq!(tracker.update(vec![overheat()], at(5)), vec![overheat()]);
+        let active = tracker.active();
+        assert_eq!((active.len(), active[0].since, active[0].acknowledged), (1, at(5), false));
+    }
+}
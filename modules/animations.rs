@@ -0,0 +1,193 @@
+// modules/animations.rs
+use std::f32::consts::PI;
+use std::time::Duration;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::modules::gpio::RGBWW;
+
+/// Time between animation frames written to the strip (~30 fps)
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// An ambient LED animation, driven by `LEDController::start_animation`.
+pub trait Animation: Send {
+    /// Color to show `elapsed` after the animation started
+    fn next_frame(&mut self, elapsed: Duration) -> RGBWW;
+}
+
+/// Built-in animations selectable through `POST /api/led/animation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationKind {
+    Breathing,
+    Thunderstorm,
+    Candle,
+}
+
+impl AnimationKind {
+    /// Builds the animation around `color`.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Brightest color of a breathing animation, the base light of the others
+    /// * `period` - Length of one breath, ignored by the other animations
+    pub fn build(self, color: RGBWW, period: Duration) -> Box<dyn Animation> {
+        let seed = rand::random();
+        match self {
+            AnimationKind::Breathing => Box::new(Breathing::new(color, period)),
+            AnimationKind::Thunderstorm => Box::new(Thunderstorm::new(color, seed)),
+            AnimationKind::Candle => Box::new(Candle::new(color, seed)),
+        }
+    }
+}
+
+/// Scales every channel of `color` by `factor` (0.0-1.0)
+fn scale(color: RGBWW, factor: f32) -> RGBWW {
+    let factor = factor.clamp(0.0, 1.0);
+    let channel = |value: u8| (value as f32 * factor).round() as u8;
+    RGBWW { r: channel(color.r), g: channel(color.g), b: channel(color.b), ww: channel(color.ww), cw: channel(color.cw) }
+}
+
+/// Slowly pulses between a dim glow and the full color
+pub struct Breathing {
+    color: RGBWW,
+    period: Duration,
+}
+
+impl Breathing {
+    /// Lowest brightness reached, so the strip never goes fully dark
+    const MIN_LEVEL: f32 = 0.05;
+
+    pub fn new(color: RGBWW, period: Duration) -> Self {
+        Self { color, period: period.max(Duration::from_secs(1)) }
+    }
+}
+
+impl Animation for Breathing {
+    fn next_frame(&mut self, elapsed: Duration) -> RGBWW {
+        let phase = (elapsed.as_secs_f32() / self.period.as_secs_f32()).fract();
+        let level = 0.5 - 0.5 * (2.0 * PI * phase).cos();
+        scale(self.color, Self::MIN_LEVEL + (1.0 - Self::MIN_LEVEL) * level)
+    }
+}
+
+/// A dim base light with random lightning flashes
+pub struct Thunderstorm {
+    base: RGBWW,
+    rng: StdRng,
+    next_flash: Duration,     // When the next flash starts
+    flash_until: Duration,    // When the current flash ends
+    flashes_left: u8,         // Further flickers of the current strike
+}
+
+impl Thunderstorm {
+    const FLASH: RGBWW = RGBWW { r: 200, g: 200, b: 255, ww: 0, cw: 255 };
+
+    pub fn new(base: RGBWW, seed: u64) -> Self {
+        let mut storm = Self {
+            base,
+            rng: StdRng::seed_from_u64(seed),
+            next_flash: Duration::ZERO,
+            flash_until: Duration::ZERO,
+            flashes_left: 0,
+        };
+        storm.next_flash = storm.pause(Duration::ZERO);
+        storm
+    }
+
+    /// Start of the next strike, a few seconds after `now`
+    fn pause(&mut self, now: Duration) -> Duration {
+        now + Duration::from_millis(self.rng.gen_range(2_000..12_000))
+    }
+}
+
+impl Animation for Thunderstorm {
+    fn next_frame(&mut self, elapsed: Duration) -> RGBWW {
+        if elapsed < self.flash_until {
+            return Self::FLASH;
+        }
+        if elapsed < self.next_flash {
+            return self.base;
+        }
+
+        // A strike is one to three short flickers
+        if self.flashes_left == 0 {
+            self.flashes_left = self.rng.gen_range(1..=3);
+        }
+        self.flashes_left -= 1;
+        self.flash_until = elapsed + Duration::from_millis(self.rng.gen_range(40..160));
+        self.next_flash = if self.flashes_left > 0 {
+            self.flash_until + Duration::from_millis(self.rng.gen_range(60..250))
+        } else {
+            let end = self.flash_until;
+            self.pause(end)
+        };
+        Self::FLASH
+    }
+}
+
+/// Warm light flickering like a candle flame
+pub struct Candle {
+    color: RGBWW,
+    rng: StdRng,
+    level: f32,
+}
+
+impl Candle {
+    pub fn new(color: RGBWW, seed: u64) -> Self {
+        Self { color, rng: StdRng::seed_from_u64(seed), level: 0.85 }
+    }
+}
+
+impl Animation for Candle {
+    fn next_frame(&mut self, _elapsed: Duration) -> RGBWW {
+        // Random walk pulled back towards a steady level, with the odd sharp dip
+        let target = if self.rng.gen_bool(0.03) { 0.55 } else { 0.85 };
+        self.level += (target - self.level) * 0.3 + self.rng.gen_range(-0.06..0.06);
+        self.level = self.level.clamp(0.5, 1.0);
+        scale(self.color, self.level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WARM: RGBWW = RGBWW { r: 255, g: 120, b: 20, ww: 200, cw: 0 };
+
+    #[test]
+    fn test_breathing_peaks_mid_period() {
+        let mut breathing = Breathing::new(WARM, Duration::from_secs(4));
+        assert_eq!(breathing.next_frame(Duration::from_secs(2)), WARM);
+
+        let dim = breathing.next_frame(Duration::ZERO);
+        assert!(dim.r < 20 && dim.r > 0, "{:?}", dim);
+        assert_eq!(breathing.next_frame(Duration::from_secs(4)), dim);
+    }
+
+    #[test]
+    fn test_thunderstorm_flashes_over_base() {
+        let base = RGBWW { r: 0, g: 0, b: 30, ww: 0, cw: 5 };
+        let mut storm = Thunderstorm::new(base, 7);
+
+        let frames: Vec<RGBWW> = (0..60 * 30)
+            .map(|i| storm.next_frame(FRAME_INTERVAL * i))
+            .collect();
+        let flashes = frames.iter().filter(|&&c| c == Thunderstorm::FLASH).count();
+
+        assert_eq!(frames[0], base);
+        assert!(flashes > 0, "no flash within a minute");
+        assert!(flashes < frames.len() / 10, "{} of {} frames flashing", flashes, frames.len());
+        assert!(frames.iter().all(|&c| c == base || c == Thunderstorm::FLASH));
+    }
+
+    #[test]
+    fn test_candle_stays_within_flicker_range() {
+        let mut candle = Candle::new(WARM, 3);
+        for i in 0..1000 {
+            let frame = candle.next_frame(FRAME_INTERVAL * i);
+            assert!(frame.r >= 127 && frame.r <= 255, "{:?}", frame);
+            assert_eq!(frame.cw, 0);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+// modules/calendar.rs
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use crate::modules::models::{SCHEDULE_WEEKS, Schedule};
+
+/// Longest content line in octets before it is folded (RFC 5545 3.1)
+const MAX_LINE_OCTETS: usize = 75;
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Exports stored schedule weeks as an iCalendar document.
+///
+/// Each device's daily on-window becomes one VEVENT on the Monday of its ISO
+/// week in `year`, repeated daily for the rest of the week. Week 52 also
+/// covers week 53 of long years, as in `Schedule::week_for`. A window that
+/// ends before it starts never switches on (see `resolve_relay_states`), so it
+/// gets no event. Times are floating, i.e. in the schedule timezone.
+///
+/// # Arguments
+///
+/// * `weeks` - Stored schedule weeks
+/// * `year` - ISO year to place the weeks in
+/// * `timezone` - Schedule timezone name, advertised as `X-WR-TIMEZONE` if set
+/// * `stamp` - Creation time of the export
+pub fn schedule_ics(weeks: &[Schedule], year: i32, timezone: Option<&str>, stamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Terra-Control//Schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Terrarium schedule".to_string(),
+    ];
+    if let Some(timezone) = timezone {
+        lines.push(format!("X-WR-TIMEZONE:{}", timezone));
+    }
+
+    let stamp = stamp.format("%Y%m%dT%H%M%SZ").to_string();
+    for week in weeks {
+        let monday = match NaiveDate::from_isoywd_opt(year, week.week_number as u32, Weekday::Mon) {
+            Some(monday) => monday,
+            None => continue,
+        };
+        let days = if week.week_number == SCHEDULE_WEEKS && NaiveDate::from_isoywd_opt(year, 53, Weekday::Mon).is_some() { 14 } else { 7 };
+
+        for (device, start, end) in [
+            ("UV1", &week.uv1_start, &week.uv1_end),
+            ("UV2", &week.uv2_start, &week.uv2_end),
+            ("Heat", &week.heat_start, &week.heat_end),
+            ("LED", &week.led_start, &week.led_end),
+        ] {
+            let (start, end) = match window(monday, start, end) {
+                Some(window) => window,
+                None => continue,
+            };
+            lines.extend([
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:{}-week{}-{}@terra-control", year, week.week_number, device.to_lowercase()),
+                format!("DTSTAMP:{}", stamp),
+                format!("DTSTART:{}", start.format(DATE_TIME_FORMAT)),
+                format!("DTEND:{}", end.format(DATE_TIME_FORMAT)),
+                format!("RRULE:FREQ=DAILY;COUNT={}", days),
+                format!("SUMMARY:{} on", device),
+                "TRANSP:TRANSPARENT".to_string(),
+                "END:VEVENT".to_string(),
+            ]);
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics = String::new();
+    for line in lines {
+        fold_into(&mut ics, &line);
+    }
+    ics
+}
+
+/// First day's on-window, or None if it never switches on.
+///
+/// The controllers keep a device on through the end minute, so the window
+/// lasts until the minute after `end`.
+fn window(day: NaiveDate, start: &str, end: &str) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let start = NaiveTime::parse_from_str(start, "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(end, "%H:%M").ok()?;
+    if end < start {
+        return None;
+    }
+    Some((day.and_time(start), day.and_time(end) + Duration::minutes(1)))
+}
+
+/// Appends `line` with CRLF, folding it into continuation lines of at most
+/// 75 octets without splitting a UTF-8 character
+fn fold_into(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::test_config;
+
+    /// Minimal RFC 5545 reader: unfolds lines and returns the properties of
+    /// each VEVENT, failing on anything a calendar app would reject
+    fn parse_events(ics: &str) -> Result<Vec<Vec<(String, String)>>, String> {
+        if !ics.ends_with("\r\n") || ics.replace("\r\n", "").contains('\n') {
+            return Err("lines must end with CRLF".to_string());
+        }
+        let mut lines: Vec<String> = Vec::new();
+        for raw in ics.trim_end_matches("\r\n").split("\r\n") {
+            if raw.len() > MAX_LINE_OCTETS {
+                return Err(format!("unfolded line: {}", raw));
+            }
+            match raw.strip_prefix(' ') {
+                Some(rest) => lines.last_mut().ok_or("continuation first")?.push_str(rest),
+                None => lines.push(raw.to_string()),
+            }
+        }
+
+        let mut stack = Vec::new();
+        let mut events = Vec::new();
+        for line in lines {
+            let (name, value) = line.split_once(':').ok_or_else(|| format!("no value: {}", line))?;
+            match name {
+                "BEGIN" => {
+                    stack.push(value.to_string());
+                    if value == "VEVENT" {
+                        events.push(Vec::new());
+                    }
+                }
+                "END" if stack.pop().as_deref() == Some(value) => {}
+                "END" => return Err(format!("unbalanced END:{}", value)),
+                _ if stack.last().map(String::as_str) == Some("VEVENT") => {
+                    events.last_mut().unwrap().push((name.to_string(), value.to_string()));
+                }
+                _ => {}
+            }
+        }
+        if !stack.is_empty() {
+            return Err(format!("unclosed {:?}", stack));
+        }
+
+        for event in &events {
+            let get = |key: &str| event.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+            for key in ["UID", "DTSTAMP", "DTSTART", "DTEND"] {
+                get(key).ok_or_else(|| format!("VEVENT without {}", key))?;
+            }
+            let start = NaiveDateTime::parse_from_str(get("DTSTART").unwrap(), DATE_TIME_FORMAT).map_err(|e| e.to_string())?;
+            let end = NaiveDateTime::parse_from_str(get("DTEND").unwrap(), DATE_TIME_FORMAT).map_err(|e| e.to_string())?;
+            if end <= start {
+                return Err(format!("event ends before it starts: {:?}", event));
+            }
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn test_schedule_ics_has_one_event_per_device_window() {
+        let config = test_config();
+        let mut weeks: Vec<Schedule> = (1..=3).map(|n| Schedule::default_week(n, &config.db)).collect();
+        // Ends before it starts: never on, so no event
+        weeks[1].uv2_start = "22:00".to_string();
+        weeks[1].uv2_end = "06:00".to_string();
+        weeks.push(Schedule::default_week(SCHEDULE_WEEKS, &config.db));
+
+        // 2026 has 53 ISO weeks
+        let ics = schedule_ics(&weeks, 2026, Some("Europe/Berlin"), Utc::now());
+        let events = parse_events(&ics).unwrap();
+        assert_eq!(events.len(), 4 * 4 - 1);
+
+        let led_week1 = &events[3];
+        let value = |event: &Vec<(String, String)>, key: &str| event.iter().find(|(name, _)| name == key).unwrap().1.clone();
+        assert_eq!(value(led_week1, "DTSTART"), "20251229T070000");
+        assert_eq!(value(led_week1, "DTEND"), "20251229T190100");
+        assert_eq!(value(led_week1, "RRULE"), "FREQ=DAILY;COUNT=7");
+        assert_eq!(value(events.last().unwrap(), "RRULE"), "FREQ=DAILY;COUNT=14");
+    }
+}
@@ -1,10 +1,16 @@
-use std::sync::Arc;
+use std::future::Future;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use raspicam::{Camera, CameraConfig, Exposure, ImageEffect};
 use std::error::Error;
 use std::fmt;
-use image::{ImageBuffer, Rgb};
-use std::io::Cursor;
+use image::{ImageBuffer, ImageFormat, Rgb};
+use image::imageops::FilterType;
+use std::io::{self, Cursor, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// Custom error type for camera operations
 #[derive(Debug)]
@@ -119,6 +125,53 @@ impl CameraController {
     }
 }
 
+/// Single-flight cache for camera snapshots.
+///
+/// Concurrent snapshot requests queue on one lock: the first caller captures,
+/// and everyone arriving while that capture runs, or within `ttl` of it,
+/// receives the same JPEG instead of triggering another capture.
+pub struct SnapshotCache {
+    ttl: Duration,
+    latest: Mutex<Option<(Instant, Arc<Vec<u8>>)>>,
+}
+
+impl SnapshotCache {
+    /// Creates an empty cache whose snapshots stay fresh for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached snapshot if it is still fresh, otherwise runs `capture`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capture` - Produces a new JPEG; only called when the cache is stale
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the shared JPEG data or the capture error
+    pub async fn get_or_capture<F, Fut>(&self, capture: F) -> Result<Arc<Vec<u8>>, CameraError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, CameraError>>,
+    {
+        // Held across the capture so concurrent callers wait for its result
+        let mut latest = self.latest.lock().await;
+        if let Some((taken_at, jpeg)) = latest.as_ref() {
+            if taken_at.elapsed() < self.ttl {
+                return Ok(Arc::clone(jpeg));
+            }
+        }
+
+        let jpeg = Arc::new(capture().await?);
+        *latest = Some((Instant::now(), Arc::clone(&jpeg)));
+        Ok(jpeg)
+    }
+}
+
 /// Thread-safe service for managing the Raspberry Pi camera.
 ///
 /// This service provides a high-level interface for camera operations, with
@@ -126,6 +179,14 @@ impl CameraController {
 /// shared across multiple asynchronous tasks that need to access the camera.
 pub struct CameraService {
     controller: Arc<Mutex<CameraController>>,
+    snapshots: SnapshotCache,
+    variants: StdMutex<HashMap<(u8, Option<u32>), SnapshotVariant>>, // Re-encodings of the cached snapshot
+}
+
+/// A re-encoded snapshot and the snapshot it was made from
+struct SnapshotVariant {
+    source: Arc<Vec<u8>>,
+    jpeg: Arc<Vec<u8>>,
 }
 
 impl CameraService {
@@ -138,8 +199,23 @@ impl CameraService {
     ///
     /// A new CameraService instance
     pub fn new() -> Self {
+        Self::with_snapshot_ttl(Duration::from_millis(1000))
+    }
+    
+    /// Creates a new CameraService that shares snapshots taken within `ttl` of each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - How long a captured snapshot is served to later requests
+    ///
+    /// # Returns
+    ///
+    /// A new CameraService instance
+    pub fn with_snapshot_ttl(ttl: Duration) -> Self {
         Self {
             controller: Arc::new(Mutex::new(CameraController::new())),
+            snapshots: SnapshotCache::new(ttl),
+            variants: StdMutex::new(HashMap::new()),
         }
     }
     
@@ -170,13 +246,78 @@ impl CameraService {
     
     /// Takes a snapshot and returns it as a JPEG image.
     ///
-    /// This function captures an image from the camera and returns it
-    /// as a JPEG-encoded byte vector.
+    /// Requests arriving while a capture is running, or within the snapshot
+    /// TTL of one, share its result instead of capturing again.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the JPEG image data or an error
+    pub async fn take_snapshot(&self) -> Result<Arc<Vec<u8>>, CameraError> {
+        self.snapshots.get_or_capture(|| self.take_frame()).await
+    }
+    
+    /// Takes a snapshot re-encoded at `quality`, scaled down to `max_width`.
+    ///
+    /// Variants are made from the shared snapshot and kept as long as it is
+    /// served, so repeated requests for the same variant are encoded once.
+    /// At most `MAX_SNAPSHOT_VARIANTS` are kept per snapshot; further ones are
+    /// encoded for each request.
+    ///
+    /// # Arguments
+    ///
+    /// * `quality` - JPEG quality, checked with `check_variant`
+    /// * `max_width` - Widest image returned, None for the captured width
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the JPEG image data or an error
+    pub async fn take_snapshot_variant(&self, quality: u8, max_width: Option<u32>) -> Result<Arc<Vec<u8>>, CameraError> {
+        let source = self.take_snapshot().await?;
+        let key = (quality, max_width);
+        {
+            let variants = self.variants.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(variant) = variants.get(&key).filter(|variant| Arc::ptr_eq(&variant.source, &source)) {
+                return Ok(Arc::clone(&variant.jpeg));
+            }
+        }
+
+        let frame = Arc::clone(&source);
+        let jpeg = tokio::task::spawn_blocking(move || reencode_jpeg(&frame, quality, max_width))
+            .await
+            .map_err(|e| CameraError::ConversionError(e.to_string()))??;
+        let jpeg = Arc::new(jpeg);
+
+        let mut variants = self.variants.lock().unwrap_or_else(|e| e.into_inner());
+        cache_variant(&mut variants, key, SnapshotVariant { source, jpeg: Arc::clone(&jpeg) });
+        Ok(jpeg)
+    }
+    
+    /// Captures `count` fresh frames `interval` apart.
+    ///
+    /// The first frame goes through the snapshot cache so a burst started
+    /// alongside snapshot requests shares their capture; the others bypass it,
+    /// since frames spaced closer than the TTL would otherwise all be the same.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the JPEG frames in capture order or an error
+    pub async fn take_burst(&self, count: u32, interval: Duration) -> Result<Vec<Vec<u8>>, CameraError> {
+        let mut frames = Vec::with_capacity(count as usize);
+        if count > 0 {
+            frames.push(self.take_snapshot().await?.as_ref().clone());
+        }
+        frames.extend(capture_burst(count.saturating_sub(1), interval, Some(interval), || self.take_frame()).await?);
+        Ok(frames)
+    }
+
+    /// Captures a fresh JPEG frame, bypassing the snapshot cache.
+    ///
+    /// Used by the live stream, which needs every frame to be new.
     ///
     /// # Returns
     ///
     /// A Result containing either the JPEG image data or an error
-    pub async fn take_snapshot(&self) -> Result<Vec<u8>, CameraError> {
+    pub async fn take_frame(&self) -> Result<Vec<u8>, CameraError> {
         let mut controller = self.controller.lock().await;
         controller.take_snapshot()
     }
@@ -204,6 +345,54 @@ impl CameraService {
     }
 }
 
+/// JPEG quality snapshots are captured at
+pub const SNAPSHOT_QUALITY: u8 = 90;
+
+/// Narrowest and widest `max_width` a snapshot can be scaled to
+pub const MIN_SNAPSHOT_WIDTH: u32 = 32;
+pub const MAX_SNAPSHOT_WIDTH: u32 = 4096;
+
+/// Most re-encodings of one snapshot `take_snapshot_variant` keeps
+pub const MAX_SNAPSHOT_VARIANTS: usize = 8;
+
+/// Keeps `variant` unless `MAX_SNAPSHOT_VARIANTS` of its snapshot are already
+/// kept. Variants of earlier snapshots are dropped first.
+fn cache_variant(variants: &mut HashMap<(u8, Option<u32>), SnapshotVariant>, key: (u8, Option<u32>), variant: SnapshotVariant) {
+    variants.retain(|_, kept| Arc::ptr_eq(&kept.source, &variant.source));
+    if variants.len() < MAX_SNAPSHOT_VARIANTS || variants.contains_key(&key) {
+        variants.insert(key, variant);
+    }
+}
+
+/// Checks the `quality` (1-100) and `max_width` of a snapshot variant
+pub fn check_variant(quality: u8, max_width: Option<u32>) -> Result<(), String> {
+    if !(1..=100).contains(&quality) {
+        return Err(format!("quality must be between 1 and 100, got: {}", quality));
+    }
+    if let Some(width) = max_width {
+        if !(MIN_SNAPSHOT_WIDTH..=MAX_SNAPSHOT_WIDTH).contains(&width) {
+            return Err(format!("max_width must be between {} and {}, got: {}", MIN_SNAPSHOT_WIDTH, MAX_SNAPSHOT_WIDTH, width));
+        }
+    }
+    Ok(())
+}
+
+/// Re-encodes a JPEG at `quality`, first scaling it down to `max_width`
+/// pixels wide with the aspect ratio kept. Narrower images aren't enlarged.
+pub fn reencode_jpeg(jpeg: &[u8], quality: u8, max_width: Option<u32>) -> Result<Vec<u8>, CameraError> {
+    let mut image = image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg)
+        .map_err(|e| CameraError::ConversionError(e.to_string()))?;
+    if let Some(max_width) = max_width.filter(|&max_width| max_width < image.width()) {
+        let height = ((image.height() as u64 * max_width as u64) / image.width() as u64).max(1) as u32;
+        image = image.resize_exact(max_width, height, FilterType::Triangle);
+    }
+
+    let mut output = Vec::new();
+    image.write_to(&mut Cursor::new(&mut output), image::ImageOutputFormat::Jpeg(quality))
+        .map_err(|e| CameraError::ConversionError(e.to_string()))?;
+    Ok(output)
+}
+
 /// Converts a raw camera frame to a JPEG image.
 ///
 /// This utility function takes a raw frame buffer from the camera
@@ -237,26 +426,254 @@ pub fn convert_to_jpeg(raw_frame: &[u8]) -> Result<Vec<u8>, CameraError> {
     let mut jpeg_data = Vec::new();
     let mut cursor = Cursor::new(&mut jpeg_data);
     
-    match img_result.write_to(&mut cursor, image::ImageOutputFormat::Jpeg(90)) {
+    match img_result.write_to(&mut cursor, image::ImageOutputFormat::Jpeg(SNAPSHOT_QUALITY)) {
         Ok(_) => Ok(jpeg_data),
         Err(e) => Err(CameraError::ConversionError(e.to_string())),
     }
 }
 
+/// Most frames a single burst may capture
+pub const MAX_BURST_FRAMES: u32 = 20;
+
+/// Shortest allowed spacing between burst frames, about the camera's frame time
+pub const MIN_BURST_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest allowed spacing between burst frames
+pub const MAX_BURST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Runs `capture` `count` times, `interval` apart.
+///
+/// # Arguments
+///
+/// * `count` - Number of frames to capture
+/// * `interval` - Spacing between the start of two captures
+/// * `first_delay` - Wait before the first capture, None to start right away
+/// * `capture` - Produces one JPEG frame
+pub async fn capture_burst<F, Fut>(
+    count: u32,
+    interval: Duration,
+    first_delay: Option<Duration>,
+    mut capture: F,
+) -> Result<Vec<Vec<u8>>, CameraError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, CameraError>>,
+{
+    let start = tokio::time::Instant::now() + first_delay.unwrap_or(Duration::ZERO);
+    let mut ticks = tokio::time::interval_at(start, interval.max(Duration::from_millis(1)));
+    let mut frames = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        ticks.tick().await;
+        frames.push(capture().await?);
+    }
+    Ok(frames)
+}
+
+/// Packs burst frames into a zip archive as `burst_01.jpg`, `burst_02.jpg`, ...
+///
+/// JPEGs don't compress further, so the entries are stored as is.
+pub fn burst_zip(frames: &[Vec<u8>]) -> Result<Vec<u8>, CameraError> {
+    let archive_error = |e: zip::result::ZipError| CameraError::ConversionError(format!("Failed to write burst archive: {}", e));
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored);
+
+    for (i, frame) in frames.iter().enumerate() {
+        zip.start_file(format!("burst_{:02}.jpg", i + 1), options).map_err(archive_error)?;
+        zip.write_all(frame).map_err(|e| archive_error(e.into()))?;
+    }
+
+    Ok(zip.finish().map_err(archive_error)?.into_inner())
+}
+
+/// Boundary between the frames of the MJPEG camera stream
+pub const MJPEG_BOUNDARY: &str = "frame";
+
+/// Wraps a JPEG frame as one part of a `multipart/x-mixed-replace` (MJPEG) stream
+pub fn mjpeg_part(jpeg: &[u8]) -> Vec<u8> {
+    let mut part = format!(
+        "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+        MJPEG_BOUNDARY,
+        jpeg.len()
+    ).into_bytes();
+    part.extend_from_slice(jpeg);
+    part.extend_from_slice(b"\r\n");
+    part
+}
+
+/// Writes a snapshot to `dir` as `snapshot_<timestamp>.jpg` and prunes old ones.
+///
+/// Used by away mode for periodic remote checks. The file names sort by time,
+/// so the oldest snapshots are deleted first once more than `max_files` exist.
+///
+/// # Arguments
+///
+/// * `dir` - Directory the snapshots are kept in, created if missing
+/// * `jpeg` - The JPEG image data
+/// * `at` - Capture time, used for the file name
+/// * `max_files` - Number of snapshots kept
+///
+/// # Returns
+///
+/// The path of the written snapshot
+pub fn save_snapshot(
+    dir: &Path,
+    jpeg: &[u8],
+    at: chrono::DateTime<chrono::Local>,
+    max_files: usize,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("snapshot_{}.jpg", at.format("%Y%m%d_%H%M%S")));
+    fs::write(&path, jpeg)?;
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+            name.starts_with("snapshot_") && name.ends_with(".jpg")
+        })
+        .collect();
+    if files.len() > max_files {
+        files.sort();
+        let excess = files.len() - max_files;
+        for old in files.into_iter().take(excess) {
+            fs::remove_file(old)?;
+        }
+    }
+
+    Ok(path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_mjpeg_part_framing() {
+        let part = mjpeg_part(&[0xFF, 0xD8, 0xFF, 0xD9]);
+        let header = b"--frame\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\n\r\n";
+
+        assert!(part.starts_with(header));
+        assert_eq!(&part[header.len()..], &[0xFF, 0xD8, 0xFF, 0xD9, b'\r', b'\n']);
+    }
+
+    #[test]
+    fn test_lower_quality_snapshot_is_smaller() {
+        let source = convert_to_jpeg(&[]).unwrap();
+        let high = reencode_jpeg(&source, SNAPSHOT_QUALITY, None).unwrap();
+        let low = reencode_jpeg(&source, 20, None).unwrap();
+        assert!(low.len() < high.len(), "quality 20: {} bytes, quality {}: {} bytes", low.len(), SNAPSHOT_QUALITY, high.len());
+
+        let scaled = image::load_from_memory(&reencode_jpeg(&source, SNAPSHOT_QUALITY, Some(320)).unwrap()).unwrap();
+        assert_eq!((scaled.width(), scaled.height()), (320, 240));
+        assert!(check_variant(0, None).is_err());
+        assert!(check_variant(80, Some(10_000)).is_err());
+    }
+
+    #[test]
+    fn test_variant_cache_is_bounded() {
+        let mut variants = HashMap::new();
+        let source = Arc::new(vec![0xFF, 0xD8]);
+        for width in MIN_SNAPSHOT_WIDTH..MIN_SNAPSHOT_WIDTH + 100 {
+            let variant = SnapshotVariant { source: Arc::clone(&source), jpeg: Arc::new(Vec::new()) };
+            cache_variant(&mut variants, (80, Some(width)), variant);
+        }
+        assert_eq!(variants.len(), MAX_SNAPSHOT_VARIANTS);
+
+        // A new snapshot starts over
+        let variant = SnapshotVariant { source: Arc::new(vec![0xFF, 0xD9]), jpeg: Arc::new(Vec::new()) };
+        cache_variant(&mut variants, (80, None), variant);
+        assert_eq!(variants.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_archive_contains_every_frame() {
+        let mut taken = 0u8;
+        let started = tokio::time::Instant::now();
+        let frames = capture_burst(4, Duration::from_millis(250), None, || {
+            taken += 1;
+            let frame = vec![0xFF, 0xD8, taken];
+            async move { Ok(frame) }
+        }).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_millis(750));
+
+        let archive = burst_zip(&frames).unwrap();
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(zip.len(), 4);
+
+        let mut last = Vec::new();
+        io::Read::read_to_end(&mut zip.by_name("burst_04.jpg").unwrap(), &mut last).unwrap();
+        assert_eq!(last, vec![0xFF, 0xD8, 4]);
+    }
+
     #[test]
     fn test_camera_controller_creation() {
         let controller = CameraController::new();
         assert!(!controller.is_initialized());
     }
 
+    #[tokio::test]
+    async fn test_concurrent_snapshots_share_one_capture() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let cache = Arc::new(SnapshotCache::new(Duration::from_secs(5)));
+        let captures = Arc::new(AtomicUsize::new(0));
+
+        let requests: Vec<_> = (0..8).map(|_| {
+            let cache = Arc::clone(&cache);
+            let captures = Arc::clone(&captures);
+            tokio::spawn(async move {
+                cache.get_or_capture(|| async {
+                    captures.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(vec![0xFF, 0xD8, 0xFF])
+                }).await
+            })
+        }).collect();
+
+        for request in requests {
+            assert_eq!(*request.await.unwrap().unwrap(), vec![0xFF, 0xD8, 0xFF]);
+        }
+        assert_eq!(captures.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_snapshot_is_recaptured() {
+        let cache = SnapshotCache::new(Duration::ZERO);
+        cache.get_or_capture(|| async { Ok(vec![1]) }).await.unwrap();
+        let second = cache.get_or_capture(|| async { Ok(vec![2]) }).await.unwrap();
+        assert_eq!(*second, vec![2]);
+    }
+
     #[tokio::test]
     async fn test_camera_service() {
         let service = CameraService::new();
         let controller = service.get_controller();
         assert!(!controller.lock().await.is_initialized());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_save_snapshot_prunes_oldest() {
+        use chrono::TimeZone;
+
+        let dir = std::env::temp_dir().join(format!("terra_snapshots_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        for minute in 0..4 {
+            let at = chrono::Local.with_ymd_and_hms(2024, 6, 1, 12, minute, 0).unwrap();
+            save_snapshot(&dir, b"jpeg", at, 3).unwrap();
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec![
+            "snapshot_20240601_120100.jpg",
+            "snapshot_20240601_120200.jpg",
+            "snapshot_20240601_120300.jpg",
+        ]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -2,24 +2,107 @@
 use std::fs;
 use std::error::Error;
 use toml;
-use chrono::NaiveTime;
+use serde::Deserialize;
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use crate::modules::gpio::{ColorOrder, LedWiring, RGBWW, RelayType};
 
 //top level config struct
+//
+// Only the relay pins in [gpio] are required, every other section and field
+// falls back to the defaults below (which match the shipped config.toml).
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub main: MainConfig,
     pub gpio: GpioConfig,
+    #[serde(default)]
     pub db: ScheduleConfig,
+    #[serde(default)]
     pub web: WebConfig, 
+    #[serde(default)]
     pub light_control: LightControlConfig,
+    #[serde(default)]
     pub get_data: GetDataConfig,
+    #[serde(default)]
     pub led: LedConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub sensors: SensorsConfig,
+    #[serde(default)]
+    pub cam: CamConfig,
+    #[serde(default)]
+    pub away: AwayConfig,
+    #[serde(default)]
+    pub thresholds: ThresholdsConfig,
 }
 
+// Fields that have no sensible default and must be present in config.toml
+const REQUIRED_FIELDS: &[(&str, &str)] = &[
+    ("gpio", "uv_relay1"),
+    ("gpio", "uv_relay2"),
+    ("gpio", "heat_relay"),
+    ("gpio", "led_relay"),
+];
+
 //main config struct
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 pub struct MainConfig {
     pub debug: bool,
+    pub name: Option<String>,                     // Name of this terrarium in /api/values and /api/system/status, to tell instances apart (default: "terrarium")
+    pub startup_stagger_ms: Option<u64>,          // Delay between loads brought up at startup (default: 500)
+    pub startup_order: Option<Vec<RelayType>>,    // Order loads are brought up in, unlisted ones follow (default: ["led", "uv1", "uv2", "heat"])
+    pub timezone: Option<String>,                 // IANA zone schedules are evaluated in, e.g. "Europe/Berlin" (default: system local time)
+    pub boot_behavior: BootBehavior,              // What the loads do after a restart (default: schedule)
+    pub simulation: bool,                         // Demo rig: allow POST /api/schedule/simulate to fast-forward the schedule on the loads (default: false)
+    pub restart_backoff_secs: Option<u64>,        // Wait before a background loop that panicked is started again (default: 5)
+}
+
+/// Longest accepted `[main] name`
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Loads brought up one by one at startup, in their default order
+pub const STARTUP_RELAYS: [RelayType; 4] = [RelayType::LED, RelayType::UV1, RelayType::UV2, RelayType::Heat];
+
+/// What the loads do when the controller starts, e.g. after a power loss
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootBehavior {
+    /// Switch every load back to its last state from the relay event log and
+    /// the strip to its last color; the control loops take over at the next
+    /// schedule transition
+    Resume,
+    /// Apply the schedule right away
+    #[default]
+    Schedule,
+    /// Keep every load off and the control loops paused until `POST /api/system/resume`
+    SafeOff,
+}
+
+impl BootBehavior {
+    /// The state a load is brought up in.
+    ///
+    /// # Arguments
+    ///
+    /// * `scheduled` - What the schedule wants right now
+    /// * `last` - The load's last logged state, None if it was never switched
+    pub fn initial_state(self, scheduled: bool, last: Option<bool>) -> bool {
+        match self {
+            BootBehavior::Resume => last.unwrap_or(scheduled),
+            BootBehavior::Schedule => scheduled,
+            BootBehavior::SafeOff => false,
+        }
+    }
+
+    /// Whether the control loops wait for a manual resume
+    pub fn holds_control_loops(self) -> bool {
+        self == BootBehavior::SafeOff
+    }
 }
 
 //GPIO struct
@@ -32,36 +115,273 @@ pub struct GpioConfig {
     pub ic_count: Option<usize>,
     pub ds18b20_bus: Option<u8>,
     pub dht22_pin: Option<u8>,
-    pub veml6075_uv1: u8,
-    pub veml6075_uv2: u8,
+    #[serde(default)]
+    pub veml6075_uv1: u8,       // I2C bus of the first UV sensor (default: 0)
+    #[serde(default = "default_veml6075_uv2")]
+    pub veml6075_uv2: u8,       // I2C bus of the second UV sensor (default: 1)
+    pub veml6075_uv1_address: Option<u8>, // 7-bit I2C address of the first UV sensor (default: 0x10)
+    pub veml6075_uv2_address: Option<u8>, // 7-bit I2C address of the second UV sensor (default: 0x10)
+    pub fan_relay: Option<u8>,  // Optional cooling fan relay, used by the heat_uv_fan overheat action
+    pub aux_relay: Option<u8>,  // Optional auxiliary relay (feeder, pump, valve) driven by POST /api/relay/pulse
+    pub max_pulse_seconds: Option<u64>, // Longest pulse accepted by POST /api/relay/pulse (default: 30)
+    pub uv1_pwm_channel: Option<u8>,    // Hardware PWM channel (0 or 1) dimming the first UV ballast (unset = relay only)
+    pub uv2_pwm_channel: Option<u8>,    // Hardware PWM channel (0 or 1) dimming the second UV ballast (unset = relay only)
+    pub uv_pwm_frequency: Option<f64>,  // Frequency of the UV dim signal in Hz (default: 1000)
+    pub heat_pwm_channel: Option<u8>,   // Hardware PWM channel (0 or 1) driving a dimmable heat element (unset = relay only)
+    pub heat_pwm_frequency: Option<f64>, // Frequency of the heat drive signal in Hz (default: 1000)
+    #[serde(default)]
+    pub active_low: bool,       // Relay board switches on when the pin is low (default: active-high)
+    #[serde(default)]
+    pub initial_state: HashMap<RelayType, bool>, // State each relay is claimed in at boot, e.g. { led = true } (default: all off, heat always off)
 }
 
+fn default_veml6075_uv2() -> u8 {
+    1
+}
+
+// The VEML6075 has a fixed address; other values only make sense behind a translator or mux
+const VEML6075_DEFAULT_ADDRESS: u8 = 0x10;
+
 //lightControl struct
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct LightControlConfig {
-    pub overheat_temp: u8,
-    pub overheat_time: u64, // Time in seconds
+    pub overheat_temp: u8,  // Heat is cut at this basking temperature in °C (default: 50)
+    pub control_overheat_temp: Option<u8>, // Heat is also cut at this control-zone temperature in °C (unset = basking only)
+    pub overheat_time: u64, // Cooldown in seconds before heat may come back (default: 3000)
+    pub overheat_action: OverheatAction, // Which loads to shed on overheat (default: heat_only)
+    pub max_rise_per_minute: Option<f32>, // Cut heat early when the basking temp rises faster than this (°C/min, unset = off)
+    pub trend_window: Option<u64>,        // Seconds of readings used to compute the rise rate (default: 300)
+    pub max_heat_on_time: Option<u64>,    // Cut heat after this many seconds on without a break, until resumed (unset = off)
+    pub heat_kp: Option<f32>,             // PID gain in % duty per °C below the schedule's heat_target (default: 10)
+    pub heat_ki: Option<f32>,             // PID gain in % duty per °C·minute of accumulated error (default: 1)
+    pub heat_kd: Option<f32>,             // PID gain in % duty per °C/minute of temperature change (default: 0)
+    pub startup_grace: Option<u64>,       // Seconds after start in which failed probe reads are waited out; later ones hold the heat off (default: 120, max 600)
+}
+
+impl Default for LightControlConfig {
+    fn default() -> Self {
+        Self {
+            overheat_temp: 50,
+            control_overheat_temp: None,
+            overheat_time: 3000,
+            overheat_action: OverheatAction::default(),
+            max_rise_per_minute: None,
+            trend_window: None,
+            max_heat_on_time: None,
+            heat_kp: None,
+            heat_ki: None,
+            heat_kd: None,
+            startup_grace: None,
+        }
+    }
+}
+
+/// Loads shed when overheat protection trips
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverheatAction {
+    /// Cut the heat relay only
+    #[default]
+    HeatOnly,
+    /// Cut the heat relay and both UV lights
+    HeatUv,
+    /// Cut heat and UV, and switch the cooling fan on
+    HeatUvFan,
+}
+
+impl OverheatAction {
+    /// Whether the UV lights are cut during an overheat
+    pub fn sheds_uv(&self) -> bool {
+        matches!(self, OverheatAction::HeatUv | OverheatAction::HeatUvFan)
+    }
+
+    /// Whether the cooling fan is switched on during an overheat
+    pub fn runs_fan(&self) -> bool {
+        matches!(self, OverheatAction::HeatUvFan)
+    }
+}
+
+/// Which requests the web server logs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestLogMode {
+    /// Every request
+    #[default]
+    All,
+    /// Only requests answered with a 4xx or 5xx status
+    Errors,
+    /// Nothing
+    Off,
+}
+
+impl RequestLogMode {
+    /// Whether a request answered with `status` is logged
+    pub fn logs(&self, status: u16) -> bool {
+        match self {
+            RequestLogMode::All => true,
+            RequestLogMode::Errors => status >= 400,
+            RequestLogMode::Off => false,
+        }
+    }
 }
 
 // New GetDataConfig struct
 #[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct GetDataConfig {
-    pub retry: u8,              // Number of retries for failed sensor readings
+    pub retry: u8,              // Number of retries for failed sensor readings (default: 3)
     pub interval: Option<u64>,  // Interval in seconds for data collection (default: 60)
-    pub backup_sensor: bool,    // Whether to use DHT22 as backup for overheat detection
-    pub storage_days: Option<u32>, // How many days of data to keep (for automatic cleanup)
+    pub backup_sensor: bool,    // Whether to use DHT22 as backup for overheat detection (default: false)
+    pub storage_days: Option<u32>, // Days of raw readings to keep; older ones live on as hourly averages (default: keep all)
+    pub rollup_after_hours: Option<u32>,   // Graph ranges longer than this are drawn from hourly averages (default: 48)
+    pub max_graph_points: Option<u32>,     // Graphs with more readings are averaged down to at most this many points (default: 720)
+    pub ds18b20_timeout_ms: Option<u64>,   // Give up on a single DS18B20 read after this long (default: 2000)
+    pub dht22_timeout_ms: Option<u64>,     // Give up on a single DHT22 read after this long (default: 3000)
+    pub veml6075_timeout_ms: Option<u64>,  // Give up on a single VEML6075 read after this long (default: 1000)
+    pub read_mode: ReadMode,               // Whether the sensor buses are read one after another, at once or spread over the interval (default: sequential)
+}
+
+impl Default for GetDataConfig {
+    fn default() -> Self {
+        Self {
+            retry: 3,
+            interval: None,
+            backup_sensor: false,
+            storage_days: None,
+            rollup_after_hours: None,
+            max_graph_points: None,
+            ds18b20_timeout_ms: None,
+            dht22_timeout_ms: None,
+            veml6075_timeout_ms: None,
+            read_mode: ReadMode::default(),
+        }
+    }
+}
+
+/// How `read_all_sensors` reads the independent sensor buses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadMode {
+    /// One sensor at a time
+    #[default]
+    Sequential,
+    /// The 1-Wire probes, the DHT22 and the UV sensors at the same time. Sensors
+    /// sharing a bus (the probes, two UV sensors on one I2C bus) are still read
+    /// one after another.
+    Concurrent,
+    /// One sensor at a time, spread evenly over the collection interval, each
+    /// updating the current readings as it comes in. Avoids a burst of bus
+    /// traffic at every interval and keeps the individual values fresher.
+    Staggered,
 }
 
 // web config struct
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct WebConfig {
-    pub address: String,    // Web server address (e.g., "127.0.0.1")
-    pub port: u16,          // Web server port (e.g., 8080)
+    pub address: String,    // Web server address (e.g., "127.0.0.1", or "::" for all IPv6 interfaces, default: "0.0.0.0")
+    pub port: u16,          // Web server port (default: 80)
+    pub bind: Option<Vec<String>>, // Optional list of socket addresses (e.g. ["0.0.0.0:80", "[::1]:80"]), overrides address/port
+    pub sensor_read_cooldown: Option<u64>, // Minimum seconds between manual sensor reads (default: 10)
+    pub request_log: RequestLogMode, // Which API requests are logged at DEBUG: "all", "errors" or "off" (default: "all")
+    pub require_api_key: bool,      // Reject /api requests without a valid API key (default: false)
+    pub admin_key: Option<String>,  // Key with admin scope from this file, e.g. to create the first stored keys
+    pub camera_port: Option<u16>,   // Port of the MJPEG camera stream server on `address`, unset to not start it (default: unset)
+    pub max_body_kb: Option<u64>,   // Larger request bodies are rejected with 413 (default: 256)
+    pub max_import_body_kb: Option<u64>, // Same for the CSV upload of POST /api/data/import (default: 8192)
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            address: "0.0.0.0".to_string(),
+            port: 80,
+            bind: None,
+            sensor_read_cooldown: None,
+            request_log: RequestLogMode::default(),
+            require_api_key: false,
+            admin_key: None,
+            camera_port: None,
+            max_body_kb: None,
+            max_import_body_kb: None,
+        }
+    }
+}
+
+// Camera settings
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CamConfig {
+    pub snapshot_ttl_ms: Option<u64>, // Concurrent snapshot requests within this window share one capture (default: 1000)
 }
 
-//schedule struct
+// Away mode settings, only used while away mode is switched on
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AwayConfig {
+    pub alert_margin: Option<f32>,      // Temperature warnings fire this many °C below overheat_temp (default: 5.0)
+    pub snapshot_interval: Option<u64>, // Seconds between camera snapshots saved to snapshots/ (default: 300)
+    pub max_snapshots: Option<usize>,   // Saved snapshots kept, oldest deleted first (default: 288)
+}
+
+// Reading ranges; a reading outside its channel's range logs a warning. Bounds without a default aren't checked while unset.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThresholdsConfig {
+    pub min_basking_temp: Option<f32>,  // °C (default: unset)
+    pub max_basking_temp: Option<f32>,  // °C (default: 45)
+    pub min_control_temp: Option<f32>,  // °C (default: unset)
+    pub max_control_temp: Option<f32>,  // °C (default: 35)
+    pub min_cool_temp: Option<f32>,     // °C (default: unset)
+    pub max_cool_temp: Option<f32>,     // °C (default: unset)
+    pub min_humidity: Option<f32>,      // % (default: 30)
+    pub max_humidity: Option<f32>,      // % (default: unset)
+    pub min_uv1: Option<f32>,           // UV index (default: unset)
+    pub max_uv1: Option<f32>,           // UV index (default: unset)
+    pub min_uv2: Option<f32>,           // UV index (default: unset)
+    pub max_uv2: Option<f32>,           // UV index (default: unset)
+}
+
+/// The configured range of one sensor channel, see `ThresholdsConfig::channels`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub channel: &'static str,  // "basking", "control", "cool", "humidity", "uv1" or "uv2"
+    pub unit: &'static str,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+// Sensor addressing
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SensorsConfig {
+    #[serde(default)]
+    pub ds18b20: HashMap<String, String>, // Probe name -> 1-Wire device id ("28-xxxxxxxxxxxx"); "basking", "control" and "cool" drive the controllers, other names are only recorded
+    pub spike_filter: Option<bool>,       // Discard implausible temperature readings (default: true)
+    pub max_temp_jump: Option<f32>,       // Largest accepted deviation in °C from the recent median (default: 5.0)
+    pub filter_window: Option<usize>,     // Recent readings the median is taken over (default: 5)
+    pub temp_range: Option<(f32, f32)>,   // Plausible temperature [min, max] in °C reported by /api/values?verbose=true (default: [-10, 60])
+    pub humidity_range: Option<(f32, f32)>, // Plausible humidity [min, max] in % (default: [0, 100])
+    pub uv_range: Option<(f32, f32)>,     // Plausible UV index [min, max] (default: [0, 15])
+    pub fault_after: Option<u32>,         // Failed reads in a row before a sensor is reported faulted (default: 3)
+}
+
+// Log file rotation settings
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoggingConfig {
+    pub max_file_size: Option<u64>, // Roll a log file over once it exceeds this many bytes (default: 1 MiB)
+    pub max_files: Option<usize>,   // Total log files kept in logs/, oldest deleted first (default: 30)
+    pub suppress: Option<Vec<String>>, // Levels or message prefixes that are never stored, WARNING/ERROR excepted (default: none)
+    pub sampled: Option<Vec<String>>,  // Levels or message prefixes stored only once per sample_rate lines (default: none)
+    pub sample_rate: Option<u32>,      // Keep 1 in this many sampled lines (default: 1 = all)
+}
+
+//schedule struct, the def_* values are used for weeks without a stored schedule
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct ScheduleConfig {
+    pub path: Option<String>,   // Path to the SQLite database file (default: "data.db")
+    pub busy_timeout_ms: Option<u64>,   // How long SQLite waits on a locked database (default: 5000)
+    pub write_retries: Option<u8>,      // Retries of a write that still finds the database busy (default: 3)
+    pub on_unavailable: DbUnavailable,  // What to do when the database can't be opened at startup (default: degraded)
+    pub seed_schedule: Option<bool>,    // Fill an empty schedule table with the def_* weeks at startup (default: true)
     pub def_uv1_start: String,
     pub def_uv1_end: String,
     pub def_uv2_start: String,
@@ -75,12 +395,69 @@ pub struct ScheduleConfig {
     pub def_led_CW: i32,
 }
 
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            busy_timeout_ms: None,
+            write_retries: None,
+            on_unavailable: DbUnavailable::default(),
+            seed_schedule: None,
+            def_uv1_start: "06:30".to_string(),
+            def_uv1_end: "19:00".to_string(),
+            def_uv2_start: "07:00".to_string(),
+            def_uv2_end: "19:30".to_string(),
+            def_heat_start: "10:00".to_string(),
+            def_heat_end: "14:30".to_string(),
+            def_led_R: 150,
+            def_led_G: 150,
+            def_led_B: 128,
+            def_led_WW: 128,
+            def_led_CW: 128,
+        }
+    }
+}
+
+/// What the controller does when the database can't be opened, e.g. a corrupt
+/// file or a full disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DbUnavailable {
+    /// Keep the loads under control on an empty in-memory database: weeks come
+    /// from the `def_*` defaults, logs go to the log files only and nothing is
+    /// kept across a restart
+    #[default]
+    Degraded,
+    /// Refuse to start
+    Exit,
+}
+
 // LED configuration
 #[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct LedConfig {
-    pub default_mode: String,                     // Either "manual" or "natural"
-    pub default_brightness: u8,                   // 0-100% brightness
-    pub season_weight: f32,                       // 0.0 - 1.0 weight of season color
+    pub default_mode: String,                     // Either "manual" or "natural" (default: "natural")
+    pub default_brightness: u8,                   // 0-100% brightness (default: 50)
+    pub season_weight: f32,                       // 0.0 - 1.0 weight of season color (default: 0.3)
+    pub fade_duration: Option<u64>,               // Fade at LED window edges in seconds (default: 0 = snap)
+    pub fade_steps: Option<u32>,                  // Number of steps per fade (default: 50)
+    pub ww_kelvin: Option<u32>,                   // Color temperature of the warm white LEDs (default: 2700)
+    pub cw_kelvin: Option<u32>,                   // Color temperature of the cool white LEDs (default: 6500)
+    pub auto_season: Option<bool>,                // Derive season color and weight from the date (default: false)
+    pub season_keyframes: Option<Vec<SeasonKeyframe>>, // [[led.season_keyframes]] used by auto_season (default: see default_season_keyframes)
+    pub power: LedPowerConfig,                    // [led.power] current estimate and budget
+    pub min_on_hours: Option<f32>,                // Shortest daily LED window a schedule may store (default: 4)
+    pub max_on_hours: Option<f32>,                // Longest daily LED window a schedule may store (default: 16)
+    pub color_order: Option<String>,              // Channel order on the wire, R/G/B/W(arm)/C(ool) once each (default: "GRBWC")
+    pub alert_color: Option<String>,              // Color pulsed on overheat or a sensor fault, "R,G,B,WW,CW" (default: "255,0,0,0,0")
+    pub max_r: Option<u32>,                       // Highest value ever written to the red channel, 0-255 (default: 255)
+    pub max_g: Option<u32>,                       // (default: 255)
+    pub max_b: Option<u32>,                       // (default: 255)
+    pub max_ww: Option<u32>,                      // e.g. 200 to spare heat-sensitive white LEDs (default: 255)
+    pub max_cw: Option<u32>,                      // (default: 255)
+    pub driver: LedDriverKind,                    // How the data line is driven (default: spi)
+    pub data_pin: Option<u8>,                     // BCM pin of the data line with driver = "bitbang"
+    pub override_precedence: OverridePrecedence,  // Whether a manual LED override or the schedule wins while both apply (default: override_always)
 
     // Natural light presets
     pub morning_r: u8,
@@ -102,27 +479,286 @@ pub struct LedConfig {
     pub evening_cw: u8,
 }
 
+impl Default for LedConfig {
+    fn default() -> Self {
+        Self {
+            default_mode: "natural".to_string(),
+            default_brightness: 50,
+            season_weight: 0.3,
+            fade_duration: None,
+            fade_steps: None,
+            ww_kelvin: None,
+            cw_kelvin: None,
+            auto_season: None,
+            season_keyframes: None,
+            power: LedPowerConfig::default(),
+            min_on_hours: None,
+            max_on_hours: None,
+            color_order: None,
+            alert_color: None,
+            max_r: None,
+            max_g: None,
+            max_b: None,
+            max_ww: None,
+            max_cw: None,
+            driver: LedDriverKind::default(),
+            data_pin: None,
+            override_precedence: OverridePrecedence::default(),
+
+            morning_r: 255,
+            morning_g: 180,
+            morning_b: 100,
+            morning_ww: 200,
+            morning_cw: 50,
+
+            noon_r: 255,
+            noon_g: 240,
+            noon_b: 220,
+            noon_ww: 50,
+            noon_cw: 255,
+
+            evening_r: 255,
+            evening_g: 140,
+            evening_b: 50,
+            evening_ww: 255,
+            evening_cw: 0,
+        }
+    }
+}
+
+// Current drawn by the LED strip, used to estimate the draw and keep it within the PSU's budget
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LedPowerConfig {
+    pub max_current_ma: Option<f32>, // Budget for the whole strip; brighter colors are scaled down (default: unlimited)
+    pub r_ma: Option<f32>,           // Current of one IC's red channel at full brightness (default: 12.0)
+    pub g_ma: Option<f32>,           // (default: 12.0)
+    pub b_ma: Option<f32>,           // (default: 12.0)
+    pub ww_ma: Option<f32>,          // (default: 12.0)
+    pub cw_ma: Option<f32>,          // (default: 12.0)
+}
+
+/// Which `LedDriver` writes to the strip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedDriverKind {
+    /// Hardware SPI0
+    #[default]
+    Spi,
+    /// GPIO `data_pin` toggled by the CPU, for boards where SPI is taken; see `BitBangStrip`
+    Bitbang,
+}
+
+/// Who decides the LED strip inside the LED window while a manual override is
+/// stored: the manual color (`led_settings.override`) or the strip switched off
+/// (`led_settings.enabled = false`). Outside the window the strip is always off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverridePrecedence {
+    /// The override holds until it is changed through the API
+    #[default]
+    OverrideAlways,
+    /// The override holds until the LED window next opens or closes, then it
+    /// is cleared and the schedule takes over
+    OverrideUntilNextTransition,
+    /// Stored overrides are ignored, the schedule is always followed
+    ScheduleAlways,
+}
+
+// One point of the seasonal color curve; days between keyframes are interpolated
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SeasonKeyframe {
+    pub day_of_year: u32, // 1-366, the day this color applies in full
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub ww: u8,
+    pub cw: u8,
+    pub weight: f32,      // Season weight on this day, 0.0 - 1.0
+}
+
+/// Mid-season keyframes: warm and strong in winter, neutral and faint in summer
+pub fn default_season_keyframes() -> Vec<SeasonKeyframe> {
+    let keyframe = |day_of_year, (r, g, b, ww, cw), weight| SeasonKeyframe { day_of_year, r, g, b, ww, cw, weight };
+    vec![
+        keyframe(15, (255, 170, 100, 255, 40), 0.4),   // Winter
+        keyframe(105, (255, 210, 170, 180, 140), 0.3), // Spring
+        keyframe(196, (240, 240, 235, 100, 255), 0.2), // Summer
+        keyframe(288, (255, 190, 130, 220, 90), 0.3),  // Autumn
+    ]
+}
+
 //validation logic
 impl Config {
     pub fn validate(&self) -> Result<(), String> {
         self.main.validate()?;
+        self.gpio.validate()?;
         self.get_data.validate()?;
         self.db.validate()?;
         self.web.validate()?;
         self.light_control.validate()?;
         self.led.validate()?;
+        self.logging.validate()?;
+        self.sensors.validate()?;
+        self.away.validate()?;
+        self.thresholds.validate()?;
+
+        if self.light_control.overheat_action.runs_fan() && self.gpio.fan_relay.is_none() {
+            return Err("overheat_action = \"heat_uv_fan\" requires gpio.fan_relay to be set".to_string());
+        }
+
+        if let LedWiring::BitBang { pin } = self.led.wiring() {
+            let mut pins = self.gpio.claimed_pins();
+            pins.push(("led.data_pin", pin));
+            check_pin_conflicts(&pins)?;
+        }
+
         Ok(())
     }
 }
 
 impl MainConfig {
+    /// Delay between loads switched on during startup
+    pub fn startup_stagger(&self) -> Duration {
+        Duration::from_millis(self.startup_stagger_ms.unwrap_or(500))
+    }
+
+    /// Every startup load in the order it's brought up: the configured ones
+    /// first, then the rest in the default order
+    pub fn startup_order(&self) -> Vec<RelayType> {
+        let mut order = self.startup_order.clone().unwrap_or_default();
+        for relay in STARTUP_RELAYS {
+            if !order.contains(&relay) {
+                order.push(relay);
+            }
+        }
+        order
+    }
+
+    /// The configured schedule timezone, `None` when the system timezone is used
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone.as_deref().and_then(|tz| tz.parse().ok())
+    }
+
+    /// Wall-clock time of `instant` in the schedule timezone
+    pub fn schedule_time(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        match self.timezone() {
+            Some(tz) => instant.with_timezone(&tz).naive_local(),
+            None => instant.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    /// Current wall-clock time in the schedule timezone
+    pub fn schedule_now(&self) -> NaiveDateTime {
+        self.schedule_time(Utc::now())
+    }
+
+    /// Name this instance reports itself as
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("terrarium")
+    }
+
+    /// Wait before a background loop that panicked is restarted, see `watchdog::supervise`
+    pub fn restart_backoff(&self) -> Duration {
+        Duration::from_secs(self.restart_backoff_secs.unwrap_or(5))
+    }
+
     pub fn validate(&self) -> Result<(), String> {
-        // No specific validation needed since debug is a boolean
+        let name = self.name();
+        if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!("main.name must be 1 to {} characters, got: {:?}", MAX_NAME_LENGTH, name));
+        }
+
+        if let Some(tz) = &self.timezone {
+            tz.parse::<Tz>()
+                .map_err(|_| format!("timezone must be an IANA zone such as \"Europe/Berlin\", got: {:?}", tz))?;
+        }
+
+        if self.startup_stagger() > Duration::from_secs(60) {
+            return Err(format!("startup_stagger_ms must be at most 60000, got: {}", self.startup_stagger().as_millis()));
+        }
+
+        if !(1..=300).contains(&self.restart_backoff().as_secs()) {
+            return Err(format!("restart_backoff_secs must be between 1 and 300, got: {}", self.restart_backoff().as_secs()));
+        }
+
+        let order = self.startup_order.as_deref().unwrap_or_default();
+        for (i, relay) in order.iter().enumerate() {
+            if !STARTUP_RELAYS.contains(relay) {
+                return Err(format!("startup_order may only list led, uv1, uv2 and heat, got: {}", relay.as_str()));
+            }
+            if order[..i].contains(relay) {
+                return Err(format!("startup_order lists {} twice", relay.as_str()));
+            }
+        }
+
         Ok(())
     }
 }
 
 impl GpioConfig {
+    /// Number of WS2805 ICs on the LED strip
+    pub fn ic_count(&self) -> usize {
+        self.ic_count.unwrap_or(16)
+    }
+
+    /// Longest pulse accepted by `POST /api/relay/pulse`
+    pub fn max_pulse(&self) -> Duration {
+        Duration::from_secs(self.max_pulse_seconds.unwrap_or(30))
+    }
+
+    /// GPIO pin of the DHT22 humidity sensor
+    pub fn dht22_pin(&self) -> u8 {
+        self.dht22_pin.unwrap_or(18)
+    }
+
+    /// I2C (bus, address) of the first VEML6075 UV sensor
+    pub fn uv1_sensor(&self) -> (u8, u8) {
+        (self.veml6075_uv1, self.veml6075_uv1_address.unwrap_or(VEML6075_DEFAULT_ADDRESS))
+    }
+
+    /// I2C (bus, address) of the second VEML6075 UV sensor
+    pub fn uv2_sensor(&self) -> (u8, u8) {
+        (self.veml6075_uv2, self.veml6075_uv2_address.unwrap_or(VEML6075_DEFAULT_ADDRESS))
+    }
+
+    /// Frequency of the PWM signal dimming the UV ballasts, in Hz
+    pub fn uv_pwm_frequency(&self) -> f64 {
+        self.uv_pwm_frequency.unwrap_or(1000.0)
+    }
+
+    /// Frequency of the PWM signal driving a dimmable heat element, in Hz
+    pub fn heat_pwm_frequency(&self) -> f64 {
+        self.heat_pwm_frequency.unwrap_or(1000.0)
+    }
+
+    /// Every GPIO pin this section claims, with the key claiming it: the relays,
+    /// the DHT22 and the pins of the PWM channels in use
+    pub fn claimed_pins(&self) -> Vec<(&'static str, u8)> {
+        let mut pins = vec![
+            ("uv_relay1", self.uv_relay1),
+            ("uv_relay2", self.uv_relay2),
+            ("heat_relay", self.heat_relay),
+            ("led_relay", self.led_relay),
+        ];
+        pins.extend(self.fan_relay.map(|pin| ("fan_relay", pin)));
+        pins.extend(self.aux_relay.map(|pin| ("aux_relay", pin)));
+        pins.push(("dht22_pin", self.dht22_pin()));
+        for (name, channel) in [
+            ("uv1_pwm_channel", self.uv1_pwm_channel),
+            ("uv2_pwm_channel", self.uv2_pwm_channel),
+            ("heat_pwm_channel", self.heat_pwm_channel),
+        ] {
+            pins.extend(channel.and_then(|channel| PWM_CHANNEL_PINS.get(channel as usize)).map(|&pin| (name, pin)));
+        }
+        pins
+    }
+
+    /// State `relay` is driven to as soon as its pin is claimed, before any
+    /// control loop runs. Heat always starts off, whatever is configured.
+    pub fn initial_state(&self, relay: RelayType) -> bool {
+        relay != RelayType::Heat && self.initial_state.get(&relay).copied().unwrap_or(false)
+    }
+
     pub fn load() -> Self {
         let config_str = std::fs::read_to_string("config.toml")
             .expect("Failed to read config.toml");
@@ -169,12 +805,59 @@ impl GpioConfig {
             veml6075_uv1: gpio.get("veml6075_uv1")
                 .and_then(|v| v.as_integer())
                 .map(|v| v as u8)
-                .expect("Missing or invalid veml6075_uv1 in config"),
+                .unwrap_or(0),
             
             veml6075_uv2: gpio.get("veml6075_uv2")
                 .and_then(|v| v.as_integer())
                 .map(|v| v as u8)
-                .expect("Missing or invalid veml6075_uv2 in config"),
+                .unwrap_or_else(default_veml6075_uv2),
+
+            veml6075_uv1_address: gpio.get("veml6075_uv1_address")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            veml6075_uv2_address: gpio.get("veml6075_uv2_address")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            fan_relay: gpio.get("fan_relay")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            aux_relay: gpio.get("aux_relay")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            max_pulse_seconds: gpio.get("max_pulse_seconds")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u64),
+
+            uv1_pwm_channel: gpio.get("uv1_pwm_channel")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            uv2_pwm_channel: gpio.get("uv2_pwm_channel")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            uv_pwm_frequency: gpio.get("uv_pwm_frequency")
+                .and_then(|v| v.as_float().or_else(|| v.as_integer().map(|i| i as f64))),
+
+            heat_pwm_channel: gpio.get("heat_pwm_channel")
+                .and_then(|v| v.as_integer())
+                .map(|v| v as u8),
+
+            heat_pwm_frequency: gpio.get("heat_pwm_frequency")
+                .and_then(|v| v.as_float().or_else(|| v.as_integer().map(|i| i as f64))),
+
+            active_low: match gpio.get("active_low") {
+                Some(v) => v.as_bool().expect("Invalid active_low in config, expected true or false"),
+                None => false,
+            },
+
+            initial_state: gpio.get("initial_state")
+                .map(|v| v.clone().try_into().expect("Invalid initial_state in config, expected e.g. { led = true }"))
+                .unwrap_or_default(),
         }
     }
     
@@ -196,20 +879,89 @@ impl GpioConfig {
             return Err(format!("Invalid LED relay GPIO pin: {}", self.led_relay));
         }
         
-        // Check for pin conflicts
-        let pins = vec![self.uv_relay1, self.uv_relay2, self.heat_relay, self.led_relay];
-        for i in 0..pins.len() {
-            for j in i+1..pins.len() {
-                if pins[i] == pins[j] {
-                    return Err(format!("GPIO pin conflict: Pin {} used multiple times", pins[i]));
+        if let Some(fan_relay) = self.fan_relay {
+            if fan_relay > 27 {
+                return Err(format!("Invalid fan relay GPIO pin: {}", fan_relay));
+            }
+        }
+        
+        if let Some(aux_relay) = self.aux_relay {
+            if aux_relay > 27 {
+                return Err(format!("Invalid aux relay GPIO pin: {}", aux_relay));
+            }
+        }
+        
+        // 0x00-0x07 and 0x78-0x7F are reserved by the I2C spec
+        for (name, (_, address)) in [("veml6075_uv1_address", self.uv1_sensor()), ("veml6075_uv2_address", self.uv2_sensor())] {
+            if !(0x08..=0x77).contains(&address) {
+                return Err(format!("Invalid {}: {:#04x}. Must be a 7-bit I2C address in 0x08-0x77.", name, address));
+            }
+        }
+        
+        if self.uv1_sensor() == self.uv2_sensor() {
+            let (bus, address) = self.uv1_sensor();
+            return Err(format!(
+                "Both UV sensors are configured at address {:#04x} on I2C bus {}; use separate buses or distinct addresses",
+                address, bus
+            ));
+        }
+        
+        if self.max_pulse().is_zero() {
+            return Err("max_pulse_seconds must be at least 1".to_string());
+        }
+        
+        if self.initial_state.get(&RelayType::Heat) == Some(&true) {
+            return Err("initial_state may not switch heat on at boot; heat always starts off".to_string());
+        }
+        for (relay, pin) in [(RelayType::Fan, self.fan_relay), (RelayType::Aux, self.aux_relay)] {
+            if pin.is_none() && self.initial_state.contains_key(&relay) {
+                return Err(format!("initial_state sets {} but no {}_relay is configured", relay.as_str(), relay.as_str()));
+            }
+        }
+        
+        let channels = [
+            ("uv1_pwm_channel", self.uv1_pwm_channel),
+            ("uv2_pwm_channel", self.uv2_pwm_channel),
+            ("heat_pwm_channel", self.heat_pwm_channel),
+        ];
+        for (i, &(name, channel)) in channels.iter().enumerate() {
+            if let Some(channel) = channel {
+                if channel > 1 {
+                    return Err(format!("Invalid {}: {}. The Pi has PWM channels 0 and 1.", name, channel));
+                }
+                if let Some((other, _)) = channels[..i].iter().find(|&&(_, used)| used == Some(channel)) {
+                    return Err(format!("{} and {} must be different channels", other, name));
                 }
             }
         }
         
-        Ok(())
+        for (name, frequency) in [("uv_pwm_frequency", self.uv_pwm_frequency()), ("heat_pwm_frequency", self.heat_pwm_frequency())] {
+            if frequency.is_nan() || frequency <= 0.0 {
+                return Err(format!("{} must be greater than 0, got: {}", name, frequency));
+            }
+        }
+        
+        // Check for pin conflicts, PWM outputs included
+        check_pin_conflicts(&self.claimed_pins())
+    }
+}
+
+/// BCM pins of hardware PWM channels 0 and 1 with the default pwm-2chan overlay
+pub const PWM_CHANNEL_PINS: [u8; 2] = [18, 19];
+
+/// Rejects two keys claiming the same GPIO pin
+fn check_pin_conflicts(pins: &[(&str, u8)]) -> Result<(), String> {
+    for (i, &(name, pin)) in pins.iter().enumerate() {
+        if let Some((other, _)) = pins[..i].iter().find(|&&(_, used)| used == pin) {
+            return Err(format!("GPIO pin conflict: {} and {} both use pin {}", other, name, pin));
+        }
     }
+    Ok(())
 }
 
+/// Longest accepted `[light_control] startup_grace`
+pub const MAX_STARTUP_GRACE: Duration = Duration::from_secs(600);
+
 impl LightControlConfig {
     pub fn validate(&self) -> Result<(), String> {
 
@@ -221,6 +973,15 @@ impl LightControlConfig {
                 ));
             }
 
+            if let Some(temp) = self.control_overheat_temp {
+                if temp > 60 {
+                    return Err(format!(
+                        "Invalid control_overheat_temp: {}. Must be in the range 0-60°C.",
+                        temp
+                    ));
+                }
+            }
+
             // Validate overheat_time (minimum 15 minutes = 900 seconds)
             if self.overheat_time < 900 {
                 return Err(format!(
@@ -229,12 +990,101 @@ impl LightControlConfig {
                 ));
             }
 
+            if let Some(rate) = self.max_rise_per_minute {
+                if !(rate > 0.0) {
+                    return Err(format!("Invalid max_rise_per_minute: {}. Must be greater than 0.", rate));
+                }
+            }
+
+            if self.trend_window() < Duration::from_secs(60) {
+                return Err(format!(
+                    "Invalid trend_window: {} seconds. Must be at least 60 seconds.",
+                    self.trend_window().as_secs()
+                ));
+            }
+
+            if let Some(limit) = self.max_heat_on_time {
+                if limit < 600 {
+                    return Err(format!(
+                        "Invalid max_heat_on_time: {} seconds. Must be at least 600 seconds (10 minutes).",
+                        limit
+                    ));
+                }
+            }
+
+            // Heat stays off until the first real reading, so the grace can't hold it off for long
+            if self.startup_grace() > MAX_STARTUP_GRACE {
+                return Err(format!(
+                    "Invalid startup_grace: {} seconds. Must be at most {} seconds.",
+                    self.startup_grace().as_secs(), MAX_STARTUP_GRACE.as_secs()
+                ));
+            }
+
+            let (kp, ki, kd) = self.heat_pid_gains();
+            for (name, gain) in [("heat_kp", kp), ("heat_ki", ki), ("heat_kd", kd)] {
+                if !(gain >= 0.0 && gain.is_finite()) {
+                    return Err(format!("Invalid {}: {}. Must be 0 or greater.", name, gain));
+                }
+            }
+
             Ok(())
     }
+
+    /// Time after start in which overheat protection only takes real probe readings
+    pub fn startup_grace(&self) -> Duration {
+        Duration::from_secs(self.startup_grace.unwrap_or(120))
+    }
+
+    /// Time span of readings used to compute the temperature trend
+    pub fn trend_window(&self) -> Duration {
+        Duration::from_secs(self.trend_window.unwrap_or(300))
+    }
+
+    /// Longest the heat lamp may stay on without a break, None when unlimited
+    pub fn max_heat_on_time(&self) -> Option<Duration> {
+        self.max_heat_on_time.map(Duration::from_secs)
+    }
+
+    /// (kp, ki, kd) of the heat PID loop, see `PidController`
+    pub fn heat_pid_gains(&self) -> (f32, f32, f32) {
+        (self.heat_kp.unwrap_or(10.0), self.heat_ki.unwrap_or(1.0), self.heat_kd.unwrap_or(0.0))
+    }
 }
 
 impl ScheduleConfig {
+    /// Returns the configured database path, falling back to `data.db`
+    pub fn db_path(&self) -> &str {
+        self.path.as_deref().unwrap_or("data.db")
+    }
+
+    /// How long SQLite waits on a locked database before reporting it busy
+    pub fn busy_timeout(&self) -> Duration {
+        Duration::from_millis(self.busy_timeout_ms.unwrap_or(5000))
+    }
+
+    /// Retries of a database write that fails because the database is busy
+    pub fn write_retries(&self) -> u8 {
+        self.write_retries.unwrap_or(3)
+    }
+
+    /// Whether an empty schedule table is filled with the def_* weeks at startup
+    pub fn seed_schedule(&self) -> bool {
+        self.seed_schedule.unwrap_or(true)
+    }
+
     pub fn validate(&self) -> Result<(), String> {
+        if self.db_path().trim().is_empty() {
+            return Err("Database path cannot be empty".to_string());
+        }
+
+        if self.busy_timeout() > Duration::from_secs(60) {
+            return Err(format!("busy_timeout_ms must be at most 60000, got: {}", self.busy_timeout().as_millis()));
+        }
+
+        if self.write_retries() > 10 {
+            return Err(format!("write_retries must be at most 10, got: {}", self.write_retries()));
+        }
+
         // Check time formats for mandatory fields
         for (field_name, value) in &[
             ("def_uv1_start", &self.def_uv1_start),
@@ -265,14 +1115,59 @@ impl ScheduleConfig {
         Ok(())
     }
 
-    fn validate_time_format(time: &str) -> Result<(), ConfigError> {
+    fn validate_time_format(time: &str) -> Result<(), String> {
     chrono::NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| 
-        ConfigError::ValidationError("Invalid time format".to_string()))?;
+        "Invalid time format".to_string())?;
     Ok(())
     }
 }
 
 impl WebConfig {
+    /// Returns every socket address the web server should listen on.
+    ///
+    /// Uses the `bind` list when it is set, otherwise the single `address`/`port` pair.
+    /// Bare IPv6 addresses such as `::` are accepted for `address`.
+    pub fn socket_addrs(&self) -> Result<Vec<SocketAddr>, String> {
+        match &self.bind {
+            Some(bind) if !bind.is_empty() => bind
+                .iter()
+                .map(|addr| addr.parse::<SocketAddr>()
+                    .map_err(|e| format!("Invalid web bind address {:?}: {}", addr, e)))
+                .collect(),
+            _ => {
+                let ip = self.address.parse::<IpAddr>()
+                    .map_err(|e| format!("Invalid web server address {:?}: {}", self.address, e))?;
+                Ok(vec![SocketAddr::new(ip, self.port)])
+            }
+        }
+    }
+
+    /// Minimum time between manually triggered sensor reads
+    pub fn sensor_read_cooldown(&self) -> Duration {
+        Duration::from_secs(self.sensor_read_cooldown.unwrap_or(10))
+    }
+
+    /// Address of the camera stream server, None when `camera_port` is unset
+    pub fn camera_stream_addr(&self) -> Result<Option<SocketAddr>, String> {
+        let port = match self.camera_port {
+            Some(port) => port,
+            None => return Ok(None),
+        };
+        let ip = self.address.parse::<IpAddr>()
+            .map_err(|e| format!("Invalid web server address {:?}: {}", self.address, e))?;
+        Ok(Some(SocketAddr::new(ip, port)))
+    }
+
+    /// Largest request body accepted, in bytes
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_kb.unwrap_or(256) as usize * 1024
+    }
+
+    /// Largest CSV accepted by the sensor data import, in bytes
+    pub fn max_import_body_bytes(&self) -> usize {
+        self.max_import_body_kb.unwrap_or(8192) as usize * 1024
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         // Ensure that the address is non-empty
         if self.address.is_empty() {
@@ -284,11 +1179,75 @@ impl WebConfig {
             return Err("Invalid port number".to_string());
         }
 
+        // Ensure every bind address parses and none is listed twice
+        let addrs = self.socket_addrs()?;
+        for i in 0..addrs.len() {
+            for j in i+1..addrs.len() {
+                if addrs[i] == addrs[j] {
+                    return Err(format!("Web bind address {} listed multiple times", addrs[i]));
+                }
+            }
+        }
+
+        // The camera stream server needs a port of its own
+        if let Some(port) = self.camera_port {
+            if port == 0 {
+                return Err("Invalid web.camera_port".to_string());
+            }
+            if addrs.iter().any(|addr| addr.port() == port) {
+                return Err(format!("web.camera_port {} is already used by the web server", port));
+            }
+        }
+
+        // A size limit of 0 would reject every body, even an empty JSON object
+        for (name, kb) in [("max_body_kb", self.max_body_kb), ("max_import_body_kb", self.max_import_body_kb)] {
+            if kb == Some(0) || kb.map_or(false, |kb| kb > 1024 * 1024) {
+                return Err(format!("web.{} must be between 1 and 1048576, got: {}", name, kb.unwrap_or_default()));
+            }
+        }
+
+        if matches!(&self.admin_key, Some(key) if key.len() < 16) {
+            return Err("web.admin_key must be at least 16 characters".to_string());
+        }
+
         Ok(())
     }
 }
 
+/// Fewest points `[get_data] max_graph_points` may cap a graph at
+pub const MIN_GRAPH_POINTS: u32 = 10;
+
 impl GetDataConfig {
+    /// Time between sensor reads
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(60))
+    }
+
+    /// Graph ranges longer than this read `sensor_history_hourly` instead of raw rows
+    pub fn rollup_after(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.rollup_after_hours.unwrap_or(48) as i64)
+    }
+
+    /// Most points a graph endpoint returns before averaging readings into buckets
+    pub fn max_graph_points(&self) -> usize {
+        self.max_graph_points.unwrap_or(720) as usize
+    }
+
+    /// Timeout for a single DS18B20 read attempt
+    pub fn ds18b20_timeout(&self) -> Duration {
+        Duration::from_millis(self.ds18b20_timeout_ms.unwrap_or(2000))
+    }
+
+    /// Timeout for a single DHT22 read attempt
+    pub fn dht22_timeout(&self) -> Duration {
+        Duration::from_millis(self.dht22_timeout_ms.unwrap_or(3000))
+    }
+
+    /// Timeout for a single VEML6075 read attempt
+    pub fn veml6075_timeout(&self) -> Duration {
+        Duration::from_millis(self.veml6075_timeout_ms.unwrap_or(1000))
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         if self.retry == 0 {
             return Err("Retry count must be at least 1".into());
@@ -306,17 +1265,378 @@ impl GetDataConfig {
             }
         }
         
+        if self.rollup_after_hours == Some(0) {
+            return Err("get_data.rollup_after_hours must be at least 1".into());
+        }
+        
+        if matches!(self.max_graph_points, Some(points) if points < MIN_GRAPH_POINTS) {
+            return Err(format!("get_data.max_graph_points must be at least {}", MIN_GRAPH_POINTS));
+        }
+        
+        for (field_name, timeout) in &[
+            ("ds18b20_timeout_ms", self.ds18b20_timeout()),
+            ("dht22_timeout_ms", self.dht22_timeout()),
+            ("veml6075_timeout_ms", self.veml6075_timeout()),
+        ] {
+            if timeout.is_zero() {
+                return Err(format!("get_data.{} must be greater than 0", field_name));
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+impl CamConfig {
+    pub fn snapshot_ttl(&self) -> Duration {
+        Duration::from_millis(self.snapshot_ttl_ms.unwrap_or(1000))
+    }
+}
+
+impl AwayConfig {
+    pub fn alert_margin(&self) -> f32 {
+        self.alert_margin.unwrap_or(5.0)
+    }
+
+    pub fn snapshot_interval(&self) -> Duration {
+        Duration::from_secs(self.snapshot_interval.unwrap_or(300))
+    }
+
+    pub fn max_snapshots(&self) -> usize {
+        self.max_snapshots.unwrap_or(288)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.alert_margin() >= 0.0) {
+            return Err(format!("away.alert_margin must not be negative, got: {}", self.alert_margin()));
+        }
+
+        if self.snapshot_interval() < Duration::from_secs(10) {
+            return Err(format!(
+                "away.snapshot_interval must be at least 10 seconds, got: {}", self.snapshot_interval().as_secs()
+            ));
+        }
+
+        if self.max_snapshots() == 0 {
+            return Err("away.max_snapshots must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl ThresholdsConfig {
+    /// The range of every sensor channel, in `SensorStatus` naming, defaults applied
+    pub fn channels(&self) -> [Threshold; 6] {
+        let channel = |channel, unit, min, max| Threshold { channel, unit, min, max };
+        [
+            channel("basking", "°C", self.min_basking_temp, Some(self.max_basking_temp.unwrap_or(45.0))),
+            channel("control", "°C", self.min_control_temp, Some(self.max_control_temp.unwrap_or(35.0))),
+            channel("cool", "°C", self.min_cool_temp, self.max_cool_temp),
+            channel("humidity", "%", Some(self.min_humidity.unwrap_or(30.0)), self.max_humidity),
+            channel("uv1", " UVI", self.min_uv1, self.max_uv1),
+            channel("uv2", " UVI", self.min_uv2, self.max_uv2),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for threshold in self.channels() {
+            for bound in [threshold.min, threshold.max].into_iter().flatten() {
+                if !bound.is_finite() {
+                    return Err(format!("thresholds for {} must be finite numbers, got: {}", threshold.channel, bound));
+                }
+            }
+            if let (Some(min), Some(max)) = (threshold.min, threshold.max) {
+                if min >= max {
+                    return Err(format!(
+                        "thresholds for {} need min below max, got: {} / {}", threshold.channel, min, max
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// DS18B20 roles the controllers read, in the order they are reported
+pub const CANONICAL_PROBES: [&str; 3] = ["basking", "control", "cool"];
+
+/// Longest accepted DS18B20 probe name
+pub const MAX_PROBE_NAME_LENGTH: usize = 32;
+
+impl SensorsConfig {
+    /// Returns the 1-Wire device id configured for a DS18B20 role
+    pub fn probe_id(&self, role: &str) -> Option<&str> {
+        self.ds18b20.get(role).map(String::as_str)
+    }
+
+    /// Names of the configured DS18B20 probes: the `CANONICAL_PROBES` first,
+    /// then any further probes by name
+    pub fn probe_names(&self) -> Vec<&str> {
+        let mut extra: Vec<&str> = self.extra_probes().collect();
+        extra.sort_unstable();
+        CANONICAL_PROBES.iter().copied()
+            .filter(|role| self.ds18b20.contains_key(*role))
+            .chain(extra)
+            .collect()
+    }
+
+    /// Configured probes beyond the `CANONICAL_PROBES`, in no particular order
+    pub fn extra_probes(&self) -> impl Iterator<Item = &str> {
+        self.ds18b20.keys().map(String::as_str).filter(|name| !CANONICAL_PROBES.contains(name))
+    }
+
+    pub fn spike_filter(&self) -> bool {
+        self.spike_filter.unwrap_or(true)
+    }
+
+    pub fn max_temp_jump(&self) -> f32 {
+        self.max_temp_jump.unwrap_or(5.0)
+    }
+
+    pub fn filter_window(&self) -> usize {
+        self.filter_window.unwrap_or(5)
+    }
+
+    /// Plausible temperature range in °C
+    pub fn temp_range(&self) -> (f32, f32) {
+        self.temp_range.unwrap_or((-10.0, 60.0))
+    }
+
+    /// Plausible relative humidity range in %
+    pub fn humidity_range(&self) -> (f32, f32) {
+        self.humidity_range.unwrap_or((0.0, 100.0))
+    }
+
+    /// Plausible UV index range
+    pub fn uv_range(&self) -> (f32, f32) {
+        self.uv_range.unwrap_or((0.0, 15.0))
+    }
+
+    /// Failed reads in a row before a sensor is reported faulted
+    pub fn fault_after(&self) -> u32 {
+        self.fault_after.unwrap_or(3)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_temp_jump().is_nan() || self.max_temp_jump() <= 0.0 {
+            return Err(format!("sensors.max_temp_jump must be greater than 0, got: {}", self.max_temp_jump()));
+        }
+        if !(1..=60).contains(&self.filter_window()) {
+            return Err(format!("sensors.filter_window must be between 1 and 60, got: {}", self.filter_window()));
+        }
+        if self.fault_after() == 0 {
+            return Err("sensors.fault_after must be at least 1".to_string());
+        }
+        for (name, (min, max)) in [("temp_range", self.temp_range()), ("humidity_range", self.humidity_range()), ("uv_range", self.uv_range())] {
+            if min.is_nan() || max.is_nan() || min >= max {
+                return Err(format!("sensors.{} must be [min, max] with min below max, got: [{}, {}]", name, min, max));
+            }
+        }
+
+        for (role, id) in &self.ds18b20 {
+            // Probe names double as sensor health names and JSON keys
+            if role.is_empty() || role.len() > MAX_PROBE_NAME_LENGTH
+                || !role.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+                return Err(format!(
+                    "sensors.ds18b20: probe name {:?} must be 1-{} characters of a-z, 0-9 and _", role, MAX_PROBE_NAME_LENGTH
+                ));
+            }
+            if ["humidity", "uv1", "uv2"].contains(&role.as_str()) {
+                return Err(format!("sensors.ds18b20: probe name {:?} is taken by another sensor", role));
+            }
+            let serial = id.strip_prefix("28-").ok_or_else(|| {
+                format!("sensors.ds18b20.{}: {:?} is not a DS18B20 id (expected 28-xxxxxxxxxxxx)", role, id)
+            })?;
+            if serial.len() != 12 || !serial.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "sensors.ds18b20.{}: {:?} must have 12 hex digits after \"28-\"", role, id
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl LoggingConfig {
+    pub fn max_file_size(&self) -> u64 {
+        self.max_file_size.unwrap_or(1024 * 1024)
+    }
+
+    pub fn max_files(&self) -> usize {
+        self.max_files.unwrap_or(30)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.unwrap_or(1)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_file_size() == 0 {
+            return Err("logging.max_file_size must be greater than 0".to_string());
+        }
+
+        if self.max_files() == 0 {
+            return Err("logging.max_files must be at least 1".to_string());
+        }
+
+        if self.sample_rate() == 0 {
+            return Err("logging.sample_rate must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl LedPowerConfig {
+    /// Full-brightness current of one IC's channels in mA, in r, g, b, ww, cw order
+    pub fn channel_ma(&self) -> [f32; 5] {
+        [self.r_ma, self.g_ma, self.b_ma, self.ww_ma, self.cw_ma].map(|ma| ma.unwrap_or(12.0))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, ma) in ["r_ma", "g_ma", "b_ma", "ww_ma", "cw_ma"].iter().zip(self.channel_ma()) {
+            if ma.is_nan() || ma < 0.0 || ma > 100.0 {
+                return Err(format!("led.power.{} must be between 0 and 100 mA, got: {}", name, ma));
+            }
+        }
+        if let Some(max) = self.max_current_ma {
+            if max.is_nan() || max <= 0.0 {
+                return Err(format!("led.power.max_current_ma must be greater than 0, got: {}", max));
+            }
+        }
         Ok(())
     }
 }
 
 impl LedConfig {
+    /// Color temperatures of the warm and cool white LEDs, in Kelvin
+    pub fn white_range(&self) -> (u32, u32) {
+        (self.ww_kelvin.unwrap_or(2700), self.cw_kelvin.unwrap_or(6500))
+    }
+
+    /// Whether the season color and weight follow the calendar
+    pub fn auto_season(&self) -> bool {
+        self.auto_season.unwrap_or(false)
+    }
+
+    /// The seasonal color curve used when `auto_season` is on
+    pub fn season_keyframes(&self) -> Vec<SeasonKeyframe> {
+        self.season_keyframes.clone().unwrap_or_else(default_season_keyframes)
+    }
+
+    /// Bounds in hours on the daily LED window accepted by the schedule handlers
+    pub fn on_hours(&self) -> (f32, f32) {
+        (self.min_on_hours.unwrap_or(4.0), self.max_on_hours.unwrap_or(16.0))
+    }
+
+    /// Channel order of the strip; `validate` rejects an order that doesn't parse
+    pub fn color_order(&self) -> ColorOrder {
+        self.color_order.as_deref()
+            .and_then(|order| ColorOrder::parse(order).ok())
+            .unwrap_or_default()
+    }
+
+    /// Color pulsed during an LED alert; `validate` rejects a color that doesn't parse
+    pub fn alert_color(&self) -> RGBWW {
+        self.alert_color.as_deref()
+            .and_then(|color| RGBWW::from_str(color).ok())
+            .unwrap_or(RGBWW { r: 255, g: 0, b: 0, ww: 0, cw: 0 })
+    }
+
+    /// The strip's data line; `validate` rejects bitbang without a data_pin
+    pub fn wiring(&self) -> LedWiring {
+        match (self.driver, self.data_pin) {
+            (LedDriverKind::Bitbang, Some(pin)) => LedWiring::BitBang { pin },
+            _ => LedWiring::Spi,
+        }
+    }
+
+    /// Per-channel maximums every color is clamped to before it's written
+    pub fn channel_caps(&self) -> RGBWW {
+        let cap = |max: Option<u32>| max.map_or(u8::MAX, |max| max.min(u8::MAX as u32) as u8);
+        RGBWW { r: cap(self.max_r), g: cap(self.max_g), b: cap(self.max_b), ww: cap(self.max_ww), cw: cap(self.max_cw) }
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         // Validate weight is between 0 and 1
         if self.season_weight < 0.0 || self.season_weight > 1.0 {
             return Err(format!("Season weight must be between 0.0 and 1.0, got: {}", self.season_weight));
         }
         
+        if let Some(steps) = self.fade_steps {
+            if steps == 0 || steps > 1000 {
+                return Err(format!("Fade steps must be between 1 and 1000, got: {}", steps));
+            }
+        }
+        
+        if let Some(duration) = self.fade_duration {
+            if duration > 600 {
+                return Err(format!("Fade duration must be at most 600 seconds, got: {}", duration));
+            }
+        }
+        
+        let (ww_kelvin, cw_kelvin) = self.white_range();
+        if ww_kelvin == 0 || ww_kelvin >= cw_kelvin {
+            return Err(format!(
+                "ww_kelvin must be above 0 and below cw_kelvin, got: {}K / {}K", ww_kelvin, cw_kelvin
+            ));
+        }
+        
+        self.power.validate()?;
+        
+        if let Some(order) = &self.color_order {
+            ColorOrder::parse(order).map_err(|e| format!("Invalid led.color_order: {}", e))?;
+        }
+        
+        if let Some(color) = &self.alert_color {
+            let parsed = RGBWW::from_str(color).map_err(|e| format!("Invalid led.alert_color: {}", e))?;
+            if parsed == RGBWW::off() {
+                return Err("led.alert_color must not be off".to_string());
+            }
+        }
+        
+        match (self.driver, self.data_pin) {
+            (LedDriverKind::Bitbang, None) => return Err("led.driver = \"bitbang\" needs led.data_pin".to_string()),
+            (LedDriverKind::Bitbang, Some(pin)) if pin > 27 => {
+                return Err(format!("led.data_pin must be a BCM GPIO pin (0-27), got: {}", pin));
+            }
+            _ => {}
+        }
+        
+        let caps = [("max_r", self.max_r), ("max_g", self.max_g), ("max_b", self.max_b), ("max_ww", self.max_ww), ("max_cw", self.max_cw)];
+        for (name, max) in caps {
+            if let Some(max) = max.filter(|&max| max > u8::MAX as u32) {
+                return Err(format!("led.{} must be between 0 and 255, got: {}", name, max));
+            }
+        }
+        
+        let (min_on, max_on) = self.on_hours();
+        if min_on.is_nan() || max_on.is_nan() || min_on < 0.0 || max_on > 24.0 || min_on > max_on {
+            return Err(format!(
+                "min_on_hours and max_on_hours must satisfy 0 <= min <= max <= 24, got: {} / {}", min_on, max_on
+            ));
+        }
+        
+        let keyframes = self.season_keyframes();
+        if keyframes.is_empty() {
+            return Err("season_keyframes must contain at least one keyframe".to_string());
+        }
+        for (i, keyframe) in keyframes.iter().enumerate() {
+            if keyframe.day_of_year == 0 || keyframe.day_of_year > 366 {
+                return Err(format!("season_keyframes day_of_year must be between 1 and 366, got: {}", keyframe.day_of_year));
+            }
+            if keyframe.weight < 0.0 || keyframe.weight > 1.0 {
+                return Err(format!(
+                    "season_keyframes weight must be between 0.0 and 1.0, got: {} (day {})", keyframe.weight, keyframe.day_of_year
+                ));
+            }
+            if keyframes[..i].iter().any(|other| other.day_of_year == keyframe.day_of_year) {
+                return Err(format!("season_keyframes has two keyframes for day {}", keyframe.day_of_year));
+            }
+        }
+        
         Ok(())
     }
 }
@@ -325,12 +1645,359 @@ impl Config {
     pub fn load(config_path: &str) -> Result<Self, String> {
         // Read and parse the config file
         let config_str = std::fs::read_to_string(config_path)
-            .map_err(|_| "Failed to read configuration file".to_string())?;
-        let config: Config = toml::de::from_str(&config_str)
-            .map_err(|_| "Failed to parse configuration file".to_string())?;
+            .map_err(|e| format!("Failed to read configuration file {}: {}", config_path, e))?;
+        Self::from_toml_str(&config_str)
+            .map_err(|e| format!("{}: {}", config_path, e))
+    }
+
+    /// Parses and validates a configuration from TOML text.
+    ///
+    /// Required fields are checked first so a missing one is reported by
+    /// name; everything else falls back to its default.
+    pub fn from_toml_str(config_str: &str) -> Result<Self, String> {
+        // The toml error messages carry the line/column and the offending key
+        let value: toml::Value = toml::from_str(config_str)
+            .map_err(|e| format!("Failed to parse configuration file: {}", e))?;
+        Self::check_required_fields(&value)?;
+
+        let config: Config = value.try_into()
+            .map_err(|e| format!("Invalid configuration: {}", e))?;
 
         // Validate the loaded configuration
         config.validate()?;
         Ok(config)
     }
-}
\ No newline at end of file
+
+    fn check_required_fields(value: &toml::Value) -> Result<(), String> {
+        for (section, field) in REQUIRED_FIELDS {
+            let table = value.get(section)
+                .ok_or_else(|| format!("Missing required section [{}] in configuration file", section))?;
+            if table.get(field).is_none() {
+                return Err(format!("Missing required field `{}` in [{}] of configuration file", field, section));
+            }
+        }
+        Ok(())
+    }
+}
+/// Complete configuration used by unit tests across modules
+#[cfg(test)]
+pub(crate) const TEST_CONFIG_TOML: &str = r#"
+[main]
+debug = false
+
+[get_data]
+retry = 3
+interval = 60
+backup_sensor = true
+
+[light_control]
+overheat_temp = 50
+overheat_time = 3000
+
+[gpio]
+led_relay = 17
+uv_relay1 = 22
+uv_relay2 = 23
+heat_relay = 27
+veml6075_uv1 = 0
+veml6075_uv2 = 1
+
+[led]
+default_mode = "natural"
+default_brightness = 50
+season_weight = 0.3
+morning_r = 255
+morning_g = 180
+morning_b = 100
+morning_ww = 200
+morning_cw = 50
+noon_r = 255
+noon_g = 240
+noon_b = 220
+noon_ww = 50
+noon_cw = 255
+evening_r = 255
+evening_g = 140
+evening_b = 50
+evening_ww = 255
+evening_cw = 0
+
+[web]
+address = "127.0.0.1"
+port = 8080
+
+[db]
+def_uv1_start = "06:30"
+def_uv1_end = "19:00"
+def_uv2_start = "07:00"
+def_uv2_end = "19:30"
+def_heat_start = "10:00"
+def_heat_end = "14:30"
+def_led_R = 150
+def_led_G = 150
+def_led_B = 128
+def_led_WW = 128
+def_led_CW = 128
+"#;
+
+/// Parses `TEST_CONFIG_TOML` into a `Config`
+#[cfg(test)]
+pub(crate) fn test_config() -> Config {
+    toml::from_str(TEST_CONFIG_TOML).expect("test config must parse")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL_CONFIG_TOML: &str = r#"
+[gpio]
+led_relay = 17
+uv_relay1 = 22
+uv_relay2 = 23
+heat_relay = 27
+"#;
+
+    #[test]
+    fn test_minimal_config_uses_defaults() {
+        let config = Config::from_toml_str(MINIMAL_CONFIG_TOML).unwrap();
+
+        assert_eq!(config.gpio.uv_relay1, 22);
+        assert_eq!(config.gpio.veml6075_uv2, 1);
+        assert_eq!(config.get_data.retry, 3);
+        assert_eq!(config.light_control.overheat_temp, 50);
+        assert_eq!(config.led.default_mode, "natural");
+        assert_eq!(config.led.noon_cw, 255);
+        assert_eq!(config.db.def_heat_start, "10:00");
+        assert_eq!(config.web.port, 80);
+        assert_eq!(config.logging.max_files(), 30);
+        assert_eq!(config.away.alert_margin(), 5.0);
+    }
+
+    #[test]
+    fn test_partial_section_keeps_other_defaults() {
+        let toml = format!("{}\n[led]\nseason_weight = 0.5\n", MINIMAL_CONFIG_TOML);
+        let config = Config::from_toml_str(&toml).unwrap();
+
+        assert_eq!(config.led.season_weight, 0.5);
+        assert_eq!(config.led.morning_r, 255);
+    }
+
+    #[test]
+    fn test_missing_required_field_is_named() {
+        let toml = MINIMAL_CONFIG_TOML.replace("heat_relay = 27\n", "");
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("heat_relay"), "{}", err);
+        assert!(err.contains("[gpio]"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_gpio_section_is_named() {
+        let err = Config::from_toml_str("[main]\ndebug = true\n").unwrap_err();
+        assert!(err.contains("[gpio]"), "{}", err);
+    }
+
+    #[test]
+    fn test_malformed_toml_error_names_bad_key() {
+        let toml = format!("{}\n[led]\nseason_weight = 0.5\nseason_weight = 0.7\n", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("season_weight"), "{}", err);
+        assert!(err.contains("line"), "{}", err);
+    }
+
+    #[test]
+    fn test_wrong_type_error_names_field() {
+        let toml = format!("{}\n[web]\nport = \"eighty\"\n", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("port"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_file_error_includes_path() {
+        let err = Config::load("/nonexistent/terra/config.toml").unwrap_err();
+        assert!(err.contains("/nonexistent/terra/config.toml"), "{}", err);
+    }
+
+    #[test]
+    fn test_uv_sensors_need_distinct_bus_or_address() {
+        let toml = format!("{}veml6075_uv2 = 0\n", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("0x10"), "{}", err);
+
+        let toml = format!("{}veml6075_uv2 = 0\nveml6075_uv2_address = 0x11\n", MINIMAL_CONFIG_TOML);
+        let config = Config::from_toml_str(&toml).unwrap();
+        assert_eq!(config.gpio.uv1_sensor(), (0, 0x10));
+        assert_eq!(config.gpio.uv2_sensor(), (0, 0x11));
+    }
+
+    #[test]
+    fn test_reserved_uv_sensor_address_rejected() {
+        let toml = format!("{}veml6075_uv1_address = 0x78\n", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("veml6075_uv1_address"), "{}", err);
+    }
+
+    #[test]
+    fn test_season_keyframes_parse_and_validate() {
+        let keyframes = "[[led.season_keyframes]]\nday_of_year = 1\nr = 255\ng = 160\nb = 90\nww = 255\ncw = 0\nweight = 0.5\n";
+        let toml = format!("{}\n[led]\nauto_season = true\n\n{}", MINIMAL_CONFIG_TOML, keyframes);
+        let config = Config::from_toml_str(&toml).unwrap();
+        assert!(config.led.auto_season());
+        assert_eq!(config.led.season_keyframes().len(), 1);
+
+        let toml = format!("{}\n{}", MINIMAL_CONFIG_TOML, keyframes.replace("weight = 0.5", "weight = 1.5"));
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("weight"), "{}", err);
+    }
+
+    #[test]
+    fn test_startup_order_appends_unlisted_loads() {
+        let toml = format!("[main]\nstartup_order = [\"heat\", \"led\"]\n{}", MINIMAL_CONFIG_TOML);
+        let config = Config::from_toml_str(&toml).unwrap();
+        assert_eq!(config.main.startup_order(), vec![RelayType::Heat, RelayType::LED, RelayType::UV1, RelayType::UV2]);
+
+        let toml = format!("[main]\nstartup_order = [\"uv1\", \"aux\"]\n{}", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("aux"), "{}", err);
+    }
+
+    #[test]
+    fn test_timezone_validated() {
+        let toml = format!("[main]\ntimezone = \"America/New_York\"\n{}", MINIMAL_CONFIG_TOML);
+        let config = Config::from_toml_str(&toml).unwrap();
+        assert_eq!(config.main.timezone(), Some(chrono_tz::America::New_York));
+
+        let toml = format!("[main]\ntimezone = \"Mars/Olympus\"\n{}", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("Mars/Olympus"), "{}", err);
+    }
+
+    #[test]
+    fn test_spike_filter_settings_validated() {
+        let config = Config::from_toml_str(MINIMAL_CONFIG_TOML).unwrap();
+        assert!(config.sensors.spike_filter());
+        assert_eq!(config.sensors.filter_window(), 5);
+
+        let toml = format!("[sensors]\nmax_temp_jump = 0.0\n{}", MINIMAL_CONFIG_TOML);
+        let err = Config::from_toml_str(&toml).unwrap_err();
+        assert!(err.contains("max_temp_jump"), "{}", err);
+    }
+
+    #[test]
+    fn test_instance_name_validated() {
+        let mut main = MainConfig::default();
+        assert_eq!(main.name(), "terrarium");
+
+        main.name = Some("  ".to_string());
+        assert!(main.validate().unwrap_err().contains("main.name"));
+        main.name = Some("x".repeat(MAX_NAME_LENGTH + 1));
+        assert!(main.validate().is_err());
+
+        main.name = Some("Leopard gecko".to_string());
+        main.validate().unwrap();
+    }
+
+    #[test]
+    fn test_camera_port_validated() {
+        let mut web = WebConfig { camera_port: Some(3030), ..Default::default() };
+        web.validate().unwrap();
+        assert_eq!(web.camera_stream_addr().unwrap(), Some("0.0.0.0:3030".parse().unwrap()));
+
+        web.camera_port = Some(80);
+        assert!(web.validate().unwrap_err().contains("camera_port"));
+        web.camera_port = Some(0);
+        assert!(web.validate().is_err());
+
+        web.camera_port = None;
+        web.validate().unwrap();
+        assert_eq!(web.camera_stream_addr().unwrap(), None);
+    }
+
+    #[test]
+    fn test_boot_behavior_initial_actions() {
+        let config = Config::from_toml_str(MINIMAL_CONFIG_TOML).unwrap();
+        assert_eq!(config.main.boot_behavior, BootBehavior::Schedule);
+
+        // (scheduled, last logged state)
+        let cases = [(true, Some(false)), (false, Some(true)), (true, None), (false, None)];
+        let run = |behavior: BootBehavior| cases.map(|(scheduled, last)| behavior.initial_state(scheduled, last));
+
+        assert_eq!(run(BootBehavior::Schedule), [true, false, true, false]);
+        assert_eq!(run(BootBehavior::Resume), [false, true, true, false]);
+        assert_eq!(run(BootBehavior::SafeOff), [false, false, false, false]);
+
+        assert!(!BootBehavior::Schedule.holds_control_loops());
+        assert!(!BootBehavior::Resume.holds_control_loops());
+        assert!(BootBehavior::SafeOff.holds_control_loops());
+
+        let toml = format!("[main]\nboot_behavior = \"safe_off\"\n{}", MINIMAL_CONFIG_TOML);
+        assert_eq!(Config::from_toml_str(&toml).unwrap().main.boot_behavior, BootBehavior::SafeOff);
+    }
+
+    #[test]
+    fn test_thresholds_parse_with_defaults() {
+        let config = Config::from_toml_str(MINIMAL_CONFIG_TOML).unwrap();
+        let defaults = config.thresholds.channels();
+        assert_eq!((defaults[0].channel, defaults[0].min, defaults[0].max), ("basking", None, Some(45.0)));
+        assert_eq!(defaults[1].max, Some(35.0));
+        assert_eq!((defaults[3].min, defaults[3].max), (Some(30.0), None));
+        assert_eq!((defaults[2].min, defaults[2].max), (None, None));
+
+        let toml = format!(
+            "{}\n[thresholds]\nmax_basking_temp = 42.5\nmax_control_temp = 31.0\nmin_humidity = 60.0\nmax_humidity = 90.0\nmax_uv1 = 6.0\n",
+            MINIMAL_CONFIG_TOML
+        );
+        let config = Config::from_toml_str(&toml).unwrap();
+        assert_eq!(config.thresholds.max_basking_temp, Some(42.5));
+        let channels = config.thresholds.channels();
+        assert_eq!(channels[0].max, Some(42.5));
+        assert_eq!(channels[1].max, Some(31.0));
+        assert_eq!((channels[3].min, channels[3].max), (Some(60.0), Some(90.0)));
+        assert_eq!((channels[4].channel, channels[4].max), ("uv1", Some(6.0)));
+
+        let toml = format!("{}\n[thresholds]\nmin_humidity = 80.0\nmax_humidity = 70.0\n", MINIMAL_CONFIG_TOML);
+        assert!(Config::from_toml_str(&toml).unwrap_err().contains("humidity"));
+        let toml = format!("{}\n[thresholds]\nmin_basking_temp = 50.0\n", MINIMAL_CONFIG_TOML);
+        assert!(Config::from_toml_str(&toml).unwrap_err().contains("basking"));
+    }
+
+    #[test]
+    fn test_full_test_config_still_parses() {
+        Config::from_toml_str(TEST_CONFIG_TOML).unwrap();
+    }
+
+    #[test]
+    fn test_pwm_pins_conflict_with_dht22_and_relays() {
+        let mut gpio = test_config().gpio;
+        gpio.heat_pwm_channel = Some(1);
+        gpio.validate().unwrap();
+
+        // Channel 0 is GPIO18, the default dht22_pin
+        gpio.uv1_pwm_channel = Some(0);
+        assert!(gpio.validate().unwrap_err().contains("dht22_pin and uv1_pwm_channel"));
+        gpio.dht22_pin = Some(24);
+        gpio.validate().unwrap();
+
+        gpio.aux_relay = Some(19);
+        assert!(gpio.validate().unwrap_err().contains("aux_relay and heat_pwm_channel"));
+    }
+
+    #[test]
+    fn test_bitbang_data_pin_must_be_free() {
+        let bitbang = |pin: u8, gpio: &str| format!(
+            "{}\n[led]\ndriver = \"bitbang\"\ndata_pin = {}\n",
+            MINIMAL_CONFIG_TOML.replace("[gpio]\n", &format!("[gpio]\n{}\n", gpio)),
+            pin,
+        );
+
+        Config::from_toml_str(&bitbang(21, "")).unwrap();
+        for (pin, gpio, claimed_by) in [
+            (18, "", "dht22_pin"),
+            (17, "", "led_relay"),
+            (19, "dht22_pin = 24\nheat_pwm_channel = 1", "heat_pwm_channel"),
+        ] {
+            let err = Config::from_toml_str(&bitbang(pin, gpio)).unwrap_err();
+            assert!(err.contains(&format!("{} and led.data_pin", claimed_by)), "{}", err);
+        }
+    }
+}
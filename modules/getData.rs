@@ -1,18 +1,23 @@
-use sqlx::PgPool;
+use sqlx::SqlitePool;
 use tokio::time::{sleep, Duration};
 use log::{error, info, warn};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex, OnceLock};
 use tokio::sync::Mutex;
-use chrono::{DateTime, Utc, NaiveDateTime};
-use crate::gpio::{read_ds18b20, read_dht22, read_veml6075};
-use crate::modules::models::SensorReadings;
-use crate::modules::config::Config;
+use chrono::{DateTime, Local, Utc, NaiveDateTime};
+use crate::modules::gpio::{read_ds18b20, read_dht22, read_veml6075};
+use crate::modules::models::{OverheatStatus, SensorReadings, SensorStatus};
+use crate::modules::config::{Config, ReadMode, SensorsConfig, ThresholdsConfig};
 use crate::modules::lightControl::LightController;
+use crate::modules::alerts::{self, Alert};
 use crate::modules::logs;
+use crate::modules::storage;
+use crate::modules::watchdog;
 use std::error::Error;
 
 /// Structure to store the most recent sensor readings from all sensors.
 /// Used to provide real-time data to the web interface and control systems.
+#[derive(Debug, Clone, PartialEq)]
 pub struct CurrentReadings {
     pub timestamp: DateTime<Utc>,
     pub basking_temp: f32,
@@ -21,6 +26,7 @@ pub struct CurrentReadings {
     pub humidity: f32,
     pub uv_1: f32,
     pub uv_2: f32,
+    pub probes: BTreeMap<String, Option<f32>>,  // DS18B20 probes beyond basking, control and cool, by name; None after a failed read
 }
 
 impl CurrentReadings {
@@ -40,6 +46,7 @@ impl CurrentReadings {
             humidity: 0.0,
             uv_1: 0.0,
             uv_2: 0.0,
+            probes: BTreeMap::new(),
         }
     }
 }
@@ -52,33 +59,56 @@ impl CurrentReadings {
 /// # Arguments
 ///
 /// * `config` - The application configuration containing sensor settings
+/// * `away` - Whether away mode is on, which lowers the warning threshold
 ///
 /// # Returns
 ///
 /// A CurrentReadings struct containing all sensor values and the current timestamp
-pub async fn read_all_sensors(config: &Config) -> CurrentReadings {
+pub async fn read_all_sensors(config: &Config, away: bool) -> CurrentReadings {
     let timestamp = Utc::now();
 
-    // Read temperatures with configured retry count
-    let basking_temp = retry(|| read_ds18b20(config.gpio.ds18b20_bus.unwrap_or(4), "basking"), config.get_data.retry)
-        .await.unwrap_or(0.0);
-        
-    let control_temp = retry(|| read_ds18b20(config.gpio.ds18b20_bus.unwrap_or(4), "control"), config.get_data.retry)
-        .await.unwrap_or(0.0);
-        
-    let cool_temp = retry(|| read_ds18b20(config.gpio.ds18b20_bus.unwrap_or(4), "cool"), config.get_data.retry)
-        .await.unwrap_or(0.0);
+    let retries = config.get_data.retry;
+    let ds18b20_timeout = config.get_data.ds18b20_timeout();
+    let dht22_timeout = config.get_data.dht22_timeout();
+    let veml6075_timeout = config.get_data.veml6075_timeout();
+
+    let mode = config.get_data.read_mode;
+
+    // Read temperatures with configured retry count; probes share the 1-Wire bus,
+    // so they're read one after another in either mode
+    let temperatures = async {
+        let mut probes = Vec::new();
+        for name in config.sensors.probe_names() {
+            probes.push((name, read_probe(config, name, ds18b20_timeout).await));
+        }
+        probes
+    };
 
     // Read humidity with configured retry count
-    let humidity = retry(|| read_dht22(config.gpio.dht22_pin.unwrap_or(18)), config.get_data.retry)
-        .await.unwrap_or(0.0);
+    let dht22_pin = config.gpio.dht22_pin();
+    let humidity = retry(|| read_with_timeout("DHT22", dht22_timeout, move || read_dht22(dht22_pin)), retries);
 
     // Read UV sensors with configured retry count, using proper I2C buses
-    let uv_1 = retry(|| read_veml6075(0, config.gpio.veml6075_uv1), config.get_data.retry)
-        .await.unwrap_or(0.0);
-        
-    let uv_2 = retry(|| read_veml6075(1, config.gpio.veml6075_uv2), config.get_data.retry)
-        .await.unwrap_or(0.0);
+    let (uv1_bus, uv1_address) = config.gpio.uv1_sensor();
+    let (uv2_bus, uv2_address) = config.gpio.uv2_sensor();
+    let uv_1 = retry(move || read_with_timeout("VEML6075", veml6075_timeout, move || read_veml6075(uv1_bus, uv1_address)), retries);
+    let uv_2 = retry(move || read_with_timeout("VEML6075", veml6075_timeout, move || read_veml6075(uv2_bus, uv2_address)), retries);
+    let uv_mode = if uv1_bus == uv2_bus { ReadMode::Sequential } else { mode };
+    let uv = read_group(uv_mode, uv_1, uv_2, async {});
+
+    let (probes, humidity, (uv_1, uv_2, ())) = read_group(mode, temperatures, humidity, uv).await;
+
+    // Health is recorded in a fixed order once every read is done; roles without a probe aren't tracked
+    let mut temperatures: BTreeMap<String, Option<f32>> = probes.into_iter()
+        .map(|(name, value)| (name.to_string(), record_read(config, name, value)))
+        .collect();
+    let mut canonical = |role: &str| temperatures.remove(role).flatten().unwrap_or(0.0);
+    let basking_temp = canonical("basking");
+    let control_temp = canonical("control");
+    let cool_temp = canonical("cool");
+    let humidity = record_read(config, "humidity", humidity).unwrap_or(0.0);
+    let uv_1 = record_read(config, "uv1", uv_1).unwrap_or(0.0);
+    let uv_2 = record_read(config, "uv2", uv_2).unwrap_or(0.0);
 
     // Create reading object with all sensor data
     let readings = CurrentReadings {
@@ -89,16 +119,386 @@ pub async fn read_all_sensors(config: &Config) -> CurrentReadings {
         humidity,
         uv_1,
         uv_2,
+        probes: temperatures,
     };
     
-    // Check critical temperature (for logging only - actual control is in lightControl.rs)
-    if basking_temp > config.light_control.overheat_temp as f32 || 
-       control_temp > config.light_control.overheat_temp as f32 {
+    warn_over_threshold(&readings, config, away);
+    
+    readings
+}
+
+/// Logs a warning when the basking or control temperature is above
+/// `alert_threshold`, returning whether it was.
+///
+/// This is for logging only - actual control is in lightControl.rs.
+pub fn warn_over_threshold(readings: &CurrentReadings, config: &Config, away: bool) -> bool {
+    let threshold = alert_threshold(config, away);
+    let over = readings.basking_temp > threshold || readings.control_temp > threshold;
+    if over {
         warn!("TEMPERATURE WARNING: Temperatures exceeding threshold: Basking={:.1}°C, Control={:.1}°C (Threshold={:.1}°C)", 
-              basking_temp, control_temp, config.light_control.overheat_temp);
+              readings.basking_temp, readings.control_temp, threshold);
     }
+    over
+}
+
+/// Temperature above which a reading is logged as a warning.
+///
+/// This is `overheat_temp`, lowered by `away.alert_margin` while away mode is
+/// on so problems are flagged before protection has to trip.
+pub fn alert_threshold(config: &Config, away: bool) -> f32 {
+    let overheat_temp = config.light_control.overheat_temp as f32;
+    if away {
+        overheat_temp - config.away.alert_margin()
+    } else {
+        overheat_temp
+    }
+}
+
+/// Runs three independent sensor reads, at the same time in `ReadMode::Concurrent`
+/// and one after another otherwise.
+///
+/// The reads run their blocking parts on `spawn_blocking` (see `read_with_timeout`),
+/// so joining them overlaps the actual bus traffic.
+async fn read_group<A, B, C>(mode: ReadMode, a: A, b: B, c: C) -> (A::Output, B::Output, C::Output)
+where
+    A: std::future::Future,
+    B: std::future::Future,
+    C: std::future::Future,
+{
+    match mode {
+        ReadMode::Concurrent => tokio::join!(a, b, c),
+        ReadMode::Sequential | ReadMode::Staggered => (a.await, b.await, c.await),
+    }
+}
+
+/// Sensors in the order `ReadMode::Staggered` reads them, one per slot of the interval
+pub const STAGGERED_SENSORS: [&str; 6] = ["basking", "control", "cool", "humidity", "uv1", "uv2"];
+
+/// `STAGGERED_SENSORS` with the probes beyond the canonical three read after "cool"
+pub fn staggered_sensors(sensors: &SensorsConfig) -> Vec<&str> {
+    let mut extra: Vec<&str> = sensors.extra_probes().collect();
+    extra.sort_unstable();
+    let (probes, others) = STAGGERED_SENSORS.split_at(3);
+    probes.iter().copied().chain(extra).chain(others.iter().copied()).collect()
+}
+
+/// Offsets from the start of the interval at which each of `sensors` is read
+/// in `ReadMode::Staggered`, `interval / sensors` apart
+pub fn stagger_offsets(interval: Duration, sensors: usize) -> Vec<Duration> {
+    let slots = sensors.max(1) as u32;
+    (0..slots).map(|slot| interval * slot / slots).collect()
+}
+
+/// Reads `sensors` one at a time at their `stagger_offsets` after `start`
+async fn read_staggered<'a, F, Fut>(start: tokio::time::Instant, interval: Duration, sensors: &[&'a str], mut read: F)
+where
+    F: FnMut(&'a str) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    for (&sensor, offset) in sensors.iter().zip(stagger_offsets(interval, sensors.len())) {
+        tokio::time::sleep_until(start + offset).await;
+        read(sensor).await;
+    }
+}
+
+/// Reads one of the `staggered_sensors` with retries and records its health.
+///
+/// # Returns
+///
+/// The reading, or None if it failed or no probe is configured for the role
+async fn read_sensor(config: &Config, sensor: &str) -> Option<f32> {
+    let retries = config.get_data.retry;
+    let value = match sensor {
+        "humidity" => {
+            let dht22_pin = config.gpio.dht22_pin();
+            let timeout = config.get_data.dht22_timeout();
+            retry(|| read_with_timeout("DHT22", timeout, move || read_dht22(dht22_pin)), retries).await
+        }
+        "uv1" | "uv2" => {
+            let (bus, address) = if sensor == "uv1" { config.gpio.uv1_sensor() } else { config.gpio.uv2_sensor() };
+            let timeout = config.get_data.veml6075_timeout();
+            retry(move || read_with_timeout("VEML6075", timeout, move || read_veml6075(bus, address)), retries).await
+        }
+        role => {
+            if config.sensors.probe_id(role).is_none() {
+                return None;
+            }
+            read_probe(config, role, config.get_data.ds18b20_timeout()).await
+        }
+    };
+    record_read(config, sensor, value)
+}
+
+/// Reads a single sensor and updates its value in `current_readings`. A failed
+/// read is 0.0 for the canonical sensors, as in `read_all_sensors`.
+async fn read_into(config: &Config, current_readings: &Mutex<CurrentReadings>, sensor: &str) {
+    let value = read_sensor(config, sensor).await;
+    let mut current = current_readings.lock().await;
+    current.timestamp = Utc::now();
+    match sensor {
+        "basking" => current.basking_temp = value.unwrap_or(0.0),
+        "control" => current.control_temp = value.unwrap_or(0.0),
+        "cool" => current.cool_temp = value.unwrap_or(0.0),
+        "humidity" => current.humidity = value.unwrap_or(0.0),
+        "uv1" => current.uv_1 = value.unwrap_or(0.0),
+        "uv2" => current.uv_2 = value.unwrap_or(0.0),
+        probe => {
+            current.probes.insert(probe.to_string(), value);
+        }
+    }
+}
+
+/// Warnings for the readings outside their `[thresholds]` range, with ids
+/// such as `high_basking` or `low_humidity`.
+///
+/// Channels whose last read failed are skipped, as their value is a 0.0
+/// placeholder rather than a measurement.
+///
+/// # Arguments
+///
+/// * `thresholds` - The configured ranges
+/// * `readings` - The readings to check
+/// * `health` - Read health of the sensors, see `sensor_health`
+pub fn threshold_warnings(thresholds: &ThresholdsConfig, readings: &CurrentReadings, health: &[SensorStatus]) -> Vec<Alert> {
+    let values = [readings.basking_temp, readings.control_temp, readings.cool_temp, readings.humidity, readings.uv_1, readings.uv_2];
+    let failed = |channel: &str| health.iter().any(|status| status.name == channel && status.consecutive_failures > 0);
     
-    readings
+    thresholds.channels().into_iter().zip(values)
+        .filter(|(threshold, _)| !failed(threshold.channel))
+        .filter_map(|(threshold, value)| {
+            let unit = threshold.unit;
+            match (threshold.min, threshold.max) {
+                (_, Some(max)) if value > max => Some(Alert::warning(
+                    format!("high_{}", threshold.channel),
+                    format!("High {} reading: {:.1}{} (max {:.1}{})", threshold.channel, value, unit, max, unit),
+                )),
+                (Some(min), _) if value < min => Some(Alert::warning(
+                    format!("low_{}", threshold.channel),
+                    format!("Low {} reading: {:.1}{} (min {:.1}{})", threshold.channel, value, unit, min, unit),
+                )),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reads the DS18B20 probe configured for `role`, with retries and a per-attempt timeout.
+///
+/// Returns None without retrying when no probe is configured for the role.
+/// With `[sensors] spike_filter` on, a rejected spike is replaced by the last
+/// accepted reading of the probe, see `SpikeFilter`. Readings at or above
+/// `overheat_temp` always pass, so overheat protection sees them.
+async fn read_probe(config: &Config, role: &str, timeout: Duration) -> Option<f32> {
+    let device_id = config.sensors.probe_id(role)?.to_string();
+    let value = retry(|| {
+        let device_id = device_id.clone();
+        read_with_timeout("DS18B20", timeout, move || read_ds18b20(&device_id))
+    }, config.get_data.retry).await?;
+
+    if !config.sensors.spike_filter() {
+        return Some(value);
+    }
+
+    let mut filters = PROBE_FILTERS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    let filter = filters.entry(role.to_string())
+        .or_insert_with(|| {
+            SpikeFilter::new(config.sensors.filter_window(), config.sensors.max_temp_jump())
+                .pass_above(config.light_control.overheat_temp as f32)
+        });
+    match filter.accept(value) {
+        FilterResult::Accepted(value) => Some(value),
+        FilterResult::Rejected(previous) => {
+            warn!("Rejected {} probe reading of {:.1}°C as a spike, keeping {:?}", role, value, previous);
+            previous
+        }
+    }
+}
+
+// Read health of every sensor, kept across reads
+static SENSOR_HEALTH: OnceLock<StdMutex<SensorHealth>> = OnceLock::new();
+
+/// Records the outcome of a sensor read in the shared `SensorHealth` and passes the value on
+fn record_read(config: &Config, name: &str, value: Option<f32>) -> Option<f32> {
+    let mut health = SENSOR_HEALTH.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    match health.record(name, value.is_some(), Utc::now(), config.sensors.fault_after()) {
+        Some(true) => error!("SENSOR FAULT: {} sensor failed {} reads in a row", name, config.sensors.fault_after()),
+        Some(false) => info!("{} sensor recovered", name),
+        None => {}
+    }
+    value
+}
+
+/// Read health of every sensor read so far, in read order
+pub fn sensor_health() -> Vec<SensorStatus> {
+    SENSOR_HEALTH.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner()).statuses().to_vec()
+}
+
+/// Counts failed reads in a row per sensor.
+///
+/// A sensor is faulted once `fault_after` reads in a row have failed, so the
+/// UI can show a fault instead of the 0.0 placeholder reading. The next
+/// successful read clears the fault.
+#[derive(Debug, Default)]
+pub struct SensorHealth {
+    sensors: Vec<SensorStatus>,
+}
+
+impl SensorHealth {
+    /// Records one read of `name`.
+    ///
+    /// # Returns
+    ///
+    /// Some(true) when the sensor just became faulted, Some(false) when it
+    /// just recovered, None when its fault state didn't change
+    pub fn record(&mut self, name: &str, ok: bool, at: DateTime<Utc>, fault_after: u32) -> Option<bool> {
+        let index = match self.sensors.iter().position(|sensor| sensor.name == name) {
+            Some(index) => index,
+            None => {
+                self.sensors.push(SensorStatus { name: name.to_string(), faulted: false, consecutive_failures: 0, last_good: None });
+                self.sensors.len() - 1
+            }
+        };
+        let sensor = &mut self.sensors[index];
+        let was_faulted = sensor.faulted;
+
+        if ok {
+            sensor.consecutive_failures = 0;
+            sensor.faulted = false;
+            sensor.last_good = Some(at);
+        } else {
+            sensor.consecutive_failures += 1;
+            sensor.faulted = sensor.consecutive_failures >= fault_after;
+        }
+
+        (sensor.faulted != was_faulted).then_some(sensor.faulted)
+    }
+
+    pub fn statuses(&self) -> &[SensorStatus] {
+        &self.sensors
+    }
+}
+
+// Spike filter of every DS18B20 role, kept across reads
+static PROBE_FILTERS: OnceLock<StdMutex<HashMap<String, SpikeFilter>>> = OnceLock::new();
+
+/// Value a DS18B20 reports after a power-on reset, before its first conversion
+pub const DS18B20_POWER_ON_VALUE: f32 = 85.0;
+
+/// Outcome of feeding a reading to a `SpikeFilter`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterResult {
+    Accepted(f32),
+    /// The reading was discarded; holds the last accepted reading, if any
+    Rejected(Option<f32>),
+}
+
+/// Rejects temperature readings that deviate implausibly from the recent trend.
+///
+/// A reading is rejected when it is the DS18B20 power-on value, or when it
+/// differs from the median of the last `window` accepted readings by more than
+/// `max_jump`. After `window` rejections in a row the temperature is taken to
+/// have really changed: the reading is accepted and the history restarts from it.
+///
+/// Readings at or above `pass_above` are never rejected, not even the power-on
+/// value: a false overheat is safer than a missed one.
+#[derive(Debug)]
+pub struct SpikeFilter {
+    window: usize,
+    max_jump: f32,
+    pass_above: f32,
+    history: VecDeque<f32>,
+    rejected_in_row: usize,
+}
+
+impl SpikeFilter {
+    pub fn new(window: usize, max_jump: f32) -> Self {
+        Self { window: window.max(1), max_jump, pass_above: f32::INFINITY, history: VecDeque::new(), rejected_in_row: 0 }
+    }
+
+    /// Accepts every reading at or above `limit`, e.g. the overheat temperature
+    pub fn pass_above(mut self, limit: f32) -> Self {
+        self.pass_above = limit;
+        self
+    }
+
+    /// Feeds the next reading through the filter
+    pub fn accept(&mut self, value: f32) -> FilterResult {
+        let hot = value >= self.pass_above;
+        if !value.is_finite() || (value == DS18B20_POWER_ON_VALUE && !hot) {
+            return FilterResult::Rejected(self.history.back().copied());
+        }
+
+        if let Some(median) = self.median() {
+            if (value - median).abs() > self.max_jump {
+                self.rejected_in_row += 1;
+                if self.rejected_in_row < self.window && !hot {
+                    return FilterResult::Rejected(self.history.back().copied());
+                }
+                self.history.clear();
+            }
+        }
+
+        self.rejected_in_row = 0;
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(value);
+        FilterResult::Accepted(value)
+    }
+
+    fn median(&self) -> Option<f32> {
+        if self.history.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f32> = self.history.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let mid = sorted.len() / 2;
+        Some(if sorted.len() % 2 == 0 { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] })
+    }
+}
+
+/// Runs a series of readings through a fresh `SpikeFilter`.
+///
+/// # Returns
+///
+/// The accepted readings, with None for every rejected one
+pub fn filter_spikes(series: &[f32], window: usize, max_jump: f32) -> Vec<Option<f32>> {
+    let mut filter = SpikeFilter::new(window, max_jump);
+    series.iter()
+        .map(|&value| match filter.accept(value) {
+            FilterResult::Accepted(value) => Some(value),
+            FilterResult::Rejected(_) => None,
+        })
+        .collect()
+}
+
+/// Runs a blocking sensor read on the blocking thread pool, giving up after `timeout`.
+///
+/// A sensor that hangs (e.g. a flaky DHT22) yields None so the caller can retry
+/// instead of stalling data collection. The stuck read keeps its blocking thread
+/// until it returns on its own, its result is discarded.
+///
+/// # Arguments
+///
+/// * `sensor` - Sensor name used in the log message
+/// * `timeout` - How long to wait for the read
+/// * `read` - The blocking read
+pub async fn read_with_timeout<F, T>(sensor: &str, timeout: Duration, read: F) -> Option<T>
+where
+    F: FnOnce() -> Option<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::time::timeout(timeout, tokio::task::spawn_blocking(read)).await {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            error!("{} read panicked: {}", sensor, e);
+            None
+        }
+        Err(_) => {
+            warn!("{} read timed out after {}ms", sensor, timeout.as_millis());
+            None
+        }
+    }
 }
 
 /// Collects sensor data, updates the current readings, and logs values to the database.
@@ -115,36 +515,36 @@ pub async fn read_all_sensors(config: &Config) -> CurrentReadings {
 /// * `current_readings` - Shared mutex containing the current sensor readings
 /// * `config` - Application configuration
 /// * `light_controller` - Reference to the light controller for temperature updates
+/// * `away` - Whether away mode is on
 ///
 /// # Returns
 ///
 /// Returns nothing. Logs errors if sensor reading or database operations fail.
 pub async fn read_sensors(
-    pool: &PgPool, 
+    pool: &SqlitePool, 
     current_readings: &Arc<Mutex<CurrentReadings>>, 
     config: &Config,
-    light_controller: &Arc<Mutex<LightController>>
+    light_controller: &Arc<Mutex<LightController>>,
+    away: bool,
 ) {
     // Get new readings
-    let readings = read_all_sensors(config).await;
+    let readings = read_all_sensors(config, away).await;
     
     // Update the shared current readings
     {
         let mut current = current_readings.lock().await;
-        current.timestamp = readings.timestamp;
-        current.basking_temp = readings.basking_temp;
-        current.control_temp = readings.control_temp;
-        current.cool_temp = readings.cool_temp;
-        current.humidity = readings.humidity;
-        current.uv_1 = readings.uv_1;
-        current.uv_2 = readings.uv_2;
+        *current = readings.clone();
     }
     
     // Pass the current temperature to the light controller for overheat protection
     {
         if let Ok(mut light_ctrl) = light_controller.try_lock() {
-            // Update the temperature for overheat protection
-            light_ctrl.update_temperature(readings.basking_temp);
+            // Only probes whose read just succeeded count as real readings
+            let health = sensor_health();
+            let fresh = |role: &str, value: f32| health.iter()
+                .find(|status| status.name == role && status.consecutive_failures == 0)
+                .map(|_| value);
+            light_ctrl.update_readings(fresh("basking", readings.basking_temp), fresh("control", readings.control_temp));
         }
     }
     
@@ -159,24 +559,32 @@ pub async fn read_sensors(
         readings.uv_2
     );
     
-    // Convert to database model
-    let db_readings = SensorReadings {
-        timestamp: readings.timestamp.naive_utc(),
+    // Save to database
+    if let Err(e) = store_readings(pool, &readings).await {
+        error!("Failed to save sensor readings to database: {}", e);
+    }
+}
+
+/// Converts readings to a `sensor_history` row, stamped in local time like the
+/// graph and export queries expect
+fn history_row(readings: &CurrentReadings) -> SensorReadings {
+    SensorReadings {
+        timestamp: readings.timestamp.with_timezone(&Local).naive_local(),
         basking_temp: Some(readings.basking_temp),
         control_temp: Some(readings.control_temp),
         cool_temp: Some(readings.cool_temp),
         humidity: Some(readings.humidity),
         uv_1: Some(readings.uv_1),
         uv_2: Some(readings.uv_2),
-    };
-    
-    // Save to database
-    if let Err(e) = save_readings_to_db(pool, &db_readings).await {
-        error!("Failed to save sensor readings to database: {}", e);
     }
 }
 
-/// Saves sensor readings to the database for historical tracking.
+/// Saves sensor readings to `sensor_history` for historical tracking, and
+/// the readings of extra DS18B20 probes to `probe_history` with the same
+/// timestamp. A failed probe read is stored as NULL.
+///
+/// The one writer of collected readings, used by both the background
+/// collection and on-demand reads.
 ///
 /// # Arguments
 ///
@@ -186,22 +594,39 @@ pub async fn read_sensors(
 /// # Returns
 ///
 /// A Result indicating success or a database error
-async fn save_readings_to_db(pool: &PgPool, readings: &SensorReadings) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-        INSERT INTO sensor_readings 
-        (timestamp, basking_temp, control_temp, cool_temp, humidity, uv_1, uv_2)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        "#,
-        readings.timestamp,
-        readings.basking_temp,
-        readings.control_temp,
-        readings.cool_temp,
-        readings.humidity,
-        readings.uv_1,
-        readings.uv_2
-    )
-    .execute(pool)
+pub async fn store_readings(pool: &SqlitePool, readings: &CurrentReadings) -> Result<(), sqlx::Error> {
+    let probes = &readings.probes;
+    let readings = &history_row(readings);
+    let timestamp = &readings.timestamp.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string();
+    storage::retry_busy(|| async move {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO sensor_history
+            (timestamp, basking_temp, control_temp, cool_temp, humidity, uv_1, uv_2)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            timestamp,
+            readings.basking_temp,
+            readings.control_temp,
+            readings.cool_temp,
+            readings.humidity,
+            readings.uv_1,
+            readings.uv_2
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for (probe, &temperature) in probes {
+            sqlx::query("INSERT INTO probe_history (timestamp, probe, temperature) VALUES (?, ?, ?)")
+                .bind(timestamp)
+                .bind(probe)
+                .bind(temperature)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await
+    })
     .await?;
     
     Ok(())
@@ -219,11 +644,13 @@ async fn save_readings_to_db(pool: &PgPool, readings: &SensorReadings) -> Result
 /// * `current_readings` - Shared state for storing the most recent readings
 /// * `config` - Application configuration
 /// * `light_controller` - Light controller for temperature monitoring
+/// * `away_mode` - Shared away mode flag, read on every collection
 pub async fn start_data_collection(
-    db_pool: Arc<PgPool>,
+    db_pool: Arc<SqlitePool>,
     current_readings: Arc<Mutex<CurrentReadings>>,
     config: Arc<Config>,
     light_controller: Arc<Mutex<LightController>>,
+    away_mode: Arc<Mutex<bool>>,
 ) {
     // Log data collection start
     if let Err(e) = logs::log(&db_pool, "INFO", "Starting sensor data collection").await {
@@ -231,24 +658,43 @@ pub async fn start_data_collection(
     }
 
     // Get collection interval from config (default to 60 seconds if not specified)
-    let interval_seconds = config.get_data.interval.unwrap_or(60);
+    let interval_seconds = config.get_data.interval().as_secs();
     
-    // Spawn a background task for data collection
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(interval_seconds));
+    // Spawn a background task for data collection, restarted by the watchdog if it panics
+    let backoff = config.main.restart_backoff();
+    tokio::spawn(watchdog::supervise(Arc::clone(&db_pool), "sensors", backoff, move || {
+        let db_pool = Arc::clone(&db_pool);
+        let current_readings = Arc::clone(&current_readings);
+        let config = Arc::clone(&config);
+        let light_controller = Arc::clone(&light_controller);
+        let away_mode = Arc::clone(&away_mode);
+        async move {
+            let period = tokio::time::Duration::from_secs(interval_seconds);
+            let mut interval = tokio::time::interval(period);
         
-        loop {
-            interval.tick().await;
+            loop {
+                let tick = interval.tick().await;
             
-            // Collect and store sensor data
-            if let Err(e) = collect_data(&db_pool, &current_readings, &config, &light_controller).await {
-                eprintln!("Error collecting sensor data: {:?}", e);
-                if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error collecting sensor data: {:?}", e)).await {
-                    eprintln!("Failed to log error: {:?}", log_err);
+                // Collect and store sensor data
+                let away = *away_mode.lock().await;
+                let collected = if config.get_data.read_mode == ReadMode::Staggered {
+                    let sensors = staggered_sensors(&config.sensors);
+                    read_staggered(tick, period, &sensors, |sensor| read_into(&config, &current_readings, sensor)).await;
+                    let readings = current_readings.lock().await.clone();
+                    warn_over_threshold(&readings, &config, away);
+                    process_readings(&db_pool, &readings, &config, &light_controller).await
+                } else {
+                    collect_data(&db_pool, &current_readings, &config, &light_controller, away).await
+                };
+                if let Err(e) = collected {
+                    eprintln!("Error collecting sensor data: {:?}", e);
+                    if let Err(log_err) = logs::log(&db_pool, "ERROR", &format!("Error collecting sensor data: {:?}", e)).await {
+                        eprintln!("Failed to log error: {:?}", log_err);
+                    }
                 }
             }
         }
-    });
+    }));
 }
 
 /// Retrieves the most recent sensor readings from shared state.
@@ -264,19 +710,12 @@ pub async fn start_data_collection(
 ///
 /// A copy of the current readings
 pub async fn get_current_readings(readings: &Arc<Mutex<CurrentReadings>>) -> CurrentReadings {
-    let current = readings.lock().await;
-    CurrentReadings {
-        timestamp: current.timestamp,
-        basking_temp: current.basking_temp,
-        control_temp: current.control_temp,
-        cool_temp: current.cool_temp,
-        humidity: current.humidity,
-        uv_1: current.uv_1,
-        uv_2: current.uv_2,
-    }
+    readings.lock().await.clone()
 }
 
-/// Checks if the system is currently in an overheat state.
+/// Gets the current overheat protection state.
+///
+/// Must not be called while holding the light controller lock.
 ///
 /// # Arguments
 ///
@@ -284,13 +723,9 @@ pub async fn get_current_readings(readings: &Arc<Mutex<CurrentReadings>>) -> Cur
 ///
 /// # Returns
 ///
-/// Boolean indicating whether the system is overheating
-pub async fn get_overheat_status(light_controller: &Arc<Mutex<LightController>>) -> bool {
-    if let Ok(light_ctrl) = light_controller.try_lock() {
-        light_ctrl.is_overheating()
-    } else {
-        false // Default to false if we can't get the lock
-    }
+/// Whether the system is overheating, with the temperature and cooldown
+pub async fn get_overheat_status(light_controller: &Arc<Mutex<LightController>>) -> OverheatStatus {
+    light_controller.lock().await.overheat_status()
 }
 
 /// Retries a fallible operation a specified number of times.
@@ -300,7 +735,8 @@ pub async fn get_overheat_status(light_controller: &Arc<Mutex<LightController>>)
 ///
 /// # Type Parameters
 ///
-/// * `F` - A function that returns an Option<T>
+/// * `F` - A function that starts one attempt
+/// * `Fut` - The future of a single attempt, resolving to an Option<T>
 /// * `T` - The return type of the function
 ///
 /// # Arguments
@@ -311,12 +747,13 @@ pub async fn get_overheat_status(light_controller: &Arc<Mutex<LightController>>)
 /// # Returns
 ///
 /// The result of the function if successful, or None if all attempts fail
-async fn retry<F, T>(mut f: F, retries: u8) -> Option<T>
+async fn retry<F, Fut, T>(mut f: F, retries: u8) -> Option<T>
 where
-    F: FnMut() -> Option<T>,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
 {
     for attempt in 1..=retries {
-        match f() {
+        match f().await {
             Some(result) => return Some(result),
             None => {
                 if attempt < retries {
@@ -339,7 +776,7 @@ where
 /// # Arguments
 ///
 /// * `pool` - Database connection pool
-pub async fn shutdown_safely(pool: &PgPool) {
+pub async fn shutdown_safely(pool: &SqlitePool) {
     // Log shutdown
     if let Err(e) = logs::log(pool, "INFO", "Shutting down data collection").await {
         eprintln!("Failed to log shutdown: {:?}", e);
@@ -368,18 +805,20 @@ pub async fn shutdown_safely(pool: &PgPool) {
 /// * `current_readings` - Shared state for current readings
 /// * `config` - Application configuration
 /// * `light_controller` - Light controller for temperature updates
+/// * `away` - Whether away mode is on
 ///
 /// # Returns
 ///
 /// Result indicating success or providing an error
 async fn collect_data(
-    db_pool: &PgPool,
+    db_pool: &SqlitePool,
     current_readings: &Arc<Mutex<CurrentReadings>>,
     config: &Config,
     light_controller: &Arc<Mutex<LightController>>,
+    away: bool,
 ) -> Result<(), Box<dyn Error>> {
     // Read all sensors
-    let readings = read_all_sensors(config).await;
+    let readings = read_all_sensors(config, away).await;
     
     // Update the current readings
     {
@@ -387,26 +826,299 @@ async fn collect_data(
         *current = readings.clone();
     }
     
+    process_readings(db_pool, &readings, config, light_controller).await
+}
+
+/// Stores a complete set of readings and raises the alerts they call for,
+/// the part of `collect_data` after the sensors are read.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database connection pool
+/// * `readings` - The readings of this interval
+/// * `config` - Application configuration
+/// * `light_controller` - Light controller, for the overheat state
+async fn process_readings(
+    db_pool: &SqlitePool,
+    readings: &CurrentReadings,
+    config: &Config,
+    light_controller: &Arc<Mutex<LightController>>,
+) -> Result<(), Box<dyn Error>> {
     // Store readings in the database
-    store_readings(db_pool, &readings).await?;
+    store_readings(db_pool, readings).await?;
     
-    // Log unusual readings
-    if readings.basking_temp > config.thresholds.max_basking_temp {
-        logs::log(db_pool, "WARNING", &format!("High basking temperature: {:.1}°C", readings.basking_temp)).await?;
-    }
+    // Readings outside their [thresholds] range
+    let mut present = threshold_warnings(&config.thresholds, readings, &sensor_health());
     
-    if readings.control_temp > config.thresholds.max_control_temp {
-        logs::log(db_pool, "WARNING", &format!("High control temperature: {:.1}°C", readings.control_temp)).await?;
+    // Check for overheat condition
+    let overheat = get_overheat_status(light_controller).await;
+    if overheat.overheating {
+        present.push(Alert::error("overheat", "OVERHEAT CONDITION DETECTED! Emergency shutdown initiated."));
     }
     
-    if readings.humidity < config.thresholds.min_humidity {
-        logs::log(db_pool, "WARNING", &format!("Low humidity: {:.1}%", readings.humidity)).await?;
+    if overheat.heat_watchdog_tripped {
+        present.push(Alert::error("heat_watchdog", "HEAT WATCHDOG TRIPPED! Heat lamp stayed on too long and is cut until resumed."));
     }
     
-    // Check for overheat condition
-    if get_overheat_status(light_controller).await {
-        logs::log(db_pool, "ERROR", "OVERHEAT CONDITION DETECTED! Emergency shutdown initiated.").await?;
-    }
+    // Logged every cycle until acknowledged through POST /api/alerts/:id/ack
+    alerts::notify(db_pool, alerts::tracker(), present).await?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_overheat_status_reported_from_spawned_task() {
+        let config = crate::modules::config::test_config();
+        let light_controller = Arc::new(Mutex::new(LightController::unavailable(config.light_control.clone())));
+        let threshold = config.light_control.overheat_temp as f32;
+        light_controller.lock().await.update_temperature(threshold + 1.0);
+
+        // The web handlers await it on the runtime's worker threads, so it has to be `Send`
+        let status = tokio::spawn({
+            let light_controller = Arc::clone(&light_controller);
+            async move { get_overheat_status(&light_controller).await }
+        }).await.unwrap();
+        assert!(status.overheating);
+        assert_eq!((status.current_temp, status.threshold), (threshold + 1.0, threshold));
+    }
+
+    #[test]
+    fn test_sensor_faulted_after_failures_and_reset_by_success() {
+        let mut health = SensorHealth::default();
+        let start = Utc::now();
+        let at = |minute: i64| start + chrono::Duration::minutes(minute);
+
+        assert_eq!(health.record("basking", true, at(0), 3), None);
+        assert_eq!(health.record("basking", false, at(1), 3), None);
+        assert_eq!(health.record("basking", false, at(2), 3), None);
+        assert_eq!(health.record("basking", false, at(3), 3), Some(true));
+        assert_eq!(health.record("basking", false, at(4), 3), None);
+
+        let basking = &health.statuses()[0];
+        assert!(basking.faulted);
+        assert_eq!(basking.consecutive_failures, 4);
+        assert_eq!(basking.last_good, Some(at(0)));
+
+        assert_eq!(health.record("basking", true, at(5), 3), Some(false));
+        assert_eq!(health.statuses()[0], SensorStatus {
+            name: "basking".to_string(),
+            faulted: false,
+            consecutive_failures: 0,
+            last_good: Some(at(5)),
+        });
+    }
+
+    #[test]
+    fn test_spike_filter_rejects_power_on_value_and_spikes() {
+        let series = [85.0, 30.1, 30.3, 85.0, 30.2, 47.9, 30.4, 12.0, 30.5];
+        assert_eq!(filter_spikes(&series, 5, 5.0), vec![
+            None, Some(30.1), Some(30.3), None, Some(30.2), None, Some(30.4), None, Some(30.5),
+        ]);
+    }
+
+    #[test]
+    fn test_spike_filter_follows_sustained_change() {
+        // The lamp coming on is a real jump: accepted once it persists for the window
+        let series = [25.0, 25.2, 25.1, 33.0, 33.4, 33.8, 34.0];
+        assert_eq!(filter_spikes(&series, 3, 5.0), vec![
+            Some(25.0), Some(25.2), Some(25.1), None, None, Some(33.8), Some(34.0),
+        ]);
+    }
+
+    #[test]
+    fn test_rejected_spike_keeps_last_reading() {
+        let mut filter = SpikeFilter::new(5, 5.0);
+        assert_eq!(filter.accept(DS18B20_POWER_ON_VALUE), FilterResult::Rejected(None));
+        assert_eq!(filter.accept(28.5), FilterResult::Accepted(28.5));
+        assert_eq!(filter.accept(60.0), FilterResult::Rejected(Some(28.5)));
+    }
+
+    #[test]
+    fn test_readings_above_overheat_are_never_filtered() {
+        let mut filter = SpikeFilter::new(5, 5.0).pass_above(45.0);
+        assert_eq!(filter.accept(30.0), FilterResult::Accepted(30.0));
+        assert_eq!(filter.accept(40.0), FilterResult::Rejected(Some(30.0)));
+        assert_eq!(filter.accept(48.0), FilterResult::Accepted(48.0));
+        assert_eq!(filter.accept(DS18B20_POWER_ON_VALUE), FilterResult::Accepted(DS18B20_POWER_ON_VALUE));
+
+        // Below the limit the power-on value is still a spike
+        let mut filter = SpikeFilter::new(5, 5.0).pass_above(90.0);
+        assert_eq!(filter.accept(DS18B20_POWER_ON_VALUE), FilterResult::Rejected(None));
+    }
+
+    #[tokio::test]
+    async fn test_slow_read_times_out() {
+        let started = Instant::now();
+        let value = read_with_timeout("mock", Duration::from_millis(50), || {
+            std::thread::sleep(Duration::from_millis(500));
+            Some(21.5)
+        }).await;
+
+        assert_eq!(value, None);
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_fast_read_returns_value() {
+        let value = read_with_timeout("mock", Duration::from_millis(500), || Some(21.5)).await;
+        assert_eq!(value, Some(21.5));
+    }
+
+    #[tokio::test]
+    async fn test_retry_recovers_after_timeout() {
+        let mut attempts = 0;
+        let value = retry(|| {
+            attempts += 1;
+            let delay = if attempts == 1 { 500 } else { 0 };
+            read_with_timeout("mock", Duration::from_millis(50), move || {
+                std::thread::sleep(Duration::from_millis(delay));
+                Some(42)
+            })
+        }, 3).await;
+
+        assert_eq!(value, Some(42));
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_collected_readings_reach_graph_and_csv() {
+        use crate::modules::web::handlers::monitoring::get_graph_data_for_date;
+
+        let pool = storage::temp_db("collect").await;
+
+        let mut readings = CurrentReadings::new();
+        readings.basking_temp = 32.5;
+        readings.control_temp = 27.0;
+        readings.cool_temp = 23.5;
+        readings.humidity = 55.0;
+        readings.uv_1 = 3.0;
+        let row = history_row(&readings);
+        store_readings(&pool, &readings).await.unwrap();
+
+        let day = row.timestamp.date();
+        let points = get_graph_data_for_date(&pool, day).await;
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].time, row.timestamp.format("%H:%M").to_string());
+        assert_eq!((points[0].temperature, points[0].controlTemp, points[0].coolZoneTemp), (32.5, 27.0, 23.5));
+
+        let date = day.format("%Y-%m-%d").to_string();
+        let csv = logs::get_sensor_data_csv(&pool, &date, &date).await.unwrap();
+        let stamp = row.timestamp.format(storage::SENSOR_TIMESTAMP_FORMAT);
+        assert_eq!(csv.lines().nth(1), Some(format!("{},32.5,55,3", stamp).as_str()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_reads_finish_faster() {
+        let events = StdMutex::new(Vec::new());
+        let slow_read = |value: f32| {
+            let events = &events;
+            async move {
+                events.lock().unwrap().push(("start", value));
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                events.lock().unwrap().push(("end", value));
+                Some(value)
+            }
+        };
+        let take_events = || std::mem::take(&mut *events.lock().unwrap());
+
+        let sequential = read_group(ReadMode::Sequential, slow_read(1.0), slow_read(2.0), slow_read(3.0)).await;
+        assert_eq!(take_events(), [("start", 1.0), ("end", 1.0), ("start", 2.0), ("end", 2.0), ("start", 3.0), ("end", 3.0)]);
+
+        // Every read starts before the first one finishes
+        let started = tokio::time::Instant::now();
+        let concurrent = read_group(ReadMode::Concurrent, slow_read(1.0), slow_read(2.0), slow_read(3.0)).await;
+        assert_eq!(take_events(), [("start", 1.0), ("start", 2.0), ("start", 3.0), ("end", 1.0), ("end", 2.0), ("end", 3.0)]);
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+
+        assert_eq!(sequential, (Some(1.0), Some(2.0), Some(3.0)));
+        assert_eq!(concurrent, sequential);
+    }
+
+    #[tokio::test]
+    async fn test_staggered_sensors_read_at_distinct_offsets() {
+        let interval = Duration::from_millis(300);
+        let offsets = stagger_offsets(interval, STAGGERED_SENSORS.len());
+        assert_eq!(offsets, (0..6).map(|slot| Duration::from_millis(50 * slot)).collect::<Vec<_>>());
+
+        let start = tokio::time::Instant::now();
+        let reads = StdMutex::new(Vec::new());
+        read_staggered(start, interval, &STAGGERED_SENSORS, |sensor| {
+            reads.lock().unwrap().push((sensor, start.elapsed()));
+            async {}
+        }).await;
+
+        let reads = reads.into_inner().unwrap();
+        assert_eq!(reads.iter().map(|(sensor, _)| *sensor).collect::<Vec<_>>(), STAGGERED_SENSORS);
+        for ((sensor, at), offset) in reads.iter().zip(&offsets) {
+            assert!(*at >= *offset && *at < *offset + Duration::from_millis(40), "{} read at {:?}, slot {:?}", sensor, at, offset);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cloned_reading_equals_original_and_persists() {
+        let pool = storage::temp_db("clone").await;
+
+        let mut readings = CurrentReadings::new();
+        readings.basking_temp = 34.25;
+        readings.humidity = 61.0;
+        readings.uv_2 = 1.5;
+        let shared = Arc::new(Mutex::new(CurrentReadings::new()));
+        *shared.lock().await = readings.clone();
+        let current = get_current_readings(&shared).await;
+        assert_eq!(current, readings);
+
+        store_readings(&pool, &current).await.unwrap();
+        let (basking, humidity, uv_2): (Option<f64>, Option<f64>, Option<f64>) =
+            sqlx::query_as("SELECT basking_temp, humidity, uv_2 FROM sensor_history")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((basking, humidity, uv_2), (Some(34.25), Some(61.0), Some(1.5)));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_reading_logs_warning() {
+        let pool = storage::temp_db("thresholds").await;
+        let thresholds = ThresholdsConfig {
+            max_basking_temp: Some(45.0),
+            min_humidity: Some(30.0),
+            min_uv2: Some(1.0),
+            ..Default::default()
+        };
+
+        let mut readings = CurrentReadings::new();
+        readings.basking_temp = 47.5;
+        readings.humidity = 55.0;
+        // uv2 failed its last read, so its 0.0 placeholder isn't "low"
+        let health = [SensorStatus { name: "uv2".to_string(), faulted: false, consecutive_failures: 1, last_good: None }];
+        let tracker = StdMutex::new(alerts::AlertTracker::default());
+        assert_eq!(alerts::notify(&pool, &tracker, threshold_warnings(&thresholds, &readings, &health)).await.unwrap(), 1);
+
+        let entries = logs::get_log_entries(&pool, Some("warning".to_string()), Some(10)).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "High basking reading: 47.5°C (max 45.0°C)");
+
+        readings.humidity = 20.0;
+        assert_eq!(threshold_warnings(&thresholds, &readings, &[]), vec![
+            Alert::warning("high_basking", "High basking reading: 47.5°C (max 45.0°C)"),
+            Alert::warning("low_humidity", "Low humidity reading: 20.0% (min 30.0%)"),
+            Alert::warning("low_uv2", "Low uv2 reading: 0.0 UVI (min 1.0 UVI)"),
+        ]);
+    }
+
+    #[test]
+    fn test_temperature_warning_uses_away_threshold() {
+        let config = crate::modules::config::test_config();
+        let mut readings = CurrentReadings::new();
+        readings.control_temp = alert_threshold(&config, false) - 1.0;
+
+        // Staggered reads go through this too, so away mode lowers their threshold as well
+        assert!(!warn_over_threshold(&readings, &config, false));
+        assert!(warn_over_threshold(&readings, &config, true));
+    }
+}
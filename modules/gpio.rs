@@ -1,23 +1,106 @@
 use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
-use rppal::gpio::{Gpio, OutputPin};
+use rppal::gpio::{Gpio, Level, OutputPin};
+use rppal::i2c::I2c;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use chrono::Utc;
 use crate::modules::config::GpioConfig;
+use crate::modules::models::{RelayEvent, RelayReason};
+
+// SPI link to the LED strip, opened by `LEDStrip::new` and reported by GET /api/system/hardware
+pub const LED_SPI_BUS: Bus = Bus::Spi0;
+pub const LED_SPI_SLAVE_SELECT: SlaveSelect = SlaveSelect::Ss0;
+pub const LED_SPI_CLOCK_HZ: u32 = 3_200_000; // 3.2MHz for correct timing
+pub const LED_SPI_MODE: Mode = Mode::Mode0;
 
 // WS2805 Constants (SPI Timing)
 const T0H: u8 = 0b10000000; // ~312.5ns high
 const T1H: u8 = 0b11000000; // ~625ns high
 const RESET_TIME_US: u64 = 300; // >280µs reset time
+// WS2805 Constants (bit-banged timing, see `BitBangStrip`)
+const BITBANG_T0H: Duration = Duration::from_nanos(300);  // 220-380ns high for a 0
+const BITBANG_T1H: Duration = Duration::from_nanos(750);  // 580-1000ns high for a 1
+const BITBANG_BIT: Duration = Duration::from_nanos(1250); // Whole bit, high and low
 const CHANNELS_PER_IC: usize = 5;  // Each WS2805 controls 5 LED channels
 const BITS_PER_CHANNEL: usize = 8; // 8 bits per channel
+const BYTES_PER_IC: usize = CHANNELS_PER_IC * BITS_PER_CHANNEL;
+/// spidev rejects transfers larger than its `bufsiz` module parameter (4096 by default)
+const SPIDEV_BUFSIZ: usize = 4096;
+/// Largest write that fits in one spidev transfer and ends on an IC boundary,
+/// so any gap between transfers falls between pixels rather than inside one
+const SPI_CHUNK_BYTES: usize = (SPIDEV_BUFSIZ / BYTES_PER_IC) * BYTES_PER_IC;
+
+// Sysfs directory where the w1-gpio overlay exposes 1-Wire devices
+const W1_DEVICES_DIR: &str = "/sys/bus/w1/devices";
+
+// VEML6075 registers (16-bit, little endian) and constants from the datasheet
+const VEML6075_REG_CONF: u8 = 0x00;
+const VEML6075_REG_UVA: u8 = 0x07;
+const VEML6075_REG_UVB: u8 = 0x09;
+const VEML6075_REG_UVCOMP1: u8 = 0x0A;
+const VEML6075_REG_UVCOMP2: u8 = 0x0B;
+const VEML6075_REG_ID: u8 = 0x0C;
+const VEML6075_DEVICE_ID: u16 = 0x0026;
+const VEML6075_CONF_SHUTDOWN: u16 = 0x0001;
+const VEML6075_CONF_IT_100MS: u16 = 0x0010;
+// Coefficients for an open-air sensor (no diffuser), Vishay app note 84339
+const VEML6075_UVA_A: f32 = 2.22;
+const VEML6075_UVA_B: f32 = 1.33;
+const VEML6075_UVB_C: f32 = 2.95;
+const VEML6075_UVB_D: f32 = 1.74;
+const VEML6075_UVA_RESPONSE: f32 = 0.001461;
+const VEML6075_UVB_RESPONSE: f32 = 0.002591;
+
+/// Error raised when a GPIO backed component can't be brought up
+#[derive(Debug)]
+pub struct GpioInitError {
+    pub component: String,
+    pub message: String,
+}
+
+impl GpioInitError {
+    /// Maps a low-level initialization error into a message that tells the user what to check
+    pub fn new(component: &str, err: &(dyn Error + 'static)) -> Self {
+        let message = match err.downcast_ref::<rppal::gpio::Error>() {
+            Some(rppal::gpio::Error::PermissionDenied(path)) => format!(
+                "permission denied on {} (run as root or add the user to the gpio group)", path
+            ),
+            Some(rppal::gpio::Error::PinNotAvailable(pin)) => format!(
+                "GPIO pin {} is not available, check the [gpio] pin numbers in config.toml", pin
+            ),
+            Some(rppal::gpio::Error::UnknownModel) => {
+                "unsupported or undetected Raspberry Pi model".to_string()
+            },
+            _ => err.to_string(),
+        };
+
+        Self { component: component.to_string(), message }
+    }
+}
+
+impl fmt::Display for GpioInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to initialize {}: {}", self.component, self.message)
+    }
+}
+
+impl Error for GpioInitError {}
 
 /// Loads LED strip count from config
 fn get_ic_count() -> usize {
-    GpioConfig::load().ic_count.unwrap_or(16) // Default to 16 if not set
+    GpioConfig::load().ic_count()
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RGBWW {
     pub r: u8,
     pub g: u8,
@@ -44,6 +127,51 @@ impl RGBWW {
             cw: parts[4].parse()?,
         })
     }
+
+    /// Channel values in the order the strip expects them on the wire
+    fn channels(&self, order: ColorOrder) -> [u8; CHANNELS_PER_IC] {
+        let logical = [self.r, self.g, self.b, self.ww, self.cw];
+        order.0.map(|channel| logical[channel])
+    }
+}
+
+/// Order in which a strip takes the five channels on the wire, written as a
+/// string such as `"GRBWC"` (W = warm white, C = cool white). Strip batches differ,
+/// and a wrong order shows every color with its channels swapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorOrder([usize; CHANNELS_PER_IC]); // Logical channel (r, g, b, ww, cw) sent in each wire slot
+
+impl ColorOrder {
+    const LETTERS: [char; CHANNELS_PER_IC] = ['R', 'G', 'B', 'W', 'C'];
+
+    /// Parses an order string, case-insensitive. Each of R, G, B, W and C
+    /// must appear exactly once.
+    pub fn parse(order: &str) -> Result<Self, String> {
+        let letters: Vec<char> = order.trim().to_ascii_uppercase().chars().collect();
+        if letters.len() != CHANNELS_PER_IC {
+            return Err(format!(
+                "color order {:?} must list {} channels: R, G, B, W (warm white) and C (cool white)", order, CHANNELS_PER_IC
+            ));
+        }
+
+        let mut map = [0; CHANNELS_PER_IC];
+        for (slot, letter) in letters.iter().enumerate() {
+            let channel = Self::LETTERS.iter().position(|l| l == letter)
+                .ok_or_else(|| format!("unknown channel {:?} in color order {:?}, expected R, G, B, W or C", letter, order))?;
+            if map[..slot].contains(&channel) {
+                return Err(format!("channel {} appears twice in color order {:?}", letter, order));
+            }
+            map[slot] = channel;
+        }
+        Ok(Self(map))
+    }
+}
+
+impl Default for ColorOrder {
+    /// G, R, B, WW, CW as documented for the WS2805
+    fn default() -> Self {
+        Self([1, 0, 2, 3, 4])
+    }
 }
 
 /// Converts a byte to SPI bit timing format
@@ -55,66 +183,542 @@ fn convert_byte(byte: u8, buffer: &mut [u8]) {
     }
 }
 
-/// Controls an SPI-based LED strip
-pub struct LEDStrip {
-    spi: Spi,
-    buffer: Vec<u8>,
+/// Encoded SPI frame for a strip of WS2805 ICs.
+///
+/// The buffer is allocated once. When the whole strip shows a single color
+/// (the common case during fades) only the channels that changed since the
+/// last frame are re-encoded, and the encoded bytes are copied across ICs.
+pub struct FrameBuffer {
+    data: Vec<u8>,
     ic_count: usize,
+    order: ColorOrder,
+    uniform: Option<RGBWW>, // Color held by every IC, if they all hold the same one
 }
 
-impl LEDStrip {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let spi = Spi::new(
-            Bus::Spi0,
-            SlaveSelect::Ss0,
-            3_200_000, // 3.2MHz for correct timing
-            Mode::Mode0,
-        )?;
-        let ic_count = get_ic_count();
-        let buffer = vec![0; ic_count * CHANNELS_PER_IC * BITS_PER_CHANNEL];
-        Ok(Self { spi, buffer, ic_count })
+impl FrameBuffer {
+    pub fn new(ic_count: usize) -> Self {
+        Self::with_color_order(ic_count, ColorOrder::default())
     }
 
+    pub fn with_color_order(ic_count: usize, order: ColorOrder) -> Self {
+        Self {
+            data: vec![0; ic_count * BYTES_PER_IC],
+            ic_count,
+            order,
+            uniform: None,
+        }
+    }
+
+    /// Sets every IC to `color`, re-encoding only the channels that changed
     pub fn set_all(&mut self, color: RGBWW) {
-        for i in 0..self.ic_count {
-            self.set_ic(i, color);
+        let previous = self.uniform.map(|c| c.channels(self.order));
+        if previous == Some(color.channels(self.order)) {
+            return;
         }
+
+        for (channel, value) in color.channels(self.order).iter().copied().enumerate() {
+            if previous.map_or(false, |p| p[channel] == value) {
+                continue;
+            }
+            let mut encoded = [0u8; BITS_PER_CHANNEL];
+            convert_byte(value, &mut encoded);
+            let offset = channel * BITS_PER_CHANNEL;
+            for ic in self.data.chunks_exact_mut(BYTES_PER_IC) {
+                ic[offset..offset + BITS_PER_CHANNEL].copy_from_slice(&encoded);
+            }
+        }
+        self.uniform = Some(color);
     }
 
     pub fn set_ic(&mut self, index: usize, color: RGBWW) {
         if index >= self.ic_count {
             return;
         }
-        let start = index * CHANNELS_PER_IC * BITS_PER_CHANNEL;
-        convert_byte(color.g, &mut self.buffer[start..start + 8]);
-        convert_byte(color.r, &mut self.buffer[start + 8..start + 16]);
-        convert_byte(color.b, &mut self.buffer[start + 16..start + 24]);
-        convert_byte(color.ww, &mut self.buffer[start + 24..start + 32]);
-        convert_byte(color.cw, &mut self.buffer[start + 32..start + 40]);
+        let start = index * BYTES_PER_IC;
+        for (channel, value) in color.channels(self.order).iter().copied().enumerate() {
+            let offset = start + channel * BITS_PER_CHANNEL;
+            convert_byte(value, &mut self.data[offset..offset + BITS_PER_CHANNEL]);
+        }
+        if self.uniform != Some(color) {
+            self.uniform = None;
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
     }
+}
+
+/// Writes frames to a strip of WS2805 ICs
+pub trait LedDriver: Send {
+    fn set_all(&mut self, color: RGBWW);
 
-    pub fn show(&mut self) -> Result<(), Box<dyn Error>> {
-        self.spi.write(&self.buffer)?;
+    fn set_ic(&mut self, index: usize, color: RGBWW);
+
+    /// Sends the frame and waits until the ICs have latched it
+    fn show(&mut self) -> Result<(), Box<dyn Error>>;
+}
+
+/// How the strip's data line is driven, see `[led] driver`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LedWiring {
+    /// Hardware SPI0 MOSI (GPIO 10)
+    Spi,
+    /// Any GPIO pin, timed by the CPU
+    BitBang { pin: u8 },
+}
+
+/// Opens the strip on `wiring` with `order` as its channel order
+pub fn open_led_driver(wiring: LedWiring, order: ColorOrder) -> Result<Box<dyn LedDriver>, Box<dyn Error>> {
+    Ok(match wiring {
+        LedWiring::Spi => Box::new(LEDStrip::new(order)?),
+        LedWiring::BitBang { pin } => Box::new(BitBangStrip::new(pin, order)?),
+    })
+}
+
+/// Controls an SPI-based LED strip
+pub struct LEDStrip {
+    spi: Spi,
+    frame: FrameBuffer,
+}
+
+impl LEDStrip {
+    pub fn new(order: ColorOrder) -> Result<Self, Box<dyn Error>> {
+        let spi = Spi::new(
+            LED_SPI_BUS,
+            LED_SPI_SLAVE_SELECT,
+            LED_SPI_CLOCK_HZ,
+            LED_SPI_MODE,
+        )?;
+        let frame = FrameBuffer::with_color_order(get_ic_count(), order);
+        Ok(Self { spi, frame })
+    }
+}
+
+impl LedDriver for LEDStrip {
+    fn set_all(&mut self, color: RGBWW) {
+        self.frame.set_all(color);
+    }
+
+    fn set_ic(&mut self, index: usize, color: RGBWW) {
+        self.frame.set_ic(index, color);
+    }
+
+    /// Sends the frame and holds the line low long enough for the ICs to latch it.
+    ///
+    /// Strips longer than `SPI_CHUNK_BYTES / BYTES_PER_IC` ICs (102) are sent in
+    /// several transfers. The strip only sees one continuous frame while the gaps
+    /// between them stay below the reset time, which `write_frame` checks.
+    fn show(&mut self) -> Result<(), Box<dyn Error>> {
+        let spi = &mut self.spi;
+        write_frame(self.frame.as_bytes(), |chunk| spi.write(chunk))?;
         thread::sleep(Duration::from_micros(RESET_TIME_US));
         Ok(())
     }
 }
 
-/// Controls relays for UV, heat, and LED via GPIO 
+/// Drives the strip from a plain GPIO pin when SPI is taken by something else.
+///
+/// Every bit is timed by spinning on the clock, so `show` keeps one CPU core
+/// fully busy for the whole frame: 50µs per IC, e.g. 0.8ms for 16 ICs or 5ms
+/// for 100. An animation at ~30 fps thus costs about 2.5% of a core for 16 ICs
+/// and 15% for 100. Userspace can't mask interrupts, so a preempted bit can
+/// stretch past the reset time and latch half a frame; `show` detects that and
+/// fails, and the next write repeats the frame. Prefer SPI where it's free.
+pub struct BitBangStrip {
+    pin: OutputPin,
+    frame: Vec<u8>, // Channel values in wire order, CHANNELS_PER_IC per IC
+    order: ColorOrder,
+}
+
+impl BitBangStrip {
+    pub fn new(pin: u8, order: ColorOrder) -> Result<Self, Box<dyn Error>> {
+        let pin = Gpio::new()?.get(pin)?.into_output_low();
+        Ok(Self { pin, frame: vec![0; get_ic_count() * CHANNELS_PER_IC], order })
+    }
+}
+
+impl LedDriver for BitBangStrip {
+    fn set_all(&mut self, color: RGBWW) {
+        let channels = color.channels(self.order);
+        for ic in self.frame.chunks_exact_mut(CHANNELS_PER_IC) {
+            ic.copy_from_slice(&channels);
+        }
+    }
+
+    fn set_ic(&mut self, index: usize, color: RGBWW) {
+        if let Some(ic) = self.frame.chunks_exact_mut(CHANNELS_PER_IC).nth(index) {
+            ic.copy_from_slice(&color.channels(self.order));
+        }
+    }
+
+    fn show(&mut self) -> Result<(), Box<dyn Error>> {
+        let reset = Duration::from_micros(RESET_TIME_US);
+        let mut interrupted = false;
+        let mut previous: Option<Instant> = None;
+        for high in bit_high_times(&self.frame) {
+            let start = Instant::now();
+            self.pin.set_high();
+            spin_until(start + high);
+            self.pin.set_low();
+            // A gap this long has already latched the bits sent so far
+            interrupted |= previous.map_or(false, |previous| start - previous >= reset);
+            previous = Some(start);
+            spin_until(start + BITBANG_BIT);
+        }
+        thread::sleep(reset);
+        
+        if interrupted {
+            return Err("Bit-banged LED frame was interrupted and only partly latched".into());
+        }
+        Ok(())
+    }
+}
+
+/// How long the data line stays high for each bit of `frame`, most significant bit first
+fn bit_high_times(frame: &[u8]) -> impl Iterator<Item = Duration> + '_ {
+    frame.iter().flat_map(|&byte| {
+        (0..BITS_PER_CHANNEL).rev().map(move |bit| if byte >> bit & 1 == 1 { BITBANG_T1H } else { BITBANG_T0H })
+    })
+}
+
+/// Busy-waits until `deadline`; sleeping can't resolve nanoseconds
+fn spin_until(deadline: Instant) {
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+/// Writes a frame in `SPI_CHUNK_BYTES` pieces, failing on a short write.
+///
+/// A short write used to go unnoticed and left the last ICs of long strips
+/// showing stale or partly shifted data. A gap of the reset time between two
+/// chunks, e.g. with the thread preempted, has already latched the chunks
+/// sent so far; the rest would shift onto the first ICs, so the write stops
+/// there and fails like the bit-banged driver does. The gap is the time
+/// between two writes returning, less the transfer time of the second chunk.
+///
+/// # Arguments
+///
+/// * `data` - The encoded frame
+/// * `write` - Writes one chunk and returns how many bytes were sent
+///
+/// # Returns
+///
+/// The number of chunks written
+fn write_frame<F, E>(data: &[u8], mut write: F) -> Result<usize, Box<dyn Error>>
+where
+    F: FnMut(&[u8]) -> Result<usize, E>,
+    E: Error + 'static,
+{
+    let reset = Duration::from_micros(RESET_TIME_US);
+    let transfer = |bytes: usize| Duration::from_nanos(bytes as u64 * 8 * 1_000_000_000 / LED_SPI_CLOCK_HZ as u64);
+    let mut chunks = 0;
+    let mut previous_end: Option<Instant> = None;
+    for chunk in data.chunks(SPI_CHUNK_BYTES) {
+        let written = write(chunk).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+        let end = Instant::now();
+        if written != chunk.len() {
+            return Err(format!(
+                "Short SPI write: {} of {} bytes sent (chunk {})", written, chunk.len(), chunks + 1
+            ).into());
+        }
+        if previous_end.map_or(false, |previous| end - previous >= transfer(chunk.len()) + reset) {
+            return Err(format!(
+                "SPI frame stalled before chunk {} and was only partly latched", chunks + 1
+            ).into());
+        }
+        previous_end = Some(end);
+        chunks += 1;
+    }
+    Ok(chunks)
+}
+
+/// Reads a DS18B20 temperature probe by its 1-Wire device id.
+///
+/// # Arguments
+///
+/// * `device_id` - The probe's device id, e.g. `28-0316a2791eff`
+///
+/// # Returns
+///
+/// The temperature in °C, or None if the probe isn't present or the read failed its CRC check
+pub fn read_ds18b20(device_id: &str) -> Option<f32> {
+    let path = Path::new(W1_DEVICES_DIR).join(device_id).join("w1_slave");
+    let contents = fs::read_to_string(path).ok()?;
+    parse_w1_slave(&contents)
+}
+
+/// Parses the contents of a DS18B20 `w1_slave` file.
+///
+/// The first line ends in `YES` when the CRC matched; the second line ends in
+/// `t=<millidegrees>`.
+pub fn parse_w1_slave(contents: &str) -> Option<f32> {
+    let mut lines = contents.lines();
+    if !lines.next()?.trim_end().ends_with("YES") {
+        return None;
+    }
+
+    let (_, millidegrees) = lines.next()?.rsplit_once("t=")?;
+    let millidegrees: i32 = millidegrees.trim().parse().ok()?;
+    Some(millidegrees as f32 / 1000.0)
+}
+
+/// Minimal I2C access used by the sensor drivers, so they can be tested without hardware
+pub trait I2cBus {
+    /// Writes `write` to the device at `address`, then reads `read.len()` bytes back
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Box<dyn Error>>;
+
+    /// Writes `data` to the device at `address`
+    fn write(&mut self, address: u8, data: &[u8]) -> Result<(), Box<dyn Error>>;
+}
+
+impl I2cBus for I2c {
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Box<dyn Error>> {
+        self.set_slave_address(address as u16)?;
+        I2c::write_read(self, write, read)?;
+        Ok(())
+    }
+
+    fn write(&mut self, address: u8, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.set_slave_address(address as u16)?;
+        I2c::write(self, data)?;
+        Ok(())
+    }
+}
+
+/// VEML6075 UVA/UVB sensor on an I2C bus
+pub struct Veml6075<B: I2cBus> {
+    bus: B,
+    address: u8,
+}
+
+impl<B: I2cBus> Veml6075<B> {
+    /// Checks the device id at `address` and powers the sensor on if it is shut down.
+    ///
+    /// After a power-on this waits one integration time (100ms) so the first
+    /// read returns a real measurement.
+    pub fn new(bus: B, address: u8) -> Result<Self, Box<dyn Error>> {
+        let mut sensor = Self { bus, address };
+
+        let id = sensor.read_register(VEML6075_REG_ID)?;
+        if id & 0xFF != VEML6075_DEVICE_ID {
+            return Err(format!("No VEML6075 at {:#04x}: device id {:#06x}", address, id).into());
+        }
+
+        if sensor.read_register(VEML6075_REG_CONF)? & VEML6075_CONF_SHUTDOWN != 0 {
+            sensor.write_register(VEML6075_REG_CONF, VEML6075_CONF_IT_100MS)?;
+            thread::sleep(Duration::from_millis(110));
+        }
+
+        Ok(sensor)
+    }
+
+    /// Reads the sensor and returns the UV index, never below 0
+    pub fn read_uv_index(&mut self) -> Result<f32, Box<dyn Error>> {
+        let uva = self.read_register(VEML6075_REG_UVA)? as f32;
+        let uvb = self.read_register(VEML6075_REG_UVB)? as f32;
+        let comp1 = self.read_register(VEML6075_REG_UVCOMP1)? as f32;
+        let comp2 = self.read_register(VEML6075_REG_UVCOMP2)? as f32;
+
+        let uva = uva - VEML6075_UVA_A * comp1 - VEML6075_UVA_B * comp2;
+        let uvb = uvb - VEML6075_UVB_C * comp1 - VEML6075_UVB_D * comp2;
+        let index = (uva * VEML6075_UVA_RESPONSE + uvb * VEML6075_UVB_RESPONSE) / 2.0;
+        Ok(index.max(0.0))
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, Box<dyn Error>> {
+        let mut value = [0u8; 2];
+        self.bus.write_read(self.address, &[register], &mut value)?;
+        Ok(u16::from_le_bytes(value))
+    }
+
+    fn write_register(&mut self, register: u8, value: u16) -> Result<(), Box<dyn Error>> {
+        let [low, high] = value.to_le_bytes();
+        self.bus.write(self.address, &[register, low, high])
+    }
+}
+
+/// Reads the UV index from a VEML6075 sensor.
+///
+/// # Arguments
+///
+/// * `bus` - I2C bus number (`/dev/i2c-<bus>`)
+/// * `address` - 7-bit I2C address of the sensor
+///
+/// # Returns
+///
+/// The UV index, or None if the sensor isn't present or the read failed
+pub fn read_veml6075(bus: u8, address: u8) -> Option<f32> {
+    let i2c = I2c::with_bus(bus).ok()?;
+    Veml6075::new(i2c, address).ok()?.read_uv_index().ok()
+}
+
+/// Maps a logical relay state to the pin level that produces it.
+///
+/// Active-high boards switch on when the pin is high; active-low boards
+/// switch on when the pin is low.
+pub fn relay_level(state: bool, active_low: bool) -> Level {
+    if state != active_low { Level::High } else { Level::Low }
+}
+
+/// Level a relay's pin is claimed at, from `[gpio] initial_state` and `active_low`
+pub fn boot_level(config: &GpioConfig, relay: RelayType) -> Level {
+    relay_level(config.initial_state(relay), config.active_low)
+}
+
+/// Claims a relay pin as an output that starts at `level`, see `boot_level`.
+///
+/// A plain `into_output` drives the pin low, which switches every relay of
+/// an active-low board on at once while the controllers are created.
+pub fn relay_output(gpio: &Gpio, pin: u8, level: Level) -> Result<OutputPin, rppal::gpio::Error> {
+    let pin = gpio.get(pin)?;
+    Ok(match level {
+        Level::High => pin.into_output_high(),
+        Level::Low => pin.into_output_low(),
+    })
+}
+
+/// Injected logger for physical relay state changes.
+///
+/// Controllers send events synchronously from their switching code;
+/// `storage::write_relay_events` drains the receiver into `relay_events`.
+#[derive(Clone)]
+pub struct RelayEventLog {
+    sender: UnboundedSender<RelayEvent>,
+}
+
+impl RelayEventLog {
+    /// Creates a logger and the receiver its events arrive on
+    pub fn channel() -> (Self, UnboundedReceiver<RelayEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender }, receiver)
+    }
+}
+
+/// Remembers the last state written to each relay and logs only real changes
+#[derive(Default)]
+pub struct RelayStateLog {
+    log: Option<RelayEventLog>,
+    states: HashMap<RelayType, bool>,
+}
+
+impl RelayStateLog {
+    /// A log that already knows the state each of `relays` was claimed in,
+    /// see `boot_level`, so status reports it before the first write.
+    /// Nothing is logged for it; the first write that differs is.
+    pub fn at_boot(config: &GpioConfig, relays: impl IntoIterator<Item = RelayType>) -> Self {
+        Self {
+            log: None,
+            states: relays.into_iter().map(|relay| (relay, config.initial_state(relay))).collect(),
+        }
+    }
+
+    /// Starts sending state changes to `log`
+    pub fn set_log(&mut self, log: RelayEventLog) {
+        self.log = Some(log);
+    }
+
+    /// Notes that `relay` was written with `state`.
+    ///
+    /// The first write to a relay always counts as a change, since its state
+    /// before startup is unknown, unless `at_boot` seeded it.
+    ///
+    /// # Returns
+    ///
+    /// True if the state changed and an event was logged
+    pub fn record(&mut self, relay: RelayType, state: bool, reason: RelayReason) -> bool {
+        if self.states.insert(relay, state) == Some(state) {
+            return false;
+        }
+        if let Some(log) = &self.log {
+            // The receiver only goes away at shutdown, nothing to do then
+            let _ = log.sender.send(RelayEvent {
+                timestamp: Utc::now(),
+                relay: relay.as_str().to_string(),
+                state,
+                reason,
+            });
+        }
+        true
+    }
+
+    /// The state last written to `relay`, None before its first write
+    pub fn state(&self, relay: RelayType) -> Option<bool> {
+        self.states.get(&relay).copied()
+    }
+}
+
+/// Controls relays for UV, heat, and LED via GPIO.
+///
+/// Pins are None on a controller created by `unavailable`, which switches nothing.
 pub struct RelayController { 
-    uv1_relay: OutputPin,
-    uv2_relay: OutputPin,
-    heat_relay: OutputPin,
-    led_relay: OutputPin,
+    uv1_relay: Option<OutputPin>,
+    uv2_relay: Option<OutputPin>,
+    heat_relay: Option<OutputPin>,
+    led_relay: Option<OutputPin>,
+    fan_relay: Option<OutputPin>,
+    aux_relay: Option<OutputPin>,
+    active_low: bool,
+    events: RelayStateLog,
+    pulses: HashMap<RelayType, u64>,   // Latest pulse started per relay, see `start_pulse`
 } 
 
 /// Defines the available relay types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RelayType {
     UV1,
     UV2,
     Heat,
     LED,
+    Fan,
+    Aux,
+}
+
+impl RelayType {
+    /// Name used in the API and the relay event log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelayType::UV1 => "uv1",
+            RelayType::UV2 => "uv2",
+            RelayType::Heat => "heat",
+            RelayType::LED => "led",
+            RelayType::Fan => "fan",
+            RelayType::Aux => "aux",
+        }
+    }
+}
+
+/// Switches a pulsed relay off if the pulse is dropped before it finishes.
+///
+/// Covers task cancellation (e.g. the runtime shutting down mid-pulse):
+/// dropping the guard while still armed schedules the off.
+struct PulseGuard {
+    controller: Arc<Mutex<RelayController>>,
+    relay_type: RelayType,
+    generation: u64,
+    armed: bool,
+}
+
+impl Drop for PulseGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let (relay_type, generation) = (self.relay_type, self.generation);
+        match self.controller.try_lock() {
+            Ok(mut controller) => {
+                controller.end_pulse(relay_type, generation);
+            }
+            Err(_) => {
+                let controller = Arc::clone(&self.controller);
+                if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                    runtime.spawn(async move { controller.lock().await.end_pulse(relay_type, generation) });
+                }
+            }
+        }
+    }
 }
 
 impl RelayController { 
@@ -122,55 +726,559 @@ impl RelayController {
         let config = GpioConfig::load();
         let gpio = Gpio::new()?; 
         
-        // Get pins from config
-        let uv1_relay = gpio.get(config.uv_relay1)?.into_output();
-        let uv2_relay = gpio.get(config.uv_relay2)?.into_output();
-        let heat_relay = gpio.get(config.heat_relay)?.into_output();
-        let led_relay = gpio.get(config.led_relay)?.into_output();
+        // Get pins from config, each driven straight to its `initial_state`
+        let uv1_relay = relay_output(&gpio, config.uv_relay1, boot_level(&config, RelayType::UV1))?;
+        let uv2_relay = relay_output(&gpio, config.uv_relay2, boot_level(&config, RelayType::UV2))?;
+        let heat_relay = relay_output(&gpio, config.heat_relay, boot_level(&config, RelayType::Heat))?;
+        let led_relay = relay_output(&gpio, config.led_relay, boot_level(&config, RelayType::LED))?;
+        let fan_relay = match config.fan_relay {
+            Some(pin) => Some(relay_output(&gpio, pin, boot_level(&config, RelayType::Fan))?),
+            None => None,
+        };
+        let aux_relay = match config.aux_relay {
+            Some(pin) => Some(relay_output(&gpio, pin, boot_level(&config, RelayType::Aux))?),
+            None => None,
+        };
+        let claimed = [RelayType::UV1, RelayType::UV2, RelayType::Heat, RelayType::LED].into_iter()
+            .chain(fan_relay.as_ref().map(|_| RelayType::Fan))
+            .chain(aux_relay.as_ref().map(|_| RelayType::Aux));
+        let events = RelayStateLog::at_boot(&config, claimed);
         
         Ok(Self { 
-            uv1_relay,
-            uv2_relay,
-            heat_relay,
-            led_relay,
+            uv1_relay: Some(uv1_relay),
+            uv2_relay: Some(uv2_relay),
+            heat_relay: Some(heat_relay),
+            led_relay: Some(led_relay),
+            fan_relay,
+            aux_relay,
+            active_low: config.active_low,
+            events,
+            pulses: HashMap::new(),
         }) 
     } 
 
-    /// Set a specific relay by type
-    pub fn set_relay(&mut self, relay_type: RelayType, state: bool) {
+    /// A controller without any relay, used when the GPIO couldn't be
+    /// initialized so the web interface and logs still come up
+    pub fn unavailable() -> Self {
+        Self {
+            uv1_relay: None,
+            uv2_relay: None,
+            heat_relay: None,
+            led_relay: None,
+            fan_relay: None,
+            aux_relay: None,
+            active_low: false,
+            events: RelayStateLog::default(),
+            pulses: HashMap::new(),
+        }
+    }
+
+    /// An unavailable controller that tracks `relays` as claimed in the
+    /// `initial_state` of `config`, like `new` does
+    #[cfg(test)]
+    pub(crate) fn unavailable_at_boot(config: &GpioConfig, relays: impl IntoIterator<Item = RelayType>) -> Self {
+        Self {
+            events: RelayStateLog::at_boot(config, relays),
+            ..Self::unavailable()
+        }
+    }
+
+    /// Whether the relays could be initialized, see `unavailable`
+    pub fn is_available(&self) -> bool {
+        self.led_relay.is_some()
+    }
+
+    /// The state last written to `relay`, or claimed at boot; None if unknown
+    pub fn state(&self, relay: RelayType) -> Option<bool> {
+        self.events.state(relay)
+    }
+
+    /// Logs every relay state change from now on to `log`
+    pub fn set_event_log(&mut self, log: RelayEventLog) {
+        self.events.set_log(log);
+    }
+
+    /// Whether a relay of this type has a pin assigned
+    pub fn has_relay(&self, relay_type: RelayType) -> bool {
+        match relay_type {
+            RelayType::UV1 => self.uv1_relay.is_some(),
+            RelayType::UV2 => self.uv2_relay.is_some(),
+            RelayType::Heat => self.heat_relay.is_some(),
+            RelayType::LED => self.led_relay.is_some(),
+            RelayType::Fan => self.fan_relay.is_some(),
+            RelayType::Aux => self.aux_relay.is_some(),
+        }
+    }
+
+    /// Turns a relay on for `duration`, then off again.
+    ///
+    /// The controller lock is only held while switching, so other control
+    /// keeps working during the pulse. The relay is switched off even if the
+    /// pulse is cancelled part way through, unless a later pulse of the same
+    /// relay has taken it over.
+    ///
+    /// # Arguments
+    ///
+    /// * `controller` - The shared relay controller
+    /// * `relay_type` - The relay to pulse
+    /// * `duration` - How long the relay stays on
+    pub async fn pulse(controller: &Arc<Mutex<RelayController>>, relay_type: RelayType, duration: Duration) {
+        let generation = controller.lock().await.start_pulse(relay_type);
+        let mut guard = PulseGuard {
+            controller: Arc::clone(controller),
+            relay_type,
+            generation,
+            armed: true,
+        };
+        
+        tokio::time::sleep(duration).await;
+        
+        controller.lock().await.end_pulse(relay_type, generation);
+        guard.armed = false;
+    }
+
+    /// Turns a relay on for a new pulse, which takes the relay over from any
+    /// earlier pulse still running
+    ///
+    /// # Returns
+    ///
+    /// The pulse's generation, to pass to `end_pulse`
+    fn start_pulse(&mut self, relay_type: RelayType) -> u64 {
+        let generation = self.pulses.entry(relay_type).or_insert(0);
+        *generation += 1;
+        let generation = *generation;
+        self.turn_on(relay_type, RelayReason::Manual);
+        generation
+    }
+
+    /// Turns a pulsed relay off, unless a later pulse has taken it over
+    ///
+    /// # Returns
+    ///
+    /// Whether the relay was switched off
+    fn end_pulse(&mut self, relay_type: RelayType, generation: u64) -> bool {
+        if self.pulses.get(&relay_type) != Some(&generation) {
+            return false;
+        }
+        self.turn_off(relay_type, RelayReason::Manual);
+        true
+    }
+
+    /// Set a specific relay by type, logging the change with `reason`
+    ///
+    /// Heat is only ever switched off here: it is switched on through
+    /// `LightController::control_heat`, where overheat protection and the
+    /// heat watchdog see it.
+    pub fn set_relay(&mut self, relay_type: RelayType, state: bool, reason: RelayReason) {
+        if relay_type == RelayType::Heat && state {
+            return;
+        }
         let pin = match relay_type {
-            RelayType::UV1 => &mut self.uv1_relay,
-            RelayType::UV2 => &mut self.uv2_relay,
-            RelayType::Heat => &mut self.heat_relay,
-            RelayType::LED => &mut self.led_relay,
+            RelayType::UV1 => self.uv1_relay.as_mut(),
+            RelayType::UV2 => self.uv2_relay.as_mut(),
+            RelayType::Heat => self.heat_relay.as_mut(),
+            RelayType::LED => self.led_relay.as_mut(),
+            RelayType::Fan => self.fan_relay.as_mut(),
+            RelayType::Aux => self.aux_relay.as_mut(),
+        };
+        let pin = match pin {
+            Some(pin) => pin,
+            None => return, // Relay not configured, or the controller is unavailable
         };
         
-        pin.write(if state { rppal::gpio::Level::High } else { rppal::gpio::Level::Low });
+        pin.write(relay_level(state, self.active_low));
+        self.events.record(relay_type, state, reason);
     }
     
     /// Turn on a specific relay
-    pub fn turn_on(&mut self, relay_type: RelayType) {
-        self.set_relay(relay_type, true);
+    pub fn turn_on(&mut self, relay_type: RelayType, reason: RelayReason) {
+        self.set_relay(relay_type, true, reason);
     }
     
     /// Turn off a specific relay
-    pub fn turn_off(&mut self, relay_type: RelayType) {
-        self.set_relay(relay_type, false);
+    pub fn turn_off(&mut self, relay_type: RelayType, reason: RelayReason) {
+        self.set_relay(relay_type, false, reason);
     }
      
     /// Turn all relays off
-    pub fn turn_all_off(&mut self) { 
-        self.uv1_relay.set_low();
-        self.uv2_relay.set_low();
-        self.heat_relay.set_low();
-        self.led_relay.set_low();
+    pub fn turn_all_off(&mut self, reason: RelayReason) { 
+        for relay_type in [RelayType::UV1, RelayType::UV2, RelayType::Heat, RelayType::LED, RelayType::Fan, RelayType::Aux] {
+            self.set_relay(relay_type, false, reason);
+        }
     }
     
-    /// Turn all relays on
-    pub fn turn_all_on(&mut self) { 
-        self.uv1_relay.set_high();
-        self.uv2_relay.set_high();
-        self.heat_relay.set_high();
-        self.led_relay.set_high();
-    }
-}
\ No newline at end of file
+    /// Turn all relays on, except heat, see `set_relay`
+    pub fn turn_all_on(&mut self, reason: RelayReason) { 
+        for relay_type in [RelayType::UV1, RelayType::UV2, RelayType::LED, RelayType::Fan] {
+            self.set_relay(relay_type, true, reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRIP_IC_COUNT: usize = 300;
+
+    /// Encodes every IC from scratch, the way `set_all` did before `FrameBuffer`
+    fn encode_full(buffer: &mut [u8], color: RGBWW) {
+        for ic in buffer.chunks_exact_mut(BYTES_PER_IC) {
+            for (channel, value) in color.channels(ColorOrder::default()).iter().copied().enumerate() {
+                let offset = channel * BITS_PER_CHANNEL;
+                convert_byte(value, &mut ic[offset..offset + BITS_PER_CHANNEL]);
+            }
+        }
+    }
+
+    fn fade_frames() -> Vec<RGBWW> {
+        (0..=255u8).map(|v| RGBWW { r: v, g: v / 2, b: 0, ww: 255 - v, cw: 40 }).collect()
+    }
+
+    #[test]
+    fn test_frame_buffer_matches_full_encode() {
+        let mut frame = FrameBuffer::new(STRIP_IC_COUNT);
+        let mut reference = vec![0u8; STRIP_IC_COUNT * BYTES_PER_IC];
+        for color in fade_frames() {
+            frame.set_all(color);
+            encode_full(&mut reference, color);
+            assert_eq!(frame.as_bytes(), &reference[..]);
+        }
+    }
+
+    /// Before/after timing of a fade on a 300 IC strip. Fails with both timings
+    /// unless the frame buffer beats the full encode. Run with
+    /// `cargo test --release bench_fade_encoding -- --ignored`
+    #[test]
+    #[ignore]
+    fn bench_fade_encoding() -> Result<(), String> {
+        use std::time::Instant;
+
+        let frames = fade_frames();
+        let rounds = 200;
+
+        let mut reference = vec![0u8; STRIP_IC_COUNT * BYTES_PER_IC];
+        let started = Instant::now();
+        for _ in 0..rounds {
+            for color in &frames {
+                encode_full(&mut reference, *color);
+            }
+        }
+        let before = started.elapsed();
+
+        let mut frame = FrameBuffer::new(STRIP_IC_COUNT);
+        let started = Instant::now();
+        for _ in 0..rounds {
+            for color in &frames {
+                frame.set_all(*color);
+            }
+        }
+        let after = started.elapsed();
+
+        assert_eq!(frame.as_bytes(), &reference[..]);
+        let report = format!(
+            "{} ICs, {} frames: full encode {:?}, frame buffer {:?}",
+            STRIP_IC_COUNT, rounds * frames.len(), before, after
+        );
+        if after < before {
+            Ok(())
+        } else {
+            Err(format!("frame buffer is no faster than a full encode: {}", report))
+        }
+    }
+
+    #[test]
+    fn test_set_ic_after_set_all_forces_full_reencode() {
+        let color = RGBWW { r: 10, g: 20, b: 30, ww: 40, cw: 50 };
+        let mut frame = FrameBuffer::new(4);
+        frame.set_all(color);
+        frame.set_ic(2, RGBWW::off());
+        frame.set_all(color);
+
+        let mut reference = vec![0u8; 4 * BYTES_PER_IC];
+        encode_full(&mut reference, color);
+        assert_eq!(frame.as_bytes(), &reference[..]);
+    }
+
+    #[test]
+    fn test_color_order_changes_wire_bytes() {
+        let color = RGBWW { r: 255, g: 0, b: 0, ww: 0, cw: 0 };
+        let mut grb = FrameBuffer::new(1);
+        let mut rgb = FrameBuffer::with_color_order(1, ColorOrder::parse("rgbwc").unwrap());
+        grb.set_all(color);
+        rgb.set_all(color);
+        assert_ne!(grb.as_bytes(), rgb.as_bytes());
+
+        // Red leads on an RGB strip and comes second on the default GRB one
+        let high = [T1H; BITS_PER_CHANNEL];
+        assert_eq!(&rgb.as_bytes()[..BITS_PER_CHANNEL], &high);
+        assert_eq!(&grb.as_bytes()[BITS_PER_CHANNEL..2 * BITS_PER_CHANNEL], &high);
+        assert_eq!(ColorOrder::parse("GRBWC"), Ok(ColorOrder::default()));
+    }
+
+    #[test]
+    fn test_color_order_needs_every_channel_once() {
+        assert!(ColorOrder::parse("GRBW").unwrap_err().contains("5 channels"));
+        assert!(ColorOrder::parse("GRBWW").unwrap_err().contains("twice"));
+        assert!(ColorOrder::parse("GRBWX").unwrap_err().contains("unknown channel"));
+    }
+
+    #[test]
+    fn test_bit_bang_encoding_matches_spi_frame() {
+        let color = RGBWW { r: 0x80, g: 0x01, b: 0xA5, ww: 0, cw: 255 };
+        let wire = color.channels(ColorOrder::default());
+        let high: Vec<Duration> = bit_high_times(&wire).collect();
+        assert_eq!(high.len(), CHANNELS_PER_IC * BITS_PER_CHANNEL);
+
+        // Green leads on the wire, most significant bit first
+        let (t0, t1) = (BITBANG_T0H, BITBANG_T1H);
+        assert_eq!(&high[..16], &[t0, t0, t0, t0, t0, t0, t0, t1, t1, t0, t0, t0, t0, t0, t0, t0]);
+        assert_eq!(&high[16..24], &[t1, t0, t1, t0, t0, t1, t0, t1]);
+
+        // Every bit is a 1 exactly where the SPI encoding sends T1H
+        let mut spi = FrameBuffer::new(1);
+        spi.set_all(color);
+        let spi_ones: Vec<bool> = spi.as_bytes().iter().map(|&byte| byte == T1H).collect();
+        let bit_bang_ones: Vec<bool> = high.iter().map(|&time| time == BITBANG_T1H).collect();
+        assert_eq!(bit_bang_ones, spi_ones);
+        assert!(BITBANG_T1H < BITBANG_BIT && BITBANG_T0H < BITBANG_T1H);
+    }
+
+    const W1_SLAVE_OK: &str = "72 01 4b 46 7f ff 0e 10 57 : crc=57 YES\n72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+
+    #[test]
+    fn test_parse_w1_slave_reads_temperature() {
+        assert_eq!(parse_w1_slave(W1_SLAVE_OK), Some(23.125));
+    }
+
+    #[test]
+    fn test_parse_w1_slave_negative_temperature() {
+        let contents = "5e ff 4b 46 7f ff 02 10 2d : crc=2d YES\n5e ff 4b 46 7f ff 02 10 2d t=-10125\n";
+        assert_eq!(parse_w1_slave(contents), Some(-10.125));
+    }
+
+    #[test]
+    fn test_parse_w1_slave_rejects_crc_failure() {
+        let contents = "72 01 4b 46 7f ff 0e 10 57 : crc=00 NO\n72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+        assert_eq!(parse_w1_slave(contents), None);
+    }
+
+    #[test]
+    fn test_parse_w1_slave_rejects_truncated_file() {
+        assert_eq!(parse_w1_slave("72 01 4b 46 7f ff 0e 10 57 : crc=57 YES\n"), None);
+        assert_eq!(parse_w1_slave(""), None);
+    }
+
+    #[test]
+    fn test_read_ds18b20_missing_device_is_none() {
+        assert_eq!(read_ds18b20("28-000000000000"), None);
+    }
+
+    #[test]
+    fn test_relay_level_active_high() {
+        assert_eq!(relay_level(true, false), Level::High);
+        assert_eq!(relay_level(false, false), Level::Low);
+    }
+
+    #[test]
+    fn test_relay_level_flips_when_active_low() {
+        assert_eq!(relay_level(true, true), Level::Low);
+        assert_eq!(relay_level(false, true), Level::High);
+    }
+
+    #[test]
+    fn test_relays_claimed_at_initial_state() {
+        let mut gpio = crate::modules::config::test_config().gpio;
+        gpio.initial_state = HashMap::from([(RelayType::LED, true), (RelayType::UV1, false), (RelayType::Heat, true)]);
+
+        for active_low in [false, true] {
+            gpio.active_low = active_low;
+            assert_eq!(boot_level(&gpio, RelayType::LED), relay_level(true, active_low));
+            assert_eq!(boot_level(&gpio, RelayType::UV1), relay_level(false, active_low));
+            assert_eq!(boot_level(&gpio, RelayType::UV2), relay_level(false, active_low));
+            // A misconfigured heat relay still comes up off
+            assert_eq!(boot_level(&gpio, RelayType::Heat), relay_level(false, active_low));
+        }
+        assert!(gpio.validate().unwrap_err().contains("heat"));
+
+        // Status knows the claimed states before anything is written
+        let mut states = RelayStateLog::at_boot(&gpio, [RelayType::LED, RelayType::UV1, RelayType::Heat]);
+        assert_eq!(states.state(RelayType::LED), Some(true));
+        assert_eq!(states.state(RelayType::UV1), Some(false));
+        assert_eq!(states.state(RelayType::Heat), Some(false));
+        assert_eq!(states.state(RelayType::UV2), None);
+        assert!(!states.record(RelayType::LED, true, RelayReason::Schedule));
+        assert!(states.record(RelayType::LED, false, RelayReason::Schedule));
+    }
+
+    #[test]
+    fn test_init_error_permission_hint() {
+        let err = rppal::gpio::Error::PermissionDenied("/dev/gpiomem".to_string());
+        let mapped = GpioInitError::new("relay controller", &err);
+        assert_eq!(mapped.component, "relay controller");
+        assert!(mapped.message.contains("/dev/gpiomem"));
+        assert!(mapped.to_string().starts_with("Failed to initialize relay controller"));
+    }
+
+    #[test]
+    fn test_init_error_names_pin() {
+        let err = rppal::gpio::Error::PinNotAvailable(22);
+        let mapped = GpioInitError::new("light controller", &err);
+        assert!(mapped.message.contains("22"));
+    }
+
+    #[test]
+    fn test_init_error_passes_through_other_errors() {
+        let err = std::io::Error::new(std::io::ErrorKind::Other, "spi disabled");
+        let mapped = GpioInitError::new("LED strip", &err);
+        assert_eq!(mapped.message, "spi disabled");
+    }
+
+    #[test]
+    fn test_relay_type_deserializes_lowercase_names() {
+        let relay: RelayType = serde_json::from_str("\"aux\"").unwrap();
+        assert_eq!(relay, RelayType::Aux);
+        let relay: RelayType = serde_json::from_str("\"uv1\"").unwrap();
+        assert_eq!(relay, RelayType::UV1);
+        assert!(serde_json::from_str::<RelayType>("\"pump\"").is_err());
+    }
+
+    #[test]
+    fn test_write_frame_chunks_256_ic_strip_on_ic_boundaries() {
+        let mut frame = FrameBuffer::new(256);
+        frame.set_all(RGBWW { r: 1, g: 2, b: 3, ww: 4, cw: 5 });
+        frame.set_ic(255, RGBWW { r: 255, g: 0, b: 0, ww: 0, cw: 0 });
+        assert_eq!(frame.as_bytes().len(), 256 * BYTES_PER_IC);
+        
+        let mut sent: Vec<Vec<u8>> = Vec::new();
+        let chunks = write_frame(frame.as_bytes(), |chunk| {
+            sent.push(chunk.to_vec());
+            Ok::<usize, std::io::Error>(chunk.len())
+        }).unwrap();
+        
+        assert_eq!(chunks, 3);
+        assert!(sent.iter().all(|chunk| chunk.len() <= SPIDEV_BUFSIZ));
+        assert!(sent.iter().all(|chunk| chunk.len() % BYTES_PER_IC == 0));
+        assert_eq!(sent.concat(), frame.as_bytes());
+    }
+
+    #[test]
+    fn test_write_frame_stops_after_a_gap_of_the_reset_time() {
+        let frame = FrameBuffer::new(256);
+        let mut sent = Vec::new();
+        let result = write_frame(frame.as_bytes(), |chunk| {
+            sent.push(chunk.len());
+            // The second transfer starts a whole reset time late
+            if sent.len() == 2 {
+                thread::sleep(Duration::from_micros(RESET_TIME_US * 2));
+            }
+            Ok::<usize, std::io::Error>(chunk.len())
+        });
+        assert!(result.unwrap_err().to_string().contains("before chunk 2"));
+        assert_eq!(sent, [SPI_CHUNK_BYTES, SPI_CHUNK_BYTES]);
+    }
+
+    #[test]
+    fn test_write_frame_fails_on_short_write() {
+        let frame = FrameBuffer::new(256);
+        let result = write_frame(frame.as_bytes(), |chunk| {
+            Ok::<usize, std::io::Error>(chunk.len() - 1)
+        });
+        assert!(result.is_err());
+    }
+
+    /// Register file of a fake VEML6075, recording every write
+    struct MockI2c {
+        address: u8,
+        registers: std::collections::HashMap<u8, u16>,
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl MockI2c {
+        fn veml6075(address: u8, conf: u16) -> Self {
+            let registers = [
+                (VEML6075_REG_CONF, conf),
+                (VEML6075_REG_ID, VEML6075_DEVICE_ID),
+                (VEML6075_REG_UVA, 1000),
+                (VEML6075_REG_UVB, 1200),
+                (VEML6075_REG_UVCOMP1, 100),
+                (VEML6075_REG_UVCOMP2, 50),
+            ].into_iter().collect();
+            Self { address, registers, writes: Vec::new() }
+        }
+    }
+
+    impl I2cBus for &mut MockI2c {
+        fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Box<dyn Error>> {
+            if address != self.address {
+                return Err("NACK".into());
+            }
+            let value = self.registers.get(&write[0]).copied().unwrap_or(0);
+            read.copy_from_slice(&value.to_le_bytes());
+            Ok(())
+        }
+
+        fn write(&mut self, address: u8, data: &[u8]) -> Result<(), Box<dyn Error>> {
+            if address != self.address {
+                return Err("NACK".into());
+            }
+            self.writes.push(data.to_vec());
+            self.registers.insert(data[0], u16::from_le_bytes([data[1], data[2]]));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_veml6075_reads_uv_index_at_configured_address() {
+        let mut bus = MockI2c::veml6075(0x11, VEML6075_CONF_IT_100MS);
+        let mut sensor = Veml6075::new(&mut bus, 0x11).unwrap();
+
+        // UVA 1000 - 2.22*100 - 1.33*50 = 711.5, UVB 1200 - 2.95*100 - 1.74*50 = 818
+        let expected = (711.5 * VEML6075_UVA_RESPONSE + 818.0 * VEML6075_UVB_RESPONSE) / 2.0;
+        assert!((sensor.read_uv_index().unwrap() - expected).abs() < 1e-4);
+        drop(sensor);
+        assert!(bus.writes.is_empty());
+    }
+
+    #[test]
+    fn test_veml6075_powers_on_shut_down_sensor() {
+        let mut bus = MockI2c::veml6075(0x10, VEML6075_CONF_SHUTDOWN);
+        Veml6075::new(&mut bus, 0x10).unwrap();
+        assert_eq!(bus.writes, vec![vec![VEML6075_REG_CONF, 0x10, 0x00]]);
+    }
+
+    #[test]
+    fn test_veml6075_wrong_address_fails() {
+        let mut bus = MockI2c::veml6075(0x10, VEML6075_CONF_IT_100MS);
+        assert!(Veml6075::new(&mut bus, 0x11).is_err());
+    }
+
+    #[test]
+    fn test_relay_state_log_only_logs_changes() {
+        let (log, mut receiver) = RelayEventLog::channel();
+        let mut states = RelayStateLog::default();
+        states.set_log(log);
+
+        assert!(states.record(RelayType::Heat, true, RelayReason::Schedule));
+        assert!(!states.record(RelayType::Heat, true, RelayReason::Schedule));
+        assert!(states.record(RelayType::Heat, false, RelayReason::Overheat));
+        assert!(states.record(RelayType::UV1, false, RelayReason::Schedule));
+
+        let events: Vec<RelayEvent> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!((events[1].relay.as_str(), events[1].state, events[1].reason), ("heat", false, RelayReason::Overheat));
+        assert_eq!(events[2].relay, "uv1");
+    }
+
+    #[test]
+    fn test_earlier_pulse_leaves_relay_to_later_one() {
+        let mut relays = RelayController::unavailable();
+        let first = relays.start_pulse(RelayType::Fan);
+        let second = relays.start_pulse(RelayType::Fan);
+        let aux = relays.start_pulse(RelayType::Aux);
+
+        assert!(!relays.end_pulse(RelayType::Fan, first), "the first pulse switched off the second one's relay");
+        assert!(relays.end_pulse(RelayType::Aux, aux));
+        assert!(relays.end_pulse(RelayType::Fan, second));
+    }
+}
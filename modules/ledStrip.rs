@@ -1,18 +1,324 @@
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 use tokio::sync::Mutex;
-use crate::modules::gpio::{LEDStrip, RGBWW, RelayController, RelayType};
-use crate::modules::config::Config;
-use chrono::{Local, NaiveTime};
+use crate::modules::animations::{Animation, AnimationKind, Breathing, FRAME_INTERVAL};
+use crate::modules::gpio::{ColorOrder, LedDriver, LedWiring, RGBWW, RelayController, RelayType, open_led_driver};
+use crate::modules::config::{Config, LedPowerConfig, OverridePrecedence, SeasonKeyframe};
+use crate::modules::models::{RelayReason, Schedule, DEFAULT_LED_END, DEFAULT_LED_START};
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
+use sqlx::SqlitePool;
+use std::time::{Duration, Instant};
+use log::{error, info, warn};
+use serde::Serialize;
+use tokio::task::JoinHandle;
 
 /// Controls the LED strip with power management via relay.
 ///
 /// This struct manages an LED strip with RGBWW (Red, Green, Blue, Warm White, Cool White)
 /// capabilities, with power control through a relay to save energy when LEDs are not in use.
 pub struct LEDController {
-    led_strip: Option<LEDStrip>,
+    led_strip: Option<Arc<StdMutex<Box<dyn LedDriver>>>>, // Only locked on the blocking pool, see `show_color`
     relay_controller: Arc<Mutex<RelayController>>,
     power_state: bool,
+    current_color: RGBWW,          // Last color written to the strip
+    written: WrittenColor,         // What the strip shows, to skip rewriting an unchanged color
+    scheduled_on: Option<bool>,    // Last on/off state applied by the schedule (None until first update)
+    window_open: Option<bool>,     // Whether the LED window was open at the last update, see `OverridePrecedence`
+    strip_init: StripInit,
+    animation: Option<RunningAnimation>,
+    power: PowerModel,
+    caps: ChannelCaps,
+    color_order: ColorOrder,       // Wire order of the strip's channels, see `[led] color_order`
+    wiring: LedWiring,             // SPI or a bit-banged pin, see `[led] driver`
+    alert: AlertLatch,
+    alert_task: Option<JoinHandle<()>>, // Pulses the alert color while an alert is active
+    fade_generation: u64,          // Bumped by every write outside a fade, which stops the running fade, see `fade_to`
+}
+
+/// Length of one pulse of the alert color
+pub const ALERT_PULSE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Conditions that make the strip pulse `[led] alert_color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedAlert {
+    Overheat,
+    SensorFault,
+}
+
+impl LedAlert {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedAlert::Overheat => "overheat",
+            LedAlert::SensorFault => "sensor fault",
+        }
+    }
+
+    /// Reason recorded when the alert pulse has to switch the strip on
+    pub fn reason(&self) -> RelayReason {
+        match self {
+            LedAlert::Overheat => RelayReason::Overheat,
+            LedAlert::SensorFault => RelayReason::SensorFault,
+        }
+    }
+}
+
+/// Latches an LED alert until it's acknowledged.
+///
+/// An alert is raised when a condition appears that wasn't present on the
+/// previous check, so acknowledging a condition that is still present keeps
+/// the strip quiet until it clears and comes back.
+#[derive(Debug, Default)]
+pub struct AlertLatch {
+    active: Option<LedAlert>,
+    seen: Vec<LedAlert>,    // Conditions present on the last check
+}
+
+impl AlertLatch {
+    /// Feeds the conditions present now.
+    ///
+    /// # Returns
+    ///
+    /// The alert to raise, if a new condition appeared while no alert was active
+    pub fn observe(&mut self, conditions: &[LedAlert]) -> Option<LedAlert> {
+        let new = conditions.iter().copied().find(|condition| !self.seen.contains(condition));
+        self.seen = conditions.to_vec();
+        match new {
+            Some(alert) if self.active.is_none() => {
+                self.active = Some(alert);
+                Some(alert)
+            }
+            _ => None,
+        }
+    }
+
+    /// Clears the active alert, returning it
+    pub fn acknowledge(&mut self) -> Option<LedAlert> {
+        self.active.take()
+    }
+
+    /// Forgets the active alert and the conditions seen, so the conditions
+    /// still present raise an alert again on the next check
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn active(&self) -> Option<LedAlert> {
+        self.active
+    }
+}
+
+/// Estimates the strip's current draw and keeps colors within the `[led.power]` budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerModel {
+    channel_ma: [f32; 5],       // Full-brightness current of one IC's r, g, b, ww, cw channels
+    ic_count: usize,
+    max_ma: Option<f32>,
+}
+
+impl PowerModel {
+    pub fn new(config: &LedPowerConfig, ic_count: usize) -> Self {
+        Self { channel_ma: config.channel_ma(), ic_count, max_ma: config.max_current_ma }
+    }
+
+    /// Estimated current in mA with every IC showing `color`
+    pub fn estimate_ma(&self, color: RGBWW) -> f32 {
+        let channels = [color.r, color.g, color.b, color.ww, color.cw];
+        let per_ic: f32 = channels.iter()
+            .zip(self.channel_ma)
+            .map(|(&value, ma)| value as f32 / 255.0 * ma)
+            .sum();
+        per_ic * self.ic_count as f32
+    }
+
+    /// Scales every channel of `color` down by the same factor until it fits the budget.
+    ///
+    /// Colors within the budget, or without one, are returned unchanged.
+    pub fn limit(&self, color: RGBWW) -> RGBWW {
+        let (max, estimate) = match self.max_ma {
+            Some(max) => (max, self.estimate_ma(color)),
+            None => return color,
+        };
+        if estimate <= max {
+            return color;
+        }
+        
+        // Rounding down keeps the scaled color within the budget
+        let factor = max / estimate;
+        let scale = |value: u8| (value as f32 * factor).floor() as u8;
+        RGBWW { r: scale(color.r), g: scale(color.g), b: scale(color.b), ww: scale(color.ww), cw: scale(color.cw) }
+    }
+}
+
+/// Per-channel maximums from `[led] max_r`..`max_cw`, e.g. to spare heat-sensitive white LEDs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelCaps(pub RGBWW);
+
+impl ChannelCaps {
+    /// Lowers every channel of `color` above its cap to the cap, leaving the others alone
+    pub fn clamp(&self, color: RGBWW) -> RGBWW {
+        let max = self.0;
+        RGBWW {
+            r: color.r.min(max.r),
+            g: color.g.min(max.g),
+            b: color.b.min(max.b),
+            ww: color.ww.min(max.ww),
+            cw: color.cw.min(max.cw),
+        }
+    }
+}
+
+impl Default for ChannelCaps {
+    fn default() -> Self {
+        Self(RGBWW { r: 255, g: 255, b: 255, ww: 255, cw: 255 })
+    }
+}
+
+impl Default for PowerModel {
+    fn default() -> Self {
+        Self::new(&LedPowerConfig::default(), 16)
+    }
+}
+
+/// An animation task and the state the strip reverts to when it's stopped
+struct RunningAnimation {
+    kind: AnimationKind,
+    task: JoinHandle<()>,
+    prior_color: RGBWW,
+    prior_power: bool,
+}
+
+/// Consecutive failures to open the LED strip before it is marked unavailable
+pub const MAX_STRIP_INIT_FAILURES: u32 = 3;
+
+/// Tracks failed attempts to open the LED strip.
+///
+/// Without SPI enabled `open_led_driver` fails on every call. After
+/// `MAX_STRIP_INIT_FAILURES` consecutive failures the strip is marked
+/// unavailable and no longer retried until `reset` (see `POST /api/led/reinit`).
+#[derive(Debug, Default)]
+pub struct StripInit {
+    failures: u32,
+    last_error: Option<String>,
+}
+
+impl StripInit {
+    /// Whether the strip may still be opened
+    pub fn available(&self) -> bool {
+        self.failures < MAX_STRIP_INIT_FAILURES
+    }
+
+    /// The error of the last failed attempt, if the most recent attempt failed
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Runs `open` unless the strip is unavailable, counting failures.
+    ///
+    /// The failure that marks the strip unavailable is logged; later calls fail
+    /// without trying again or logging.
+    pub fn try_open<T, E: std::fmt::Display>(&mut self, open: impl FnOnce() -> Result<T, E>) -> Result<T, String> {
+        if !self.available() {
+            return Err(format!("LED strip unavailable: {}", self.last_error.as_deref().unwrap_or("initialization failed")));
+        }
+        
+        match open() {
+            Ok(strip) => {
+                self.failures = 0;
+                self.last_error = None;
+                Ok(strip)
+            }
+            Err(e) => {
+                self.failures += 1;
+                self.last_error = Some(e.to_string());
+                if !self.available() {
+                    error!("LED strip failed to initialize {} times, disabling it until POST /api/led/reinit: {}",
+                           self.failures, e);
+                }
+                Err(format!("Failed to initialize LED strip: {}", e))
+            }
+        }
+    }
+
+    /// Allows the strip to be opened again after it was marked unavailable
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.last_error = None;
+    }
+}
+
+/// Remembers the color last sent to the strip so unchanged colors aren't rewritten.
+///
+/// Every `show` is a full SPI frame, and some strips flicker on each one.
+/// The color is forgotten whenever the strip's content is unknown, e.g. after
+/// its relay switched or a write failed, so the next write always goes out.
+#[derive(Debug, Default)]
+pub struct WrittenColor {
+    color: Option<RGBWW>,
+}
+
+impl WrittenColor {
+    /// Writes `color` with `show` unless it is already on the strip.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - The color to show
+    /// * `force` - Write even if `color` is already shown, e.g. for fades and user commands
+    /// * `show` - Sends a color to the strip
+    ///
+    /// # Returns
+    ///
+    /// Whether `show` was called
+    pub async fn write<F, Fut>(&mut self, color: RGBWW, force: bool, show: F) -> Result<bool, Box<dyn Error>>
+    where
+        F: FnOnce(RGBWW) -> Fut,
+        Fut: std::future::Future<Output = Result<(), Box<dyn Error>>>,
+    {
+        if !force && self.color == Some(color) {
+            return Ok(false);
+        }
+        self.color = None;
+        show(color).await?;
+        self.color = Some(color);
+        Ok(true)
+    }
+
+    /// Forgets the written color, so the next write goes out
+    pub fn forget(&mut self) {
+        self.color = None;
+    }
+}
+
+/// A change of the scheduled LED on/off state between two updates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LedTransition {
+    /// The LED window just opened
+    FadeIn,
+    /// The LED window just closed
+    FadeOut,
+    /// No boundary was crossed since the last update
+    None,
+}
+
+/// Detects whether the scheduled LED state crossed a window boundary.
+///
+/// The first update after startup (`previous_on == None`) never fades, so the
+/// strip snaps to the correct state instead of fading in on every reboot.
+///
+/// # Arguments
+///
+/// * `previous_on` - The on/off state applied by the previous update, if any
+/// * `target_on` - Whether the LEDs should be on now
+///
+/// # Returns
+///
+/// The transition, if any, that should be animated
+pub fn detect_transition(previous_on: Option<bool>, target_on: bool) -> LedTransition {
+    match (previous_on, target_on) {
+        (Some(false), true) => LedTransition::FadeIn,
+        (Some(true), false) => LedTransition::FadeOut,
+        _ => LedTransition::None,
+    }
 }
 
 /// Natural light presets for different times of day.
@@ -149,6 +455,38 @@ impl LightPreset {
     }
 }
 
+/// Colors for every step of a fade between two colors, computed up front.
+///
+/// Each step is `interpolate` at `step / steps`, so the strip shows exactly
+/// what the per-step float interpolation showed; the fade loop itself only
+/// copies precomputed colors.
+struct FadePlan {
+    colors: Vec<RGBWW>,
+}
+
+impl FadePlan {
+    fn new(start: RGBWW, end: RGBWW, steps: u32) -> Self {
+        let steps = steps.max(1);
+        let from = LightPreset::new(start.r, start.g, start.b, start.ww, start.cw);
+        let to = LightPreset::new(end.r, end.g, end.b, end.ww, end.cw);
+        let colors = (1..=steps)
+            .map(|step| from.interpolate(&to, step as f32 / steps as f32).to_rgbww())
+            .collect();
+        Self { colors }
+    }
+
+    /// Number of colors in the fade
+    fn steps(&self) -> u32 {
+        self.colors.len() as u32
+    }
+
+    /// Color written at `step` (1..=steps)
+    fn color_at(&self, step: u32) -> RGBWW {
+        let index = (step.max(1) as usize).min(self.colors.len()) - 1;
+        self.colors[index]
+    }
+}
+
 // Default presets for different times of day (fallbacks if config doesn't have values)
 const MORNING_PRESET: LightPreset = LightPreset { r: 255, g: 180, b: 100, ww: 200, cw: 50 };
 const NOON_PRESET: LightPreset = LightPreset { r: 255, g: 240, b: 220, ww: 50, cw: 255 };
@@ -165,11 +503,87 @@ impl LEDController {
     ///
     /// A new LEDController instance
     pub fn new(relay_controller: Arc<Mutex<RelayController>>) -> Self {
+        // The LED relay may have been claimed on, see `[gpio] initial_state`
+        let power_state = relay_controller.try_lock().ok()
+            .and_then(|relays| relays.state(RelayType::LED))
+            .unwrap_or(false);
         Self {
             led_strip: None,
             relay_controller,
-            power_state: false,
+            power_state,
+            current_color: RGBWW::off(),
+            written: WrittenColor::default(),
+            scheduled_on: None,
+            window_open: None,
+            strip_init: StripInit::default(),
+            animation: None,
+            power: PowerModel::default(),
+            caps: ChannelCaps::default(),
+            color_order: ColorOrder::default(),
+            wiring: LedWiring::Spi,
+            alert: AlertLatch::default(),
+            alert_task: None,
+            fade_generation: 0,
+        }
+    }
+
+    /// Sets the current model used to estimate and limit the strip's draw
+    pub fn set_power_model(&mut self, power: PowerModel) {
+        self.power = power;
+    }
+
+    /// Sets the per-channel maximums applied to every color written
+    pub fn set_channel_caps(&mut self, caps: ChannelCaps) {
+        self.caps = caps;
+    }
+
+    pub fn channel_caps(&self) -> ChannelCaps {
+        self.caps
+    }
+
+    /// Sets the wire order of the strip's channels, used when the strip is next opened
+    pub fn set_color_order(&mut self, order: ColorOrder) {
+        self.color_order = order;
+    }
+
+    /// Sets how the strip's data line is driven, used when the strip is next opened
+    pub fn set_wiring(&mut self, wiring: LedWiring) {
+        self.wiring = wiring;
+    }
+
+    /// Estimated current drawn by the strip in mA, 0 while its relay is off
+    pub fn estimated_current_ma(&self) -> f32 {
+        if self.power_state { self.power.estimate_ma(self.current_color) } else { 0.0 }
+    }
+
+    /// Opens the LED strip if it isn't open yet, see `StripInit`
+    fn open_strip(&mut self) -> Result<Arc<StdMutex<Box<dyn LedDriver>>>, Box<dyn Error>> {
+        if let Some(strip) = &self.led_strip {
+            return Ok(Arc::clone(strip));
         }
+        
+        let (wiring, order) = (self.wiring, self.color_order);
+        let strip = Arc::new(StdMutex::new(self.strip_init.try_open(|| open_led_driver(wiring, order))?));
+        self.led_strip = Some(Arc::clone(&strip));
+        Ok(strip)
+    }
+
+    /// Whether the LED strip is usable, i.e. not disabled after repeated init failures
+    pub fn is_available(&self) -> bool {
+        self.strip_init.available()
+    }
+
+    /// Retries opening the LED strip, e.g. after enabling SPI.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the strip could be opened
+    pub async fn reinit(&mut self) -> Result<(), Box<dyn Error>> {
+        self.strip_init.reset();
+        self.led_strip = None;
+        self.written.forget();
+        self.open_strip()?;
+        Ok(())
     }
 
     /// Initializes the LED controller.
@@ -181,13 +595,13 @@ impl LEDController {
     /// A Result indicating success or an error
     pub async fn initialize(&mut self) -> Result<(), Box<dyn Error>> {
         // First, turn on the power relay
-        self.power_on().await?;
+        self.power_on(RelayReason::Schedule).await?;
         
         // Wait a moment for the power to stabilize
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         
         // Initialize the LED strip
-        self.led_strip = Some(LEDStrip::new()?);
+        self.open_strip()?;
         
         Ok(())
     }
@@ -196,71 +610,181 @@ impl LEDController {
     ///
     /// Turns on power to the LED strip and waits for it to initialize.
     ///
+    /// # Arguments
+    ///
+    /// * `reason` - Why the relay is switched, for the relay event log
+    ///
     /// # Returns
     ///
     /// A Result indicating success or an error
-    pub async fn power_on(&mut self) -> Result<(), Box<dyn Error>> {
+    pub async fn power_on(&mut self, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        self.cancel_fade();
+        self.switch_relay_on(reason).await
+    }
+
+    /// Switches the strip's relay on without stopping a running fade
+    async fn switch_relay_on(&mut self, reason: RelayReason) -> Result<(), Box<dyn Error>> {
         let mut relay = self.relay_controller.lock().await;
-        relay.turn_on(RelayType::LED);
+        relay.turn_on(RelayType::LED, reason);
         self.power_state = true;
+        // A freshly powered strip doesn't show what was written before
+        self.written.forget();
         Ok(())
     }
 
     /// Powers off the LED strip via relay.
     ///
-    /// Turns off power to the LED strip to save energy when not in use.
+    /// Turns off power to the LED strip to save energy when not in use,
+    /// stopping any running animation.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - Why the relay is switched, for the relay event log
     ///
     /// # Returns
     ///
     /// A Result indicating success or an error
-    pub async fn power_off(&mut self) -> Result<(), Box<dyn Error>> {
+    pub async fn power_off(&mut self, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        self.cancel_animation();
+        self.cancel_fade();
+        
         // First turn off all LEDs if the strip is initialized
-        if let Some(ref mut strip) = self.led_strip {
-            strip.set_all(RGBWW::off());
-            strip.show()?;
+        if let Some(strip) = &self.led_strip {
+            show_color(strip, RGBWW::off()).await?;
         }
         
         // Then turn off the power relay
         let mut relay = self.relay_controller.lock().await;
-        relay.turn_off(RelayType::LED);
+        relay.turn_off(RelayType::LED, reason);
         self.power_state = false;
+        self.current_color = RGBWW::off();
+        self.written.forget();
         
         Ok(())
     }
 
     /// Sets the LED strip color.
     ///
-    /// Powers on the strip if needed and sets the specified color.
+    /// Powers on the strip if needed and sets the specified color, clamped to
+    /// the `[led]` channel caps and scaled down to the `[led.power]` budget
+    /// if it would draw more. The color is
+    /// always written, even if the strip already shows it.
     ///
     /// # Arguments
     ///
     /// * `color` - The RGBWW color to set
+    /// * `reason` - Why the strip is set, logged if its relay has to be switched on
     ///
     /// # Returns
     ///
     /// A Result indicating success or an error
-    pub async fn set_color(&mut self, color: RGBWW) -> Result<(), Box<dyn Error>> {
+    pub async fn set_color(&mut self, color: RGBWW, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        self.cancel_fade();
+        self.apply_color(color, reason, true).await
+    }
+
+    /// Like `set_color`, but skips the strip write if it already shows `color`.
+    ///
+    /// Used by the periodic schedule update, which mostly repeats the last color.
+    pub async fn update_color(&mut self, color: RGBWW, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        self.cancel_fade();
+        self.apply_color(color, reason, false).await
+    }
+
+    /// Stops a running fade before its next step, see `fade_to`
+    fn cancel_fade(&mut self) {
+        self.fade_generation += 1;
+    }
+
+    async fn apply_color(&mut self, color: RGBWW, reason: RelayReason, force: bool) -> Result<(), Box<dyn Error>> {
+        // Open the strip first, so an unavailable strip doesn't switch the relay on
+        let strip = self.open_strip()?;
+        
         // If the strip is powered off, power it on first
         if !self.power_state {
-            self.power_on().await?;
-            
-            // Initialize the strip if needed
-            if self.led_strip.is_none() {
-                self.led_strip = Some(LEDStrip::new()?);
-            }
+            self.switch_relay_on(reason).await?;
         }
         
-        // Set the color
-        if let Some(ref mut strip) = self.led_strip {
-            strip.set_all(color);
-            strip.show()?;
-        } else {
-            return Err("LED strip not initialized".into());
-        }
+        let color = self.power.limit(self.caps.clamp(color));
+        self.written.write(color, force, |color| show_color(&strip, color)).await?;
+        self.current_color = color;
         
         Ok(())
     }
 
+    /// Fades from the current color to `target` in evenly spaced steps.
+    ///
+    /// The controller lock is only held while each step is written, so web
+    /// handlers reading the LED state aren't blocked for the whole fade. Any
+    /// other color or power command, or a later fade, stops the fade before
+    /// its next step, so a manual color sent mid-fade stays.
+    ///
+    /// # Arguments
+    ///
+    /// * `controller` - The shared LED controller
+    /// * `target` - The RGBWW color to end on
+    /// * `duration` - Total duration of the fade
+    /// * `steps` - Number of intermediate colors written to the strip
+    /// * `reason` - Why the strip is faded, for the relay event log
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error
+    pub async fn fade_to(controller: &Arc<Mutex<Self>>, target: RGBWW, duration: Duration, steps: u32, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        Self::fade(controller, target, duration, steps, reason).await.map(|_| ())
+    }
+
+    /// Runs a fade, see `fade_to`
+    ///
+    /// # Returns
+    ///
+    /// Whether the fade ran to the end without being stopped
+    async fn fade(controller: &Arc<Mutex<Self>>, target: RGBWW, duration: Duration, steps: u32, reason: RelayReason) -> Result<bool, Box<dyn Error>> {
+        let (start, generation) = {
+            let mut led = controller.lock().await;
+            led.cancel_fade();
+            (led.current_color, led.fade_generation)
+        };
+        run_fade(FadePlan::new(start, target, steps), duration, |color| async move {
+            let mut led = controller.lock().await;
+            if led.fade_generation != generation {
+                return Ok(false);
+            }
+            led.apply_color(color, reason, true).await?;
+            Ok(true)
+        }).await
+    }
+
+    /// Powers on the strip (if needed) and fades up from black to `target`.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error
+    pub async fn fade_in(controller: &Arc<Mutex<Self>>, target: RGBWW, duration: Duration, steps: u32, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        {
+            let mut led = controller.lock().await;
+            if !led.power_state {
+                led.current_color = RGBWW::off();
+            }
+        }
+        Self::fade_to(controller, target, duration, steps, reason).await
+    }
+
+    /// Fades the strip down to black and then powers it off.
+    ///
+    /// If another command stops the fade, the strip is left as that command set it.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error
+    pub async fn fade_out(controller: &Arc<Mutex<Self>>, duration: Duration, steps: u32, reason: RelayReason) -> Result<(), Box<dyn Error>> {
+        let powered = controller.lock().await.power_state;
+        if powered && !Self::fade(controller, RGBWW::off(), duration, steps, reason).await? {
+            return Ok(());
+        }
+        controller.lock().await.power_off(reason).await
+    }
+
     /// Sets the LED color components individually.
     ///
     /// # Arguments
@@ -275,8 +799,9 @@ impl LEDController {
     ///
     /// A Result indicating success or an error
     pub async fn set_rgbww(&mut self, r: u8, g: u8, b: u8, ww: u8, cw: u8) -> Result<(), Box<dyn Error>> {
+        self.cancel_animation();
         let color = RGBWW { r, g, b, ww, cw };
-        self.set_color(color).await
+        self.set_color(color, RelayReason::Manual).await
     }
 
     /// Sets the LED color from a string representation.
@@ -289,8 +814,195 @@ impl LEDController {
     ///
     /// A Result indicating success or an error
     pub async fn set_color_from_str(&mut self, color_str: &str) -> Result<(), Box<dyn Error>> {
+        self.cancel_animation();
         let color = RGBWW::from_str(color_str)?;
-        self.set_color(color).await
+        self.set_color(color, RelayReason::Manual).await
+    }
+
+    /// Shows a white color temperature using only the warm and cool white channels.
+    ///
+    /// See `cct_to_rgbww` for the mapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `kelvin` - Color temperature, clamped to the range of the white LEDs
+    /// * `brightness` - Brightness in percent (0-100)
+    /// * `white_range` - Color temperatures of the (warm, cool) white LEDs
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error
+    pub async fn set_color_temperature(&mut self, kelvin: u32, brightness: u8, white_range: (u32, u32)) -> Result<(), Box<dyn Error>> {
+        self.cancel_animation();
+        self.set_color(cct_to_rgbww(kelvin, brightness, white_range), RelayReason::Manual).await
+    }
+
+    /// Starts an animation, replacing any one already running.
+    ///
+    /// Frames are written from a spawned task that takes the controller lock
+    /// only per frame and sleeps in between, so other LED commands can take
+    /// over at any time. Manual color and power commands stop the animation,
+    /// and the schedule leaves the strip alone while it runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `controller` - The shared LED controller
+    /// * `kind` - Which animation to run
+    /// * `animation` - The animation, see `AnimationKind::build`
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the strip could be opened
+    pub async fn start_animation(controller: &Arc<Mutex<Self>>, kind: AnimationKind, mut animation: Box<dyn Animation>) -> Result<(), Box<dyn Error>> {
+        let mut led = controller.lock().await;
+        led.open_strip()?;
+        
+        // Revert to what was shown before the first animation, not to a frame of the previous one
+        let (prior_color, prior_power) = match led.animation.take() {
+            Some(previous) => {
+                previous.task.abort();
+                (previous.prior_color, previous.prior_power)
+            }
+            None => (led.current_color, led.power_state),
+        };
+        
+        let shared = Arc::clone(controller);
+        let task = tokio::spawn(async move {
+            let started = Instant::now();
+            loop {
+                let frame = animation.next_frame(started.elapsed());
+                if let Err(e) = shared.lock().await.set_color(frame, RelayReason::Manual).await {
+                    warn!("LED animation stopped: {}", e);
+                    break;
+                }
+                tokio::time::sleep(FRAME_INTERVAL).await;
+            }
+        });
+        
+        led.animation = Some(RunningAnimation { kind, task, prior_color, prior_power });
+        Ok(())
+    }
+
+    /// Stops the running animation without touching the strip
+    fn cancel_animation(&mut self) -> Option<RunningAnimation> {
+        // The task only writes while holding the controller lock, which the
+        // caller holds, so no frame lands after this
+        let running = self.animation.take()?;
+        running.task.abort();
+        Some(running)
+    }
+
+    /// Stops the running animation and restores the color and power from before it.
+    ///
+    /// # Returns
+    ///
+    /// True if an animation was running
+    pub async fn stop_animation(&mut self) -> Result<bool, Box<dyn Error>> {
+        let running = match self.cancel_animation() {
+            Some(running) => running,
+            None => return Ok(false),
+        };
+        
+        if running.prior_power {
+            self.set_color(running.prior_color, RelayReason::Manual).await?;
+        } else {
+            self.power_off(RelayReason::Manual).await?;
+        }
+        Ok(true)
+    }
+
+    /// The running animation, if any
+    pub fn animation(&self) -> Option<AnimationKind> {
+        self.animation.as_ref()
+            .filter(|running| !running.task.is_finished())
+            .map(|running| running.kind)
+    }
+
+    /// Raises an alert when a new alert condition appears, see `AlertLatch`.
+    ///
+    /// The strip then pulses `color` until `acknowledge_alert`, over the schedule,
+    /// animations and manual colors. The pulse takes the controller lock per frame
+    /// like an animation.
+    ///
+    /// # Arguments
+    ///
+    /// * `controller` - The shared LED controller
+    /// * `conditions` - The alert conditions present now
+    /// * `color` - Brightest color of the pulse, see `[led] alert_color`
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the strip could be opened for a new alert
+    pub async fn check_alerts(controller: &Arc<Mutex<Self>>, conditions: &[LedAlert], color: RGBWW) -> Result<(), Box<dyn Error>> {
+        let mut led = controller.lock().await;
+        let alert = match led.alert.observe(conditions) {
+            Some(alert) => alert,
+            None => return Ok(()),
+        };
+        
+        // Without a strip nothing pulses, so don't leave an alert to acknowledge;
+        // the next check raises it again
+        if let Err(e) = led.open_strip() {
+            led.alert.reset();
+            return Err(e);
+        }
+        warn!("LED alert raised: {}, pulsing until POST /api/led/alert/ack", alert.as_str());
+        led.cancel_animation();
+        let shared = Arc::clone(controller);
+        let mut pulse = Breathing::new(color, ALERT_PULSE_PERIOD);
+        let reason = alert.reason();
+        led.alert_task = Some(tokio::spawn(async move {
+            let started = Instant::now();
+            loop {
+                let frame = pulse.next_frame(started.elapsed());
+                if let Err(e) = shared.lock().await.set_color(frame, reason).await {
+                    warn!("LED alert pulse stopped: {}", e);
+                    break;
+                }
+                tokio::time::sleep(FRAME_INTERVAL).await;
+            }
+        }));
+        Ok(())
+    }
+
+    /// Stops the alert pulse. The next schedule update (see `update_leds`)
+    /// brings back the scheduled color.
+    ///
+    /// # Returns
+    ///
+    /// The alert that was active, if any
+    pub fn acknowledge_alert(&mut self) -> Option<LedAlert> {
+        if let Some(task) = self.alert_task.take() {
+            task.abort();
+        }
+        self.written.forget();
+        let alert = self.alert.acknowledge();
+        if let Some(alert) = alert {
+            info!("LED alert acknowledged: {}", alert.as_str());
+        }
+        alert
+    }
+
+    /// The active alert, if any
+    pub fn alert(&self) -> Option<LedAlert> {
+        self.alert.active()
+    }
+
+    /// Stops the alert pulse and any animation, without restoring what was
+    /// shown before them, and powers the strip off.
+    ///
+    /// # Returns
+    ///
+    /// The alert that was active, if any
+    pub async fn force_off(&mut self, reason: RelayReason) -> Result<Option<LedAlert>, Box<dyn Error>> {
+        let alert = self.acknowledge_alert();
+        self.power_off(reason).await?;
+        Ok(alert)
+    }
+
+    /// Returns the last color written to the strip.
+    pub fn current_color(&self) -> RGBWW {
+        self.current_color
     }
 
     /// Checks if the LED strip is currently powered on.
@@ -303,7 +1015,146 @@ impl LEDController {
     }
 }
 
-/// Calculates a natural light color based on the time of day.
+#[cfg(test)]
+impl LEDController {
+    /// A controller writing to `strip` instead of opening the hardware
+    pub(crate) fn with_driver(relay_controller: Arc<Mutex<RelayController>>, strip: impl LedDriver + 'static) -> Self {
+        Self { led_strip: Some(Arc::new(StdMutex::new(Box::new(strip)))), ..Self::new(relay_controller) }
+    }
+}
+
+/// Stands in for the strip in tests, recording every frame shown
+#[cfg(test)]
+#[derive(Clone)]
+pub(crate) struct RecordingStrip {
+    color: RGBWW,
+    shown: Arc<StdMutex<Vec<RGBWW>>>,
+}
+
+#[cfg(test)]
+impl RecordingStrip {
+    pub(crate) fn new() -> Self {
+        Self { color: RGBWW::off(), shown: Arc::default() }
+    }
+
+    /// Every frame shown so far, oldest first
+    pub(crate) fn shown(&self) -> Vec<RGBWW> {
+        self.shown.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl LedDriver for RecordingStrip {
+    fn set_all(&mut self, color: RGBWW) {
+        self.color = color;
+    }
+
+    fn set_ic(&mut self, _index: usize, color: RGBWW) {
+        self.color = color;
+    }
+
+    fn show(&mut self) -> Result<(), Box<dyn Error>> {
+        self.shown.lock().unwrap().push(self.color);
+        Ok(())
+    }
+}
+
+/// Writes `color` to the strip on the blocking thread pool.
+///
+/// `LedDriver::show` blocks for the whole frame and the reset time, which
+/// would otherwise stall a Tokio worker (and the web server with it).
+async fn show_color(strip: &Arc<StdMutex<Box<dyn LedDriver>>>, color: RGBWW) -> Result<(), Box<dyn Error>> {
+    let strip = Arc::clone(strip);
+    tokio::task::spawn_blocking(move || {
+        let mut strip = strip.lock().map_err(|_| "LED strip lock poisoned".to_string())?;
+        strip.set_all(color);
+        strip.show().map_err(|e| e.to_string())
+    }).await??;
+    Ok(())
+}
+
+/// Writes every step of `plan`, sleeping between steps.
+///
+/// # Arguments
+///
+/// * `plan` - The colors to write
+/// * `duration` - Total duration of the fade
+/// * `write` - Writes one color, returning false to stop the fade; should
+///   only hold shared locks while it runs
+///
+/// # Returns
+///
+/// Whether every step was written
+async fn run_fade<F, Fut>(plan: FadePlan, duration: Duration, mut write: F) -> Result<bool, Box<dyn Error>>
+where
+    F: FnMut(RGBWW) -> Fut,
+    Fut: std::future::Future<Output = Result<bool, Box<dyn Error>>>,
+{
+    let steps = plan.steps();
+    let step_delay = duration / steps;
+    
+    for step in 1..=steps {
+        if !write(plan.color_at(step)).await? {
+            return Ok(false);
+        }
+        if step < steps {
+            tokio::time::sleep(step_delay).await;
+        }
+    }
+    
+    Ok(true)
+}
+
+/// Maps a color temperature to a warm/cool white mix with RGB off.
+///
+/// The mix is linear in mired (1,000,000 / K), which tracks perceived color
+/// far better than mixing linearly in Kelvin: at the warm LED's temperature
+/// only WW is lit, at the cool LED's only CW, and in between the two add up
+/// to the requested brightness. Out-of-range temperatures are clamped.
+///
+/// # Arguments
+///
+/// * `kelvin` - Requested color temperature
+/// * `brightness` - Brightness in percent, values above 100 are clamped
+/// * `white_range` - Color temperatures of the (warm, cool) white LEDs
+///
+/// # Returns
+///
+/// The RGBWW color to write
+pub fn cct_to_rgbww(kelvin: u32, brightness: u8, white_range: (u32, u32)) -> RGBWW {
+    let (warm, cool) = white_range;
+    let kelvin = kelvin.clamp(warm, cool);
+    let (warm_mired, cool_mired) = (1_000_000.0 / warm as f32, 1_000_000.0 / cool as f32);
+    let cool_share = (warm_mired - 1_000_000.0 / kelvin as f32) / (warm_mired - cool_mired);
+    
+    let total = 255.0 * brightness.min(100) as f32 / 100.0;
+    let cw = (total * cool_share).round();
+    let ww = total.round() - cw;
+    
+    RGBWW { r: 0, g: 0, b: 0, ww: ww as u8, cw: cw as u8 }
+}
+
+/// Estimates the color temperature of a color made only of white channels.
+///
+/// Inverse of `cct_to_rgbww`.
+///
+/// # Returns
+///
+/// The temperature in Kelvin, or None if any RGB channel is lit or the strip is dark
+pub fn estimate_cct(color: RGBWW, white_range: (u32, u32)) -> Option<u32> {
+    if color.r != 0 || color.g != 0 || color.b != 0 || (color.ww == 0 && color.cw == 0) {
+        return None;
+    }
+    
+    let (warm, cool) = white_range;
+    let (warm_mired, cool_mired) = (1_000_000.0 / warm as f32, 1_000_000.0 / cool as f32);
+    let cool_share = color.cw as f32 / (color.ww as f32 + color.cw as f32);
+    let mired = warm_mired - cool_share * (warm_mired - cool_mired);
+    
+    Some((1_000_000.0 / mired).round() as u32)
+}
+
+/// Calculates a natural light color based on the time of day.
 ///
 /// This function interpolates between morning, noon, and evening light presets
 /// based on the current time, and also factors in seasonal variations.
@@ -389,127 +1240,387 @@ pub fn calculate_natural_light(
     ))
 }
 
+/// Manual LED settings as stored in the `led_settings` table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LedSettings {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub ww: u8,
+    pub cw: u8,
+    pub enabled: bool,
+    pub override_natural: bool,
+    pub season_weight: f32,
+}
+
+impl LedSettings {
+    /// Drops the manual parts of the settings so the strip follows the schedule.
+    ///
+    /// Used in away mode: the strip is treated as enabled and natural light is
+    /// used even if a manual color override is stored. The season weight is kept.
+    pub fn without_overrides(self) -> Self {
+        Self { enabled: true, override_natural: false, ..self }
+    }
+}
+
+/// Resolves the color the LED strip should show at a given time.
+///
+/// This is the single source of truth for what `update_leds` applies:
+/// the week's LED on/off window decides whether the strip is lit, and the
+/// manual settings decide between the manual color and natural light.
+///
+/// # Arguments
+///
+/// * `current_time` - The time to resolve in 24-hour format (HH:MM)
+/// * `date` - The day to resolve, for the seasonal color when `[led] auto_season` is on
+/// * `schedule` - The schedule for the current week, if one is stored
+/// * `settings` - The manual LED settings, if any are stored
+/// * `config` - Application configuration (defaults and natural light presets)
+///
+/// # Returns
+///
+/// A Result containing `Some(color)` if the LEDs should be on, or `None` if they should be off
+pub fn resolve_led_color(
+    current_time: &str,
+    date: NaiveDate,
+    schedule: Option<&Schedule>,
+    settings: Option<&LedSettings>,
+    config: &Config
+) -> Result<Option<RGBWW>, Box<dyn Error>> {
+    // Check if LEDs should be enabled based on the week's LED window
+    if !in_led_window(current_time, schedule) {
+        return Ok(None);
+    }
+    
+    match settings {
+        Some(settings) => {
+            if !settings.enabled {
+                return Ok(None);
+            }
+            
+            if settings.override_natural {
+                // Use manual settings
+                return Ok(Some(RGBWW {
+                    r: settings.r,
+                    g: settings.g,
+                    b: settings.b,
+                    ww: settings.ww,
+                    cw: settings.cw,
+                }));
+            }
+            
+            // Calculate natural light colors based on time of day and season
+            NaturalLightInputs::resolve(date, schedule, Some(settings), config)
+                .color_at(current_time, config)
+                .map(Some)
+        },
+        None => {
+            // No settings stored yet: fall back according to the configured default mode
+            if config.led.default_mode == "natural" {
+                return NaturalLightInputs::resolve(date, schedule, None, config)
+                    .color_at(current_time, config)
+                    .map(Some);
+            }
+            
+            let (r, g, b, ww, cw) = default_led_color(config);
+            Ok(Some(RGBWW { r, g, b, ww, cw }))
+        }
+    }
+}
+
+/// Whether `current_time` (HH:MM) is inside the week's LED window; always
+/// true without a stored week
+pub fn in_led_window(current_time: &str, schedule: Option<&Schedule>) -> bool {
+    match schedule {
+        Some(week) => current_time >= week.led_start.as_str() && current_time <= week.led_end.as_str(),
+        None => true,
+    }
+}
+
+/// Applies `[led] override_precedence` to the stored manual settings.
+///
+/// # Arguments
+///
+/// * `precedence` - The configured precedence
+/// * `settings` - The stored manual settings, if any
+/// * `window_before` - Whether the LED window was open at the previous update, None on the first
+/// * `window_open` - Whether the LED window is open now
+///
+/// # Returns
+///
+/// The settings to resolve the color with, and whether the stored override
+/// has to be cleared because the window crossed an edge
+pub fn apply_precedence(
+    precedence: OverridePrecedence,
+    settings: Option<LedSettings>,
+    window_before: Option<bool>,
+    window_open: bool,
+) -> (Option<LedSettings>, bool) {
+    let overridden = matches!(settings, Some(s) if s.override_natural || !s.enabled);
+    match precedence {
+        OverridePrecedence::OverrideAlways => (settings, false),
+        OverridePrecedence::ScheduleAlways => (settings.map(LedSettings::without_overrides), false),
+        OverridePrecedence::OverrideUntilNextTransition => {
+            let crossed = matches!(window_before, Some(before) if before != window_open);
+            if overridden && crossed {
+                (settings.map(LedSettings::without_overrides), true)
+            } else {
+                (settings, false)
+            }
+        }
+    }
+}
+
+/// Clears the stored manual overrides: the strip is enabled and follows natural light
+pub async fn clear_led_overrides(db_pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE led_settings SET enabled = 1, override = 0 WHERE id = 1")
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+/// Disables the strip in the stored settings and clears the manual color
+/// override, so the schedule keeps it off until it is enabled again
+pub async fn disable_led_settings(db_pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE led_settings SET enabled = 0, override = 0 WHERE id = 1")
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+/// The manual default color from `[db]` in config.toml
+fn default_led_color(config: &Config) -> (u8, u8, u8, u8, u8) {
+    (
+        config.db.def_led_R as u8,
+        config.db.def_led_G as u8,
+        config.db.def_led_B as u8,
+        config.db.def_led_WW as u8,
+        config.db.def_led_CW as u8,
+    )
+}
+
+/// Everything `calculate_natural_light` needs besides the time of day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NaturalLightInputs {
+    pub morning_time: String,
+    pub noon_time: String,
+    pub evening_time: String,
+    pub season_color: (u8, u8, u8, u8, u8),
+    pub season_weight: f32,
+}
+
+impl NaturalLightInputs {
+    /// Derives the inputs `resolve_led_color` uses for natural light.
+    ///
+    /// Morning and evening are the week's LED window (07:00-19:00 without a
+    /// schedule). With `[led] auto_season` on, the season color and weight come
+    /// from the calendar via `seasonal_color`. Otherwise the season color comes
+    /// from the schedule, falling back to the manual settings and then the config
+    /// default, and the season weight comes from the settings, falling back to
+    /// `[led] season_weight`.
+    pub fn resolve(date: NaiveDate, schedule: Option<&Schedule>, settings: Option<&LedSettings>, config: &Config) -> Self {
+        let (morning_time, evening_time) = match schedule {
+            Some(week) => (week.led_start.clone(), week.led_end.clone()),
+            None => (DEFAULT_LED_START.to_string(), DEFAULT_LED_END.to_string()),
+        };
+        let season_color = match (schedule, settings) {
+            (Some(week), _) => (
+                week.led_r as u8,
+                week.led_g as u8,
+                week.led_b as u8,
+                week.led_ww as u8,
+                week.led_cw as u8,
+            ),
+            (None, Some(settings)) => (settings.r, settings.g, settings.b, settings.ww, settings.cw),
+            (None, None) => default_led_color(config),
+        };
+        
+        let season_weight = settings.map_or(config.led.season_weight, |settings| settings.season_weight);
+        let (season_color, season_weight) = if config.led.auto_season() {
+            seasonal_color(&config.led.season_keyframes(), date.ordinal())
+        } else {
+            (season_color, season_weight)
+        };
+        
+        Self {
+            morning_time,
+            noon_time: "12:00".to_string(), // Fixed noon time
+            evening_time,
+            season_color,
+            season_weight,
+        }
+    }
+
+    /// Natural light color at `current_time` (HH:MM), via `calculate_natural_light`
+    pub fn color_at(&self, current_time: &str, config: &Config) -> Result<RGBWW, Box<dyn Error>> {
+        let (r, g, b, ww, cw) = calculate_natural_light(
+            current_time,
+            &self.morning_time,
+            &self.noon_time,
+            &self.evening_time,
+            &self.season_color,
+            self.season_weight,
+            config
+        )?;
+        Ok(RGBWW { r, g, b, ww, cw })
+    }
+}
+
+/// Maps a day of the year to a season color and weight.
+///
+/// Interpolates linearly between the keyframes on either side of the day,
+/// wrapping from the last keyframe of the year to the first.
+///
+/// # Arguments
+///
+/// * `keyframes` - The seasonal color curve, in any order; must not be empty
+/// * `day_of_year` - 1-366
+///
+/// # Returns
+///
+/// A tuple of the (r,g,b,ww,cw) season color and the season weight
+pub fn seasonal_color(keyframes: &[SeasonKeyframe], day_of_year: u32) -> ((u8, u8, u8, u8, u8), f32) {
+    let mut keyframes = keyframes.to_vec();
+    keyframes.sort_by_key(|keyframe| keyframe.day_of_year);
+    
+    // Treat day 366 like 365 so the year wraps at a fixed length
+    let day = day_of_year.clamp(1, 365);
+    let next_index = keyframes.iter().position(|keyframe| keyframe.day_of_year > day).unwrap_or(0);
+    let prev = &keyframes[(next_index + keyframes.len() - 1) % keyframes.len()];
+    let next = &keyframes[next_index];
+    
+    let prev_day = prev.day_of_year.min(365);
+    let span = (next.day_of_year.min(365) + 365 - prev_day) % 365;
+    let factor = if span == 0 { 0.0 } else { ((day + 365 - prev_day) % 365) as f32 / span as f32 };
+    
+    let color = LightPreset::new(prev.r, prev.g, prev.b, prev.ww, prev.cw)
+        .interpolate(&LightPreset::new(next.r, next.g, next.b, next.ww, next.cw), factor);
+    let weight = prev.weight + (next.weight - prev.weight) * factor;
+    
+    ((color.r, color.g, color.b, color.ww, color.cw), weight)
+}
+
+/// Most samples `natural_light_curve` returns: one per minute of the day
+pub const MAX_CURVE_SAMPLES: usize = 24 * 60;
+
+/// Samples the natural light color across a day, starting at 00:00.
+///
+/// # Arguments
+///
+/// * `inputs` - The LED window, season color and weight to use
+/// * `step_minutes` - Minutes between samples, at least 1
+/// * `config` - Application configuration (natural light presets)
+///
+/// # Returns
+///
+/// A Result containing (HH:MM, color) pairs, at most `MAX_CURVE_SAMPLES` of them
+pub fn natural_light_curve(
+    inputs: &NaturalLightInputs,
+    step_minutes: u32,
+    config: &Config
+) -> Result<Vec<(String, RGBWW)>, Box<dyn Error>> {
+    if step_minutes == 0 {
+        return Err("step_minutes must be at least 1".into());
+    }
+    
+    (0..24 * 60)
+        .step_by(step_minutes as usize)
+        .take(MAX_CURVE_SAMPLES)
+        .map(|minute| {
+            let time = format!("{:02}:{:02}", minute / 60, minute % 60);
+            let color = inputs.color_at(&time, config)?;
+            Ok((time, color))
+        })
+        .collect()
+}
+
 /// Updates the LED strip based on schedule and database settings.
 ///
 /// This function is called periodically to:
-/// 1. Check the current time against the configured schedule
-/// 2. Retrieve manual settings from the database
-/// 3. Calculate the appropriate colors for the current time of day
-/// 4. Update the LED strip or power it off during night hours
+/// 1. Look up the current week's schedule and the manual LED settings
+/// 2. Resolve the target color with `resolve_led_color`
+/// 3. Update the LED strip or power it off outside the LED window
 ///
 /// # Arguments
 ///
-/// * `db_pool` - Database connection for retrieving settings
+/// * `db_pool` - Database pool for retrieving settings
 /// * `led_controller` - Reference to the LED controller
 /// * `config` - Application configuration
+/// * `away` - Whether away mode is on, in which case manual overrides are ignored
 ///
 /// # Returns
 ///
 /// A Result indicating success or an error
 pub async fn update_leds(
-    db_pool: &rusqlite::Connection,
+    db_pool: &SqlitePool,
     led_controller: &Arc<Mutex<LEDController>>,
-    config: &Config
+    config: &Config,
+    away: bool,
 ) -> Result<(), Box<dyn Error>> {
-    // Get current time
-    let now = Local::now();
+    // A strip disabled after repeated init failures stays dark until reinitialized,
+    // and a running animation or alert owns the strip until it's stopped
+    {
+        let led = led_controller.lock().await;
+        if !led.is_available() || led.animation().is_some() || led.alert().is_some() {
+            return Ok(());
+        }
+    }
+    
+    // Get current time in the schedule timezone
+    let now = config.main.schedule_now();
     let current_time = now.format("%H:%M").to_string();
     
-    // Try to get schedule from database first
-    let schedule_result = db_pool.query_row(
-        "SELECT led_start, led_end, led_r, led_g, led_b, led_cw, led_ww FROM schedule WHERE ? BETWEEN week_start AND week_end",
-        [now.format("%Y-%m-%d").to_string()],
-        |row| {
-            Ok((
-                row.get::<_, String>(0)?, // led_start
-                row.get::<_, String>(1)?, // led_end
-                row.get::<_, i32>(2)? as u8, // led_r
-                row.get::<_, i32>(3)? as u8, // led_g
-                row.get::<_, i32>(4)? as u8, // led_b
-                row.get::<_, i32>(5)? as u8, // led_cw
-                row.get::<_, i32>(6)? as u8, // led_ww
-            ))
-        }
-    );
+    // Try to get schedule and manual settings from database
+    let schedule = Schedule::get_week(db_pool, Schedule::week_for(&now)).await?;
+    let mut settings = get_led_settings(db_pool).await?;
+    if away {
+        settings = settings.map(LedSettings::without_overrides);
+    }
     
-    // Get led settings from database
-    let led_settings_result = db_pool.query_row(
-        "SELECT r, g, b, ww, cw, enabled, override, season_weight FROM led_settings WHERE id = 1",
-        [],
-        |row| {
-            Ok((
-                row.get::<_, i32>(0)? as u8,  // r
-                row.get::<_, i32>(1)? as u8,  // g
-                row.get::<_, i32>(2)? as u8,  // b
-                row.get::<_, i32>(3)? as u8,  // ww
-                row.get::<_, i32>(4)? as u8,  // cw
-                row.get::<_, bool>(5)?,       // enabled
-                row.get::<_, i32>(6)? != 0,   // override (true if using manual settings)
-                row.get::<_, f32>(7)?         // season_weight
-            ))
-        }
-    );
+    // Override or schedule, per [led] override_precedence
+    let window_open = in_led_window(&current_time, schedule.as_ref());
+    let window_before = std::mem::replace(&mut led_controller.lock().await.window_open, Some(window_open));
+    let (settings, clear) = apply_precedence(config.led.override_precedence, settings, window_before, window_open);
+    if clear {
+        crate::modules::storage::retry_busy(|| clear_led_overrides(db_pool)).await?;
+        info!("LED window {} at {}: manual LED override cleared", if window_open { "opened" } else { "closed" }, current_time);
+    }
     
-    // Check if LEDs should be enabled based on schedule
-    let (leds_enabled, morning_time, evening_time) = match &schedule_result {
-        Ok((start, end, _, _, _, _, _)) => {
-            // Check if current time is between start and end
-            (current_time >= *start && current_time <= *end, start.clone(), end.clone())
-        },
-        Err(_) => (true, "07:00".to_string(), "19:00".to_string()) // Default if no schedule
+    // POST /api/led/off holds over the precedence and away mode until a color is set again
+    let forced_off = crate::modules::storage::led_forced_off(db_pool).await?;
+    let target = if forced_off {
+        None
+    } else {
+        resolve_led_color(&current_time, now.date(), schedule.as_ref(), settings.as_ref(), config)?
+    };
+    // Disabling the LEDs in the settings is the only override that switches the relay
+    let reason = if forced_off {
+        RelayReason::Manual
+    } else {
+        match &settings {
+            Some(settings) if !settings.enabled => RelayReason::Override,
+            _ => RelayReason::Schedule,
+        }
     };
     
-    // Fixed noon time
-    let noon_time = "12:00".to_string();
-    
-    let mut controller = led_controller.lock().await;
-    
-    match led_settings_result {
-        Ok((r, g, b, ww, cw, enabled, override_natural, season_weight)) => {
-            if enabled && leds_enabled {
-                // Get the season color from the schedule
-                let season_color = match &schedule_result {
-                    Ok((_, _, sr, sg, sb, scw, sww)) => (*sr, *sg, *sb, *sww, *scw),
-                    Err(_) => (r, g, b, ww, cw), // Use manual settings as fallback
-                };
-                
-                if override_natural {
-                    // Use manual settings
-                    controller.set_rgbww(r, g, b, ww, cw).await?;
-                } else {
-                    // Calculate natural light colors based on time of day and season
-                    let (calc_r, calc_g, calc_b, calc_ww, calc_cw) = calculate_natural_light(
-                        &current_time,
-                        &morning_time,
-                        &noon_time,
-                        &evening_time,
-                        &season_color,
-                        season_weight,
-                        config
-                    )?;
-                    
-                    controller.set_rgbww(calc_r, calc_g, calc_b, calc_ww, calc_cw).await?;
-                }
-            } else {
-                controller.power_off().await?;
-            }
-        },
-        Err(_) => {
-            // Use defaults from config if no settings found
-            let (r, g, b, ww, cw, enabled) = (
-                config.db.def_led_R as u8,
-                config.db.def_led_G as u8,
-                config.db.def_led_B as u8,
-                config.db.def_led_WW as u8,
-                config.db.def_led_CW as u8,
-                true // Default to enabled
-            );
-            
-            if enabled && leds_enabled {
-                controller.set_rgbww(r, g, b, ww, cw).await?;
-            } else {
-                controller.power_off().await?;
-            }
+    let fade_duration = Duration::from_secs(config.led.fade_duration.unwrap_or(0));
+    let fade_steps = config.led.fade_steps.unwrap_or(50);
+    let scheduled_on = led_controller.lock().await.scheduled_on;
+    
+    // Only fade when the schedule actually crosses a window edge, not on every tick.
+    // Fades take the controller lock per step rather than for their whole duration.
+    match (detect_transition(scheduled_on, target.is_some()), target) {
+        (LedTransition::FadeIn, Some(color)) => LEDController::fade_in(led_controller, color, fade_duration, fade_steps, reason).await?,
+        (LedTransition::FadeOut, _) => LEDController::fade_out(led_controller, fade_duration, fade_steps, reason).await?,
+        (_, Some(color)) => led_controller.lock().await.update_color(color, reason).await?,
+        (_, None) => led_controller.lock().await.power_off(reason).await?,
+    }
+    led_controller.lock().await.scheduled_on = Some(target.is_some());
+    
+    // Remembered for `[main] boot_behavior = "resume"`
+    if let Some(color) = target {
+        if crate::modules::storage::last_led_color(db_pool).await? != Some(color) {
+            crate::modules::storage::retry_busy(|| crate::modules::storage::save_led_color(db_pool, color)).await?;
         }
     }
     
@@ -520,44 +1631,587 @@ pub async fn update_leds(
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
-/// * `config` - Application configuration
+/// * `db_pool` - Database pool
 ///
 /// # Returns
 ///
-/// A Result containing a tuple of (r,g,b,ww,cw,enabled) settings
-fn get_led_settings(
-    db: &rusqlite::Connection,
-    config: &Config
-) -> Result<(u8, u8, u8, u8, u8, bool), Box<dyn Error>> {
-    // Try to get settings from database
-    let result = db.query_row(
-        "SELECT r, g, b, ww, cw, enabled FROM led_settings WHERE id = 1",
-        [],
-        |row| {
-            Ok((
-                row.get::<_, i32>(0)? as u8,
-                row.get::<_, i32>(1)? as u8,
-                row.get::<_, i32>(2)? as u8,
-                row.get::<_, i32>(3)? as u8,
-                row.get::<_, i32>(4)? as u8,
-                row.get::<_, bool>(5)?
-            ))
-        }
-    );
+/// A Result containing the stored settings, or None if no settings row exists
+pub async fn get_led_settings(db_pool: &SqlitePool) -> Result<Option<LedSettings>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT r, g, b, ww, cw, enabled, override AS "override_natural", season_weight
+        FROM led_settings
+        WHERE id = 1
+        "#
+    )
+    .fetch_optional(db_pool)
+    .await?;
     
-    match result {
-        Ok(settings) => Ok(settings),
-        Err(_) => {
-            // Use defaults from config
-            Ok((
-                config.db.def_led_R as u8,
-                config.db.def_led_G as u8,
-                config.db.def_led_B as u8,
-                config.db.def_led_WW as u8,
-                config.db.def_led_CW as u8,
-                true // Default to enabled
-            ))
-        }
-    }
-} 
\ No newline at end of file
+    Ok(row.map(|row| LedSettings {
+        r: row.r as u8,
+        g: row.g as u8,
+        b: row.b as u8,
+        ww: row.ww as u8,
+        cw: row.cw as u8,
+        enabled: row.enabled != 0,
+        override_natural: row.override_natural != 0,
+        season_weight: row.season_weight as f32,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::test_config;
+    use crate::modules::storage;
+
+    #[test]
+    fn test_white_scaled_to_power_budget() {
+        let config = LedPowerConfig { max_current_ma: Some(3000.0), ..LedPowerConfig::default() };
+        let power = PowerModel::new(&config, 100);
+        let white = RGBWW { r: 255, g: 255, b: 255, ww: 255, cw: 255 };
+        assert_eq!(power.estimate_ma(white), 6000.0);
+
+        let limited = power.limit(white);
+        assert!(power.estimate_ma(limited) <= 3000.0, "{:?}", limited);
+        assert!(power.estimate_ma(limited) > 2950.0, "{:?}", limited);
+        assert_eq!(limited, RGBWW { r: 127, g: 127, b: 127, ww: 127, cw: 127 });
+
+        let dim = RGBWW { r: 40, g: 10, b: 0, ww: 60, cw: 0 };
+        assert_eq!(power.limit(dim), dim);
+        assert_eq!(PowerModel::new(&LedPowerConfig::default(), 100).limit(white), white);
+    }
+
+    #[tokio::test]
+    async fn test_white_clamped_to_channel_cap() {
+        let mut config = test_config();
+        config.led.max_ww = Some(200);
+        config.led.max_cw = Some(180);
+        assert!(config.led.validate().is_ok());
+        let caps = ChannelCaps(config.led.channel_caps());
+
+        let commanded = RGBWW { r: 255, g: 10, b: 0, ww: 255, cw: 100 };
+        let capped = RGBWW { r: 255, g: 10, b: 0, ww: 200, cw: 100 };
+        assert_eq!(caps.clamp(commanded), capped);
+        assert_eq!(ChannelCaps::default().clamp(commanded), commanded);
+
+        // The strip itself only ever sees the capped color
+        let strip = RecordingStrip::new();
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let mut controller = LEDController::with_driver(relays, strip.clone());
+        controller.set_channel_caps(caps);
+        controller.update_color(commanded, RelayReason::Manual).await.unwrap();
+        assert_eq!(strip.shown().last(), Some(&capped));
+        assert!(strip.shown().iter().all(|frame| frame.ww <= 200 && frame.cw <= 180), "{:?}", strip.shown());
+
+        config.led.max_ww = Some(256);
+        assert!(config.led.validate().is_err());
+    }
+
+    fn week_with_led_window(start: &str, end: &str) -> Schedule {
+        Schedule {
+            week_number: 10,
+            uv1_start: "06:30".to_string(),
+            uv1_end: "19:00".to_string(),
+            uv2_start: "07:00".to_string(),
+            uv2_end: "19:30".to_string(),
+            heat_start: "10:00".to_string(),
+            heat_end: "14:30".to_string(),
+            led_start: start.to_string(),
+            led_end: end.to_string(),
+            led_r: 150,
+            led_g: 150,
+            led_b: 128,
+            led_cw: 128,
+            led_ww: 128,
+            uv1_level: 100,
+            uv2_level: 100,
+            heat_target: None,
+        }
+    }
+
+    /// A day in week 10, matching `week_with_led_window`
+    fn test_day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 6).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_led_window_round_trip_turns_off_after_end() {
+        let pool = storage::temp_db("led_window").await;
+        let config = test_config();
+
+        let week = week_with_led_window("08:00", "20:00");
+        week.validate().unwrap();
+        week.upsert(&pool).await.unwrap();
+
+        let stored = Schedule::get_week(&pool, 10).await.unwrap().unwrap();
+        assert_eq!(stored, week);
+
+        let settings = get_led_settings(&pool).await.unwrap();
+        let at_noon = resolve_led_color("12:00", test_day(), Some(&stored), settings.as_ref(), &config).unwrap();
+        let at_nine_pm = resolve_led_color("21:00", test_day(), Some(&stored), settings.as_ref(), &config).unwrap();
+        assert!(at_noon.is_some());
+        assert!(at_nine_pm.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_settings_keep_strip_dark_in_window() {
+        let pool = storage::temp_db("led_off").await;
+        let config = test_config();
+        let week = week_with_led_window("08:00", "20:00");
+
+        // A manual color override is active
+        sqlx::query("UPDATE led_settings SET r = 255, enabled = 1, override = 1 WHERE id = 1").execute(&pool).await.unwrap();
+        disable_led_settings(&pool).await.unwrap();
+
+        let settings = get_led_settings(&pool).await.unwrap().unwrap();
+        assert!(!settings.enabled && !settings.override_natural);
+        for precedence in [OverridePrecedence::OverrideAlways, OverridePrecedence::OverrideUntilNextTransition] {
+            let (settings, clear) = apply_precedence(precedence, Some(settings), Some(true), true);
+            assert!(!clear);
+            assert_eq!(resolve_led_color("12:00", test_day(), Some(&week), settings.as_ref(), &config).unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_led_off_holds_in_every_precedence_and_away_mode() {
+        let pool = storage::temp_db("led_forced_off").await;
+        let mut config = test_config();
+        config.led.fade_duration = Some(0);
+        // The LED window is open all day this week
+        let mut week = week_with_led_window("00:00", "23:59");
+        week.week_number = Schedule::week_for(&config.main.schedule_now());
+        week.upsert(&pool).await.unwrap();
+
+        for precedence in [OverridePrecedence::OverrideAlways, OverridePrecedence::OverrideUntilNextTransition, OverridePrecedence::ScheduleAlways] {
+            for away in [false, true] {
+                config.led.override_precedence = precedence;
+                let strip = RecordingStrip::new();
+                let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+                let controller = Arc::new(Mutex::new(LEDController::with_driver(relays, strip.clone())));
+
+                // What POST /api/led/off stores, just before the window opens
+                storage::set_setting(&pool, storage::LED_FORCED_OFF_KEY, "true").await.unwrap();
+                disable_led_settings(&pool).await.unwrap();
+                {
+                    let mut led = controller.lock().await;
+                    led.window_open = Some(false);
+                    led.scheduled_on = Some(false);
+                }
+                for _ in 0..2 {
+                    update_leds(&pool, &controller, &config, away).await.unwrap();
+                    assert!(!controller.lock().await.is_powered_on(), "{:?}, away {}: the strip came back on", precedence, away);
+                }
+                assert!(strip.shown().iter().all(|&color| color == RGBWW::off()), "{:?}, away {}", precedence, away);
+
+                // Setting a color lifts it, and the schedule takes over again
+                storage::set_setting(&pool, storage::LED_FORCED_OFF_KEY, "false").await.unwrap();
+                clear_led_overrides(&pool).await.unwrap();
+                update_leds(&pool, &controller, &config, away).await.unwrap();
+                assert!(controller.lock().await.is_powered_on(), "{:?}, away {}", precedence, away);
+            }
+        }
+    }
+
+    #[test]
+    fn test_within_window_tick_does_not_fade() {
+        assert_eq!(detect_transition(Some(true), true), LedTransition::None);
+        assert_eq!(detect_transition(Some(false), false), LedTransition::None);
+        assert_eq!(detect_transition(None, true), LedTransition::None);
+        assert_eq!(detect_transition(Some(false), true), LedTransition::FadeIn);
+        assert_eq!(detect_transition(Some(true), false), LedTransition::FadeOut);
+    }
+
+    #[test]
+    fn test_override_precedence_across_window_transition() {
+        let config = test_config();
+        let week = week_with_led_window("08:00", "20:00");
+        let manual = LedSettings { r: 10, g: 20, b: 30, ww: 40, cw: 50, enabled: true, override_natural: true, season_weight: 0.3 };
+        let natural = resolve_led_color("12:00", test_day(), Some(&week), Some(&manual.without_overrides()), &config).unwrap();
+        let manual_color = Some(RGBWW { r: 10, g: 20, b: 30, ww: 40, cw: 50 });
+        assert_ne!(natural, manual_color);
+
+        // Noon, the window closing in the evening, and noon of the next day
+        let run = |precedence| {
+            let mut stored = Some(manual);
+            let mut window_before = None;
+            let mut colors = Vec::new();
+            for time in ["12:00", "21:00", "07:00", "12:00"] {
+                let window_open = in_led_window(time, Some(&week));
+                let (settings, clear) = apply_precedence(precedence, stored, window_before, window_open);
+                if clear {
+                    stored = stored.map(LedSettings::without_overrides);
+                }
+                window_before = Some(window_open);
+                colors.push(resolve_led_color(time, test_day(), Some(&week), settings.as_ref(), &config).unwrap());
+            }
+            (colors, stored.unwrap().override_natural)
+        };
+
+        assert_eq!(run(OverridePrecedence::OverrideAlways), (vec![manual_color, None, None, manual_color], true));
+        assert_eq!(run(OverridePrecedence::OverrideUntilNextTransition), (vec![manual_color, None, None, natural], false));
+        assert_eq!(run(OverridePrecedence::ScheduleAlways), (vec![natural, None, None, natural], true));
+
+        // Switching the strip off by hand is an override too
+        let off = LedSettings { enabled: false, ..manual };
+        assert_eq!(apply_precedence(OverridePrecedence::OverrideUntilNextTransition, Some(off), Some(true), true), (Some(off), false));
+        assert!(apply_precedence(OverridePrecedence::OverrideUntilNextTransition, Some(off), Some(true), false).1);
+    }
+
+    #[test]
+    fn test_invalid_led_window_rejected() {
+        assert!(week_with_led_window("8am", "20:00").validate().is_err());
+    }
+
+    #[test]
+    fn test_fallback_without_settings_follows_default_mode() {
+        let mut config = test_config();
+        let manual_default = RGBWW {
+            r: config.db.def_led_R as u8,
+            g: config.db.def_led_G as u8,
+            b: config.db.def_led_B as u8,
+            ww: config.db.def_led_WW as u8,
+            cw: config.db.def_led_CW as u8,
+        };
+
+        config.led.default_mode = "natural".to_string();
+        let natural = resolve_led_color("12:00", test_day(), None, None, &config).unwrap().unwrap();
+        let (r, g, b, ww, cw) = calculate_natural_light(
+            "12:00", "07:00", "12:00", "19:00",
+            &(manual_default.r, manual_default.g, manual_default.b, manual_default.ww, manual_default.cw),
+            config.led.season_weight,
+            &config,
+        ).unwrap();
+        assert_eq!(natural, RGBWW { r, g, b, ww, cw });
+        assert_ne!(natural, manual_default);
+
+        config.led.default_mode = "manual".to_string();
+        let manual = resolve_led_color("12:00", test_day(), None, None, &config).unwrap().unwrap();
+        assert_eq!(manual, manual_default);
+    }
+
+    #[test]
+    fn test_natural_light_curve_matches_production_calculation() {
+        let config = test_config();
+        let week = week_with_led_window("08:00", "20:00");
+        let inputs = NaturalLightInputs::resolve(test_day(), Some(&week), None, &config);
+
+        let curve = natural_light_curve(&inputs, 60, &config).unwrap();
+        assert_eq!(curve.len(), 24);
+        assert_eq!(curve[0].0, "00:00");
+        assert_eq!(curve[23].0, "23:00");
+        for (time, color) in &curve {
+            let (r, g, b, ww, cw) = calculate_natural_light(
+                time, "08:00", "12:00", "20:00",
+                &inputs.season_color, config.led.season_weight, &config,
+            ).unwrap();
+            assert_eq!(*color, RGBWW { r, g, b, ww, cw });
+        }
+
+        assert_eq!(natural_light_curve(&inputs, 1, &config).unwrap().len(), MAX_CURVE_SAMPLES);
+        assert_eq!(natural_light_curve(&inputs, 45, &config).unwrap().len(), 32);
+        assert!(natural_light_curve(&inputs, 0, &config).is_err());
+    }
+
+    #[test]
+    fn test_strip_init_disables_after_repeated_failures_until_reset() {
+        let mut init = StripInit::default();
+        let mut attempts = 0;
+        let fail = |attempts: &mut u32| -> Result<(), String> {
+            *attempts += 1;
+            Err("SPI not enabled".to_string())
+        };
+
+        for _ in 0..MAX_STRIP_INIT_FAILURES {
+            assert!(init.available());
+            assert!(init.try_open(|| fail(&mut attempts)).is_err());
+        }
+        assert!(!init.available());
+        assert_eq!(init.last_error(), Some("SPI not enabled"));
+
+        // Unavailable: further ticks fail without touching SPI again
+        assert!(init.try_open(|| fail(&mut attempts)).is_err());
+        assert_eq!(attempts, MAX_STRIP_INIT_FAILURES);
+
+        // After a reinit the next attempt can succeed
+        init.reset();
+        assert_eq!(init.try_open(|| Ok::<_, String>("strip")), Ok("strip"));
+        assert!(init.available());
+        assert_eq!(init.last_error(), None);
+    }
+
+    #[test]
+    fn test_auto_season_differs_between_january_and_july() {
+        let mut config = test_config();
+        config.led.auto_season = Some(true);
+        let week = week_with_led_window("07:00", "19:00");
+        let january = NaiveDate::from_ymd_opt(2024, 1, 20).unwrap();
+        let july = NaiveDate::from_ymd_opt(2024, 7, 20).unwrap();
+
+        let winter = NaturalLightInputs::resolve(january, Some(&week), None, &config);
+        let summer = NaturalLightInputs::resolve(july, Some(&week), None, &config);
+        assert_ne!(winter.season_color, summer.season_color);
+        assert!(winter.season_color.3 > summer.season_color.3, "winter should be warmer");
+        assert!(winter.season_weight > summer.season_weight);
+        assert_ne!(
+            resolve_led_color("12:00", january, Some(&week), None, &config).unwrap(),
+            resolve_led_color("12:00", july, Some(&week), None, &config).unwrap(),
+        );
+
+        // Without auto_season the schedule's season color is used on any date
+        config.led.auto_season = Some(false);
+        assert_eq!(NaturalLightInputs::resolve(january, Some(&week), None, &config).season_color, (150, 150, 128, 128, 128));
+    }
+
+    #[test]
+    fn test_seasonal_color_wraps_around_the_new_year() {
+        let keyframes = crate::modules::config::default_season_keyframes();
+        let (winter, _) = seasonal_color(&keyframes, 15);
+        assert_eq!(winter, (255, 170, 100, 255, 40));
+        let (late_december, _) = seasonal_color(&keyframes, 360);
+        let (early_january, _) = seasonal_color(&keyframes, 5);
+        assert!(late_december.3 > 220 && early_january.3 > 220);
+        assert_eq!(seasonal_color(&keyframes[..1], 200), ((255, 170, 100, 255, 40), 0.4));
+    }
+
+    #[test]
+    fn test_cct_range_endpoints_use_one_white_channel() {
+        let range = (2700, 6500);
+        assert_eq!(cct_to_rgbww(2700, 100, range), RGBWW { r: 0, g: 0, b: 0, ww: 255, cw: 0 });
+        assert_eq!(cct_to_rgbww(6500, 100, range), RGBWW { r: 0, g: 0, b: 0, ww: 0, cw: 255 });
+    }
+
+    #[test]
+    fn test_cct_out_of_range_is_clamped() {
+        let range = (2700, 6500);
+        assert_eq!(cct_to_rgbww(1000, 50, range), cct_to_rgbww(2700, 50, range));
+        assert_eq!(cct_to_rgbww(10000, 50, range), cct_to_rgbww(6500, 50, range));
+        assert_eq!(cct_to_rgbww(4000, 0, range), RGBWW::off());
+    }
+
+    #[test]
+    fn test_estimate_cct_round_trips() {
+        let range = (2700, 6500);
+        for kelvin in [2700, 3500, 4000, 5000, 6500] {
+            let estimate = estimate_cct(cct_to_rgbww(kelvin, 100, range), range).unwrap();
+            assert!((estimate as i32 - kelvin as i32).abs() <= 30, "{}K estimated as {}K", kelvin, estimate);
+        }
+        assert_eq!(estimate_cct(RGBWW { r: 1, g: 0, b: 0, ww: 100, cw: 100 }, range), None);
+        assert_eq!(estimate_cct(RGBWW::off(), range), None);
+    }
+
+    #[test]
+    fn test_fade_plan_hits_endpoints_and_steps_evenly() {
+        let start = RGBWW { r: 255, g: 0, b: 100, ww: 10, cw: 0 };
+        let end = RGBWW { r: 0, g: 255, b: 100, ww: 0, cw: 3 };
+        let plan = FadePlan::new(start, end, 5);
+
+        assert_eq!(plan.color_at(5), end);
+        assert_eq!(plan.color_at(1), RGBWW { r: 204, g: 51, b: 100, ww: 8, cw: 0 });
+        // Float rounding lands just below 51 and 2, as it always has
+        assert_eq!(plan.color_at(4), RGBWW { r: 50, g: 204, b: 100, ww: 1, cw: 2 });
+    }
+
+    #[tokio::test]
+    async fn test_lock_stays_available_during_fade() {
+        let strip = RecordingStrip::new();
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let controller = Arc::new(Mutex::new(LEDController::with_driver(relays, strip.clone())));
+        let target = RGBWW { r: 200, g: 100, b: 50, ww: 0, cw: 0 };
+
+        let fade = tokio::spawn({
+            let controller = Arc::clone(&controller);
+            async move {
+                LEDController::fade_to(&controller, target, Duration::from_millis(400), 20, RelayReason::Manual)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        });
+
+        // A handler polling the controller gets it between steps; if the fade
+        // held the lock throughout, it could only ever see the final color
+        let mut seen_midway = false;
+        while !fade.is_finished() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            if let Ok(led) = controller.try_lock() {
+                let color = led.current_color();
+                seen_midway |= color != RGBWW::off() && color != target;
+            }
+        }
+
+        fade.await.unwrap().unwrap();
+        assert!(seen_midway, "the controller was locked for the whole fade");
+        assert_eq!(controller.lock().await.current_color(), target);
+        assert_eq!(strip.shown().len(), 20);
+        assert_eq!(strip.shown().last(), Some(&target));
+    }
+
+    #[tokio::test]
+    async fn test_manual_color_sent_mid_fade_survives() {
+        let strip = RecordingStrip::new();
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let controller = Arc::new(Mutex::new(LEDController::with_driver(relays, strip.clone())));
+        let target = RGBWW { r: 200, g: 100, b: 50, ww: 0, cw: 0 };
+        let manual = RGBWW { r: 0, g: 0, b: 255, ww: 0, cw: 0 };
+
+        let fade = tokio::spawn({
+            let controller = Arc::clone(&controller);
+            async move {
+                LEDController::fade_to(&controller, target, Duration::from_millis(400), 20, RelayReason::Schedule)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        controller.lock().await.set_rgbww(0, 0, 255, 0, 0).await.unwrap();
+        fade.await.unwrap().unwrap();
+
+        assert_eq!(controller.lock().await.current_color(), manual);
+        assert_eq!(strip.shown().last(), Some(&manual));
+        assert!(strip.shown().len() < 20, "the fade kept writing after the manual color");
+
+        // A fade out stopped by a manual color doesn't power the strip off afterwards
+        let fade_out = tokio::spawn({
+            let controller = Arc::clone(&controller);
+            async move {
+                LEDController::fade_out(&controller, Duration::from_millis(400), 20, RelayReason::Schedule)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        controller.lock().await.set_color(manual, RelayReason::Manual).await.unwrap();
+        fade_out.await.unwrap().unwrap();
+
+        let led = controller.lock().await;
+        assert!(led.is_powered_on());
+        assert_eq!(led.current_color(), manual);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_color_is_not_rewritten() {
+        let mut written = WrittenColor::default();
+        let mut shows = 0;
+        let color = RGBWW { r: 255, g: 180, b: 100, ww: 200, cw: 50 };
+
+        for force in [false, false, true] {
+            written.write(color, force, |_| { shows += 1; async { Ok(()) } }).await.unwrap();
+        }
+        assert_eq!(shows, 2, "the repeated update must not call show, the forced one must");
+
+        written.forget();
+        assert!(written.write(color, false, |_| async { Ok(()) }).await.unwrap());
+
+        let failed = written.write(RGBWW::off(), false, |_| async { Err("spi".into()) }).await;
+        assert!(failed.is_err());
+        assert!(written.write(RGBWW::off(), false, |_| async { Ok(()) }).await.unwrap());
+    }
+
+    #[test]
+    fn test_alert_latches_until_acknowledged() {
+        let mut latch = AlertLatch::default();
+        assert_eq!(latch.observe(&[]), None);
+        assert_eq!(latch.observe(&[LedAlert::Overheat]), Some(LedAlert::Overheat));
+
+        // Raised once, and a second condition doesn't replace the active alert
+        assert_eq!(latch.observe(&[LedAlert::Overheat]), None);
+        assert_eq!(latch.observe(&[LedAlert::Overheat, LedAlert::SensorFault]), None);
+        assert_eq!(latch.active(), Some(LedAlert::Overheat));
+
+        // Acknowledged conditions that persist stay quiet until they come back
+        assert_eq!(latch.acknowledge(), Some(LedAlert::Overheat));
+        assert_eq!(latch.observe(&[LedAlert::Overheat, LedAlert::SensorFault]), None);
+        assert_eq!(latch.active(), None);
+        assert_eq!(latch.observe(&[LedAlert::SensorFault]), None);
+        assert_eq!(latch.observe(&[LedAlert::Overheat, LedAlert::SensorFault]), Some(LedAlert::Overheat));
+        assert_eq!(latch.acknowledge(), Some(LedAlert::Overheat));
+        assert_eq!(latch.acknowledge(), None);
+    }
+
+    #[tokio::test]
+    async fn test_alert_pulses_over_schedule_until_acknowledged() {
+        let pool = storage::temp_db("led_alert").await;
+        let config = test_config();
+        let strip = RecordingStrip::new();
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let controller = Arc::new(Mutex::new(LEDController::with_driver(relays, strip.clone())));
+        let scheduled = RGBWW { r: 10, g: 200, b: 30, ww: 40, cw: 50 };
+        controller.lock().await.update_color(scheduled, RelayReason::Schedule).await.unwrap();
+
+        let alert = RGBWW { r: 255, g: 0, b: 0, ww: 0, cw: 0 };
+        LEDController::check_alerts(&controller, &[LedAlert::Overheat], alert).await.unwrap();
+        tokio::time::sleep(FRAME_INTERVAL * 3).await;
+
+        // The schedule update leaves the pulse alone
+        update_leds(&pool, &controller, &config, false).await.unwrap();
+        let pulsing = controller.lock().await.current_color();
+        assert_ne!(pulsing, scheduled);
+        assert!(pulsing.g == 0 && pulsing.b == 0 && pulsing.ww == 0 && pulsing.cw == 0, "{:?}", pulsing);
+        assert_eq!(strip.shown().last(), Some(&pulsing));
+
+        // Acknowledged, the next schedule write shows the scheduled color again
+        assert_eq!(controller.lock().await.acknowledge_alert(), Some(LedAlert::Overheat));
+        controller.lock().await.update_color(scheduled, RelayReason::Schedule).await.unwrap();
+        assert_eq!(controller.lock().await.current_color(), scheduled);
+        assert_eq!(strip.shown().last(), Some(&scheduled));
+    }
+
+    #[tokio::test]
+    async fn test_alert_not_latched_without_a_strip() {
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let controller = Arc::new(Mutex::new(LEDController::new(relays)));
+        controller.lock().await.set_wiring(LedWiring::BitBang { pin: 28 });
+
+        let alert = RGBWW { r: 255, g: 0, b: 0, ww: 0, cw: 0 };
+        assert!(LEDController::check_alerts(&controller, &[LedAlert::SensorFault], alert).await.is_err());
+        assert_eq!(controller.lock().await.alert(), None);
+        // Still present, so the next check tries again
+        assert!(LEDController::check_alerts(&controller, &[LedAlert::SensorFault], alert).await.is_err());
+        assert_eq!(LedAlert::SensorFault.reason(), RelayReason::SensorFault);
+    }
+
+    #[test]
+    fn test_power_state_follows_led_initial_state() {
+        let mut gpio = crate::modules::config::test_config().gpio;
+        gpio.initial_state = std::collections::HashMap::from([(RelayType::LED, true)]);
+        let relays = RelayController::unavailable_at_boot(&gpio, [RelayType::LED, RelayType::UV1]);
+        assert_eq!((relays.state(RelayType::LED), relays.state(RelayType::UV1)), (Some(true), Some(false)));
+
+        let controller = LEDController::new(Arc::new(Mutex::new(relays)));
+        assert!(controller.is_powered_on());
+        assert!(!LEDController::new(Arc::new(Mutex::new(RelayController::unavailable()))).is_powered_on());
+    }
+
+    #[test]
+    fn test_alert_pulse_peaks_at_alert_color() {
+        let alert = RGBWW { r: 255, g: 0, b: 0, ww: 0, cw: 0 };
+        let mut pulse = Breathing::new(alert, ALERT_PULSE_PERIOD);
+        let frames: Vec<RGBWW> = (0..30).map(|i| pulse.next_frame(FRAME_INTERVAL * i)).collect();
+
+        assert!(frames.contains(&alert));
+        assert!(frames.iter().any(|frame| frame.r < 50));
+        assert!(frames.iter().all(|frame| frame.g == 0 && frame.b == 0 && frame.ww == 0 && frame.cw == 0));
+    }
+
+    #[test]
+    fn test_fade_plan_matches_float_interpolation_exactly() {
+        // Every start/end channel pair, each channel offset so all five differ
+        for steps in [1, 7, 50] {
+            for a in 0..=255u8 {
+                for b in 0..=255u8 {
+                    let start = RGBWW { r: a, g: a.wrapping_add(1), b: a.wrapping_add(2), ww: a.wrapping_add(3), cw: a.wrapping_add(4) };
+                    let end = RGBWW { r: b, g: b.wrapping_add(5), b: b.wrapping_add(6), ww: b.wrapping_add(7), cw: b.wrapping_add(8) };
+                    let plan = FadePlan::new(start, end, steps);
+                    let from = LightPreset::new(start.r, start.g, start.b, start.ww, start.cw);
+                    let to = LightPreset::new(end.r, end.g, end.b, end.ww, end.cw);
+
+                    for step in 1..=steps {
+                        // The fade loop before precomputing
+                        let factor = step as f32 / steps as f32;
+                        assert_eq!(plan.color_at(step), from.interpolate(&to, factor).to_rgbww(), "{:?} -> {:?}, step {}/{}", start, end, step, steps);
+                    }
+                }
+            }
+        }
+    }
+}
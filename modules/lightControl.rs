@@ -1,27 +1,365 @@
-use crate::modules::config::LightControlConfig;
+use crate::modules::config::{GpioConfig, LightControlConfig, OverheatAction};
+use crate::modules::gpio::{boot_level, relay_level, relay_output, RelayEventLog, RelayStateLog, RelayType};
 
-use std::thread;
+use crate::modules::models::{OverheatEvent, OverheatStatus, RelayReason, Schedule};
+
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
-use chrono::Local;
+use chrono::{DateTime, Utc};
 use rppal::gpio::{Gpio, OutputPin};
-use rusqlite::{params, Connection, Result};
+use rppal::pwm::{Channel, Polarity, Pwm};
+use sqlx::SqlitePool;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use log::{info, warn};
+use log::{error, info, warn};
+use serde::Serialize;
+
+/// On/off states the schedule asks for at a given time, before overheat protection.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RelayStates {
+    pub uv1: bool,
+    pub uv2: bool,
+    pub heat: bool,
+    pub uv1_level: u8,  // UV intensity in percent while on, used by dimmable ballasts
+    pub uv2_level: u8,
+    pub heat_target: Option<f32>, // Basking setpoint in °C, used by a dimmable heat element
+}
+
+/// Works out how a UV output is driven for a scheduled state and level.
+///
+/// A level of 0 switches the ballast off. With a dimmable ballast the level
+/// becomes the PWM duty cycle (0.0-1.0, 0.0 while off); without one any other
+/// level is plain relay on.
+///
+/// # Returns
+///
+/// The relay state, and the duty cycle if the output is dimmable
+pub fn uv_output(state: bool, level: u8, dimmable: bool) -> (bool, Option<f64>) {
+    let on = state && level > 0;
+    let duty = dimmable.then(|| if on { level.min(100) as f64 / 100.0 } else { 0.0 });
+    (on, duty)
+}
+
+/// A change to an overheat event that still has to be written to the database.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverheatEventChange {
+    /// Protection tripped, a new event row should be inserted
+    Started(OverheatEvent),
+    /// Protection cleared, the event row should be completed
+    Cleared(OverheatEvent),
+}
+
+/// Tracks the currently active overheat event and its peak temperature.
+///
+/// The tracker is purely in-memory; the changes it queues are drained and
+/// persisted by `update_lights`, so the light controller itself never
+/// needs database access.
+#[derive(Debug, Default)]
+pub struct OverheatTracker {
+    active: Option<OverheatEvent>,
+    pending: Vec<OverheatEventChange>,
+}
+
+impl OverheatTracker {
+    /// Records that protection tripped. Starts a new event unless one is already active.
+    pub fn trip(&mut self, zone: &str, temp: f32, now: DateTime<Utc>) {
+        match self.active.as_mut() {
+            Some(event) => event.peak_temp = event.peak_temp.max(temp),
+            None => {
+                let event = OverheatEvent {
+                    started_at: now,
+                    ended_at: None,
+                    peak_temp: temp,
+                    duration_seconds: None,
+                    zone: zone.to_string(),
+                };
+                self.pending.push(OverheatEventChange::Started(event.clone()));
+                self.active = Some(event);
+            }
+        }
+    }
+
+    /// Feeds a temperature reading of `zone` into the active event's peak,
+    /// if that zone tripped it
+    pub fn observe(&mut self, zone: &str, temp: f32) {
+        if let Some(event) = self.active.as_mut().filter(|event| event.zone == zone) {
+            event.peak_temp = event.peak_temp.max(temp);
+        }
+    }
+
+    /// Records that protection cleared, completing the active event
+    pub fn clear(&mut self, now: DateTime<Utc>) {
+        if let Some(mut event) = self.active.take() {
+            event.ended_at = Some(now);
+            event.duration_seconds = Some((now - event.started_at).num_seconds());
+            self.pending.push(OverheatEventChange::Cleared(event));
+        }
+    }
+
+    /// Takes all changes queued since the last call
+    pub fn drain(&mut self) -> Vec<OverheatEventChange> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Rolling window of temperature readings used to catch runaway heating
+/// before the absolute overheat threshold is reached.
+#[derive(Debug)]
+pub struct TemperatureTrend {
+    window: Duration,
+    samples: VecDeque<(Instant, f32)>,
+}
+
+impl TemperatureTrend {
+    /// Creates an empty trend keeping readings from the last `window`
+    pub fn new(window: Duration) -> Self {
+        Self { window, samples: VecDeque::new() }
+    }
+
+    /// Adds a reading and drops those that fell out of the window
+    pub fn push(&mut self, at: Instant, temp: f32) {
+        self.samples.push_back((at, temp));
+        while let Some(&(oldest, _)) = self.samples.front() {
+            if at.saturating_duration_since(oldest) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Rate of change over the window in °C per minute.
+    ///
+    /// Uses a least-squares fit so a single noisy reading doesn't dominate.
+    ///
+    /// # Returns
+    ///
+    /// The slope, or None with fewer than 3 readings or no time between them
+    pub fn rate_per_minute(&self) -> Option<f32> {
+        if self.samples.len() < 3 {
+            return None;
+        }
+
+        let first = self.samples.front()?.0;
+        let points: Vec<(f32, f32)> = self.samples.iter()
+            .map(|&(at, temp)| (at.duration_since(first).as_secs_f32() / 60.0, temp))
+            .collect();
+        let n = points.len() as f32;
+        let mean_t = points.iter().map(|p| p.0).sum::<f32>() / n;
+        let mean_temp = points.iter().map(|p| p.1).sum::<f32>() / n;
+
+        let covariance: f32 = points.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_temp)).sum();
+        let variance: f32 = points.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+        if variance == 0.0 {
+            return None;
+        }
+        Some(covariance / variance)
+    }
+}
+
+/// Safety backstop that cuts the heat lamp once it has been on without a break
+/// for longer than allowed, independent of the temperature reading. This catches
+/// a sensor stuck reading low, which never trips the overheat path.
+///
+/// Once tripped the lamp stays off until `resume` is called.
+#[derive(Debug, Default)]
+pub struct HeatWatchdog {
+    max_on: Option<Duration>,   // None disables the watchdog
+    on_since: Option<Instant>,  // Start of the current uninterrupted on-period
+    tripped: bool,
+    unsaved_trip: bool,         // Tripped since `take_trip` was last called
+}
+
+impl HeatWatchdog {
+    pub fn new(max_on: Option<Duration>) -> Self {
+        Self { max_on, ..Self::default() }
+    }
+
+    /// Records the heat lamp state at `now`.
+    ///
+    /// # Returns
+    ///
+    /// True if the lamp has now been on for longer than allowed and the watchdog tripped
+    pub fn observe(&mut self, on: bool, now: Instant) -> bool {
+        if !on || self.tripped {
+            self.on_since = None;
+            return false;
+        }
+        let since = *self.on_since.get_or_insert(now);
+        match self.max_on {
+            Some(max_on) if now.saturating_duration_since(since) > max_on => {
+                self.tripped = true;
+                self.unsaved_trip = true;
+                self.on_since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the watchdog has cut the heat and is waiting for a resume
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// How long the lamp has been on without a break at `now`, None while off
+    pub fn on_time(&self, now: Instant) -> Option<Duration> {
+        self.on_since.map(|since| now.saturating_duration_since(since))
+    }
+
+    /// Re-enables the heat after a trip.
+    ///
+    /// # Returns
+    ///
+    /// True if the watchdog had tripped
+    pub fn resume(&mut self) -> bool {
+        self.unsaved_trip = false;
+        std::mem::replace(&mut self.tripped, false)
+    }
+
+    /// Trips the watchdog without an on-period, e.g. to restore a trip
+    /// persisted before a restart
+    pub fn trip(&mut self) {
+        self.tripped = true;
+        self.on_since = None;
+    }
+
+    /// Whether the watchdog tripped since this was last called, so the trip
+    /// can be persisted once
+    pub fn take_trip(&mut self) -> bool {
+        std::mem::replace(&mut self.unsaved_trip, false)
+    }
+}
+
+/// What overheat protection makes of one probe read, see `StartupGrace`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeRead {
+    /// A real reading
+    Fresh(f32),
+    /// A failed read during the grace, waited out
+    Pending,
+    /// A failed read after the grace: the last reading no longer counts and
+    /// the heat is held off until the probe reads again
+    Lost,
+}
+
+/// Lets a probe warm up after start before a failed read counts against it.
+///
+/// Protection only ever sees real readings, never a placeholder. A real
+/// reading is passed on at once, so protection engages on the first one over
+/// the threshold. A failed read is waited out during the grace; after it the
+/// probe counts as lost.
+#[derive(Debug)]
+pub struct StartupGrace {
+    until: Instant,
+}
+
+impl StartupGrace {
+    pub fn new(started: Instant, grace: Duration) -> Self {
+        Self { until: started + grace }
+    }
+
+    /// What protection makes of a read at `now`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reading` - The reading, None if the read failed
+    /// * `now` - When it was read
+    pub fn reading(&self, reading: Option<f32>, now: Instant) -> ProbeRead {
+        match reading {
+            Some(temp) => ProbeRead::Fresh(temp),
+            None if now < self.until => ProbeRead::Pending,
+            None => ProbeRead::Lost,
+        }
+    }
+}
+
+/// PID loop turning the distance to a basking setpoint into a heat duty cycle.
+///
+/// Gains are in percent duty: `kp` per °C of error, `ki` per °C·minute of
+/// accumulated error and `kd` per °C/minute of temperature change. The
+/// derivative acts on the measurement, so a setpoint change doesn't kick the
+/// output, and the integral only accumulates while the output isn't saturated.
+#[derive(Debug)]
+pub struct PidController {
+    kp: f32,
+    ki: f32,
+    kd: f32,
+    integral: f32,                 // Accumulated error in °C·minutes
+    last: Option<(Instant, f32)>,  // Previous reading, for the time step and derivative
+}
+
+impl PidController {
+    pub fn new((kp, ki, kd): (f32, f32, f32)) -> Self {
+        Self { kp, ki, kd, integral: 0.0, last: None }
+    }
+
+    /// Feeds a temperature reading taken at `now`.
+    ///
+    /// # Returns
+    ///
+    /// The duty cycle in percent (0-100)
+    pub fn update(&mut self, setpoint: f32, temp: f32, now: Instant) -> f32 {
+        let error = setpoint - temp;
+        let (minutes, slope) = match self.last {
+            Some((at, previous)) => {
+                let minutes = now.saturating_duration_since(at).as_secs_f32() / 60.0;
+                let slope = if minutes > 0.0 { (temp - previous) / minutes } else { 0.0 };
+                (minutes, slope)
+            }
+            None => (0.0, 0.0),
+        };
+        self.last = Some((now, temp));
+
+        let integral = self.integral + error * minutes;
+        let output = self.kp * error + self.ki * integral - self.kd * slope;
+        let clamped = output.clamp(0.0, 100.0);
+        // Keep integrating only while that doesn't drive the output further into saturation
+        if output == clamped || (output > 100.0) == (error < 0.0) {
+            self.integral = integral;
+        }
+        clamped
+    }
+
+    /// Forgets the accumulated error, e.g. when the heat window ends
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last = None;
+    }
+}
 
 /// Structure for the light controller with overheat protection.
 ///
 /// This struct manages the UV lights and heat lamp for the terrarium,
 /// including safety features that prevent dangerous overheating conditions.
+/// The relay pins are None on a controller created by `unavailable`.
 pub struct LightController {
-    uv1: OutputPin,
-    uv2: OutputPin,
-    heat: OutputPin,
+    uv1: Option<OutputPin>,
+    uv2: Option<OutputPin>,
+    heat: Option<OutputPin>,
+    fan: Option<OutputPin>,
+    uv1_dim: Option<Pwm>,       // PWM dim signal of a dimmable UV1 ballast
+    uv2_dim: Option<Pwm>,
+    heat_dim: Option<Pwm>,      // PWM drive of a dimmable heat element
+    heat_pid: PidController,
+    heat_target: Option<f32>,   // Scheduled basking setpoint, None for plain on/off heat
+    active_low: bool,           // Relay board switches on when the pin is driven low
     overheat_temp: u8,
+    control_overheat_temp: Option<u8>,
     overheat_time: Duration,
+    overheat_action: OverheatAction,
     last_overheat: Option<Instant>,
     current_temp: f32,          // Current temperature from sensor
+    control_temp: Option<f32>,  // Latest control-zone temperature
     is_overheating: AtomicBool, // Atomic flag for thread-safe access
+    overheat_tracker: OverheatTracker,
+    trend: TemperatureTrend,
+    max_rise_per_minute: Option<f32>,
+    rising_too_fast: bool,      // Heat held off because the temperature climbs faster than allowed
+    heat_watchdog: HeatWatchdog,
+    has_reading: bool,          // Heat held off until the first temperature reading arrives
+    startup_grace: StartupGrace,
+    events: RelayStateLog,
 }
 
 //gpio logic with overheat protection
@@ -34,55 +372,145 @@ impl LightController {
     /// # Arguments
     ///
     /// * `config` - Configuration for the light controller containing
-    ///              pin assignments and safety thresholds
+    ///              safety thresholds and the overheat action
+    /// * `gpio_config` - GPIO configuration containing the relay pin assignments
     ///
     /// # Returns
     ///
     /// A Result containing either the new LightController or an error
-    pub fn new(config: LightControlConfig) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(config: LightControlConfig, gpio_config: &GpioConfig) -> Result<Self, Box<dyn std::error::Error>> {
         let gpio = Gpio::new()?;
+        let fan = match gpio_config.fan_relay {
+            Some(pin) => Some(relay_output(&gpio, pin, boot_level(gpio_config, RelayType::Fan))?),
+            None => None,
+        };
+        // Dim signals start at 0% so the ballasts and heat element come up dark
+        let dimmer = |channel: Option<u8>, frequency: f64| -> Result<Option<Pwm>, rppal::pwm::Error> {
+            channel.map(|channel| {
+                let channel = if channel == 0 { Channel::Pwm0 } else { Channel::Pwm1 };
+                Pwm::with_frequency(channel, frequency, 0.0, Polarity::Normal, true)
+            }).transpose()
+        };
         Ok(LightController {
-            uv1: gpio.get(config.uv_relay1)?.into_output(),
-            uv2: gpio.get(config.uv_relay2)?.into_output(),
-            heat: gpio.get(config.heat_relay)?.into_output(),
+            uv1: Some(relay_output(&gpio, gpio_config.uv_relay1, boot_level(gpio_config, RelayType::UV1))?),
+            uv2: Some(relay_output(&gpio, gpio_config.uv_relay2, boot_level(gpio_config, RelayType::UV2))?),
+            heat: Some(relay_output(&gpio, gpio_config.heat_relay, boot_level(gpio_config, RelayType::Heat))?),
+            fan,
+            uv1_dim: dimmer(gpio_config.uv1_pwm_channel, gpio_config.uv_pwm_frequency())?,
+            uv2_dim: dimmer(gpio_config.uv2_pwm_channel, gpio_config.uv_pwm_frequency())?,
+            heat_dim: dimmer(gpio_config.heat_pwm_channel, gpio_config.heat_pwm_frequency())?,
+            active_low: gpio_config.active_low,
+            events: RelayStateLog::at_boot(gpio_config, [RelayType::UV1, RelayType::UV2, RelayType::Heat].into_iter()
+                .chain(gpio_config.fan_relay.map(|_| RelayType::Fan))),
+            ..Self::unavailable(config)
+        })
+    }
+
+    /// A controller without relay pins, used when the GPIO couldn't be
+    /// initialized so the web interface and logs still come up.
+    ///
+    /// Overheat protection and the schedule run as usual and the relay
+    /// states they decide are tracked, but nothing is switched.
+    pub fn unavailable(config: LightControlConfig) -> Self {
+        LightController {
+            uv1: None,
+            uv2: None,
+            heat: None,
+            fan: None,
+            uv1_dim: None,
+            uv2_dim: None,
+            heat_dim: None,
+            heat_pid: PidController::new(config.heat_pid_gains()),
+            heat_target: None,
+            active_low: false,
             overheat_temp: config.overheat_temp,
-            overheat_time: Duration::from_secs(config.overheat_cooldown_seconds as u64),
+            control_overheat_temp: config.control_overheat_temp,
+            overheat_time: Duration::from_secs(config.overheat_time),
+            overheat_action: config.overheat_action,
             last_overheat: None,
             current_temp: 0.0,
+            control_temp: None,
             is_overheating: AtomicBool::new(false),
-        })
+            overheat_tracker: OverheatTracker::default(),
+            trend: TemperatureTrend::new(config.trend_window()),
+            max_rise_per_minute: config.max_rise_per_minute,
+            rising_too_fast: false,
+            heat_watchdog: HeatWatchdog::new(config.max_heat_on_time()),
+            has_reading: false,
+            startup_grace: StartupGrace::new(Instant::now(), config.startup_grace()),
+            events: RelayStateLog::default(),
+        }
+    }
+
+    /// Whether the relay pins could be initialized, see `unavailable`
+    pub fn is_available(&self) -> bool {
+        self.heat.is_some()
+    }
+
+    /// Logs every relay state change from now on to `log`
+    pub fn set_event_log(&mut self, log: RelayEventLog) {
+        self.events.set_log(log);
     }
 
     /// Controls the first UV light.
     ///
+    /// While an overheat is active and the overheat action sheds UV,
+    /// the light is held off regardless of `state`.
+    ///
     /// # Arguments
     ///
     /// * `state` - True to turn on, False to turn off
-    pub fn set_uv1(&mut self, state: bool) {
-        if state {
-            self.uv1.set_high();
-        } else {
-            self.uv1.set_low();
-        }
+    /// * `level` - Intensity in percent, see `uv_output`
+    pub fn set_uv1(&mut self, state: bool, level: u8) {
+        self.set_uv(RelayType::UV1, state, level);
     }
 
     /// Controls the second UV light.
     ///
+    /// While an overheat is active and the overheat action sheds UV,
+    /// the light is held off regardless of `state`.
+    ///
     /// # Arguments
     ///
     /// * `state` - True to turn on, False to turn off
-    pub fn set_uv2(&mut self, state: bool) {
-        if state {
-            self.uv2.set_high();
-        } else {
-            self.uv2.set_low();
+    /// * `level` - Intensity in percent, see `uv_output`
+    pub fn set_uv2(&mut self, state: bool, level: u8) {
+        self.set_uv(RelayType::UV2, state, level);
+    }
+
+    /// Applies a scheduled UV state and level, holding the light off while UV is blocked
+    fn set_uv(&mut self, relay: RelayType, state: bool, level: u8) {
+        let blocked = state && self.uv_blocked();
+        let reason = if blocked { RelayReason::Overheat } else { RelayReason::Schedule };
+        let (on, duty) = uv_output(state && !blocked, level, self.uv_dimmer(relay).is_some());
+        self.write_relay(relay, on, reason);
+        if let Some(duty) = duty {
+            self.set_uv_dim(relay, duty);
+        }
+    }
+
+    /// The PWM dim signal of a UV relay, if its ballast is dimmable
+    fn uv_dimmer(&self, relay: RelayType) -> Option<&Pwm> {
+        match relay {
+            RelayType::UV1 => self.uv1_dim.as_ref(),
+            RelayType::UV2 => self.uv2_dim.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Sets the duty cycle of a UV dim signal, if there is one
+    fn set_uv_dim(&self, relay: RelayType, duty: f64) {
+        if let Some(pwm) = self.uv_dimmer(relay) {
+            if let Err(e) = pwm.set_duty_cycle(duty) {
+                warn!("Failed to dim {} to {:.0}%: {}", relay.as_str(), duty * 100.0, e);
+            }
         }
     }
 
     /// Safely controls the heat lamp with overheat protection.
     ///
     /// This method will:
-    /// 1. Check if the system is in an overheat condition
+    /// 1. Check if the basking or control zone is in an overheat condition
     /// 2. If overheating, it will block attempts to turn on the heat lamp
     /// 3. Update the overheat state based on current temperature and cooldown
     ///
@@ -91,18 +519,19 @@ impl LightController {
     /// * `state` - True to turn on, False to turn off
     pub fn control_heat(&mut self, state: bool) {
         // Check for overheat condition
-        if self.current_temp >= self.overheat_temp as f32 {
+        if let Some((zone, temp, threshold)) = self.overheated_zone() {
             // Set overheat flag
             self.is_overheating.store(true, Ordering::SeqCst);
             
-            // Turn off heat
-            self.set_heat(false);
+            // Turn off heat and any additional loads
+            let shed = self.shed_loads();
             
             // Record overheat time
             self.last_overheat = Some(Instant::now());
+            self.overheat_tracker.trip(zone, temp, Utc::now());
             
-            warn!("OVERHEAT PROTECTION ACTIVATED: Temperature ({:.1}°C) exceeds threshold ({} °C)",
-                  self.current_temp, self.overheat_temp);
+            warn!("OVERHEAT PROTECTION ACTIVATED: {} temperature ({:.1}°C) exceeds threshold ({} °C). Loads shed: {}",
+                  zone, temp, threshold, shed.join(", "));
                   
             return;
         }
@@ -113,34 +542,218 @@ impl LightController {
                 // Cooldown period is over
                 self.last_overheat = None;
                 self.is_overheating.store(false, Ordering::SeqCst);
-                self.set_heat(state);
+                self.overheat_tracker.clear(Utc::now());
+                self.set_fan(false, RelayReason::Overheat);
+                self.set_scheduled_heat(state);
                 
                 if state {
                     info!("Overheat cooldown period complete. Heat enabled.");
                 }
             } else {
                 // Still in cooldown period
-                self.set_heat(false);
+                self.set_heat(false, RelayReason::Overheat);
             }
         } else {
-            // Normal operation
-            self.set_heat(state);
+            // Normal operation, unless the temperature is rising too fast
+            self.set_scheduled_heat(state);
+        }
+    }
+
+    /// The first zone at or above its own overheat threshold, see `overheated_zone`
+    fn overheated_zone(&self) -> Option<(&'static str, f32, f32)> {
+        let control = self.control_overheat_temp
+            .zip(self.control_temp)
+            .map(|(threshold, temp)| (temp, threshold as f32));
+        overheated_zone((self.current_temp, self.overheat_temp as f32), control)
+    }
+
+    /// Applies a scheduled heat state, holding the lamp off while the temperature
+    /// rises too fast or before there is any reading for overheat protection to check
+    fn set_scheduled_heat(&mut self, state: bool) {
+        let held = state && (self.rising_too_fast || !self.has_reading);
+        let reason = if held { RelayReason::Overheat } else { RelayReason::Schedule };
+        self.set_heat(state && !held, reason);
+    }
+    
+    /// Cuts the loads selected by the configured overheat action.
+    ///
+    /// # Returns
+    ///
+    /// The names of the loads that were shed, for logging
+    fn shed_loads(&mut self) -> Vec<&'static str> {
+        let mut shed = vec!["heat"];
+        self.set_heat(false, RelayReason::Overheat);
+        
+        if self.overheat_action.sheds_uv() {
+            self.write_relay(RelayType::UV1, false, RelayReason::Overheat);
+            self.write_relay(RelayType::UV2, false, RelayReason::Overheat);
+            self.set_uv_dim(RelayType::UV1, 0.0);
+            self.set_uv_dim(RelayType::UV2, 0.0);
+            shed.push("UV1");
+            shed.push("UV2");
         }
+        
+        if self.overheat_action.runs_fan() && self.fan.is_some() {
+            self.set_fan(true, RelayReason::Overheat);
+            shed.push("fan on");
+        }
+        
+        shed
+    }
+    
+    /// Whether UV output is currently blocked by overheat protection
+    fn uv_blocked(&self) -> bool {
+        self.overheat_action.sheds_uv() && self.is_overheating.load(Ordering::SeqCst)
+    }
+    
+    /// Internal function to control the cooling fan relay, if one is configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - True to turn on, False to turn off
+    /// * `reason` - Why the fan is switched, for the relay event log
+    fn set_fan(&mut self, state: bool, reason: RelayReason) {
+        self.write_relay(RelayType::Fan, state, reason);
     }
     
     /// Internal function to directly control the heat lamp relay.
     ///
+    /// With a dimmable heat element and a heat target the PID loop sets the
+    /// duty cycle while `state` is on, and the relay opens whenever it asks
+    /// for 0%. The lamp is held off once the heat watchdog has tripped, until
+    /// `resume_heat` is called.
+    ///
     /// # Arguments
     ///
     /// * `state` - True to turn on, False to turn off
-    fn set_heat(&mut self, state: bool) {
-        if state {
-            self.heat.set_high();
-        } else {
-            self.heat.set_low();
+    /// * `reason` - Why the lamp is switched, for the relay event log
+    fn set_heat(&mut self, state: bool, reason: RelayReason) {
+        let duty = self.heat_duty(state);
+        let state = state && duty.map_or(true, |duty| duty > 0.0);
+        if self.heat_watchdog.observe(state, Instant::now()) {
+            error!("HEAT WATCHDOG: heat lamp on for more than {} minutes without a break, cut until resumed",
+                   self.heat_watchdog.max_on.unwrap_or_default().as_secs() / 60);
+        }
+        let cut = state && self.heat_watchdog.is_tripped();
+        let reason = if cut { RelayReason::Overheat } else { reason };
+        let on = state && !cut;
+        self.write_relay(RelayType::Heat, on, reason);
+        if let (Some(pwm), Some(duty)) = (self.heat_dim.as_ref(), duty) {
+            let duty = if on { duty } else { 0.0 };
+            if let Err(e) = pwm.set_duty_cycle(duty) {
+                warn!("Failed to drive heat at {:.0}%: {}", duty * 100.0, e);
+            }
+        }
+    }
+
+    /// Duty cycle (0.0-1.0) of a dimmable heat element for a heat state,
+    /// None without one. Full power when there is no heat target.
+    fn heat_duty(&mut self, state: bool) -> Option<f64> {
+        self.heat_dim.as_ref()?;
+        if !state {
+            self.heat_pid.reset();
+            return Some(0.0);
+        }
+        let duty = match self.heat_target {
+            Some(target) => self.heat_pid.update(target, self.current_temp, Instant::now()),
+            None => 100.0,
+        };
+        Some(duty as f64 / 100.0)
+    }
+
+    /// Sets the basking temperature the heat PID loop holds, None for plain
+    /// on/off heat. Ignored without `gpio.heat_pwm_channel`.
+    pub fn set_heat_target(&mut self, target: Option<f32>) {
+        self.heat_target = target;
+    }
+
+    /// Whether the heat watchdog has cut the lamp and is waiting for `resume_heat`
+    pub fn heat_watchdog_tripped(&self) -> bool {
+        self.heat_watchdog.is_tripped()
+    }
+
+    /// Keeps the heat cut after a restart while a trip persisted before it
+    /// hasn't been resumed, see `storage::HEAT_WATCHDOG_TRIPPED_KEY`
+    pub fn restore_heat_watchdog_trip(&mut self) {
+        self.heat_watchdog.trip();
+        self.set_heat(false, RelayReason::Overheat);
+    }
+
+    /// Re-enables the heat lamp after the heat watchdog cut it. The lamp comes
+    /// back with the next scheduled update.
+    ///
+    /// # Returns
+    ///
+    /// True if the watchdog had tripped
+    pub fn resume_heat(&mut self) -> bool {
+        let resumed = self.heat_watchdog.resume();
+        if resumed {
+            info!("Heat watchdog resumed, heat enabled.");
         }
+        resumed
+    }
+
+    /// Writes a relay pin and logs the change. Relays this controller
+    /// doesn't own, or an unconfigured fan, are ignored.
+    fn write_relay(&mut self, relay: RelayType, state: bool, reason: RelayReason) {
+        let level = relay_level(state, self.active_low);
+        let pin = match relay {
+            RelayType::UV1 => self.uv1.as_mut(),
+            RelayType::UV2 => self.uv2.as_mut(),
+            RelayType::Heat => self.heat.as_mut(),
+            RelayType::Fan => match self.fan.as_mut() {
+                Some(fan) => Some(fan),
+                None => return,
+            },
+            RelayType::LED | RelayType::Aux => return,
+        };
+        // Without pins (see `unavailable`) the state is only tracked
+        if let Some(pin) = pin {
+            pin.write(level);
+        }
+        self.events.record(relay, state, reason);
+    }
+
+    /// Whether the heat lamp was last switched on, None before its first switch
+    pub fn heat_on(&self) -> Option<bool> {
+        self.events.state(RelayType::Heat)
     }
     
+    /// Passes a cycle's probe readings to overheat protection, see `StartupGrace`.
+    ///
+    /// # Arguments
+    ///
+    /// * `basking` - The basking temperature, None if the read failed
+    /// * `control` - The control-zone temperature, None if the read failed
+    pub fn update_readings(&mut self, basking: Option<f32>, control: Option<f32>) {
+        let now = Instant::now();
+        match self.startup_grace.reading(basking, now) {
+            ProbeRead::Fresh(temp) => self.update_temperature(temp),
+            ProbeRead::Lost => self.lose_temperature(),
+            ProbeRead::Pending => {}
+        }
+        match self.startup_grace.reading(control, now) {
+            ProbeRead::Fresh(temp) => self.update_control_temperature(temp),
+            ProbeRead::Lost => self.control_temp = None,
+            ProbeRead::Pending => {}
+        }
+    }
+
+    /// Drops the basking reading after a failed read, see `ProbeRead::Lost`.
+    ///
+    /// As before the first reading the heat is held off, so neither the trend
+    /// nor the PID loop run on a stale or made-up temperature.
+    fn lose_temperature(&mut self) {
+        if self.has_reading {
+            warn!("Basking probe read failed, heat held off until it reads again");
+        }
+        self.has_reading = false;
+        self.heat_pid.reset();
+        if self.heat_on() == Some(true) {
+            self.set_heat(false, RelayReason::Overheat);
+        }
+    }
+
     /// Updates the current temperature reading and checks for overheat conditions.
     ///
     /// This method is called periodically with new temperature readings and
@@ -151,12 +764,43 @@ impl LightController {
     /// * `temp` - The current temperature from the sensor
     pub fn update_temperature(&mut self, temp: f32) {
         self.current_temp = temp;
+        self.has_reading = true;
+        self.overheat_tracker.observe("basking", temp);
+        self.trend.push(Instant::now(), temp);
+        
+        // Cut heat early if the temperature is climbing faster than allowed
+        let rate = self.trend.rate_per_minute();
+        let runaway = match (self.max_rise_per_minute, rate) {
+            (Some(limit), Some(rate)) => rate > limit,
+            _ => false,
+        };
+        if runaway && !self.rising_too_fast {
+            warn!("Temperature rising at {:.2}°C/min (limit {:.2}°C/min), cutting heat early at {:.1}°C",
+                  rate.unwrap_or(0.0), self.max_rise_per_minute.unwrap_or(0.0), temp);
+            self.set_heat(false, RelayReason::Overheat);
+        }
+        self.rising_too_fast = runaway;
         
         // If temperature is too high, trigger overheat protection
-        if temp >= self.overheat_temp as f32 {
-            if !self.is_overheating.load(Ordering::SeqCst) {
-                self.control_heat(false); // This will activate overheat protection
-            }
+        self.check_overheat();
+    }
+
+    /// Updates the control-zone temperature and checks it against its own
+    /// overheat threshold, if one is configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `temp` - The current control-zone (ambient) temperature
+    pub fn update_control_temperature(&mut self, temp: f32) {
+        self.control_temp = Some(temp);
+        self.overheat_tracker.observe("control", temp);
+        self.check_overheat();
+    }
+
+    /// Activates overheat protection as soon as either zone is too hot
+    fn check_overheat(&mut self) {
+        if self.overheated_zone().is_some() && !self.is_overheating.load(Ordering::SeqCst) {
+            self.control_heat(false); // This will activate overheat protection
         }
     }
     
@@ -169,6 +813,13 @@ impl LightController {
         self.is_overheating.load(Ordering::SeqCst)
     }
     
+    /// Whether a temperature reading has arrived, i.e. overheat protection is armed.
+    ///
+    /// Until then the heat lamp is held off.
+    pub fn has_temperature(&self) -> bool {
+        self.has_reading
+    }
+    
     /// Gets the current temperature reading.
     ///
     /// # Returns
@@ -178,34 +829,89 @@ impl LightController {
         self.current_temp
     }
     
+    /// Gets the current temperature trend.
+    ///
+    /// # Returns
+    ///
+    /// The rate of change in °C per minute, or None until enough readings are collected
+    pub fn temperature_trend(&self) -> Option<f32> {
+        self.trend.rate_per_minute()
+    }
+    
+    /// Takes the overheat event changes that still need to be persisted.
+    ///
+    /// # Returns
+    ///
+    /// The queued changes, oldest first
+    pub fn take_overheat_changes(&mut self) -> Vec<OverheatEventChange> {
+        self.overheat_tracker.drain()
+    }
+    
     /// Gets the remaining time in the overheat cooldown period.
     ///
     /// # Returns
     ///
     /// Some(seconds) if in cooldown, None if not in cooldown
     pub fn get_overheat_cooldown_remaining(&self) -> Option<u64> {
-        self.last_overheat.map(|time| {
-            let elapsed = time.elapsed();
-            if elapsed >= self.overheat_time {
-                0
-            } else {
-                (self.overheat_time.as_secs() - elapsed.as_secs()).min(self.overheat_time.as_secs())
-            }
-        })
+        self.last_overheat.map(|time| cooldown_remaining(time.elapsed(), self.overheat_time))
+    }
+    
+    /// Gets a snapshot of the overheat protection.
+    ///
+    /// # Returns
+    ///
+    /// The overheat state, temperatures and the running cooldown, if any
+    pub fn overheat_status(&self) -> OverheatStatus {
+        let now = Utc::now();
+        OverheatStatus {
+            overheating: self.is_overheating(),
+            current_temp: self.current_temp,
+            threshold: self.overheat_temp as f32,
+            control_temp: self.control_temp,
+            control_threshold: self.control_overheat_temp.map(f32::from),
+            cooldown_remaining_seconds: self.get_overheat_cooldown_remaining().filter(|&seconds| seconds > 0),
+            heat_on_seconds: self.heat_watchdog.on_time(Instant::now()).map(|on| on.as_secs()),
+            heat_watchdog_tripped: self.heat_watchdog.is_tripped(),
+            last_overheat: self.last_overheat.map(|time| {
+                now - chrono::Duration::from_std(time.elapsed()).unwrap_or_else(|_| chrono::Duration::zero())
+            }),
+        }
     }
 }
 
+/// Picks the zone overheat protection trips on, checking the basking zone first.
+///
+/// # Arguments
+///
+/// * `basking` - Basking temperature and its threshold in °C
+/// * `control` - Control-zone temperature and threshold, None while unconfigured or unread
+///
+/// # Returns
+///
+/// The zone name with its temperature and threshold, None if neither zone is too hot
+fn overheated_zone(basking: (f32, f32), control: Option<(f32, f32)>) -> Option<(&'static str, f32, f32)> {
+    std::iter::once(("basking", basking))
+        .chain(control.map(|zone| ("control", zone)))
+        .find(|&(_, (temp, threshold))| temp >= threshold)
+        .map(|(zone, (temp, threshold))| (zone, temp, threshold))
+}
+
+/// Seconds left of a `cooldown` that started `elapsed` ago, 0 once it has passed
+fn cooldown_remaining(elapsed: Duration, cooldown: Duration) -> u64 {
+    cooldown.as_secs().saturating_sub(elapsed.as_secs())
+}
+
 /// Updates the light control system based on schedule and current settings.
 ///
 /// This function is called periodically to:
-/// 1. Check the current time against the configured schedule
-/// 2. Check the database for manual overrides
-/// 3. Update UV lights and heat lamp accordingly
-/// 4. Handle safety conditions like overheat protection
+/// 1. Check the current time against the current week's schedule
+/// 2. Update UV lights and heat lamp accordingly
+/// 3. Handle safety conditions like overheat protection
+/// 4. Persist any overheat events that started or cleared
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection for retrieving settings
+/// * `db_pool` - Database pool for retrieving the schedule
 /// * `light_controller` - Reference to the light controller
 /// * `config` - Application configuration containing schedules
 ///
@@ -213,50 +919,106 @@ impl LightController {
 ///
 /// A Result indicating success or an error
 pub async fn update_lights(
-    db: &rusqlite::Connection,
+    db_pool: &SqlitePool,
     light_controller: &Arc<tokio::sync::Mutex<LightController>>,
     config: &crate::modules::config::Config
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Get current time
-    let now = Local::now();
+    // Get current time in the schedule timezone
+    let now = config.main.schedule_now();
     let current_time = now.format("%H:%M").to_string();
     
     // Get current schedule from DB
-    let mut stmt = db.prepare("SELECT uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end FROM schedule WHERE ? BETWEEN week_start AND week_end")?;
-    let schedule = stmt.query_row(params![now.format("%Y-%m-%d").to_string()], |row| {
-        Ok((
-            row.get::<_, String>(0)?,
-            row.get::<_, String>(1)?,
-            row.get::<_, String>(2)?,
-            row.get::<_, String>(3)?,
-            row.get::<_, String>(4)?,
-            row.get::<_, String>(5)?
-        ))
-    });
+    let schedule = Schedule::get_week(db_pool, Schedule::week_for(&now)).await?;
+    let states = resolve_relay_states(&current_time, schedule.as_ref(), config);
     
     // Update relays based on schedule
-    let mut controller = light_controller.lock().await;
+    let (changes, tripped) = {
+        let mut controller = light_controller.lock().await;
+        
+        controller.set_uv1(states.uv1, states.uv1_level);
+        controller.set_uv2(states.uv2, states.uv2_level);
+        
+        // Heat is controlled with overheat protection
+        controller.set_heat_target(states.heat_target);
+        controller.control_heat(states.heat);
+        
+        (controller.take_overheat_changes(), controller.heat_watchdog.take_trip())
+    };
+    
+    persist_overheat_changes(db_pool, &changes).await?;
+    // Kept until resumed, so a restart doesn't switch the lamp back on
+    if tripped {
+        crate::modules::storage::save_heat_watchdog_tripped(db_pool, true).await?;
+    }
     
+    Ok(())
+}
+
+/// Resolves which relays the schedule wants on at a given time.
+///
+/// This is the single source of truth for what `update_lights` applies,
+/// before overheat protection gets a say.
+///
+/// # Arguments
+///
+/// * `current_time` - The time to resolve in 24-hour format (HH:MM)
+/// * `schedule` - The schedule for the week, if one is stored
+/// * `config` - Application configuration containing the default schedule
+///
+/// # Returns
+///
+/// The scheduled UV1, UV2 and heat states, with the UV levels
+pub fn resolve_relay_states(
+    current_time: &str,
+    schedule: Option<&Schedule>,
+    config: &crate::modules::config::Config
+) -> RelayStates {
     // Get schedule times (or use defaults if no schedule found)
     let (uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end) = match schedule {
-        Ok(s) => s,
-        Err(_) => (
-            config.db.def_uv1_start.clone(),
-            config.db.def_uv1_end.clone(),
-            config.db.def_uv2_start.clone(),
-            config.db.def_uv2_end.clone(),
-            config.db.def_heat_start.clone(),
-            config.db.def_heat_end.clone()
+        Some(s) => (&s.uv1_start, &s.uv1_end, &s.uv2_start, &s.uv2_end, &s.heat_start, &s.heat_end),
+        None => (
+            &config.db.def_uv1_start,
+            &config.db.def_uv1_end,
+            &config.db.def_uv2_start,
+            &config.db.def_uv2_end,
+            &config.db.def_heat_start,
+            &config.db.def_heat_end,
         )
     };
     
-    // Check if we're within the scheduled times and update relays
-    controller.set_uv1(is_time_between(&current_time, &uv1_start, &uv1_end));
-    controller.set_uv2(is_time_between(&current_time, &uv2_start, &uv2_end));
-    
-    // Heat is controlled with overheat protection
-    controller.control_heat(is_time_between(&current_time, &heat_start, &heat_end));
+    // Levels are validated to 0-100 when a schedule is stored
+    let (uv1_level, uv2_level) = schedule.map_or((100, 100), |s| (s.uv1_level.clamp(0, 100) as u8, s.uv2_level.clamp(0, 100) as u8));
     
+    RelayStates {
+        uv1: is_time_between(current_time, uv1_start, uv1_end),
+        uv2: is_time_between(current_time, uv2_start, uv2_end),
+        heat: is_time_between(current_time, heat_start, heat_end),
+        uv1_level,
+        uv2_level,
+        heat_target: schedule.and_then(|s| s.heat_target).map(|target| target as f32),
+    }
+}
+
+/// Writes queued overheat event changes to the `overheat_events` table.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database pool
+/// * `changes` - Changes drained from the light controller
+///
+/// # Returns
+///
+/// A Result indicating success or a database error
+pub async fn persist_overheat_changes(
+    db_pool: &SqlitePool,
+    changes: &[OverheatEventChange]
+) -> Result<(), sqlx::Error> {
+    for change in changes {
+        match change {
+            OverheatEventChange::Started(event) => event.insert(db_pool).await?,
+            OverheatEventChange::Cleared(event) => event.finish(db_pool).await?,
+        }
+    }
     Ok(())
 }
 
@@ -273,4 +1035,299 @@ pub async fn update_lights(
 /// True if the time is between start and end, False otherwise
 fn is_time_between(time: &str, start: &str, end: &str) -> bool {
     time >= start && time <= end
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::test_config;
+    use crate::modules::storage;
+
+    #[test]
+    fn test_trend_of_rising_series() {
+        let start = Instant::now();
+        let mut trend = TemperatureTrend::new(Duration::from_secs(300));
+        for minute in 0..5u64 {
+            trend.push(start + Duration::from_secs(minute * 60), 30.0 + 2.0 * minute as f32);
+        }
+        let rate = trend.rate_per_minute().unwrap();
+        assert!((rate - 2.0).abs() < 1e-4, "rate was {}", rate);
+    }
+
+    #[test]
+    fn test_trend_drops_readings_outside_window() {
+        let start = Instant::now();
+        let mut trend = TemperatureTrend::new(Duration::from_secs(120));
+        // A fast rise long ago followed by a flat stretch
+        trend.push(start, 20.0);
+        trend.push(start + Duration::from_secs(60), 30.0);
+        for minute in 5..9u64 {
+            trend.push(start + Duration::from_secs(minute * 60), 30.0);
+        }
+        assert_eq!(trend.rate_per_minute(), Some(0.0));
+    }
+
+    #[test]
+    fn test_trend_needs_three_readings() {
+        let start = Instant::now();
+        let mut trend = TemperatureTrend::new(Duration::from_secs(300));
+        trend.push(start, 25.0);
+        trend.push(start + Duration::from_secs(60), 26.0);
+        assert_eq!(trend.rate_per_minute(), None);
+    }
+
+    #[test]
+    fn test_protection_engages_on_first_real_reading_in_grace() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let grace = StartupGrace::new(start, Duration::from_secs(120));
+
+        // Failed reads during the grace never reach protection
+        assert_eq!(grace.reading(None, at(0)), ProbeRead::Pending);
+        assert_eq!(grace.reading(None, at(30)), ProbeRead::Pending);
+
+        // The first real reading is passed on at once and trips protection
+        let temp = match grace.reading(Some(55.0), at(40)) {
+            ProbeRead::Fresh(temp) => temp,
+            other => panic!("expected a reading, got {:?}", other),
+        };
+        assert_eq!(overheated_zone((temp, 50.0), None), Some(("basking", 55.0, 50.0)));
+
+        // After the grace a failed read counts as a lost probe, never as a reading
+        assert_eq!(grace.reading(None, at(120)), ProbeRead::Lost);
+
+        let mut config = test_config();
+        config.light_control.startup_grace = Some(601);
+        assert!(config.light_control.validate().is_err());
+    }
+
+    #[test]
+    fn test_dead_probe_after_grace_never_turns_heat_on() {
+        let mut config = test_config();
+        config.light_control.startup_grace = Some(0);
+
+        // Dead from the start: heat never comes on
+        let mut lights = LightController::unavailable(config.light_control.clone());
+        lights.set_heat_target(Some(35.0));
+        for _ in 0..5 {
+            lights.update_readings(None, None);
+            lights.control_heat(true);
+            assert_ne!(lights.heat_on(), Some(true));
+        }
+
+        // Dies while heating: cut at once and held off on later ticks
+        let mut lights = LightController::unavailable(config.light_control.clone());
+        lights.update_readings(Some(30.0), Some(25.0));
+        lights.control_heat(true);
+        assert_eq!(lights.heat_on(), Some(true));
+        lights.update_readings(None, Some(25.0));
+        assert_eq!(lights.heat_on(), Some(false));
+        for _ in 0..5 {
+            lights.control_heat(true);
+            assert_eq!(lights.heat_on(), Some(false));
+            lights.update_readings(None, Some(25.0));
+        }
+
+        // Back once it reads again
+        lights.update_readings(Some(30.0), Some(25.0));
+        lights.control_heat(true);
+        assert_eq!(lights.heat_on(), Some(true));
+    }
+
+    #[test]
+    fn test_relay_states_fall_back_to_config_defaults() {
+        let config = test_config();
+        let early = resolve_relay_states("05:00", None, &config);
+        assert_eq!(early, RelayStates { uv1: false, uv2: false, heat: false, uv1_level: 100, uv2_level: 100, heat_target: None });
+
+        let midday = resolve_relay_states(&config.db.def_heat_start, None, &config);
+        assert!(midday.heat);
+
+        // A stored week passes its UV levels on
+        let mut week = Schedule::default_week(10, &config.db);
+        week.uv1_level = 60;
+        assert_eq!(resolve_relay_states("12:00", Some(&week), &config).uv1_level, 60);
+    }
+
+    #[test]
+    fn test_cooldown_counts_down_to_zero() {
+        let cooldown = Duration::from_secs(300);
+        assert_eq!(cooldown_remaining(Duration::ZERO, cooldown), 300);
+        assert_eq!(cooldown_remaining(Duration::from_millis(120_400), cooldown), 180);
+        assert_eq!(cooldown_remaining(cooldown, cooldown), 0);
+        assert_eq!(cooldown_remaining(Duration::from_secs(900), cooldown), 0);
+    }
+
+    #[test]
+    fn test_control_zone_alone_trips_overheat() {
+        // Basking well below its limit, control zone over its own
+        assert_eq!(overheated_zone((32.0, 50.0), Some((38.5, 38.0))), Some(("control", 38.5, 38.0)));
+        assert_eq!(overheated_zone((32.0, 50.0), Some((37.9, 38.0))), None);
+        // Without a control threshold only the basking zone counts
+        assert_eq!(overheated_zone((32.0, 50.0), None), None);
+        assert_eq!(overheated_zone((50.0, 50.0), Some((38.5, 38.0))), Some(("basking", 50.0, 50.0)));
+    }
+
+    #[test]
+    fn test_control_zone_event_peak_ignores_basking() {
+        let mut tracker = OverheatTracker::default();
+        tracker.trip("control", 38.5, Utc::now());
+        tracker.observe("basking", 45.0);
+        tracker.observe("control", 39.2);
+        tracker.clear(Utc::now());
+
+        match tracker.drain().as_slice() {
+            [OverheatEventChange::Started(_), OverheatEventChange::Cleared(event)] => {
+                assert_eq!(event.zone, "control");
+                assert_eq!(event.peak_temp, 39.2);
+            }
+            changes => panic!("unexpected changes: {:?}", changes),
+        }
+    }
+
+    #[test]
+    fn test_heat_watchdog_trips_until_resumed() {
+        let start = Instant::now();
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+        let mut watchdog = HeatWatchdog::new(Some(Duration::from_secs(60 * 60)));
+
+        // A break resets the on-time
+        assert!(!watchdog.observe(true, start));
+        assert!(!watchdog.observe(false, minutes(50)));
+        assert!(!watchdog.observe(true, minutes(51)));
+        assert_eq!(watchdog.on_time(minutes(100)), Some(Duration::from_secs(49 * 60)));
+        assert!(!watchdog.observe(true, minutes(111)));
+
+        // Past the limit it trips once and stays tripped
+        assert!(watchdog.observe(true, minutes(112)));
+        assert!(watchdog.is_tripped());
+        assert!(!watchdog.observe(true, minutes(300)));
+        assert!(watchdog.is_tripped());
+        assert_eq!(watchdog.on_time(minutes(300)), None);
+
+        assert!(watchdog.take_trip());
+        assert!(!watchdog.take_trip());
+
+        assert!(watchdog.resume());
+        assert!(!watchdog.resume());
+        assert!(!watchdog.observe(true, minutes(301)));
+        assert!(!watchdog.is_tripped());
+    }
+
+    #[tokio::test]
+    async fn test_heat_watchdog_trip_survives_restart() {
+        let pool = storage::temp_db("heat_watchdog").await;
+        let mut config = test_config();
+        // Below what validation allows, so the second update already trips it
+        config.light_control.max_heat_on_time = Some(0);
+
+        // Trip it as the lights loop would, then persist as `update_lights` does
+        let mut before = LightController::unavailable(config.light_control.clone());
+        before.update_temperature(25.0);
+        before.control_heat(true);
+        std::thread::sleep(Duration::from_millis(2));
+        before.control_heat(true);
+        assert!(before.heat_watchdog_tripped());
+        assert!(before.heat_watchdog.take_trip());
+        storage::save_heat_watchdog_tripped(&pool, true).await.unwrap();
+
+        // After a restart the lamp stays cut until resumed
+        let mut after = LightController::unavailable(config.light_control.clone());
+        assert!(storage::heat_watchdog_tripped(&pool).await.unwrap());
+        after.restore_heat_watchdog_trip();
+        after.update_temperature(25.0);
+        after.control_heat(true);
+        assert_eq!(after.heat_on(), Some(false));
+        assert!(!after.heat_watchdog.take_trip());
+        assert!(after.resume_heat());
+    }
+
+    #[test]
+    fn test_heat_watchdog_disabled_without_limit() {
+        let start = Instant::now();
+        let mut watchdog = HeatWatchdog::new(None);
+        assert!(!watchdog.observe(true, start));
+        assert!(!watchdog.observe(true, start + Duration::from_secs(7 * 24 * 3600)));
+        assert!(!watchdog.is_tripped());
+    }
+
+    #[test]
+    fn test_same_instant_in_two_timezones() {
+        let mut config = test_config();
+        // 09:30 UTC, before UV1 starts in New York and after it starts in Berlin
+        let instant = DateTime::parse_from_rfc3339("2024-06-01T09:30:00Z").unwrap().with_timezone(&Utc);
+        let states_in = |config: &crate::modules::config::Config| {
+            let time = config.main.schedule_time(instant).format("%H:%M").to_string();
+            resolve_relay_states(&time, None, config)
+        };
+
+        config.main.timezone = Some("Europe/Berlin".to_string());
+        let berlin = states_in(&config);
+        config.main.timezone = Some("America/New_York".to_string());
+        let new_york = states_in(&config);
+
+        assert_eq!(config.main.schedule_time(instant).format("%H:%M").to_string(), "05:30");
+        assert!(berlin.uv1);
+        assert!(!new_york.uv1);
+    }
+
+    #[test]
+    fn test_uv_output_without_pwm_falls_back_to_relay() {
+        assert_eq!(uv_output(true, 40, false), (true, None));
+        assert_eq!(uv_output(true, 100, false), (true, None));
+        assert_eq!(uv_output(false, 40, false), (false, None));
+        assert_eq!(uv_output(true, 0, false), (false, None));
+    }
+
+    #[test]
+    fn test_uv_output_with_pwm_dims() {
+        assert_eq!(uv_output(true, 40, true), (true, Some(0.4)));
+        assert_eq!(uv_output(true, 100, true), (true, Some(1.0)));
+        assert_eq!(uv_output(false, 40, true), (false, Some(0.0)));
+        assert_eq!(uv_output(true, 0, true), (false, Some(0.0)));
+    }
+
+    #[test]
+    fn test_heat_pid_settles_on_target() {
+        // First-order enclosure: 25°C ambient, +30°C at full power, 10 minute time constant
+        let (ambient, gain, tau) = (25.0_f32, 30.0_f32, 600.0_f32);
+        let step = Duration::from_secs(30);
+        let mut pid = PidController::new(test_config().light_control.heat_pid_gains());
+        let start = Instant::now();
+        let mut temp = ambient;
+        let mut peak = temp;
+
+        for i in 0..240 {
+            let duty = pid.update(35.0, temp, start + step * i);
+            assert!((0.0..=100.0).contains(&duty));
+            temp += (ambient + gain * duty / 100.0 - temp) * step.as_secs_f32() / tau;
+            peak = peak.max(temp);
+        }
+
+        assert!((temp - 35.0).abs() < 0.2, "settled at {:.2}°C", temp);
+        assert!(peak < 36.0, "overshot to {:.2}°C", peak);
+    }
+
+    #[tokio::test]
+    async fn test_overheat_then_recover_records_one_event() {
+        let pool = storage::temp_db("overheat").await;
+
+        let start = Utc::now();
+        let mut tracker = OverheatTracker::default();
+        tracker.trip("basking", 51.0, start);
+        tracker.observe("basking", 53.5);
+        tracker.trip("basking", 52.0, start + chrono::Duration::seconds(30));
+        tracker.observe("basking", 49.0);
+        persist_overheat_changes(&pool, &tracker.drain()).await.unwrap();
+
+        tracker.clear(start + chrono::Duration::seconds(900));
+        persist_overheat_changes(&pool, &tracker.drain()).await.unwrap();
+
+        let events = OverheatEvent::get_history(&pool, 10).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].peak_temp, 53.5);
+        assert_eq!(events[0].duration_seconds, Some(900));
+        assert_eq!(events[0].zone, "basking");
+        assert!(events[0].ended_at.is_some());
+    }
+}
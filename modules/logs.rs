@@ -1,13 +1,75 @@
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use chrono::{DateTime, Utc, Local, NaiveDateTime};
+use chrono::{DateTime, Utc, Local, NaiveDate, NaiveDateTime};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
+use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
 use zip::{ZipWriter, write::FileOptions};
+use crate::modules::config::LoggingConfig;
+use crate::modules::storage;
+
+// Rotation settings for the log files, set once at startup by `init`
+static LOG_SETTINGS: OnceLock<LoggingConfig> = OnceLock::new();
+
+// Suppression and sampling of routine lines, set once at startup by `init`
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+// Applies the `[logging]` settings. Until this is called the defaults are used.
+pub fn init(config: &LoggingConfig) {
+    let _ = LOG_SETTINGS.set(config.clone());
+    let _ = LOG_FILTER.set(LogFilter::new(config));
+}
+
+// Decides which lines `log` persists. WARNING and ERROR lines always are; other
+// lines matching a `suppress` entry never are, and lines matching a `sampled`
+// entry are kept once every `sample_rate` times. Entries match a level
+// (case-insensitive) or the start of a message.
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    suppress: Vec<String>,
+    sampled: Vec<String>,
+    sample_rate: u64,
+    seen: std::sync::Mutex<HashMap<String, u64>>, // Lines seen per sampled entry
+}
+
+impl LogFilter {
+    pub fn new(config: &LoggingConfig) -> Self {
+        Self {
+            suppress: config.suppress.clone().unwrap_or_default(),
+            sampled: config.sampled.clone().unwrap_or_default(),
+            sample_rate: config.sample_rate().max(1) as u64,
+            seen: Default::default(),
+        }
+    }
+
+    pub fn admit(&self, level: &str, message: &str) -> bool {
+        if matches!(level, "WARNING" | "ERROR") {
+            return true;
+        }
+        let matches = |entry: &&String| entry.eq_ignore_ascii_case(level) || message.starts_with(entry.as_str());
+        if self.suppress.iter().any(|entry| matches(&entry)) {
+            return false;
+        }
+        match self.sampled.iter().find(matches) {
+            Some(entry) => {
+                let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+                let count = seen.entry(entry.clone()).or_insert(0);
+                let keep = *count % self.sample_rate == 0;
+                *count += 1;
+                keep
+            }
+            None => true,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -101,8 +163,15 @@ pub async fn get_log_entries(
     Ok(entries)
 }
 
-// Function to create a zip file with all log files
-pub async fn create_logs_zip() -> Result<PathBuf, Box<dyn Error>> {
+// Counter used to give every zip archive a unique temp filename
+static ZIP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Function to create a zip file with all log files and the database log entries.
+//
+// Each call writes to its own uniquely named file in `temp/` and only renames it
+// to its final `.zip` name once the archive is complete, so concurrent download
+// requests never see (or clobber) each other's half-written archives.
+pub async fn create_logs_zip(db_pool: &SqlitePool) -> Result<PathBuf, Box<dyn Error>> {
     let logs_dir = Path::new("logs");
     let temp_dir = Path::new("temp");
     
@@ -111,8 +180,31 @@ pub async fn create_logs_zip() -> Result<PathBuf, Box<dyn Error>> {
         fs::create_dir_all(temp_dir)?;
     }
     
-    let zip_path = temp_dir.join("terrarium_logs.zip");
-    let file = File::create(&zip_path)?;
+    // Fetch the database entries before touching the filesystem
+    let log_entries = get_log_entries(db_pool, None, None).await?;
+    
+    let unique_name = format!(
+        "terrarium_logs_{}_{}",
+        Utc::now().format("%Y%m%d%H%M%S"),
+        ZIP_COUNTER.fetch_add(1, Ordering::SeqCst)
+    );
+    let partial_path = temp_dir.join(format!("{}.zip.part", unique_name));
+    let zip_path = temp_dir.join(format!("{}.zip", unique_name));
+    
+    if let Err(e) = write_logs_zip(&partial_path, logs_dir, &log_entries) {
+        let _ = fs::remove_file(&partial_path);
+        return Err(e);
+    }
+    
+    // Atomically publish the finished archive
+    fs::rename(&partial_path, &zip_path)?;
+    
+    Ok(zip_path)
+}
+
+// Writes the log files and database entries into a zip archive at `path`
+fn write_logs_zip(path: &Path, logs_dir: &Path, log_entries: &[LogEntry]) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path)?;
     
     let mut zip = ZipWriter::new(file);
     let options = FileOptions::default()
@@ -139,9 +231,6 @@ pub async fn create_logs_zip() -> Result<PathBuf, Box<dyn Error>> {
     }
     
     // Add database log entries as a CSV file
-    let db_pool = sqlx::SqlitePool::connect("sqlite:data.db").await?;
-    let log_entries = get_log_entries(&db_pool, None, None).await?;
-    
     zip.start_file("database_logs.csv", options)?;
     zip.write_all(b"Timestamp,Level,Message\n")?;
     
@@ -155,9 +244,172 @@ pub async fn create_logs_zip() -> Result<PathBuf, Box<dyn Error>> {
         zip.write_all(line.as_bytes())?;
     }
     
-    zip.finish()?;
+    let file = zip.finish()?;
+    file.sync_all()?;
     
-    Ok(zip_path)
+    Ok(())
+}
+
+// Header row of the sensor data CSV, shared by export and import
+const SENSOR_CSV_HEADER: &str = "Timestamp,Temperature,Humidity,UV Index";
+
+// Most row errors listed when an import is rejected
+const MAX_IMPORT_ERRORS: usize = 20;
+
+/// One row of the sensor data CSV. Temperature is the basking zone and the
+/// UV index the first UV sensor of the `sensor_history` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryRow {
+    pub timestamp: NaiveDateTime,
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub uv_index: Option<f64>,
+}
+
+/// What an import does with a row whose timestamp is already stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateMode {
+    /// Keep the stored row
+    #[default]
+    Skip,
+    /// Overwrite the stored row with the imported values
+    Upsert,
+}
+
+/// Row counts of a finished import
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+// Parses the sensor data CSV written by `get_sensor_data_csv`.
+//
+// Every row is checked before anything is returned, so the errors name all
+// malformed lines (1-based, the header is line 1) up to MAX_IMPORT_ERRORS.
+// Empty value fields are read as missing readings.
+pub fn parse_sensor_data_csv(csv: &str) -> Result<Vec<HistoryRow>, String> {
+    let mut lines = csv.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim().eq_ignore_ascii_case(SENSOR_CSV_HEADER) => {}
+        Some((_, header)) => {
+            return Err(format!("Line 1: expected header {:?}, got {:?}", SENSOR_CSV_HEADER, header.trim()));
+        }
+        None => return Err("CSV is empty".to_string()),
+    }
+    
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_history_row(line) {
+            Ok(row) => rows.push(row),
+            Err(e) => errors.push(format!("Line {}: {}", index + 1, e)),
+        }
+    }
+    
+    if errors.is_empty() {
+        Ok(rows)
+    } else {
+        let total = errors.len();
+        errors.truncate(MAX_IMPORT_ERRORS);
+        if total > MAX_IMPORT_ERRORS {
+            errors.push(format!("... and {} more", total - MAX_IMPORT_ERRORS));
+        }
+        Err(errors.join("\n"))
+    }
+}
+
+// Parses one data line of the sensor data CSV
+fn parse_history_row(line: &str) -> Result<HistoryRow, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if fields.len() != 4 {
+        return Err(format!("expected 4 fields, got {}", fields.len()));
+    }
+    
+    let timestamp = NaiveDateTime::parse_from_str(fields[0], storage::SENSOR_TIMESTAMP_FORMAT)
+        .map_err(|_| format!("invalid timestamp {:?}, expected YYYY-MM-DD HH:MM:SS", fields[0]))?;
+    let value = |name: &str, field: &str| -> Result<Option<f64>, String> {
+        if field.is_empty() {
+            return Ok(None);
+        }
+        field.parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .map(Some)
+            .ok_or_else(|| format!("invalid {} {:?}", name, field))
+    };
+    
+    Ok(HistoryRow {
+        timestamp,
+        temperature: value("temperature", fields[1])?,
+        humidity: value("humidity", fields[2])?,
+        uv_index: value("UV index", fields[3])?,
+    })
+}
+
+// Writes parsed rows into `sensor_history` in a single transaction, so a failed
+// import leaves nothing behind. Rows are matched on their timestamp.
+pub async fn import_sensor_data(
+    db_pool: &SqlitePool,
+    rows: &[HistoryRow],
+    mode: DuplicateMode,
+) -> Result<ImportSummary, sqlx::Error> {
+    let mut summary = ImportSummary::default();
+    let mut tx = db_pool.begin().await?;
+    
+    for row in rows {
+        let timestamp = row.timestamp.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string();
+        let existing = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM sensor_history WHERE timestamp = ? LIMIT 1
+            "#,
+            timestamp
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        
+        match (existing, mode) {
+            (Some(_), DuplicateMode::Skip) => summary.skipped += 1,
+            (Some(id), DuplicateMode::Upsert) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE sensor_history SET basking_temp = ?, humidity = ?, uv_1 = ?
+                    WHERE id = ?
+                    "#,
+                    row.temperature,
+                    row.humidity,
+                    row.uv_index,
+                    id
+                )
+                .execute(&mut tx)
+                .await?;
+                summary.updated += 1;
+            }
+            (None, _) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO sensor_history (timestamp, basking_temp, humidity, uv_1)
+                    VALUES (?, ?, ?, ?)
+                    "#,
+                    timestamp,
+                    row.temperature,
+                    row.humidity,
+                    row.uv_index
+                )
+                .execute(&mut tx)
+                .await?;
+                summary.inserted += 1;
+            }
+        }
+    }
+    
+    tx.commit().await?;
+    Ok(summary)
 }
 
 // Function to get sensor data as CSV
@@ -170,10 +422,10 @@ pub async fn get_sensor_data_csv(
         r#"
         SELECT 
             timestamp,
-            temperature,
+            basking_temp AS temperature,
             humidity,
-            uv_index
-        FROM history
+            uv_1 AS uv_index
+        FROM sensor_history
         WHERE date(timestamp) BETWEEN date(?) AND date(?)
         ORDER BY timestamp
         "#,
@@ -183,7 +435,7 @@ pub async fn get_sensor_data_csv(
     .fetch_all(db_pool)
     .await?;
     
-    let mut csv = String::from("Timestamp,Temperature,Humidity,UV Index\n");
+    let mut csv = format!("{}\n", SENSOR_CSV_HEADER);
     
     for reading in readings {
         csv.push_str(&format!(
@@ -198,6 +450,74 @@ pub async fn get_sensor_data_csv(
     Ok(csv)
 }
 
+// Checks the start/end query of a sensor data export: both YYYY-MM-DD, start not after end
+pub fn parse_export_range(start: &str, end: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |name: &str, value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid {} date {:?}, expected YYYY-MM-DD", name, value));
+    let (start, end) = (parse("start", start)?, parse("end", end)?);
+    if start > end {
+        return Err(format!("start {} is after end {}", start, end));
+    }
+    Ok((start, end))
+}
+
+/// One stored reading of the JSON Lines export
+#[derive(Debug, Serialize)]
+pub struct HistoryRecord {
+    pub timestamp: String,
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub uv_index: Option<f64>,
+}
+
+// Rows buffered between the database and a slow client
+const EXPORT_CHANNEL_SIZE: usize = 64;
+
+// Streams the readings of a date range as JSON Lines, one object per line.
+//
+// Rows are read by a spawned task and handed over through a bounded channel,
+// so the range is never held in memory; the task stops once the receiver is dropped.
+pub fn stream_sensor_data_jsonl(
+    db_pool: SqlitePool,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> ReceiverStream<Result<String, sqlx::Error>> {
+    let (tx, rx) = mpsc::channel(EXPORT_CHANNEL_SIZE);
+    
+    tokio::spawn(async move {
+        let mut rows = sqlx::query_as!(
+            HistoryRecord,
+            r#"
+            SELECT
+                timestamp,
+                basking_temp AS temperature,
+                humidity,
+                uv_1 AS uv_index
+            FROM sensor_history
+            WHERE date(timestamp) BETWEEN date(?) AND date(?)
+            ORDER BY timestamp
+            "#,
+            start_date,
+            end_date
+        )
+        .fetch(&db_pool);
+        
+        while let Some(row) = rows.next().await {
+            let line = row.map(|record| {
+                let mut line = serde_json::to_string(&record).expect("history record serializes");
+                line.push('\n');
+                line
+            });
+            let failed = line.is_err();
+            if tx.send(line).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    
+    ReceiverStream::new(rx)
+}
+
 // Function to log a message to the database
 pub async fn log_to_db(
     db_pool: &SqlitePool,
@@ -206,7 +526,7 @@ pub async fn log_to_db(
 ) -> Result<(), Box<dyn Error>> {
     let timestamp = Utc::now();
     
-    sqlx::query!(
+    storage::retry_busy(|| sqlx::query!(
         r#"
         INSERT INTO logs (timestamp, level, message)
         VALUES (?, ?, ?)
@@ -215,38 +535,305 @@ pub async fn log_to_db(
         level,
         message
     )
-    .execute(db_pool)
+    .execute(db_pool))
     .await?;
     
     Ok(())
 }
 
-// Function to log a message to both file and database
+// Function to log a message to both file and database, unless `[logging]`
+// suppresses or samples it out
 pub async fn log(
     db_pool: &SqlitePool,
     level: &str,
     message: &str,
 ) -> Result<(), Box<dyn Error>> {
-    // Log to database
-    log_to_db(db_pool, level, message).await?;
+    if let Some(filter) = LOG_FILTER.get() {
+        if !filter.admit(level, message) {
+            return Ok(());
+        }
+    }
+    
+    // Log to database, unless it's the in-memory stand-in of a degraded start.
+    // A failed write, e.g. on a full disk, still goes to the log file.
+    let stored = match storage::degraded() {
+        None => log_to_db(db_pool, level, message).await,
+        Some(_) => Ok(()),
+    };
     
     // Log to file
     let now = Local::now();
     let date_str = now.format("%Y-%m-%d").to_string();
     let time_str = now.format("%H:%M:%S").to_string();
     
-    let logs_dir = Path::new("logs");
+    let default_settings = LoggingConfig::default();
+    let settings = LOG_SETTINGS.get().unwrap_or(&default_settings);
+    let line = format!("[{}] [{}] {}\n", time_str, level, message);
+    append_to_log_file(Path::new("logs"), &date_str, &line, settings)?;
+    
+    stored
+}
+
+/// `log` for callers that carry on when logging fails, e.g. startup on a full
+/// disk; the failure goes to stderr
+pub async fn log_or_warn(db_pool: &SqlitePool, level: &str, message: &str) {
+    if let Err(e) = log(db_pool, level, message).await {
+        eprintln!("Failed to log {:?}: {:?}", message, e);
+    }
+}
+
+// Appends `line` to `<logs_dir>/<date>.log`, rolling the file over first if the
+// line would push it past `max_file_size`, then deletes the oldest log files
+// until no more than `max_files` remain.
+fn append_to_log_file(
+    logs_dir: &Path,
+    date_str: &str,
+    line: &str,
+    settings: &LoggingConfig,
+) -> io::Result<()> {
     if !logs_dir.exists() {
         fs::create_dir_all(logs_dir)?;
     }
     
     let log_file_path = logs_dir.join(format!("{}.log", date_str));
+    let current_size = fs::metadata(&log_file_path).map(|m| m.len()).unwrap_or(0);
+    if current_size > 0 && current_size + line.len() as u64 > settings.max_file_size() {
+        rotate_log_file(logs_dir, date_str)?;
+    }
+    
     let mut file = fs::OpenOptions::new()
         .create(true)
         .append(true)
-        .open(log_file_path)?;
+        .open(&log_file_path)?;
+    file.write_all(line.as_bytes())?;
     
-    writeln!(file, "[{}] [{}] {}", time_str, level, message)?;
+    prune_log_files(logs_dir, settings.max_files())
+}
+
+// Shifts `<date>.log.N` to `.N+1` (highest first) and `<date>.log` to `.1`
+fn rotate_log_file(logs_dir: &Path, date_str: &str) -> io::Result<()> {
+    let base = format!("{}.log", date_str);
+    let mut highest = 0;
+    while logs_dir.join(format!("{}.{}", base, highest + 1)).exists() {
+        highest += 1;
+    }
+    
+    for index in (1..=highest).rev() {
+        fs::rename(
+            logs_dir.join(format!("{}.{}", base, index)),
+            logs_dir.join(format!("{}.{}", base, index + 1)),
+        )?;
+    }
+    fs::rename(logs_dir.join(&base), logs_dir.join(format!("{}.1", base)))
+}
+
+// Whether `name` is a log file (`<date>.log` or a rotated `<date>.log.N`)
+fn is_log_file_name(name: &str) -> bool {
+    match name.rsplit_once(".log") {
+        Some((stem, suffix)) => !stem.is_empty() && (suffix.is_empty()
+            || (suffix.len() > 1 && suffix.starts_with('.') && suffix[1..].chars().all(|c| c.is_ascii_digit()))),
+        None => false,
+    }
+}
+
+// Deletes the least recently modified log files until at most `max_files` remain
+fn prune_log_files(logs_dir: &Path, max_files: usize) -> io::Result<()> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(logs_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.path().is_file() && is_log_file_name(&name) {
+            files.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    
+    if files.len() <= max_files {
+        return Ok(());
+    }
+    
+    // Oldest first; rotated files with higher numbers are older than lower ones
+    files.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| rotation_index(&b.1).cmp(&rotation_index(&a.1))));
+    let excess = files.len() - max_files;
+    for (_, path) in files.into_iter().take(excess) {
+        fs::remove_file(path)?;
+    }
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+// Rotation number of a log file path, 0 for the live `<date>.log`
+fn rotation_index(path: &Path) -> u32 {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| ext.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::storage::temp_db;
+
+    fn temp_logs_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("terra_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_log_file_rotates_past_max_size() {
+        let dir = temp_logs_dir("log_rotate");
+        let settings = LoggingConfig { max_file_size: Some(100), max_files: Some(10), ..Default::default() };
+        let line = "[12:00:00] [INFO] rotation test\n";
+
+        for _ in 0..10 {
+            append_to_log_file(&dir, "2024-05-01", line, &settings).unwrap();
+        }
+
+        assert!(dir.join("2024-05-01.log.1").exists());
+        assert!(fs::metadata(dir.join("2024-05-01.log")).unwrap().len() <= 100);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_log_files_capped_at_max_files() {
+        let dir = temp_logs_dir("log_prune");
+        let settings = LoggingConfig { max_file_size: Some(40), max_files: Some(3), ..Default::default() };
+        let line = "[12:00:00] [INFO] rotation test\n";
+
+        for _ in 0..8 {
+            append_to_log_file(&dir, "2024-05-01", line, &settings).unwrap();
+        }
+
+        let remaining: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(remaining.len(), 3);
+        assert!(remaining.contains(&"2024-05-01.log".to_string()));
+        assert!(remaining.contains(&"2024-05-01.log.1".to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_log_file_name() {
+        assert!(is_log_file_name("2024-05-01.log"));
+        assert!(is_log_file_name("2024-05-01.log.12"));
+        assert!(!is_log_file_name("2024-05-01.log.bak"));
+        assert!(!is_log_file_name("notes.txt"));
+    }
+
+    const IMPORT_CSV: &str = "Timestamp,Temperature,Humidity,UV Index
+2024-03-01 10:00:00,31.5,62.0,2.1
+2024-03-01 10:05:00,32.0,,2.3
+2024-03-02 09:00:00,29.8,65.5,0.4
+";
+
+    #[tokio::test]
+    async fn test_sampled_info_stored_once_per_rate() {
+        let pool = temp_db("log_sampling").await;
+        let filter = LogFilter::new(&LoggingConfig {
+            suppress: Some(vec!["Heartbeat".to_string()]),
+            sampled: Some(vec!["Sensor readings".to_string()]),
+            sample_rate: Some(10),
+            ..Default::default()
+        });
+
+        for i in 0..10 {
+            let message = format!("Sensor readings #{}", i);
+            if filter.admit("INFO", &message) {
+                log_to_db(&pool, "INFO", &message).await.unwrap();
+            }
+        }
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM logs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        assert!(filter.admit("INFO", "Starting web server"));
+        assert!(!filter.admit("INFO", "Heartbeat"));
+        assert!(filter.admit("WARNING", "Sensor readings out of range"));
+        assert!(filter.admit("ERROR", "Heartbeat missed"));
+    }
+
+    #[test]
+    fn test_malformed_rows_reported_with_line_numbers() {
+        let csv = "Timestamp,Temperature,Humidity,UV Index\n\
+                   2024-03-01 10:00:00,31.5,62.0,2.1\n\
+                   2024-03-01 10:05,32.0,60.0,2.3\n\
+                   2024-03-01 10:10:00,hot,60.0,2.3\n\
+                   2024-03-01 10:15:00,31.0,60.0\n";
+        let err = parse_sensor_data_csv(csv).unwrap_err();
+        assert!(err.contains("Line 3: invalid timestamp"), "{}", err);
+        assert!(err.contains("Line 4: invalid temperature"), "{}", err);
+        assert!(err.contains("Line 5: expected 4 fields"), "{}", err);
+        assert!(!err.contains("Line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_wrong_header_rejected() {
+        let err = parse_sensor_data_csv("time,temp\n2024-03-01 10:00:00,31.5\n").unwrap_err();
+        assert!(err.starts_with("Line 1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_imported_rows_appear_in_export() {
+        let pool = temp_db("csv_import").await;
+
+        let rows = parse_sensor_data_csv(IMPORT_CSV).unwrap();
+        let summary = import_sensor_data(&pool, &rows, DuplicateMode::Skip).await.unwrap();
+        assert_eq!(summary, ImportSummary { inserted: 3, updated: 0, skipped: 0 });
+
+        let csv = get_sensor_data_csv(&pool, "2024-03-01", "2024-03-01").await.unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "2024-03-01 10:00:00,31.5,62,2.1");
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_export_has_one_line_per_row() {
+        let pool = temp_db("jsonl_export").await;
+        let rows = parse_sensor_data_csv(IMPORT_CSV).unwrap();
+        import_sensor_data(&pool, &rows, DuplicateMode::Skip).await.unwrap();
+
+        let (start, end) = parse_export_range("2024-03-01", "2024-03-02").unwrap();
+        let lines: Vec<String> = stream_sensor_data_jsonl(pool.clone(), start, end)
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sensor_history")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(lines.len() as i64, count);
+        let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(first["temperature"], 31.5);
+        assert_eq!(first["uv_index"], 2.1);
+        let second: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert!(second["humidity"].is_null());
+
+        assert!(parse_export_range("2024-03-02", "2024-03-01").is_err());
+        assert!(parse_export_range("03/01/2024", "2024-03-01").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_timestamps_skip_or_upsert() {
+        let pool = temp_db("csv_import_dupes").await;
+        let rows = parse_sensor_data_csv(IMPORT_CSV).unwrap();
+        import_sensor_data(&pool, &rows, DuplicateMode::Skip).await.unwrap();
+
+        let changed = parse_sensor_data_csv(
+            "Timestamp,Temperature,Humidity,UV Index\n2024-03-01 10:00:00,40.0,50.0,3.0\n"
+        ).unwrap();
+        let skipped = import_sensor_data(&pool, &changed, DuplicateMode::Skip).await.unwrap();
+        assert_eq!(skipped, ImportSummary { inserted: 0, updated: 0, skipped: 1 });
+
+        let updated = import_sensor_data(&pool, &changed, DuplicateMode::Upsert).await.unwrap();
+        assert_eq!(updated, ImportSummary { inserted: 0, updated: 1, skipped: 0 });
+
+        let csv = get_sensor_data_csv(&pool, "2024-03-01", "2024-03-01").await.unwrap();
+        assert!(csv.contains("2024-03-01 10:00:00,40,50,3"), "{}", csv);
+        assert_eq!(csv.lines().count(), 3);
+    }
+}
@@ -0,0 +1,380 @@
+// modules/migrations.rs
+use chrono::Utc;
+use sqlx::{Executor, SqlitePool};
+
+/// A numbered schema change, applied once and recorded in `schema_version`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    sql: &'static str,
+}
+
+/// Every schema change in the order it is applied. Append new migrations
+/// with the next version; never edit one that has shipped.
+///
+/// Version 1 is the schema from before versioning. Its tables are created with
+/// `IF NOT EXISTS`, so databases from that time pass through it unchanged and
+/// `storage::audit_schema` adds the columns they may still lack.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS schedule (
+                week_number INTEGER PRIMARY KEY,
+                uv1_start TEXT NOT NULL,
+                uv1_end TEXT NOT NULL,
+                uv2_start TEXT NOT NULL,
+                uv2_end TEXT NOT NULL,
+                heat_start TEXT NOT NULL,
+                heat_end TEXT NOT NULL,
+                led_start TEXT NOT NULL,
+                led_end TEXT NOT NULL,
+                led_r INTEGER NOT NULL,
+                led_g INTEGER NOT NULL,
+                led_b INTEGER NOT NULL,
+                led_cw INTEGER NOT NULL,
+                led_ww INTEGER NOT NULL,
+                uv1_level INTEGER NOT NULL DEFAULT 100,
+                uv2_level INTEGER NOT NULL DEFAULT 100,
+                heat_target REAL
+            );
+
+            CREATE TABLE IF NOT EXISTS overrides (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                date TEXT NOT NULL,
+                uv1_enabled INTEGER NOT NULL,
+                uv2_enabled INTEGER NOT NULL,
+                heat_enabled INTEGER NOT NULL,
+                led_enabled INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                temperature REAL,
+                humidity REAL,
+                uv_index REAL
+            );
+
+            CREATE TABLE IF NOT EXISTS led_settings (
+                id INTEGER PRIMARY KEY,
+                r INTEGER NOT NULL,
+                g INTEGER NOT NULL,
+                b INTEGER NOT NULL,
+                ww INTEGER NOT NULL,
+                cw INTEGER NOT NULL,
+                enabled INTEGER NOT NULL,
+                override INTEGER NOT NULL DEFAULT 0,
+                season_weight REAL NOT NULL DEFAULT 0.3
+            );
+
+            CREATE TABLE IF NOT EXISTS logs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                level TEXT NOT NULL,
+                message TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS overheat_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at TEXT NOT NULL UNIQUE,
+                ended_at TEXT,
+                peak_temp REAL NOT NULL,
+                duration_seconds INTEGER,
+                zone TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS relay_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                relay TEXT NOT NULL,
+                state INTEGER NOT NULL,
+                reason TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                scope TEXT NOT NULL,
+                key_hash TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL,
+                revoked_at TEXT
+            );
+        "#,
+    },
+    Migration {
+        version: 2,
+        description: "move sensor readings from history into sensor_history",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS sensor_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                basking_temp REAL,
+                control_temp REAL,
+                cool_temp REAL,
+                humidity REAL,
+                uv_1 REAL,
+                uv_2 REAL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_sensor_history_timestamp ON sensor_history (timestamp);
+
+            INSERT INTO sensor_history (timestamp, basking_temp, humidity, uv_1)
+            SELECT timestamp, temperature, humidity, uv_index FROM history ORDER BY id;
+
+            DROP TABLE history;
+        "#,
+    },
+    Migration {
+        version: 3,
+        description: "add hourly sensor_history rollup",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS sensor_history_hourly (
+                hour TEXT PRIMARY KEY,
+                basking_temp REAL,
+                control_temp REAL,
+                cool_temp REAL,
+                humidity REAL,
+                uv_1 REAL,
+                uv_2 REAL,
+                samples INTEGER NOT NULL
+            );
+        "#,
+    },
+    Migration {
+        version: 4,
+        description: "add named schedule profiles",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS schedule_profiles (
+                name TEXT PRIMARY KEY,
+                display_name TEXT,
+                color TEXT,
+                saved_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS schedule_profile_weeks (
+                profile TEXT NOT NULL,
+                week_number INTEGER NOT NULL,
+                uv1_start TEXT NOT NULL,
+                uv1_end TEXT NOT NULL,
+                uv2_start TEXT NOT NULL,
+                uv2_end TEXT NOT NULL,
+                heat_start TEXT NOT NULL,
+                heat_end TEXT NOT NULL,
+                led_start TEXT NOT NULL,
+                led_end TEXT NOT NULL,
+                led_r INTEGER NOT NULL,
+                led_g INTEGER NOT NULL,
+                led_b INTEGER NOT NULL,
+                led_cw INTEGER NOT NULL,
+                led_ww INTEGER NOT NULL,
+                uv1_level INTEGER NOT NULL,
+                uv2_level INTEGER NOT NULL,
+                heat_target REAL,
+                PRIMARY KEY (profile, week_number)
+            );
+        "#,
+    },
+    Migration {
+        version: 5,
+        description: "add readings of DS18B20 probes beyond the canonical three",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS probe_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                probe TEXT NOT NULL,
+                temperature REAL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_probe_history_timestamp ON probe_history (timestamp);
+        "#,
+    },
+    Migration {
+        version: 6,
+        description: "add hourly probe_history rollup",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS probe_history_hourly (
+                hour TEXT NOT NULL,
+                probe TEXT NOT NULL,
+                temperature REAL,
+                samples INTEGER NOT NULL,
+                PRIMARY KEY (hour, probe)
+            );
+        "#,
+    },
+    Migration {
+        version: 7,
+        description: "convert sensor_history timestamps written in UTC to local time",
+        // Rows from before `storage::SENSOR_TIMESTAMP_FORMAT` were UTC with
+        // fractional seconds; the hours rolled up from them are rebuilt
+        sql: r#"
+            DELETE FROM sensor_history_hourly
+            WHERE hour IN (
+                SELECT strftime('%Y-%m-%d %H:00:00', timestamp) FROM sensor_history WHERE length(timestamp) > 19
+            );
+
+            UPDATE sensor_history
+            SET timestamp = datetime(timestamp, 'localtime')
+            WHERE length(timestamp) > 19;
+        "#,
+    },
+];
+
+/// Version the newest migration brings the database to
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Version the database is at, 0 before any migration ran
+pub async fn current_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let (version,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(version)
+}
+
+/// Applies every migration newer than the database's version, in order.
+///
+/// Each migration runs in its own transaction together with its
+/// `schema_version` row, so a failed one leaves the database at the
+/// previous version.
+///
+/// # Returns
+///
+/// The migrations that were applied
+pub async fn run(pool: &SqlitePool) -> Result<Vec<Migration>, sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let current = current_version(pool).await?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+        let mut tx = pool.begin().await?;
+        tx.execute(migration.sql).await?;
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now())
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        applied.push(*migration);
+    }
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::test_config;
+    use crate::modules::ledStrip::get_led_settings;
+    use crate::modules::logs::{self, DuplicateMode, HistoryRow};
+    use crate::modules::models::{ApiKey, Override, OverheatEvent, RelayEvent, Schedule, ScheduleProfile};
+    use crate::modules::storage;
+    use crate::modules::web::handlers::monitoring::get_graph_data_for_date;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_versions_are_consecutive() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i64 + 1, "{}", migration.description);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_reaches_latest_version() {
+        let pool = storage::temp_db("migrations_fresh").await;
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+        assert!(run(&pool).await.unwrap().is_empty());
+
+        // The queries of every module line up with the migrated tables
+        storage::reset_to_defaults(&pool, &test_config()).await.unwrap();
+        assert_eq!(Schedule::get_schedule(&pool).await.unwrap().len(), 52);
+        assert!(get_led_settings(&pool).await.unwrap().is_some());
+        Override::get_overrides(&pool).await.unwrap();
+        OverheatEvent::get_history(&pool, 10).await.unwrap();
+        RelayEvent::get_history(&pool, 10).await.unwrap();
+        ApiKey::list(&pool).await.unwrap();
+        ScheduleProfile::list(&pool).await.unwrap();
+        storage::load_settings(&pool).await.unwrap();
+        logs::get_log_entries(&pool, None, Some(10)).await.unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let row = HistoryRow {
+            timestamp: day.and_hms_opt(10, 0, 0).unwrap(),
+            temperature: Some(31.5),
+            humidity: Some(60.0),
+            uv_index: Some(2.0),
+        };
+        logs::import_sensor_data(&pool, &[row], DuplicateMode::Skip).await.unwrap();
+        let csv = logs::get_sensor_data_csv(&pool, "2024-03-01", "2024-03-01").await.unwrap();
+        assert!(csv.contains("2024-03-01 10:00:00,31.5,60,2"), "{}", csv);
+        assert_eq!(get_graph_data_for_date(&pool, day).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_utc_sensor_history_converted_to_local_time() {
+        use chrono::{Local, TimeZone, Utc};
+
+        let pool = storage::temp_db("migrations_utc_history").await;
+        sqlx::query("DELETE FROM schema_version WHERE version >= 7").execute(&*pool).await.unwrap();
+        let utc = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_nano_opt(10, 0, 0, 123_456_789).unwrap();
+        sqlx::query("INSERT INTO sensor_history (timestamp, basking_temp) VALUES (?, 31.5), ('2024-03-01 12:00:00', 32.0)")
+            .bind(utc)
+            .execute(&*pool).await.unwrap();
+        sqlx::query("INSERT INTO sensor_history_hourly (hour, basking_temp, samples) VALUES ('2024-03-01 10:00:00', 31.5, 1)")
+            .execute(&*pool).await.unwrap();
+
+        assert_eq!(run(&pool).await.unwrap().len(), 1);
+        let local = Utc.from_utc_datetime(&utc).with_timezone(&Local).naive_local();
+        let stamps: Vec<(String,)> = sqlx::query_as("SELECT timestamp FROM sensor_history ORDER BY id")
+            .fetch_all(&*pool).await.unwrap();
+        assert_eq!(stamps, vec![
+            (local.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string(),),
+            ("2024-03-01 12:00:00".to_string(),),
+        ]);
+        let (hours,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sensor_history_hourly")
+            .fetch_one(&*pool).await.unwrap();
+        assert_eq!(hours, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_history_moves_to_sensor_history() {
+        let path = storage::TempDbPath::new("migrations_legacy");
+        let options = sqlx::sqlite::SqliteConnectOptions::new().filename(path.as_str()).create_if_missing(true);
+        let legacy = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE history (id INTEGER PRIMARY KEY AUTOINCREMENT, timestamp TEXT NOT NULL, temperature REAL, humidity REAL, uv_index REAL)")
+            .execute(&legacy).await.unwrap();
+        sqlx::query("INSERT INTO history (timestamp, temperature, humidity, uv_index) VALUES ('2024-03-01 10:00:00', 31.5, 60.0, 2.0)")
+            .execute(&legacy).await.unwrap();
+        legacy.close().await;
+
+        let pool = storage::initialize_db(path.as_str()).await.unwrap();
+        let moved: (String, f64, f64, f64) = sqlx::query_as("SELECT timestamp, basking_temp, humidity, uv_1 FROM sensor_history")
+            .fetch_one(&pool).await.unwrap();
+        assert_eq!(moved, ("2024-03-01 10:00:00".to_string(), 31.5, 60.0, 2.0));
+
+        let (legacy_tables,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE name = 'history'")
+            .fetch_one(&pool).await.unwrap();
+        assert_eq!(legacy_tables, 0);
+        pool.close().await;
+    }
+}
@@ -1,10 +1,17 @@
 pub mod config;
 pub mod getData;
 pub mod lightControl;
+pub mod simulation;
 pub mod ledStrip;
+pub mod animations;
 pub mod web;
 pub mod display;
 pub mod cam;
 pub mod storage;
+pub mod migrations;
 pub mod models;
-pub mod logs;
\ No newline at end of file
+pub mod calendar;
+pub mod logs;
+pub mod alerts;
+pub mod watchdog;
+pub mod openapi;
\ No newline at end of file
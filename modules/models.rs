@@ -15,7 +15,7 @@ pub struct DefaultConfig {
     pub def_led_WW: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Schedule {
     pub week_number: i32,
     pub uv1_start: String,
@@ -31,6 +31,229 @@ pub struct Schedule {
     pub led_b: i32,
     pub led_cw: i32,
     pub led_ww: i32,
+    #[serde(default = "full_uv_level")]
+    pub uv1_level: i32,     // UV1 intensity in percent while on, needs a dimmable ballast (default: 100)
+    #[serde(default = "full_uv_level")]
+    pub uv2_level: i32,     // UV2 intensity in percent while on (default: 100)
+    #[serde(default)]
+    pub heat_target: Option<f64>, // Basking setpoint in °C held by the heat PID loop, needs gpio.heat_pwm_channel (default: unset = on/off)
+}
+
+fn full_uv_level() -> i32 {
+    100
+}
+
+/// Number of stored schedule weeks. ISO week 53 uses the last of them.
+pub const SCHEDULE_WEEKS: i32 = 52;
+
+/// LED window used by weeks without a stored schedule
+pub const DEFAULT_LED_START: &str = "07:00";
+pub const DEFAULT_LED_END: &str = "19:00";
+
+impl Schedule {
+    /// Returns the schedule week that applies on `date`: its ISO week number,
+    /// with week 53 of long years falling back to week 52
+    pub fn week_for<D: chrono::Datelike>(date: &D) -> i32 {
+        (date.iso_week().week() as i32).min(SCHEDULE_WEEKS)
+    }
+
+    /// Builds a week from the `[db]` defaults in config.toml, at full UV level
+    pub fn default_week(week_number: i32, defaults: &crate::modules::config::ScheduleConfig) -> Self {
+        Schedule {
+            week_number,
+            uv1_start: defaults.def_uv1_start.clone(),
+            uv1_end: defaults.def_uv1_end.clone(),
+            uv2_start: defaults.def_uv2_start.clone(),
+            uv2_end: defaults.def_uv2_end.clone(),
+            heat_start: defaults.def_heat_start.clone(),
+            heat_end: defaults.def_heat_end.clone(),
+            led_start: DEFAULT_LED_START.to_string(),
+            led_end: DEFAULT_LED_END.to_string(),
+            led_r: defaults.def_led_R,
+            led_g: defaults.def_led_G,
+            led_b: defaults.def_led_B,
+            led_cw: defaults.def_led_CW,
+            led_ww: defaults.def_led_WW,
+            uv1_level: full_uv_level(),
+            uv2_level: full_uv_level(),
+            heat_target: None,
+        }
+    }
+
+    /// Validates a schedule week before it is persisted.
+    ///
+    /// Checks that the week is within 1-52, every start/end time is a valid
+    /// `HH:MM` value, every LED channel is within 0-255, both UV levels
+    /// are within 0-100 and the heat target, if set, is within 0-60 °C.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=SCHEDULE_WEEKS).contains(&self.week_number) {
+            return Err(format!("Invalid week_number: {}. Must be in the range 1-52.", self.week_number));
+        }
+
+        for (field_name, value) in &[
+            ("uv1_start", &self.uv1_start),
+            ("uv1_end", &self.uv1_end),
+            ("uv2_start", &self.uv2_start),
+            ("uv2_end", &self.uv2_end),
+            ("heat_start", &self.heat_start),
+            ("heat_end", &self.heat_end),
+            ("led_start", &self.led_start),
+            ("led_end", &self.led_end),
+        ] {
+            if chrono::NaiveTime::parse_from_str(value, "%H:%M").is_err() {
+                return Err(format!("Invalid time for {} in week {}: {:?}", field_name, self.week_number, value));
+            }
+        }
+
+        for (field_name, &value) in &[
+            ("led_r", self.led_r),
+            ("led_g", self.led_g),
+            ("led_b", self.led_b),
+            ("led_cw", self.led_cw),
+            ("led_ww", self.led_ww),
+        ] {
+            if !(0..=255).contains(&value) {
+                return Err(format!("Invalid value for {} in week {}: {}", field_name, self.week_number, value));
+            }
+        }
+
+        for (field_name, &value) in &[("uv1_level", self.uv1_level), ("uv2_level", self.uv2_level)] {
+            if !(0..=100).contains(&value) {
+                return Err(format!("Invalid {} in week {}: {}. Must be a percentage in 0-100.", field_name, self.week_number, value));
+            }
+        }
+
+        if let Some(target) = self.heat_target {
+            if !(0.0..=60.0).contains(&target) {
+                return Err(format!("Invalid heat_target in week {}: {}. Must be in the range 0-60°C.", self.week_number, target));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Minutes per day the LEDs are on, from `led_start` to `led_end`.
+    ///
+    /// A window that ends before it starts never switches on, as in
+    /// `resolve_led_color`. None if either time doesn't parse.
+    pub fn led_on_minutes(&self) -> Option<i64> {
+        let start = chrono::NaiveTime::parse_from_str(&self.led_start, "%H:%M").ok()?;
+        let end = chrono::NaiveTime::parse_from_str(&self.led_end, "%H:%M").ok()?;
+        Some((end - start).num_minutes().max(0))
+    }
+
+    /// Checks that the daily LED window lasts between `min_hours` and `max_hours`, inclusive.
+    pub fn check_led_hours(&self, min_hours: f32, max_hours: f32) -> Result<(), String> {
+        let minutes = match self.led_on_minutes() {
+            Some(minutes) => minutes,
+            None => return Err(format!("Invalid LED window in week {}: {:?}-{:?}", self.week_number, self.led_start, self.led_end)),
+        };
+        let hours = minutes as f32 / 60.0;
+        if hours < min_hours || hours > max_hours {
+            return Err(format!(
+                "LED window of week {} ({}-{}) is on for {}h {:02}m. Must be between {} and {} hours.",
+                self.week_number, self.led_start, self.led_end, minutes / 60, minutes % 60, min_hours, max_hours
+            ));
+        }
+        Ok(())
+    }
+
+    /// Flattens the week into the day's switching events, sorted by time.
+    ///
+    /// Each window gives an "on" event at its start and an "off" event at its
+    /// end; the LED "on" event carries the week's color. Events at the same
+    /// minute keep the order UV1, UV2, heat, LED.
+    pub fn timeline(&self) -> Vec<TimelineEvent> {
+        let color = TimelineColor { r: self.led_r, g: self.led_g, b: self.led_b, ww: self.led_ww, cw: self.led_cw };
+        let windows = [
+            ("uv1", &self.uv1_start, &self.uv1_end, None),
+            ("uv2", &self.uv2_start, &self.uv2_end, None),
+            ("heat", &self.heat_start, &self.heat_end, None),
+            ("led", &self.led_start, &self.led_end, Some(color)),
+        ];
+
+        let mut events = Vec::with_capacity(windows.len() * 2);
+        for (device, start, end, color) in windows {
+            events.push(TimelineEvent { time: start.clone(), device, action: TimelineAction::On, color });
+            events.push(TimelineEvent { time: end.clone(), device, action: TimelineAction::Off, color: None });
+        }
+        // Validated times are zero-padded HH:MM, so they sort as strings
+        events.sort_by(|a, b| a.time.cmp(&b.time));
+        events
+    }
+}
+
+/// One switching event of `Schedule::timeline`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEvent {
+    /// "HH:MM"
+    pub time: String,
+    /// "uv1", "uv2", "heat" or "led"
+    pub device: &'static str,
+    pub action: TimelineAction,
+    /// Color the LEDs switch on with, only set on the LED "on" event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<TimelineColor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineAction {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimelineColor {
+    pub r: i32,
+    pub g: i32,
+    pub b: i32,
+    pub ww: i32,
+    pub cw: i32,
+}
+
+/// A named copy of the schedule weeks, e.g. a summer and a winter schedule,
+/// that can be loaded back into `schedule`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduleProfile {
+    pub name: String,                   // Identifier used in the URL, see `validate_name`
+    pub display_name: Option<String>,   // Shown instead of the name (default: the name)
+    pub color: Option<String>,          // "#RRGGBB" to tell profiles apart in the UI (default: none)
+    pub weeks: i64,                     // Number of weeks saved
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Longest profile name and display name, in characters
+pub const MAX_PROFILE_NAME_LENGTH: usize = 32;
+
+impl ScheduleProfile {
+    /// Checks that `name` is 1-32 lowercase ASCII letters, digits, '-' or '_'
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        let valid_chars = name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if name.is_empty() || name.len() > MAX_PROFILE_NAME_LENGTH || !valid_chars {
+            return Err(format!(
+                "Invalid profile name {:?}. Must be 1-{} lowercase letters, digits, '-' or '_'.", name, MAX_PROFILE_NAME_LENGTH
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the optional display name (1-32 characters) and color ("#RRGGBB")
+    pub fn validate_label(display_name: Option<&str>, color: Option<&str>) -> Result<(), String> {
+        if let Some(display_name) = display_name {
+            let length = display_name.trim().chars().count();
+            if length == 0 || length > MAX_PROFILE_NAME_LENGTH {
+                return Err(format!("display_name must be 1-{} characters, got: {:?}", MAX_PROFILE_NAME_LENGTH, display_name));
+            }
+        }
+        if let Some(color) = color {
+            let hex = color.strip_prefix('#').unwrap_or("");
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("color must be \"#RRGGBB\", got: {:?}", color));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,6 +267,157 @@ pub struct Override {
     pub active: bool,
 }
 
+/// Why a relay was switched, recorded with every relay state change
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum RelayReason {
+    /// The weekly schedule (or the default schedule from config.toml)
+    Schedule,
+    /// A stored manual setting, e.g. the LEDs disabled in the LED settings
+    Override,
+    /// Overheat protection or the temperature rise limit
+    Overheat,
+    /// A direct API request, e.g. LED power or a relay pulse
+    Manual,
+    /// An LED alert for a sensor that stopped answering
+    #[serde(rename = "sensor_fault")]
+    #[sqlx(rename = "sensor_fault")]
+    SensorFault,
+}
+
+/// What an API key may do; each scope includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum ApiScope {
+    /// GET requests only, e.g. a wall display
+    Read,
+    /// Switching lights and relays, triggering reads
+    Control,
+    /// Schedule, settings and API key changes
+    Admin,
+}
+
+impl ApiScope {
+    /// Whether a key with this scope may make a request needing `required`
+    pub fn allows(self, required: ApiScope) -> bool {
+        self >= required
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::Read => "read",
+            ApiScope::Control => "control",
+            ApiScope::Admin => "admin",
+        }
+    }
+}
+
+/// A stored API key; only a hash of the key itself is kept
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub scope: ApiScope,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A physical relay state change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayEvent {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub relay: String,
+    pub state: bool,
+    pub reason: RelayReason,
+}
+
+/// Snapshot of the overheat protection, for a live cooldown countdown
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OverheatStatus {
+    pub overheating: bool,
+    pub current_temp: f32,
+    pub threshold: f32,
+    /// Latest control-zone temperature, None before the first reading
+    pub control_temp: Option<f32>,
+    /// Control-zone overheat threshold, None when only the basking zone is protected
+    pub control_threshold: Option<f32>,
+    /// Seconds until heat may come back on, None when no cooldown is running
+    pub cooldown_remaining_seconds: Option<u64>,
+    /// Seconds the heat lamp has been on without a break, None while off
+    pub heat_on_seconds: Option<u64>,
+    /// Heat cut by the maximum on-time watchdog until `POST /api/overheat/resume`
+    pub heat_watchdog_tripped: bool,
+    pub last_overheat: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Read health of one sensor, for `GET /api/sensors/health`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SensorStatus {
+    /// "humidity", "uv1", "uv2" or a DS18B20 probe name such as "basking"
+    pub name: String,
+    /// Set after `sensors.fault_after` failed reads in a row, cleared by the next good read
+    pub faulted: bool,
+    pub consecutive_failures: u32,
+    /// Time of the last successful read, None if the sensor never answered
+    pub last_good: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// One entry of the merged activity feed (`GET /api/activity`), tagged with its source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActivityEntry {
+    Log {
+        timestamp: chrono::DateTime<chrono::Utc>,
+        level: String,
+        message: String,
+    },
+    Relay(RelayEvent),
+    Overheat(OverheatEvent),
+}
+
+impl ActivityEntry {
+    /// When the entry happened; an overheat event counts from when it tripped
+    pub fn timestamp(&self) -> chrono::DateTime<chrono::Utc> {
+        match self {
+            ActivityEntry::Log { timestamp, .. } => *timestamp,
+            ActivityEntry::Relay(event) => event.timestamp,
+            ActivityEntry::Overheat(event) => event.started_at,
+        }
+    }
+}
+
+/// Position of an entry in the activity feed, ordered by `timestamp`, then
+/// `id` for entries sharing a timestamp.
+///
+/// `id` is the entry's row id times 3 plus its source (0 logs, 1 relay
+/// events, 2 overheat events), so it is unique across the three tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ActivityCursor {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub id: i64,
+}
+
+/// How long a relay was energized over a period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DutyCycle {
+    pub relay: String,
+    pub on_minutes: f64,
+    /// Share of the period the relay was on, 0-100
+    pub percent: f64,
+}
+
+/// A single overheat event, from the moment protection tripped until it cleared
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverheatEvent {
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub ended_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub peak_temp: f32,
+    pub duration_seconds: Option<i64>,
+    pub zone: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Data {
     pub id: i32,
@@ -57,12 +431,117 @@ pub struct Data {
     pub time_heat: Option<String>,
     pub overheat: Option<String>,
 }
+/// A raw `sensor_history` row, as returned by `GET /api/sensors/recent`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SensorHistoryRow {
+    pub id: i64,
+    pub timestamp: chrono::NaiveDateTime,   // Local time the readings were taken
+    pub basking_temp: Option<f64>,
+    pub control_temp: Option<f64>,
+    pub cool_temp: Option<f64>,
+    pub humidity: Option<f64>,
+    pub uv_1: Option<f64>,
+    pub uv_2: Option<f64>,
+}
+
 #[derive(Debug)]
 pub struct SensorReadings {
     pub timestamp: chrono::NaiveDateTime,
     pub basking_temp: Option<f32>,
+    pub control_temp: Option<f32>,
     pub cool_temp: Option<f32>,
     pub humidity: Option<f32>,
     pub uv_1: Option<f32>,
     pub uv_2: Option<f32>,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::test_config;
+
+    #[test]
+    fn test_week_timeline_is_sorted() {
+        let config = test_config();
+
+        let mut week = Schedule::default_week(12, &config.db);
+        week.uv1_start = "09:00".to_string();
+        week.uv1_end = "17:00".to_string();
+        week.uv2_start = "10:30".to_string();
+        week.uv2_end = "15:45".to_string();
+        week.heat_start = "07:00".to_string();
+        week.heat_end = "19:30".to_string();
+        week.led_start = "07:00".to_string();
+        week.led_end = "20:00".to_string();
+
+        let events: Vec<(String, &str, TimelineAction)> = week.timeline()
+            .into_iter()
+            .map(|event| (event.time, event.device, event.action))
+            .collect();
+        let expected = [
+            ("07:00", "heat", TimelineAction::On),
+            ("07:00", "led", TimelineAction::On),
+            ("09:00", "uv1", TimelineAction::On),
+            ("10:30", "uv2", TimelineAction::On),
+            ("15:45", "uv2", TimelineAction::Off),
+            ("17:00", "uv1", TimelineAction::Off),
+            ("19:30", "heat", TimelineAction::Off),
+            ("20:00", "led", TimelineAction::Off),
+        ];
+        assert_eq!(events, expected.map(|(time, device, action)| (time.to_string(), device, action)));
+
+        let led_on = week.timeline().into_iter().find(|event| event.device == "led").unwrap();
+        assert_eq!(led_on.color.map(|c| c.r), Some(week.led_r));
+    }
+
+    #[test]
+    fn test_schedule_uv_level_validated() {
+        let config = test_config();
+        let mut week = Schedule {
+            week_number: 10,
+            uv1_start: config.db.def_uv1_start.clone(),
+            uv1_end: config.db.def_uv1_end.clone(),
+            uv2_start: config.db.def_uv2_start.clone(),
+            uv2_end: config.db.def_uv2_end.clone(),
+            heat_start: config.db.def_heat_start.clone(),
+            heat_end: config.db.def_heat_end.clone(),
+            led_start: "07:00".to_string(),
+            led_end: "19:00".to_string(),
+            led_r: 0, led_g: 0, led_b: 0, led_cw: 0, led_ww: 0,
+            uv1_level: 60,
+            uv2_level: 100,
+            heat_target: None,
+        };
+        assert!(week.validate().is_ok());
+
+        week.uv2_level = 101;
+        assert!(week.validate().unwrap_err().contains("uv2_level"));
+
+        week.uv2_level = 100;
+        week.heat_target = Some(65.0);
+        assert!(week.validate().unwrap_err().contains("heat_target"));
+    }
+
+    #[test]
+    fn test_led_hours_accepted_at_both_bounds() {
+        let config = test_config();
+        let (min_hours, max_hours) = (4.0, 16.0);
+        let mut week = Schedule::default_week(20, &config.db);
+
+        for (start, end, ok) in [
+            ("08:00", "12:00", true),   // exactly 4h
+            ("08:00", "11:59", false),
+            ("04:00", "20:00", true),   // exactly 16h
+            ("04:00", "20:01", false),
+            ("19:00", "07:00", false),  // ends before it starts, never on
+        ] {
+            week.led_start = start.to_string();
+            week.led_end = end.to_string();
+            assert_eq!(week.check_led_hours(min_hours, max_hours).is_ok(), ok, "{}-{}", start, end);
+        }
+
+        week.led_end = "20:01".to_string();
+        let err = week.check_led_hours(min_hours, max_hours).unwrap_err();
+        assert!(err.contains("16h 01m"), "{}", err);
+    }
+}
@@ -0,0 +1,958 @@
+// modules/openapi.rs
+//! Hand-written OpenAPI 3 description of the web API, served at `/api/openapi.json`.
+//!
+//! The schemas mirror the `serde` request/response structs in `web.rs` and
+//! `models.rs`. When a handler or one of those structs changes, update the
+//! matching entry here.
+
+use serde_json::{json, Map, Value};
+
+/// Builds the OpenAPI document for every route registered in `web::create_router`.
+///
+/// # Returns
+///
+/// The OpenAPI 3.0 document as JSON
+pub fn spec() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Terra-Control API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Terrarium lighting, heating and monitoring controller. Temperatures are Celsius unless `units=f` is passed."
+        },
+        "paths": paths(),
+        // Keys are only checked with [web] require_api_key on, hence the empty alternative
+        "security": [{}, { "bearerAuth": [] }, { "apiKeyHeader": [] }],
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+                "apiKeyHeader": { "type": "apiKey", "in": "header", "name": "x-api-key" }
+            },
+            "responses": {
+                "Error": {
+                    "description": "Request failed",
+                    "content": { "application/json": { "schema": schema_ref("Error") } }
+                }
+            }
+        }
+    })
+}
+
+// ===== Paths =====
+
+fn paths() -> Value {
+    json!({
+        "/api/schedule": {
+            "get": operation("Schedule", "Get the schedule for every stored week", vec![],
+                ok_json("Stored weeks", array_of("Schedule"))),
+            "post": with_status(
+                with_body(
+                    operation("Schedule", "Create or update schedule weeks; every week is validated before any is written", vec![force_param()],
+                        ok_json("Schedule updated", string_schema())),
+                    array_of("Schedule")),
+                "400", "Invalid week, or an LED window outside `[led] min_on_hours`/`max_on_hours` without force"),
+        },
+        "/api/schedule/preview": {
+            "get": operation("Schedule", "Preview the relay states and LED color the schedule applies at an instant",
+                vec![query_param("at", true, "RFC 3339 timestamp", json!({ "type": "string", "format": "date-time" }))],
+                ok_json("Scheduled states", schema_ref("SchedulePreviewResponse"))),
+        },
+        "/api/schedule/simulate": {
+            "post": with_status(
+                with_status(
+                    operation("Schedule", "Replay today's schedule on the loads at speed× real time, holding the control loops meanwhile",
+                        vec![query_param("speed", true, "Virtual seconds per real second, at most 240 (a schedule minute per 250ms step, a day in 6 minutes)",
+                            json!({ "type": "integer", "minimum": 1, "maximum": 240 }))],
+                        ok_json("Simulation started", schema_ref("SimulateResponse"))),
+                    "400", "speed out of range or a simulation is already running"),
+                "403", "[main] simulation is off"),
+        },
+        "/api/schedule/simulate/cancel": {
+            "post": with_status(
+                operation("Schedule", "Stop the running schedule simulation; the control loops take over from their next tick",
+                    vec![], ok_json("Simulation cancelled", schema_ref("CancelSimulationResponse"))),
+                "400", "No simulation is running"),
+        },
+        "/api/schedule/timeline": {
+            "get": with_status(
+                operation("Schedule", "Get a stored week as the day's on/off events sorted by time, e.g. for a Gantt chart",
+                    vec![query_param("week", true, "Week number (1-52)", json!({ "type": "integer", "minimum": 1, "maximum": 52 }))],
+                    ok_json("Events", array_of("TimelineEvent"))),
+                "404", "week outside 1..52 or not stored"),
+        },
+        "/api/schedule/export.ics": {
+            "get": operation("Schedule", "Export this year's stored weeks as iCalendar events, one daily-repeating VEVENT per device on-window and week",
+                vec![], ok_file("iCalendar file", "text/calendar")),
+        },
+        "/api/schedule/profiles": {
+            "get": operation("Schedule", "List the saved schedule profiles", vec![], ok_json("Profiles", array_of("ScheduleProfile"))),
+            "post": with_status(
+                with_body(
+                    operation("Schedule", "Save the current schedule weeks as a named profile, replacing one of the same name", vec![],
+                        ok_json("Saved profile", schema_ref("ScheduleProfile"))),
+                    schema_ref("SaveProfileRequest")),
+                "400", "Invalid name, display_name or color"),
+        },
+        "/api/schedule/profiles/{name}/activate": {
+            "post": with_status(
+                operation("Schedule", "Replace every stored week with the weeks of a saved profile",
+                    vec![json!({ "name": "name", "in": "path", "required": true, "schema": profile_name() })],
+                    ok_json("Profile activated", string_schema())),
+                "404", "No profile with this name"),
+        },
+        "/api/schedule/{week}": {
+            "get": with_status(
+                operation("Schedule", "Get the schedule for a single week", vec![week_param()],
+                    ok_json("Stored week", schema_ref("Schedule"))),
+                "404", "week outside 1..52 or not stored"),
+            "put": with_status(
+                with_status(
+                    with_body(
+                        operation("Schedule", "Create or replace a single week; `week_number` must match the path", vec![week_param(), force_param()],
+                            ok_json("Stored week", schema_ref("Schedule"))),
+                        schema_ref("Schedule")),
+                    "400", "Invalid week, an LED window outside `[led] min_on_hours`/`max_on_hours` without force, or `week_number` does not match the path"),
+                "404", "week outside 1..52"),
+        },
+        "/api/led/power": {
+            "post": with_body(
+                operation("LED", "Switch the LED strip power relay", vec![], ok_json("Power state updated", string_schema())),
+                schema_ref("LEDPowerRequest")),
+        },
+        "/api/led/off": {
+            "post": operation("LED", "Make it dark: stop the alert pulse and any animation, power the strip off and disable it in the stored settings, manual override cleared. Sets the `led_forced_off` setting, so it stays off over every `led.override_precedence` and in away mode until a color, color temperature, animation or power on is set",
+                vec![], ok_json("LED state afterwards", schema_ref("LEDStatus"))),
+        },
+        "/api/led/color": {
+            "post": with_body(
+                operation("LED", "Set the LED strip color", vec![], ok_json("Color updated", string_schema())),
+                schema_ref("LEDColorRequest")),
+        },
+        "/api/led/cct": {
+            "post": with_body(
+                operation("LED", "Show a white color temperature using only the WW/CW channels", vec![],
+                    ok_json("Color temperature updated", string_schema())),
+                schema_ref("LEDCctRequest")),
+        },
+        "/api/led/status": {
+            "get": operation("LED", "Get the LED strip state", vec![], ok_json("LED state", schema_ref("LEDStatus"))),
+        },
+        "/api/led/reinit": {
+            "post": with_status(
+                operation("LED", "Retry opening the LED strip after it was disabled by repeated init failures", vec![],
+                    ok_json("Strip initialized", string_schema())),
+                "500", "The strip still can't be opened, e.g. SPI is not enabled"),
+        },
+        "/api/led/animation": {
+            "post": with_status(
+                with_body(
+                    operation("LED", "Start an ambient animation; manual color or power commands stop it", vec![],
+                        ok_json("Animation started", string_schema())),
+                    schema_ref("LEDAnimationRequest")),
+                "400", "period_seconds outside 1-120"),
+            "delete": operation("LED", "Stop the running animation and restore the state from before it", vec![],
+                ok_json("Animation stopped", string_schema())),
+        },
+        "/api/led/alert/ack": {
+            "post": with_status(
+                operation("LED", "Stop the alert pulse raised by overheat or a sensor fault and restore the scheduled color", vec![],
+                    ok_json("Alert acknowledged", schema_ref("LEDAlertAckResponse"))),
+                "400", "No LED alert active"),
+        },
+        "/api/led/natural": {
+            "post": with_body(
+                operation("LED", "Configure natural light mode", vec![], ok_json("Settings updated", string_schema())),
+                schema_ref("NaturalLightRequest")),
+        },
+        "/api/led/natural/curve": {
+            "get": with_status(
+                operation("LED", "Get the natural light color across today, for drawing the day's color progression",
+                    vec![query_param("step_minutes", false, "Minutes between samples (1-1440, default 15)",
+                        json!({ "type": "integer", "minimum": 1, "maximum": 1440 }))],
+                    ok_json("Samples from 00:00, at most one per minute", array_of("NaturalCurvePoint"))),
+                "400", "step_minutes outside 1..1440"),
+        },
+        "/api/led/presets": {
+            "get": operation("LED", "Get the natural light presets", vec![],
+                ok_json("Presets", schema_ref("NaturalLightPresets"))),
+            "post": with_body(
+                operation("LED", "Set the natural light presets", vec![], ok_json("Presets updated", string_schema())),
+                schema_ref("NaturalLightPresets")),
+        },
+        "/api/values": {
+            "get": operation("Monitoring", "Get the latest sensor readings and relay states; with `verbose=true` each reading carries its unit, plausible range (`[sensors]` *_range) and staleness",
+                vec![
+                    units_param(),
+                    query_param("verbose", false, "Expand readings into `SensorValue` objects (default false)", boolean()),
+                ],
+                ok_json("Current values", json!({ "oneOf": [schema_ref("CurrentValuesResponse"), schema_ref("VerboseValuesResponse")] }))),
+        },
+        "/api/sensors/read": {
+            "post": with_status(
+                operation("Monitoring", "Read every sensor now, store the row and return the fresh values", vec![units_param()],
+                    ok_json("Fresh values", schema_ref("CurrentValuesResponse"))),
+                "429", "Called again within `web.sensor_read_cooldown`"),
+        },
+        "/api/sensors/health": {
+            "get": operation("Monitoring", "Get every sensor's read health; a sensor is faulted after `sensors.fault_after` failed reads in a row", vec![],
+                ok_json("Sensor health", array_of("SensorStatus"))),
+        },
+        "/api/sensors/recent": {
+            "get": operation("Monitoring", "Get the newest raw sensor_history rows, newest first, with every column as stored",
+                vec![query_param("count", false, "Rows to return, capped at 500 (default 20)", json!({ "type": "integer", "minimum": 1, "maximum": 500 }))],
+                ok_json("Rows", array_of("SensorHistoryRow"))),
+        },
+        "/api/graph/today": {
+            "get": operation("Monitoring", "Get today's readings for the dashboard graph", vec![units_param(), smooth_param()],
+                ok_json("Graph points", graph_data())),
+        },
+        "/api/graph/yesterday": {
+            "get": operation("Monitoring", "Get yesterday's readings for the dashboard graph", vec![units_param(), smooth_param()],
+                ok_json("Graph points", graph_data())),
+        },
+        "/api/graph/last/{hours}": {
+            "get": with_status(
+                operation("Monitoring", "Get readings from the last N hours, oldest first; `time` is `YYYY-MM-DD HH:MM`. Ranges over `get_data.rollup_after_hours` (default 48) return hourly averages",
+                    vec![
+                        json!({
+                            "name": "hours",
+                            "in": "path",
+                            "required": true,
+                            "description": "Window length in hours",
+                            "schema": { "type": "integer", "minimum": 1, "maximum": 168 }
+                        }),
+                        units_param(),
+                        smooth_param(),
+                    ],
+                    ok_json("Graph points", graph_data())),
+                "400", "hours outside 1..168, or an invalid smooth"),
+        },
+        "/api/graph/compare": {
+            "get": with_status(
+                operation("Monitoring", "Overlay the readings of up to 7 days, averaged into time-of-day buckets labelled `HH:MM`; a day without readings gets an empty series",
+                    vec![
+                        query_param("dates", true, "Comma-separated dates, e.g. `2024-01-01,2024-01-02`", string_schema()),
+                        query_param("bucket", false, "Bucket width in minutes (1-240, default 15)", json!({ "type": "integer" })),
+                        units_param(),
+                        smooth_param(),
+                    ],
+                    ok_json("Series per date", schema_ref("GraphCompareResponse"))),
+                "400", "Malformed date, more than 7 dates, bucket outside 1..240 or an invalid smooth"),
+        },
+        "/api/data/download": {
+            "get": with_status(
+                operation("Monitoring", "Download sensor readings between two dates as CSV",
+                    export_range(),
+                    ok_file("CSV export", "text/csv")),
+                "400", "Malformed date, or start after end"),
+        },
+        "/api/data/export.jsonl": {
+            "get": with_status(
+                operation("Monitoring", "Stream sensor readings between two dates as JSON Lines, one object per reading",
+                    export_range(),
+                    ok_file("JSON Lines export", "application/x-ndjson")),
+                "400", "Malformed date, or start after end"),
+        },
+        "/api/data/import": {
+            "post": with_status(with_status(
+                with_csv_body(
+                    operation("Monitoring", "Import sensor readings from a CSV in the export format, in one transaction",
+                        vec![query_param("on_duplicate", false, "What to do with rows whose timestamp is already stored (default skip)",
+                            json!({ "type": "string", "enum": ["skip", "upsert"] }))],
+                        ok_json("Import summary", schema_ref("ImportSummary")))),
+                "400", "Malformed CSV; the message lists the offending line numbers"),
+                "413", "CSV larger than [web] max_import_body_kb"),
+        },
+        "/api/overheat/history": {
+            "get": operation("Monitoring", "Get recorded overheat events, newest first",
+                vec![query_param("limit", false, "Maximum events returned (1-1000, default 100)", json!({ "type": "integer" }))],
+                ok_json("Overheat events", array_of("OverheatEvent"))),
+        },
+        "/api/overheat/status": {
+            "get": operation("Monitoring", "Get the overheat protection state and the remaining cooldown",
+                vec![],
+                ok_json("Overheat status", schema_ref("OverheatStatus"))),
+        },
+        "/api/overheat/resume": {
+            "post": with_status(
+                operation("Monitoring", "Re-enable the heat lamp after the maximum on-time watchdog (`light_control.max_heat_on_time`) cut it; heat returns with the next scheduled update",
+                    vec![],
+                    ok_json("Overheat status after the resume", schema_ref("OverheatStatus"))),
+                "400", "The watchdog has not tripped"),
+        },
+        "/api/duty": {
+            "get": operation("Monitoring", "Get how long the heat and UV relays were on during a day, from the relay event log",
+                vec![query_param("date", false, "Local calendar day (default today, measured up to now)",
+                    json!({ "type": "string", "format": "date" }))],
+                ok_json("Duty cycle per relay", schema_ref("DutyResponse"))),
+        },
+        "/api/system/status": {
+            "get": operation("System", "Get controller status", vec![],
+                ok_json("System status", schema_ref("SystemStatusResponse"))),
+        },
+        "/api/system/hardware": {
+            "get": operation("System", "Get the relay pins, sensor buses and addresses and LED strip SPI link from the running config, defaults applied", vec![],
+                ok_json("Hardware wiring", schema_ref("HardwareResponse"))),
+        },
+        "/api/logs": {
+            "get": operation("System", "Get log entries, newest first",
+                vec![
+                    query_param("filter", false, "Level filter", json!({ "type": "string", "enum": ["all", "info", "warning", "error"] })),
+                    query_param("limit", false, "Maximum entries returned (default 50)", json!({ "type": "integer" })),
+                ],
+                ok_json("Log entries", array_of("LogEntry"))),
+        },
+        "/api/logs/download": {
+            "get": operation("System", "Download the log files and database log as a zip archive", vec![],
+                ok_file("Zip archive", "application/zip")),
+        },
+        "/api/openapi.json": {
+            "get": operation("System", "Get this OpenAPI document", vec![],
+                ok_json("OpenAPI document", json!({ "type": "object" }))),
+        },
+        "/api/relay/pulse": {
+            "post": with_status(
+                with_body(
+                    operation("System", "Switch the aux or fan relay on for a number of seconds", vec![],
+                        ok_json("Pulse started", string_schema())),
+                    schema_ref("RelayPulseRequest")),
+                "400", "Relay not pulsable or not configured, or seconds outside 1..`gpio.max_pulse_seconds`"),
+        },
+        "/api/relay/history": {
+            "get": operation("System", "Get recorded relay state changes and their reasons, newest first",
+                vec![query_param("limit", false, "Maximum events returned (1-1000, default 100)", json!({ "type": "integer" }))],
+                ok_json("Relay events", array_of("RelayEvent"))),
+        },
+        "/api/alerts": {
+            "get": operation("System", "Get the alert conditions present now (overheat, heat watchdog, [thresholds] ranges), oldest first",
+                vec![], ok_json("Active alerts", array_of("AlertStatus"))),
+        },
+        "/api/alerts/{id}/ack": {
+            "post": with_status(
+                operation("System", "Stop logging an alert on every cycle until its condition clears; it notifies again if it comes back",
+                    vec![json!({ "name": "id", "in": "path", "required": true, "description": "Alert id, e.g. overheat or high_basking", "schema": { "type": "string" } })],
+                    ok_json("Acknowledged alert", schema_ref("AlertStatus"))),
+                "404", "No active alert with this id"),
+        },
+        "/api/activity": {
+            "get": operation("System", "Get log lines, relay changes and overheat events merged into one feed, newest first",
+                vec![
+                    query_param("limit", false, "Maximum entries returned (1-200, default 50)", json!({ "type": "integer" })),
+                    query_param("before", false, "Only entries older than this, e.g. the previous page's next_before", json!({ "type": "string", "format": "date-time" })),
+                    query_param("before_id", false, "With before, also the entries at that time with a lower cursor id, e.g. the previous page's next_before_id", json!({ "type": "integer" })),
+                ],
+                ok_json("Activity page", schema_ref("ActivityPage"))),
+        },
+        "/api/system/away": {
+            "get": operation("System", "Get whether away mode is on", vec![],
+                ok_json("Away mode", schema_ref("AwayMode"))),
+            "post": with_body(
+                operation("System", "Switch away mode on or off; persisted across restarts. While on, manual LED overrides are ignored, temperature warnings fire `away.alert_margin` earlier and snapshots are saved every `away.snapshot_interval`", vec![],
+                    ok_json("Away mode", schema_ref("AwayMode"))),
+                schema_ref("AwayMode")),
+        },
+        "/api/system/resume": {
+            "post": with_status(
+                operation("System", "Start the control loops after a `main.boot_behavior = \"safe_off\"` boot; the loads follow the schedule from the next tick", vec![],
+                    ok_json("Resumed", schema_ref("ResumeResponse"))),
+                "400", "The control loops are not held"),
+        },
+        "/api/settings": {
+            "get": operation("System", "Get every known setting as a key/value map", vec![],
+                ok_json("Settings", json!({ "type": "object", "additionalProperties": { "type": "string" } }))),
+        },
+        "/api/settings/{key}": {
+            "get": with_status(
+                operation("System", "Get a single setting", vec![setting_key_param()],
+                    ok_json("Setting", schema_ref("Setting"))),
+                "404", "Unknown setting"),
+            "put": with_status(
+                with_status(
+                    with_body(
+                        operation("System", "Set and persist a single setting", vec![setting_key_param()],
+                            ok_json("Setting", schema_ref("Setting"))),
+                        schema_ref("SettingRequest")),
+                    "400", "Invalid value for the setting"),
+                "404", "Unknown setting"),
+        },
+        "/api/system/reset": {
+            "post": operation("System", "Reset all 52 schedule weeks and the LED settings to the config.toml defaults in one transaction; sensor history and logs are kept (admin scope)", vec![],
+                ok_json("Rows reset", schema_ref("ResetResponse"))),
+        },
+        "/api/keys": {
+            "get": operation("System", "List API keys, revoked ones included (admin scope)", vec![],
+                ok_json("API keys", array_of("ApiKey"))),
+            "post": with_status(
+                with_body(
+                    operation("System", "Create an API key; the key is only returned by this request (admin scope)", vec![],
+                        ok_json("Created key", schema_ref("CreatedApiKey"))),
+                    schema_ref("CreateApiKeyRequest")),
+                "400", "Empty or overlong name"),
+        },
+        "/api/keys/{id}": {
+            "delete": with_status(
+                operation("System", "Revoke an API key (admin scope)",
+                    vec![json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" } })],
+                    ok_json("Key revoked", string_schema())),
+                "404", "No active key with this id"),
+        },
+        "/api/camera/status": {
+            "get": operation("Camera", "Get camera availability", vec![],
+                ok_json("Camera status", schema_ref("CameraStatusResponse"))),
+        },
+        "/api/camera/snapshot": {
+            "get": with_status(
+                operation("Camera", "Capture a JPEG snapshot, optionally re-encoded and scaled down",
+                    vec![
+                        query_param("quality", false, "JPEG quality (1-100, default 90)", integer()),
+                        query_param("max_width", false, "Widest image in pixels (32-4096), the aspect ratio is kept", integer()),
+                    ],
+                    ok_file("JPEG image", "image/jpeg")),
+                "400", "quality or max_width out of range"),
+        },
+        "/api/camera/burst": {
+            "get": with_status(
+                with_status(
+                    operation("Camera", "Capture a burst of snapshots, returned as a zip of `burst_01.jpg`, `burst_02.jpg`, ...",
+                        vec![
+                            query_param("count", false, "Frames to capture (1-20, default 5)", integer()),
+                            query_param("interval_ms", false, "Milliseconds between frames (50-5000, default 200)", integer()),
+                        ],
+                        ok_file("Zip archive", "application/zip")),
+                    "400", "count or interval_ms out of range"),
+                "404", "No camera"),
+        },
+        "/api/camera/stream": {
+            "get": with_status(
+                operation("Camera", "Get the MJPEG stream URL, served on `web.camera_port`; with `web.require_api_key` append `?key=` with a read key", vec![],
+                    ok_json("Stream URL", schema_ref("CameraStreamResponse"))),
+                "404", "No camera, or `web.camera_port` is unset"),
+        },
+    })
+}
+
+// ===== Schemas =====
+
+fn schemas() -> Value {
+    let color = || object(&[
+        ("r", channel()), ("g", channel()), ("b", channel()), ("ww", channel()), ("cw", channel()),
+    ]);
+
+    json!({
+        "Error": object(&[("error", string_schema())]),
+        "TemperatureUnit": { "type": "string", "enum": ["c", "f"] },
+        "Schedule": object(&[
+            ("week_number", json!({ "type": "integer", "minimum": 1, "maximum": 52 })),
+            ("uv1_start", time()), ("uv1_end", time()),
+            ("uv2_start", time()), ("uv2_end", time()),
+            ("heat_start", time()), ("heat_end", time()),
+            ("led_start", time()), ("led_end", time()),
+            ("led_r", channel()), ("led_g", channel()), ("led_b", channel()),
+            ("led_cw", channel()), ("led_ww", channel()),
+            ("uv1_level", json!({ "type": "integer", "minimum": 0, "maximum": 100, "default": 100 })),
+            ("uv2_level", json!({ "type": "integer", "minimum": 0, "maximum": 100, "default": 100 })),
+            ("heat_target", nullable(json!({ "type": "number", "minimum": 0, "maximum": 60 }))),
+        ]),
+        "SaveProfileRequest": object(&[
+            ("name", profile_name()),
+            ("display_name", nullable(json!({ "type": "string", "maxLength": 32 }))),
+            ("color", nullable(profile_color())),
+        ]),
+        "ScheduleProfile": object(&[
+            ("name", profile_name()),
+            ("display_name", nullable(json!({ "type": "string" }))),
+            ("color", nullable(profile_color())),
+            ("weeks", json!({ "type": "integer", "description": "Number of weeks saved" })),
+            ("saved_at", json!({ "type": "string", "format": "date-time" })),
+        ]),
+        "SimulateResponse": object(&[
+            ("speed", json!({ "type": "integer" })),
+            ("duration_seconds", json!({ "type": "integer", "description": "Real time the simulated day takes" })),
+        ]),
+        "CancelSimulationResponse": object(&[("cancelled", boolean())]),
+        "TimelineEvent": object(&[
+            ("time", time()),
+            ("device", json!({ "type": "string", "enum": ["uv1", "uv2", "heat", "led"] })),
+            ("action", json!({ "type": "string", "enum": ["on", "off"] })),
+            ("color", json!({ "$ref": "#/components/schemas/LEDColorRequest", "description": "LED on event only" })),
+        ]),
+        "SchedulePreviewResponse": object(&[
+            ("at", json!({ "type": "string", "format": "date-time" })),
+            ("week", json!({ "type": "integer" })),
+            ("uv1_on", boolean()), ("uv2_on", boolean()), ("heat_on", boolean()), ("led_on", boolean()),
+            ("led", nullable(schema_ref("LEDColorRequest"))),
+        ]),
+        "LEDPowerRequest": object(&[("power", boolean())]),
+        "LEDColorRequest": color(),
+        "LEDStatus": object(&[
+            ("power", boolean()),
+            ("r", channel()), ("g", channel()), ("b", channel()), ("ww", channel()), ("cw", channel()),
+            ("use_natural", boolean()),
+            ("season_weight", weight()),
+            ("cct", nullable(json!({ "type": "integer", "description": "Kelvin, when only white channels are lit" }))),
+            ("available", json!({ "type": "boolean", "description": "False once the strip is disabled after repeated init failures" })),
+            ("animation", nullable(animation_kind())),
+            ("estimated_current_ma", json!({ "type": "number", "description": "Estimated strip draw from [led.power] and ic_count, 0 while the relay is off" })),
+            ("alert", nullable(led_alert())),
+            ("caps", schema_ref("LEDChannelCaps")),
+        ]),
+        "LEDChannelCaps": object(&[
+            ("r", channel()), ("g", channel()), ("b", channel()), ("ww", channel()), ("cw", channel()),
+        ]),
+        "LEDAlertAckResponse": object(&[("acknowledged", led_alert())]),
+        "LEDAnimationRequest": object(&[
+            ("animation", animation_kind()),
+            ("color", nullable(schema_ref("LEDColorRequest"))),
+            ("period_seconds", json!({ "type": "number", "minimum": 1, "maximum": 120, "description": "Breathing only (default 6)" })),
+        ]),
+        "LEDCctRequest": object(&[
+            ("kelvin", json!({ "type": "integer", "description": "Clamped to [led] ww_kelvin..cw_kelvin" })),
+            ("brightness", json!({ "type": "integer", "minimum": 0, "maximum": 100 })),
+        ]),
+        "RelayPulseRequest": object(&[
+            ("relay", json!({ "type": "string", "enum": ["aux", "fan"] })),
+            ("seconds", json!({ "type": "integer", "minimum": 1, "description": "At most [gpio] max_pulse_seconds (default 30)" })),
+        ]),
+        "RelayEvent": object(&[
+            ("timestamp", json!({ "type": "string", "format": "date-time" })),
+            ("relay", json!({ "type": "string", "enum": ["uv1", "uv2", "heat", "led", "fan", "aux"] })),
+            ("state", boolean()),
+            ("reason", json!({ "type": "string", "enum": ["schedule", "override", "overheat", "manual", "sensor_fault"] })),
+        ]),
+        "AlertStatus": object(&[
+            ("id", json!({ "type": "string", "description": "Condition type: overheat, heat_watchdog, or high_/low_ plus a [thresholds] channel" })),
+            ("level", json!({ "type": "string", "enum": ["WARNING", "ERROR"] })),
+            ("message", string_schema()),
+            ("since", json!({ "type": "string", "format": "date-time" })),
+            ("acknowledged", boolean()),
+        ]),
+        "ActivityPage": object(&[
+            ("entries", json!({ "type": "array", "items": schema_ref("ActivityEntry") })),
+            ("next_before", nullable(json!({ "type": "string", "format": "date-time", "description": "Pass as before for the next page" }))),
+            ("next_before_id", nullable(json!({ "type": "integer", "description": "Pass as before_id for the next page" }))),
+        ]),
+        "ActivityEntry": json!({
+            "description": "A log line (timestamp, level, message), a RelayEvent or an OverheatEvent, with a type discriminator",
+            "type": "object",
+            "required": ["type"],
+            "properties": { "type": { "type": "string", "enum": ["log", "relay", "overheat"] } },
+            "additionalProperties": true,
+        }),
+        "ApiKey": object(&[
+            ("id", json!({ "type": "integer" })),
+            ("name", string_schema()),
+            ("scope", api_scope()),
+            ("created_at", json!({ "type": "string", "format": "date-time" })),
+            ("revoked_at", nullable(json!({ "type": "string", "format": "date-time" }))),
+        ]),
+        "CreateApiKeyRequest": object(&[("name", string_schema()), ("scope", api_scope())]),
+        "CreatedApiKey": json!({
+            "allOf": [
+                schema_ref("ApiKey"),
+                object(&[("key", json!({ "type": "string", "description": "Not stored, can't be retrieved again" }))]),
+            ]
+        }),
+        "AwayMode": object(&[("away", boolean())]),
+        "ResumeResponse": object(&[("resumed", boolean())]),
+        "Setting": object(&[("key", string_schema()), ("value", string_schema())]),
+        "SettingRequest": object(&[("value", string_schema())]),
+        "NaturalCurvePoint": object(&[
+            ("time", time()),
+            ("r", channel()), ("g", channel()), ("b", channel()), ("ww", channel()), ("cw", channel()),
+        ]),
+        "NaturalLightRequest": object(&[
+            ("override_settings", boolean()),
+            ("season_weight", weight()),
+        ]),
+        "NaturalLightPresets": object(&[
+            ("morning_r", channel()), ("morning_g", channel()), ("morning_b", channel()),
+            ("morning_ww", channel()), ("morning_cw", channel()),
+            ("noon_r", channel()), ("noon_g", channel()), ("noon_b", channel()),
+            ("noon_ww", channel()), ("noon_cw", channel()),
+            ("evening_r", channel()), ("evening_g", channel()), ("evening_b", channel()),
+            ("evening_ww", channel()), ("evening_cw", channel()),
+        ]),
+        "CurrentValuesResponse": object(&[
+            ("instance", string_schema()),
+            ("timestamp", json!({ "type": "string", "format": "date-time" })),
+            ("unit", schema_ref("TemperatureUnit")),
+            ("baskingTemp", number()), ("controlTemp", number()), ("coolZoneTemp", number()),
+            ("tempTrend", nullable(json!({ "type": "number", "description": "Basking temperature change per minute" }))),
+            ("humidity", number()),
+            ("uv1", number()), ("uv2", number()),
+            ("probes", json!({ "type": "object", "additionalProperties": nullable(number()), "description": "DS18B20 probes beyond basking, control and cool, by name; null after a failed read" })),
+            ("uv1_on", boolean()), ("uv2_on", boolean()), ("heat_on", boolean()), ("led_on", boolean()),
+            ("overheat", boolean()),
+        ]),
+        "SensorValue": object(&[
+            ("value", number()),
+            ("unit", string_schema()),
+            ("min", number()),
+            ("max", number()),
+            ("stale", json!({ "type": "boolean", "description": "No successful read of this sensor for three collection intervals" })),
+        ]),
+        "VerboseValuesResponse": object(&[
+            ("instance", string_schema()),
+            ("timestamp", json!({ "type": "string", "format": "date-time" })),
+            ("unit", schema_ref("TemperatureUnit")),
+            ("baskingTemp", schema_ref("SensorValue")), ("controlTemp", schema_ref("SensorValue")), ("coolZoneTemp", schema_ref("SensorValue")),
+            ("tempTrend", nullable(json!({ "type": "number", "description": "Basking temperature change per minute" }))),
+            ("humidity", schema_ref("SensorValue")),
+            ("uv1", schema_ref("SensorValue")), ("uv2", schema_ref("SensorValue")),
+            ("probes", json!({ "type": "object", "additionalProperties": nullable(schema_ref("SensorValue")) })),
+            ("uv1_on", boolean()), ("uv2_on", boolean()), ("heat_on", boolean()), ("led_on", boolean()),
+            ("overheat", boolean()),
+        ]),
+        "GraphDataPoint": object(&[
+            ("time", json!({ "type": "string", "description": "HH:MM" })),
+            ("temperature", number()), ("controlTemp", number()), ("coolZoneTemp", number()),
+            ("humidity", number()),
+            ("probes", json!({ "type": "object", "additionalProperties": number(), "description": "DS18B20 probes beyond basking, control and cool, by name; absent without any" })),
+        ]),
+        "SensorHistoryRow": object(&[
+            ("id", integer()),
+            ("timestamp", json!({ "type": "string", "description": "Local time, YYYY-MM-DDTHH:MM:SS" })),
+            ("basking_temp", nullable(number())), ("control_temp", nullable(number())), ("cool_temp", nullable(number())),
+            ("humidity", nullable(number())), ("uv_1", nullable(number())), ("uv_2", nullable(number())),
+        ]),
+        "GraphDataResponse": object(&[
+            ("unit", schema_ref("TemperatureUnit")),
+            ("points", array_of("GraphDataPoint")),
+            ("bucket_size", json!({ "type": "integer", "description": "Readings averaged into each point to stay within `get_data.max_graph_points` (default 720), 1 for raw readings. Ranges read from hourly averages count an hour of readings (3600 / `get_data.interval`) per hourly point" })),
+        ]),
+        "GraphCompareResponse": object(&[
+            ("unit", schema_ref("TemperatureUnit")),
+            ("bucket_minutes", json!({ "type": "integer" })),
+            ("days", json!({ "type": "object", "additionalProperties": array_of("GraphDataPoint") })),
+        ]),
+        "ResetResponse": object(&[("rows_reset", json!({ "type": "integer" }))]),
+        "ImportSummary": object(&[
+            ("inserted", json!({ "type": "integer" })),
+            ("updated", json!({ "type": "integer" })),
+            ("skipped", json!({ "type": "integer" })),
+        ]),
+        "DutyResponse": object(&[
+            ("date", json!({ "type": "string", "format": "date" })),
+            ("relays", json!({ "type": "array", "items": schema_ref("DutyCycle") })),
+        ]),
+        "DutyCycle": object(&[
+            ("relay", json!({ "type": "string", "enum": ["heat", "uv1", "uv2"] })),
+            ("on_minutes", number()),
+            ("percent", json!({ "type": "number", "minimum": 0, "maximum": 100 })),
+        ]),
+        "OverheatEvent": object(&[
+            ("started_at", json!({ "type": "string", "format": "date-time" })),
+            ("ended_at", nullable(json!({ "type": "string", "format": "date-time" }))),
+            ("peak_temp", number()),
+            ("duration_seconds", nullable(json!({ "type": "integer" }))),
+            ("zone", string_schema()),
+        ]),
+        "SensorStatus": object(&[
+            ("name", string_schema()),
+            ("faulted", boolean()),
+            ("consecutive_failures", integer()),
+            ("last_good", nullable(json!({ "type": "string", "format": "date-time" }))),
+        ]),
+        "OverheatStatus": object(&[
+            ("overheating", boolean()),
+            ("current_temp", number()),
+            ("threshold", number()),
+            ("control_temp", nullable(number())),
+            ("control_threshold", nullable(number())),
+            ("cooldown_remaining_seconds", nullable(json!({ "type": "integer" }))),
+            ("heat_on_seconds", nullable(json!({ "type": "integer" }))),
+            ("heat_watchdog_tripped", boolean()),
+            ("last_overheat", nullable(json!({ "type": "string", "format": "date-time" }))),
+        ]),
+        "HardwareResponse": object(&[
+            ("relays", object(&[
+                ("uv1", integer()),
+                ("uv2", integer()),
+                ("heat", integer()),
+                ("led", integer()),
+                ("fan", nullable(integer())),
+                ("aux", nullable(integer())),
+                ("active_low", boolean()),
+            ])),
+            ("uv_dimming", object(&[
+                ("uv1_pwm_channel", nullable(integer())),
+                ("uv2_pwm_channel", nullable(integer())),
+                ("frequency_hz", number()),
+            ])),
+            ("sensors", object(&[
+                ("ds18b20_bus", nullable(integer())),
+                ("ds18b20", json!({ "type": "object", "additionalProperties": { "type": "string" } })),
+                ("dht22_pin", integer()),
+                ("veml6075_uv1", i2c_sensor()),
+                ("veml6075_uv2", i2c_sensor()),
+            ])),
+            ("led_strip", object(&[
+                ("ic_count", integer()),
+                ("spi_bus", integer()),
+                ("spi_slave_select", integer()),
+                ("spi_clock_hz", integer()),
+                ("spi_mode", integer()),
+                ("data_pin", nullable(json!({ "type": "integer", "description": "GPIO pin bit-banging the strip when [led] driver = \"bitbang\"" }))),
+            ])),
+        ]),
+        "SystemStatusResponse": object(&[
+            ("instance", string_schema()),
+            ("version", string_schema()),
+            ("uptime_seconds", json!({ "type": "integer" })),
+            ("overheat_detected", boolean()),
+            ("last_overheat", nullable(string_schema())),
+            ("cooldown_remaining", nullable(json!({ "type": "integer" }))),
+            ("data_collection_interval", json!({ "type": "integer" })),
+            ("free_disk_space_mb", json!({ "type": "integer" })),
+            ("degraded", nullable(json!({ "type": "string", "description": "Why the database couldn't be opened; set while running on the config.toml defaults" }))),
+            ("unavailable", json!({ "type": "array", "items": { "type": "string", "enum": ["relays", "lights"] }, "description": "Controllers running without GPIO because it couldn't be initialized; nothing they drive is switched" })),
+            ("task_restarts", json!({ "type": "object", "additionalProperties": integer(), "description": "Restarts after a panic per background loop: sensors, lights, leds, history and snapshots" })),
+        ]),
+        "LogEntry": object(&[
+            ("timestamp", json!({ "type": "string", "format": "date-time" })),
+            ("level", json!({ "type": "string", "enum": ["INFO", "WARNING", "ERROR"] })),
+            ("message", string_schema()),
+        ]),
+        "CameraStatusResponse": object(&[
+            ("camera_available", boolean()),
+            ("camera_initialized", boolean()),
+            ("stream_url", nullable(string_schema())),
+        ]),
+        "CameraStreamResponse": object(&[("stream_url", string_schema())]),
+    })
+}
+
+// ===== Builders =====
+
+fn operation(tag: &str, summary: &str, parameters: Vec<Value>, ok: Value) -> Value {
+    let mut op = json!({
+        "tags": [tag],
+        "summary": summary,
+        "responses": {
+            "200": ok,
+            "default": { "$ref": "#/components/responses/Error" }
+        }
+    });
+    if !parameters.is_empty() {
+        op["parameters"] = Value::Array(parameters);
+    }
+    op
+}
+
+fn with_body(mut op: Value, schema: Value) -> Value {
+    op["requestBody"] = json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema } }
+    });
+    op
+}
+
+fn with_csv_body(mut op: Value) -> Value {
+    op["requestBody"] = json!({
+        "required": true,
+        "content": { "text/csv": { "schema": { "type": "string", "description": "Timestamp,Temperature,Humidity,UV Index" } } }
+    });
+    op
+}
+
+fn with_status(mut op: Value, status: &str, description: &str) -> Value {
+    op["responses"][status] = json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema_ref("Error") } }
+    });
+    op
+}
+
+fn ok_json(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn ok_file(description: &str, content_type: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { content_type: { "schema": { "type": "string", "format": "binary" } } }
+    })
+}
+
+fn query_param(name: &str, required: bool, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": required,
+        "description": description,
+        "schema": schema
+    })
+}
+
+fn week_param() -> Value {
+    json!({
+        "name": "week",
+        "in": "path",
+        "required": true,
+        "description": "Schedule week (ISO week number)",
+        "schema": { "type": "integer", "minimum": 1, "maximum": 52 }
+    })
+}
+
+fn force_param() -> Value {
+    query_param("force", false, "Store LED windows outside `[led] min_on_hours`/`max_on_hours` (default false)", boolean())
+}
+
+fn setting_key_param() -> Value {
+    json!({
+        "name": "key",
+        "in": "path",
+        "required": true,
+        "description": "Setting name",
+        "schema": { "type": "string", "enum": ["away_mode", "led_forced_off"] }
+    })
+}
+
+fn units_param() -> Value {
+    query_param("units", false, "Temperature unit of the response (default c)", schema_ref("TemperatureUnit"))
+}
+
+/// The bare Celsius points, or `GraphDataResponse` when `units` is passed
+fn graph_data() -> Value {
+    json!({ "oneOf": [array_of("GraphDataPoint"), schema_ref("GraphDataResponse")] })
+}
+
+fn smooth_param() -> Value {
+    query_param("smooth", false, "Points in a centered moving average, odd and at most 15; 0 or absent for raw data",
+        json!({ "type": "integer", "minimum": 0, "maximum": 15 }))
+}
+
+fn object(fields: &[(&str, Value)]) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for (name, schema) in fields {
+        if schema.get("nullable") != Some(&Value::Bool(true)) {
+            required.push(Value::String(name.to_string()));
+        }
+        properties.insert(name.to_string(), schema.clone());
+    }
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn array_of(name: &str) -> Value {
+    json!({ "type": "array", "items": schema_ref(name) })
+}
+
+fn nullable(schema: Value) -> Value {
+    // OpenAPI 3.0 ignores siblings of $ref, so wrap references in allOf
+    match schema.get("$ref") {
+        Some(_) => json!({ "allOf": [schema], "nullable": true }),
+        None => {
+            let mut schema = schema;
+            schema["nullable"] = Value::Bool(true);
+            schema
+        }
+    }
+}
+
+fn string_schema() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn number() -> Value {
+    json!({ "type": "number" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn i2c_sensor() -> Value {
+    object(&[("bus", integer()), ("address", integer())])
+}
+
+fn channel() -> Value {
+    json!({ "type": "integer", "minimum": 0, "maximum": 255 })
+}
+
+fn weight() -> Value {
+    json!({ "type": "number", "minimum": 0.0, "maximum": 1.0 })
+}
+
+fn time() -> Value {
+    json!({ "type": "string", "pattern": "^[0-2][0-9]:[0-5][0-9]$", "description": "HH:MM" })
+}
+
+fn profile_name() -> Value {
+    json!({ "type": "string", "pattern": "^[a-z0-9_-]{1,32}$" })
+}
+
+fn profile_color() -> Value {
+    json!({ "type": "string", "pattern": "^#[0-9A-Fa-f]{6}$", "description": "#RRGGBB" })
+}
+
+fn export_range() -> Vec<Value> {
+    vec![
+        query_param("start", true, "First day (YYYY-MM-DD)", json!({ "type": "string", "format": "date" })),
+        query_param("end", true, "Last day (YYYY-MM-DD)", json!({ "type": "string", "format": "date" })),
+    ]
+}
+
+fn api_scope() -> Value {
+    json!({ "type": "string", "enum": ["read", "control", "admin"], "description": "read: GET only; control: also LED, relay and sensor commands; admin: also schedule, settings and keys" })
+}
+
+fn animation_kind() -> Value {
+    json!({ "type": "string", "enum": ["breathing", "thunderstorm", "candle"] })
+}
+
+fn led_alert() -> Value {
+    json!({ "type": "string", "enum": ["overheat", "sensor_fault"] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    match (key.as_str(), child) {
+                        ("$ref", Value::String(target)) => refs.push(target.clone()),
+                        _ => collect_refs(child, refs),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| collect_refs(item, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_ref_resolves() {
+        let doc = spec();
+        let mut refs = Vec::new();
+        collect_refs(&doc, &mut refs);
+        assert!(!refs.is_empty());
+
+        for target in refs {
+            let pointer = target.trim_start_matches('#');
+            assert!(doc.pointer(pointer).is_some(), "unresolved $ref {}", target);
+        }
+    }
+
+    #[test]
+    fn test_error_shape_documented() {
+        let doc = spec();
+        assert_eq!(doc["components"]["schemas"]["Error"]["required"], json!(["error"]));
+        assert!(doc["paths"]["/api/sensors/read"]["post"]["responses"]["429"].is_object());
+    }
+}
@@ -0,0 +1,256 @@
+// modules/simulation.rs
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use chrono::NaiveDateTime;
+use log::{error, info};
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+use crate::modules::config::Config;
+use crate::modules::gpio::RGBWW;
+use crate::modules::ledStrip::{LEDController, get_led_settings, resolve_led_color};
+use crate::modules::lightControl::{LightController, RelayStates, persist_overheat_changes, resolve_relay_states};
+use crate::modules::models::{RelayReason, Schedule};
+
+/// Real time between two simulated instants
+pub const SIMULATION_STEP: Duration = Duration::from_millis(250);
+
+/// Fastest accepted simulation, one schedule minute per step so no transition is skipped
+pub const MAX_SPEED: u32 = 240;
+
+// Set while a simulation drives the loads
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Set by `cancel` to stop the running simulation at its next step
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+// Frees the loads when `run_day` returns, panics included
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A clock running `speed` times faster than real time from `start`
+#[derive(Debug, Clone, Copy)]
+pub struct VirtualClock {
+    start: NaiveDateTime,
+    speed: u32,
+}
+
+impl VirtualClock {
+    pub fn new(start: NaiveDateTime, speed: u32) -> Self {
+        Self { start, speed: speed.max(1) }
+    }
+
+    /// Virtual time `elapsed` real time after the start
+    pub fn at(&self, elapsed: Duration) -> NaiveDateTime {
+        let virtual_elapsed = elapsed * self.speed;
+        self.start + chrono::Duration::from_std(virtual_elapsed).unwrap_or_else(|_| chrono::Duration::max_value())
+    }
+
+    /// Real time it takes to cover `span` of virtual time
+    pub fn real_duration(&self, span: chrono::Duration) -> Duration {
+        span.to_std().unwrap_or_default() / self.speed
+    }
+}
+
+/// What the schedule asks of the loads at one instant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulatedState {
+    pub relays: RelayStates,
+    pub led: Option<RGBWW>,
+}
+
+/// Resolves the schedule at `at` with the same logic as `update_lights` and `update_leds`.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database connection pool holding the schedule and LED settings
+/// * `config` - Application configuration with the default schedule
+/// * `at` - The instant to resolve, in the schedule timezone
+pub async fn resolve_at(db_pool: &SqlitePool, config: &Config, at: NaiveDateTime) -> Result<SimulatedState, Box<dyn Error>> {
+    let current_time = at.format("%H:%M").to_string();
+    let schedule = Schedule::get_week(db_pool, Schedule::week_for(&at)).await?;
+    let settings = get_led_settings(db_pool).await?;
+
+    Ok(SimulatedState {
+        relays: resolve_relay_states(&current_time, schedule.as_ref(), config),
+        led: resolve_led_color(&current_time, at.date(), schedule.as_ref(), settings.as_ref(), config)?,
+    })
+}
+
+/// Claims the loads for a simulation.
+///
+/// # Returns
+///
+/// False if another simulation is already running
+pub fn try_start() -> bool {
+    let started = RUNNING.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_ok();
+    if started {
+        CANCELLED.store(false, Ordering::SeqCst);
+    }
+    started
+}
+
+/// Whether a simulation drives the loads; the control loops skip their ticks meanwhile
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Stops the running simulation at its next step.
+///
+/// # Returns
+///
+/// False if no simulation is running
+pub fn cancel() -> bool {
+    if !is_running() {
+        return false;
+    }
+    CANCELLED.store(true, Ordering::SeqCst);
+    true
+}
+
+/// Replays today's schedule from midnight at `clock`'s speed on the loads.
+///
+/// The control loops skip their ticks while `is_running` and take over again
+/// from their next tick once it ends or is cancelled. Overheat protection
+/// stays active. Must only be started after `try_start`.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database connection pool
+/// * `config` - Application configuration
+/// * `light_controller` - Drives the UV and heat relays
+/// * `led_controller` - Drives the LED strip
+/// * `clock` - Virtual clock starting at midnight
+pub async fn run_day(
+    db_pool: Arc<SqlitePool>,
+    config: Arc<Config>,
+    light_controller: Arc<Mutex<LightController>>,
+    led_controller: Arc<Mutex<LEDController>>,
+    clock: VirtualClock,
+) {
+    let _running = RunningGuard;
+    let day = chrono::Duration::days(1);
+    let real_length = clock.real_duration(day);
+    let end = clock.at(real_length);
+    info!("Simulating a schedule day in {}s", real_length.as_secs());
+
+    let started = tokio::time::Instant::now();
+    let mut last: Option<SimulatedState> = None;
+    loop {
+        let at = clock.at(started.elapsed()).min(end - chrono::Duration::minutes(1));
+        match resolve_at(&db_pool, &config, at).await {
+            Ok(state) if last != Some(state) => {
+                if let Err(e) = apply(&db_pool, &light_controller, &led_controller, &state).await {
+                    error!("Simulation failed at {}: {}", at.format("%H:%M"), e);
+                    break;
+                }
+                last = Some(state);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Simulation failed at {}: {}", at.format("%H:%M"), e);
+                break;
+            }
+        }
+        if started.elapsed() >= real_length {
+            break;
+        }
+        tokio::time::sleep(SIMULATION_STEP).await;
+        if CANCELLED.load(Ordering::SeqCst) {
+            info!("Schedule simulation cancelled at {}", clock.at(started.elapsed()).format("%H:%M"));
+            return;
+        }
+    }
+
+    info!("Schedule simulation finished");
+}
+
+/// Switches the loads to `state`, like one tick of the control loops
+async fn apply(
+    db_pool: &SqlitePool,
+    light_controller: &Arc<Mutex<LightController>>,
+    led_controller: &Arc<Mutex<LEDController>>,
+    state: &SimulatedState,
+) -> Result<(), Box<dyn Error>> {
+    let changes = {
+        let mut controller = light_controller.lock().await;
+        controller.set_uv1(state.relays.uv1, state.relays.uv1_level);
+        controller.set_uv2(state.relays.uv2, state.relays.uv2_level);
+        controller.set_heat_target(state.relays.heat_target);
+        controller.control_heat(state.relays.heat);
+        controller.take_overheat_changes()
+    };
+    persist_overheat_changes(db_pool, &changes).await?;
+
+    let mut leds = led_controller.lock().await;
+    match state.led {
+        Some(color) => leds.update_color(color, RelayReason::Schedule).await,
+        None => leds.power_off(RelayReason::Schedule).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::test_config;
+    use crate::modules::storage;
+    use chrono::NaiveDate;
+
+    #[tokio::test]
+    async fn test_virtual_clock_crosses_heat_transition() {
+        let pool = storage::temp_db("simulation").await;
+        let config = test_config();
+
+        // A day in a minute: each real second is 24 virtual minutes
+        let midnight = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let clock = VirtualClock::new(midnight, 1440);
+        assert_eq!(clock.real_duration(chrono::Duration::days(1)), Duration::from_secs(60));
+
+        let mut week = Schedule::default_week(10, &config.db);
+        week.heat_start = "10:00".to_string();
+        week.heat_end = "14:30".to_string();
+        week.upsert(&pool).await.unwrap();
+
+        let before = clock.at(Duration::from_secs(24));
+        let after = clock.at(Duration::from_secs(26));
+        assert_eq!((before.format("%H:%M").to_string(), after.format("%H:%M").to_string()), ("09:36".to_string(), "10:24".to_string()));
+        assert!(!resolve_at(&pool, &config, before).await.unwrap().relays.heat);
+        assert!(resolve_at(&pool, &config, after).await.unwrap().relays.heat);
+    }
+
+    #[test]
+    fn test_max_speed_steps_at_most_a_minute() {
+        let midnight = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let clock = VirtualClock::new(midnight, MAX_SPEED);
+        assert_eq!(clock.at(SIMULATION_STEP) - midnight, chrono::Duration::minutes(1));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_frees_the_loads() {
+        use crate::modules::gpio::RelayController;
+        use crate::modules::ledStrip::RecordingStrip;
+
+        let db = storage::temp_db("simulation_cancel").await;
+        let pool = Arc::new((*db).clone());
+        let config = test_config();
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let lights = Arc::new(Mutex::new(LightController::unavailable(config.light_control.clone())));
+        let leds = Arc::new(Mutex::new(LEDController::with_driver(relays, RecordingStrip::new())));
+        let midnight = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        assert!(!cancel());
+        assert!(try_start());
+        assert!(!try_start());
+        let run = tokio::spawn(run_day(pool, Arc::new(config), lights, leds, VirtualClock::new(midnight, MAX_SPEED)));
+        assert!(is_running());
+        assert!(cancel());
+        tokio::time::timeout(Duration::from_secs(5), run).await.unwrap().unwrap();
+        assert!(!is_running());
+    }
+}
@@ -1,122 +1,417 @@
 use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
-use sqlx::sqlite::SqlitePoolOptions;
+use std::future::Future;
+use std::sync::OnceLock;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use crate::modules::config::{Config, DbUnavailable, ScheduleConfig};
+use crate::modules::gpio::RGBWW;
+use crate::modules::migrations;
+use crate::modules::models::{ActivityCursor, ActivityEntry, ApiKey, ApiScope, DutyCycle, Schedule, Override, OverheatEvent, RelayEvent, RelayReason, ScheduleProfile, SensorHistoryRow, SCHEDULE_WEEKS};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, Utc};
+use tokio::sync::mpsc::UnboundedReceiver;
+use log::{error, info, warn};
+
+/// Format of `sensor_history.timestamp`: local time, to the second. The graph
+/// and export queries compare timestamps as text, so every writer uses it.
+pub const SENSOR_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How long SQLite waits on a locked database before returning `SQLITE_BUSY` (see `[db] busy_timeout_ms`)
+pub const DEFAULT_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Delay before the first retry of a busy write, doubled on every further attempt
+const BUSY_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_millis(50);
+
+// Retries of a write that hit a busy database, set once at startup by `set_write_retries`
+static WRITE_RETRIES: OnceLock<u8> = OnceLock::new();
+
+// Applies `[db] write_retries`. Until this is called 3 retries are used.
+pub fn set_write_retries(retries: u8) {
+    let _ = WRITE_RETRIES.set(retries);
+}
+
+// Why the database couldn't be opened, set once at startup by `set_degraded`
+static DEGRADED: OnceLock<String> = OnceLock::new();
+
+// Marks the controller as running degraded, see `open_db`
+pub fn set_degraded(reason: String) {
+    let _ = DEGRADED.set(reason);
+}
+
+/// Why the database couldn't be opened, None unless running degraded
+pub fn degraded() -> Option<&'static str> {
+    DEGRADED.get().map(String::as_str)
+}
 
 /// Initializes the SQLite database connection and sets up required tables.
 ///
 /// This function:
-/// 1. Creates a connection pool to the SQLite database
-/// 2. Creates all necessary tables if they don't exist, including:
+/// 1. Creates a connection pool to the SQLite database at `path`,
+///    creating the file if it doesn't exist yet
+/// 2. Applies the pending migrations (see `migrations::MIGRATIONS`), which
+///    create all necessary tables, including:
 ///    - Schedule table for lighting schedules
-///    - Sensor data table for historical readings
+///    - Sensor history table for historical readings
 ///    - Log table for system events
 ///    - LED settings table for LED strip configuration
 ///    - Overrides table for manual control overrides
+///    - Overheat events table for overheat protection history
+///    - Relay events table auditing every relay state change
+///    - Settings table for runtime toggles that survive a restart
+///    - API keys table holding hashed, scoped API keys
+/// 3. Audits the tables against the columns the queries expect, see `audit_schema`
+///
+/// # Arguments
+///
+/// * `path` - Filesystem path of the database file (see `[db].path`)
 ///
 /// # Returns
 ///
 /// A Result containing either the SQLite connection pool or an error
-pub async fn initialize_db() -> Result<SqlitePool, Box<dyn Error>> {
+pub async fn initialize_db(path: &str) -> Result<SqlitePool, Box<dyn Error>> {
+    initialize_db_with_busy_timeout(path, DEFAULT_BUSY_TIMEOUT).await
+}
+
+/// `initialize_db` with a custom SQLite busy timeout
+pub async fn initialize_db_with_busy_timeout(path: &str, busy_timeout: std::time::Duration) -> Result<SqlitePool, Box<dyn Error>> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .busy_timeout(busy_timeout);
+
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
-        .connect("sqlite:data.db")
+        .connect_with(options)
         .await?;
 
-    // Create tables if they don't exist
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS schedule (
-            week_number INTEGER PRIMARY KEY,
-            uv1_start TEXT NOT NULL,
-            uv1_end TEXT NOT NULL,
-            uv2_start TEXT NOT NULL,
-            uv2_end TEXT NOT NULL,
-            heat_start TEXT NOT NULL,
-            heat_end TEXT NOT NULL,
-            led_r INTEGER NOT NULL,
-            led_g INTEGER NOT NULL,
-            led_b INTEGER NOT NULL,
-            led_cw INTEGER NOT NULL,
-            led_ww INTEGER NOT NULL
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+    prepare_db(pool).await
+}
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS overrides (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            date TEXT NOT NULL,
-            uv1_enabled INTEGER NOT NULL,
-            uv2_enabled INTEGER NOT NULL,
-            heat_enabled INTEGER NOT NULL,
-            led_enabled INTEGER NOT NULL
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+/// Opens the database at `[db] path`.
+///
+/// If it can't be opened and `[db] on_unavailable` is `degraded`, an empty
+/// in-memory database takes its place so the control loops keep running off
+/// the `config.toml` defaults. The file itself is left untouched.
+///
+/// # Returns
+///
+/// The pool, and why the database couldn't be opened if the pool is the
+/// in-memory stand-in
+pub async fn open_db(config: &ScheduleConfig) -> Result<(SqlitePool, Option<String>), Box<dyn Error>> {
+    let error = match initialize_db_with_busy_timeout(config.db_path(), config.busy_timeout()).await {
+        Ok(pool) => return Ok((pool, None)),
+        Err(e) => e,
+    };
+    if config.on_unavailable == DbUnavailable::Exit {
+        return Err(error);
+    }
+    
+    let reason = format!("Database {} unavailable: {}", config.db_path(), error);
+    error!("{}, running degraded on the config.toml defaults", reason);
+    Ok((initialize_memory_db().await?, Some(reason)))
+}
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS history (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp TEXT NOT NULL,
-            temperature REAL,
-            humidity REAL,
-            uv_index REAL
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+/// An empty database held in memory, standing in for an unavailable file
+pub async fn initialize_memory_db() -> Result<SqlitePool, Box<dyn Error>> {
+    let options = SqliteConnectOptions::new().filename(":memory:");
+    
+    // Every connection would get its own empty database, so keep exactly one open
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(options)
+        .await?;
+
+    prepare_db(pool).await
+}
 
-    // Create LED settings table
+// Migrates, seeds and audits a freshly opened database
+async fn prepare_db(pool: SqlitePool) -> Result<SqlitePool, Box<dyn Error>> {
+    for migration in migrations::run(&pool).await? {
+        info!("Applied database migration {}: {}", migration.version, migration.description);
+    }
+
+    // Insert default LED settings if not exists
     sqlx::query(
         r#"
-        CREATE TABLE IF NOT EXISTS led_settings (
-            id INTEGER PRIMARY KEY,
-            r INTEGER NOT NULL,
-            g INTEGER NOT NULL,
-            b INTEGER NOT NULL,
-            ww INTEGER NOT NULL,
-            cw INTEGER NOT NULL,
-            enabled INTEGER NOT NULL,
-            override INTEGER NOT NULL DEFAULT 0,
-            season_weight REAL NOT NULL DEFAULT 0.3
-        )
+        INSERT OR IGNORE INTO led_settings (id, r, g, b, ww, cw, enabled, override, season_weight)
+        VALUES (1, 150, 150, 128, 128, 128, 1, 0, 0.3)
         "#,
     )
     .execute(&pool)
     .await?;
 
-    // Create logs table
-    sqlx::query(
+    for column in audit_schema(&pool).await? {
+        info!("Migrated database: added column {}", column);
+    }
+
+    Ok(pool)
+}
+
+/// Whether `e` is SQLite reporting the database as busy or locked by another connection
+pub fn is_busy(e: &sqlx::Error) -> bool {
+    match e {
+        // Extended result codes keep the primary code (SQLITE_BUSY = 5, SQLITE_LOCKED = 6) in the low byte
+        sqlx::Error::Database(db) => db.code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .map_or(false, |code| matches!(code & 0xff, 5 | 6)),
+        _ => false,
+    }
+}
+
+/// Runs a database write, retrying it with backoff while the database is busy.
+///
+/// Uses the retry count from `[db] write_retries`; other errors are returned at once.
+///
+/// # Arguments
+///
+/// * `write` - Starts the write; called again for every attempt
+pub async fn retry_busy<F, Fut, T>(write: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    retry_busy_with(*WRITE_RETRIES.get().unwrap_or(&3), write).await
+}
+
+async fn retry_busy_with<F, Fut, T>(retries: u8, mut write: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match write().await {
+            Err(e) if attempt < retries && is_busy(&e) => {
+                let backoff = BUSY_RETRY_BACKOFF * 2u32.pow(attempt as u32);
+                attempt += 1;
+                warn!("Database busy, retrying write in {:?} (attempt {} of {})", backoff, attempt, retries);
+                tokio::time::sleep(backoff).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Columns the queries of this crate rely on, per table.
+///
+/// `CREATE TABLE IF NOT EXISTS` leaves a table from an older version as it is,
+/// so `audit_schema` compares these against the actual columns. A column with
+/// a definition is added when missing; one without can't be migrated.
+const EXPECTED_COLUMNS: &[(&str, &[(&str, Option<&str>)])] = &[
+    ("schedule", &[
+        ("week_number", None),
+        ("uv1_start", None), ("uv1_end", None),
+        ("uv2_start", None), ("uv2_end", None),
+        ("heat_start", None), ("heat_end", None),
+        // Matches `models::DEFAULT_LED_START` / `DEFAULT_LED_END`
+        ("led_start", Some("TEXT NOT NULL DEFAULT '07:00'")),
+        ("led_end", Some("TEXT NOT NULL DEFAULT '19:00'")),
+        ("led_r", Some("INTEGER NOT NULL DEFAULT 0")),
+        ("led_g", Some("INTEGER NOT NULL DEFAULT 0")),
+        ("led_b", Some("INTEGER NOT NULL DEFAULT 0")),
+        ("led_cw", Some("INTEGER NOT NULL DEFAULT 0")),
+        ("led_ww", Some("INTEGER NOT NULL DEFAULT 0")),
+        ("uv1_level", Some("INTEGER NOT NULL DEFAULT 100")),
+        ("uv2_level", Some("INTEGER NOT NULL DEFAULT 100")),
+        ("heat_target", Some("REAL")),
+    ]),
+    ("overrides", &[
+        ("id", None), ("date", None),
+        ("uv1_enabled", None), ("uv2_enabled", None), ("heat_enabled", None), ("led_enabled", None),
+    ]),
+    ("sensor_history", &[
+        ("id", None), ("timestamp", None),
+        ("basking_temp", None), ("control_temp", None), ("cool_temp", None),
+        ("humidity", None), ("uv_1", None), ("uv_2", None),
+    ]),
+    ("probe_history", &[("id", None), ("timestamp", None), ("probe", None), ("temperature", None)]),
+    ("sensor_history_hourly", &[
+        ("hour", None),
+        ("basking_temp", None), ("control_temp", None), ("cool_temp", None),
+        ("humidity", None), ("uv_1", None), ("uv_2", None), ("samples", None),
+    ]),
+    ("probe_history_hourly", &[("hour", None), ("probe", None), ("temperature", None), ("samples", None)]),
+    ("led_settings", &[
+        ("id", None), ("r", None), ("g", None), ("b", None), ("ww", None), ("cw", None), ("enabled", None),
+        ("override", Some("INTEGER NOT NULL DEFAULT 0")),
+        ("season_weight", Some("REAL NOT NULL DEFAULT 0.3")),
+    ]),
+    ("logs", &[("id", None), ("timestamp", None), ("level", None), ("message", None)]),
+    ("overheat_events", &[
+        ("id", None), ("started_at", None), ("ended_at", None), ("peak_temp", None), ("duration_seconds", None),
+        ("zone", Some("TEXT NOT NULL DEFAULT 'basking'")),
+    ]),
+    ("relay_events", &[("id", None), ("timestamp", None), ("relay", None), ("state", None), ("reason", None)]),
+    ("settings", &[("key", None), ("value", None)]),
+    ("schedule_profiles", &[("name", None), ("display_name", None), ("color", None), ("saved_at", None)]),
+    ("schedule_profile_weeks", &[
+        ("profile", None), ("week_number", None),
+        ("uv1_start", None), ("uv1_end", None),
+        ("uv2_start", None), ("uv2_end", None),
+        ("heat_start", None), ("heat_end", None),
+        ("led_start", None), ("led_end", None),
+        ("led_r", None), ("led_g", None), ("led_b", None), ("led_cw", None), ("led_ww", None),
+        ("uv1_level", None), ("uv2_level", None), ("heat_target", None),
+    ]),
+    ("api_keys", &[
+        ("id", None), ("name", None), ("scope", None), ("key_hash", None), ("created_at", None),
+        ("revoked_at", Some("TEXT")),
+    ]),
+];
+
+/// Checks every table against `EXPECTED_COLUMNS` via `PRAGMA table_info` and
+/// adds the missing columns that can be migrated.
+///
+/// Nothing is changed if any missing column can't be migrated, so a database
+/// from an incompatible version fails startup instead of the first query.
+///
+/// # Returns
+///
+/// The added columns as `table.column`, or an error naming every column that
+/// is missing and can't be migrated
+pub async fn audit_schema(pool: &SqlitePool) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut migrations = Vec::new();
+    let mut unmigratable = Vec::new();
+
+    for &(table, columns) in EXPECTED_COLUMNS {
+        let present: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info(?)")
+            .bind(table)
+            .fetch_all(pool)
+            .await?;
+
+        for &(column, definition) in columns {
+            if present.iter().any(|(name,)| name == column) {
+                continue;
+            }
+            match definition {
+                Some(definition) => migrations.push((table, column, definition)),
+                None => unmigratable.push(format!("{}.{}", table, column)),
+            }
+        }
+    }
+
+    if !unmigratable.is_empty() {
+        return Err(format!(
+            "Database schema doesn't match this version: missing column(s) {} can't be migrated. \
+             Move the database file aside to start with a fresh one.",
+            unmigratable.join(", ")
+        ).into());
+    }
+
+    let mut added = Vec::new();
+    for (table, column, definition) in migrations {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+        added.push(format!("{}.{}", table, column));
+    }
+
+    Ok(added)
+}
+
+/// Settings key of the away mode flag (`"true"` / `"false"`)
+pub const AWAY_MODE_KEY: &str = "away_mode";
+
+/// Settings key of the `POST /api/led/off` flag (`"true"` / `"false"`), which
+/// keeps the strip dark over the schedule, `[led] override_precedence` and away
+/// mode until a color is set again
+pub const LED_FORCED_OFF_KEY: &str = "led_forced_off";
+
+/// Settings key of the color the LED loop last applied, as `R,G,B,WW,CW`. It is
+/// restored by `[main] boot_behavior = "resume"` and not exposed as a setting.
+pub const LED_LAST_COLOR_KEY: &str = "led_last_color";
+
+/// Settings key that is `"true"` while the heat watchdog has cut the lamp and
+/// waits for `POST /api/overheat/resume`, so a restart keeps the heat cut.
+/// Not exposed as a setting either.
+pub const HEAT_WATCHDOG_TRIPPED_KEY: &str = "heat_watchdog_tripped";
+
+/// Settings exposed through `/api/settings/:key`, with the value used until one is stored
+pub const KNOWN_SETTINGS: &[(&str, &str)] = &[
+    (AWAY_MODE_KEY, "false"),
+    (LED_FORCED_OFF_KEY, "false"),
+];
+
+/// Checks that `key` is a known setting and `value` is valid for it
+pub fn validate_setting(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        AWAY_MODE_KEY | LED_FORCED_OFF_KEY => value.parse::<bool>()
+            .map(|_| ())
+            .map_err(|_| format!("{} must be \"true\" or \"false\", got: {:?}", key, value)),
+        _ => Err(format!("Unknown setting: {}", key)),
+    }
+}
+
+/// Loads every known setting, falling back to its default when none is stored
+pub async fn load_settings(pool: &SqlitePool) -> Result<HashMap<String, String>, sqlx::Error> {
+    let mut settings = HashMap::new();
+    for (key, default) in KNOWN_SETTINGS {
+        let value = get_setting(pool, key).await?.unwrap_or_else(|| default.to_string());
+        settings.insert(key.to_string(), value);
+    }
+
+    Ok(settings)
+}
+
+/// Whether `POST /api/led/off` is holding the strip dark, see `LED_FORCED_OFF_KEY`
+pub async fn led_forced_off(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    Ok(get_setting(pool, LED_FORCED_OFF_KEY).await?.as_deref() == Some("true"))
+}
+
+/// Color the LED loop last applied, None if it never lit the strip
+pub async fn last_led_color(pool: &SqlitePool) -> Result<Option<RGBWW>, sqlx::Error> {
+    Ok(get_setting(pool, LED_LAST_COLOR_KEY).await?.and_then(|value| RGBWW::from_str(&value).ok()))
+}
+
+/// Remembers `color` for `last_led_color`
+pub async fn save_led_color(pool: &SqlitePool, color: RGBWW) -> Result<(), sqlx::Error> {
+    let value = format!("{},{},{},{},{}", color.r, color.g, color.b, color.ww, color.cw);
+    set_setting(pool, LED_LAST_COLOR_KEY, &value).await
+}
+
+/// Whether the heat watchdog tripped and wasn't resumed before the last shutdown
+pub async fn heat_watchdog_tripped(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    Ok(get_setting(pool, HEAT_WATCHDOG_TRIPPED_KEY).await?.as_deref() == Some("true"))
+}
+
+/// Remembers a heat watchdog trip, or its resume, for `heat_watchdog_tripped`
+pub async fn save_heat_watchdog_tripped(pool: &SqlitePool, tripped: bool) -> Result<(), sqlx::Error> {
+    set_setting(pool, HEAT_WATCHDOG_TRIPPED_KEY, if tripped { "true" } else { "false" }).await
+}
+
+/// Reads a persisted setting, None if it was never set
+pub async fn get_setting(pool: &SqlitePool, key: &str) -> Result<Option<String>, sqlx::Error> {
+    let value = sqlx::query_scalar!(
         r#"
-        CREATE TABLE IF NOT EXISTS logs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp TEXT NOT NULL,
-            level TEXT NOT NULL,
-            message TEXT NOT NULL
-        )
+        SELECT value FROM settings WHERE key = ?
         "#,
+        key
     )
-    .execute(&pool)
+    .fetch_optional(pool)
     .await?;
 
-    // Insert default LED settings if not exists
-    sqlx::query(
+    Ok(value)
+}
+
+/// Stores a setting, replacing any previous value
+pub async fn set_setting(pool: &SqlitePool, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
         r#"
-        INSERT OR IGNORE INTO led_settings (id, r, g, b, ww, cw, enabled, override, season_weight)
-        VALUES (1, 150, 150, 128, 128, 128, 1, 0, 0.3)
+        INSERT INTO settings (key, value) VALUES (?, ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value
         "#,
+        key,
+        value
     )
-    .execute(&pool)
+    .execute(pool)
     .await?;
 
-    Ok(pool)
+    Ok(())
 }
 
 impl Schedule {
@@ -124,7 +419,10 @@ impl Schedule {
         let schedules = sqlx::query_as!(
             Schedule,
             r#"
-            SELECT * FROM schedule
+            SELECT week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                   led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target
+            FROM schedule
+            ORDER BY week_number
             "#
         )
         .fetch_all(pool)
@@ -132,6 +430,300 @@ impl Schedule {
 
         Ok(schedules)
     }
+
+    /// Fetches the schedule for a single week, if one is stored
+    pub async fn get_week(pool: &SqlitePool, week_number: i32) -> Result<Option<Schedule>, sqlx::Error> {
+        let schedule = sqlx::query_as!(
+            Schedule,
+            r#"
+            SELECT week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                   led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target
+            FROM schedule
+            WHERE week_number = ?
+            "#,
+            week_number
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    /// Inserts or replaces the stored schedule for this week
+    pub async fn upsert(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        self.upsert_with(pool).await
+    }
+
+    /// Upserts every week in one transaction, so an error on any of them
+    /// leaves the stored schedule as it was
+    pub async fn upsert_all(pool: &SqlitePool, weeks: &[Schedule]) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        for week in weeks {
+            week.upsert_with(&mut tx).await?;
+        }
+        tx.commit().await
+    }
+
+    async fn upsert_with<'e, E: sqlx::SqliteExecutor<'e>>(&self, executor: E) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO schedule (week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                                  led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(week_number) DO UPDATE SET
+                uv1_start = excluded.uv1_start,
+                uv1_end = excluded.uv1_end,
+                uv2_start = excluded.uv2_start,
+                uv2_end = excluded.uv2_end,
+                heat_start = excluded.heat_start,
+                heat_end = excluded.heat_end,
+                led_start = excluded.led_start,
+                led_end = excluded.led_end,
+                led_r = excluded.led_r,
+                led_g = excluded.led_g,
+                led_b = excluded.led_b,
+                led_cw = excluded.led_cw,
+                led_ww = excluded.led_ww,
+                uv1_level = excluded.uv1_level,
+                uv2_level = excluded.uv2_level,
+                heat_target = excluded.heat_target
+            "#,
+            self.week_number,
+            self.uv1_start,
+            self.uv1_end,
+            self.uv2_start,
+            self.uv2_end,
+            self.heat_start,
+            self.heat_end,
+            self.led_start,
+            self.led_end,
+            self.led_r,
+            self.led_g,
+            self.led_b,
+            self.led_cw,
+            self.led_ww,
+            self.uv1_level,
+            self.uv2_level,
+            self.heat_target,
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Fills an empty `schedule` table with the 52 `def_*` weeks from `[db]`,
+/// so a fresh database has a schedule to edit. Does nothing once any week
+/// is stored, so it is safe to run on every start.
+///
+/// # Returns
+///
+/// The number of weeks written, 0 if the schedule wasn't empty
+pub async fn seed_schedule(pool: &SqlitePool, defaults: &ScheduleConfig) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let (stored,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM schedule").fetch_one(&mut tx).await?;
+    if stored > 0 {
+        return Ok(0);
+    }
+
+    let rows = insert_default_weeks(&mut tx, defaults).await?;
+    tx.commit().await?;
+    Ok(rows)
+}
+
+// Inserts every schedule week with the `def_*` values; the weeks must not exist yet
+async fn insert_default_weeks(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, defaults: &ScheduleConfig) -> Result<u64, sqlx::Error> {
+    let mut rows = 0;
+    for week_number in 1..=SCHEDULE_WEEKS {
+        let week = Schedule::default_week(week_number, defaults);
+        rows += sqlx::query!(
+            r#"
+            INSERT INTO schedule (week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                                  led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            week.week_number,
+            week.uv1_start,
+            week.uv1_end,
+            week.uv2_start,
+            week.uv2_end,
+            week.heat_start,
+            week.heat_end,
+            week.led_start,
+            week.led_end,
+            week.led_r,
+            week.led_g,
+            week.led_b,
+            week.led_cw,
+            week.led_ww,
+            week.uv1_level,
+            week.uv2_level,
+            week.heat_target,
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    Ok(rows)
+}
+
+/// Puts the 52 schedule weeks and the manual LED settings back to the config
+/// defaults in a single transaction. Sensor history and logs are left alone.
+///
+/// # Returns
+///
+/// The number of rows written
+pub async fn reset_to_defaults(pool: &SqlitePool, config: &Config) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut rows = 0;
+
+    sqlx::query!("DELETE FROM schedule").execute(&mut tx).await?;
+    rows += insert_default_weeks(&mut tx, &config.db).await?;
+
+    // The manual color and the natural light override go back to the defaults
+    let db = &config.db;
+    rows += sqlx::query!(
+        r#"
+        INSERT OR REPLACE INTO led_settings (id, r, g, b, ww, cw, enabled, override, season_weight)
+        VALUES (1, ?, ?, ?, ?, ?, 1, 0, ?)
+        "#,
+        db.def_led_R,
+        db.def_led_G,
+        db.def_led_B,
+        db.def_led_WW,
+        db.def_led_CW,
+        config.led.season_weight,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(rows)
+}
+
+impl ScheduleProfile {
+    /// Returns every saved profile, by name
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<ScheduleProfile>, sqlx::Error> {
+        sqlx::query_as!(
+            ScheduleProfile,
+            r#"
+            SELECT
+                p.name as "name!",
+                p.display_name,
+                p.color,
+                COUNT(w.week_number) as "weeks!: i64",
+                p.saved_at as "saved_at: DateTime<Utc>"
+            FROM schedule_profiles p
+            LEFT JOIN schedule_profile_weeks w ON w.profile = p.name
+            GROUP BY p.name
+            ORDER BY p.name
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Saves the current `schedule` weeks as profile `name`, replacing a
+    /// profile of the same name, in a single transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool` - Database connection pool
+    /// * `name` - Profile name, checked with `validate_name` by the caller
+    /// * `display_name` - Optional label shown instead of the name
+    /// * `color` - Optional "#RRGGBB" color
+    pub async fn save(
+        pool: &SqlitePool,
+        name: &str,
+        display_name: Option<&str>,
+        color: Option<&str>,
+    ) -> Result<ScheduleProfile, sqlx::Error> {
+        let saved_at = Utc::now();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO schedule_profiles (name, display_name, color, saved_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                display_name = excluded.display_name,
+                color = excluded.color,
+                saved_at = excluded.saved_at
+            "#,
+            name,
+            display_name,
+            color,
+            saved_at,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM schedule_profile_weeks WHERE profile = ?", name)
+            .execute(&mut tx)
+            .await?;
+        let weeks = sqlx::query!(
+            r#"
+            INSERT INTO schedule_profile_weeks (profile, week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                                                led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target)
+            SELECT ?, week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                   led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target
+            FROM schedule
+            "#,
+            name,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(ScheduleProfile {
+            name: name.to_string(),
+            display_name: display_name.map(str::to_string),
+            color: color.map(str::to_string),
+            weeks: weeks as i64,
+            saved_at,
+        })
+    }
+
+    /// Replaces every `schedule` week with the weeks of profile `name`, in a
+    /// single transaction. Weeks the profile lacks fall back to the config
+    /// defaults, as any week without a stored schedule does.
+    ///
+    /// # Returns
+    ///
+    /// The number of weeks loaded, or None if there is no such profile
+    pub async fn activate(pool: &SqlitePool, name: &str) -> Result<Option<u64>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let exists = sqlx::query_scalar!("SELECT name FROM schedule_profiles WHERE name = ?", name)
+            .fetch_optional(&mut tx)
+            .await?
+            .is_some();
+        if !exists {
+            return Ok(None);
+        }
+
+        sqlx::query!("DELETE FROM schedule").execute(&mut tx).await?;
+        let weeks = sqlx::query!(
+            r#"
+            INSERT INTO schedule (week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                                  led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target)
+            SELECT week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
+                   led_start, led_end, led_r, led_g, led_b, led_cw, led_ww, uv1_level, uv2_level, heat_target
+            FROM schedule_profile_weeks
+            WHERE profile = ?
+            "#,
+            name,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(Some(weeks))
+    }
 }
 
 impl Override {
@@ -149,6 +741,658 @@ impl Override {
     }
 }
 
+impl OverheatEvent {
+    /// Records a newly tripped (still active) overheat event
+    pub async fn insert(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO overheat_events (started_at, ended_at, peak_temp, duration_seconds, zone)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            self.started_at,
+            self.ended_at,
+            self.peak_temp,
+            self.duration_seconds,
+            self.zone,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stores the end time, duration and final peak temperature of an event
+    pub async fn finish(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE overheat_events
+            SET ended_at = ?, peak_temp = ?, duration_seconds = ?
+            WHERE started_at = ?
+            "#,
+            self.ended_at,
+            self.peak_temp,
+            self.duration_seconds,
+            self.started_at,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the most recent overheat events, newest first
+    pub async fn get_history(pool: &SqlitePool, limit: i64) -> Result<Vec<OverheatEvent>, sqlx::Error> {
+        let events = sqlx::query_as!(
+            OverheatEvent,
+            r#"
+            SELECT
+                started_at as "started_at: DateTime<Utc>",
+                ended_at as "ended_at: DateTime<Utc>",
+                peak_temp as "peak_temp: f32",
+                duration_seconds,
+                zone
+            FROM overheat_events
+            ORDER BY started_at DESC
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+/// Hex SHA-256 of an API key, as stored in `api_keys`
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Compares two key hashes without stopping at the first differing byte,
+/// so the time taken doesn't tell how much of a guessed key was right
+pub fn key_hashes_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl ApiKey {
+    /// Creates a key with a random secret.
+    ///
+    /// # Returns
+    ///
+    /// The stored key and its secret, which is not kept and can't be shown again
+    pub async fn create(pool: &SqlitePool, name: &str, scope: ApiScope) -> Result<(ApiKey, String), sqlx::Error> {
+        let mut secret = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let key = format!("tc_{}", secret.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+        let key_hash = hash_api_key(&key);
+        let created_at = Utc::now();
+
+        let id = sqlx::query!(
+            r#"
+            INSERT INTO api_keys (name, scope, key_hash, created_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+            name,
+            scope,
+            key_hash,
+            created_at,
+        )
+        .execute(pool)
+        .await?
+        .last_insert_rowid();
+
+        Ok((ApiKey { id, name: name.to_string(), scope, created_at, revoked_at: None }, key))
+    }
+
+    /// Returns every key, revoked ones included, oldest first
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT
+                id as "id!",
+                name,
+                scope as "scope: ApiScope",
+                created_at as "created_at: DateTime<Utc>",
+                revoked_at as "revoked_at: DateTime<Utc>"
+            FROM api_keys
+            ORDER BY id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Returns the scope of `key` if it is a stored key that hasn't been revoked.
+    ///
+    /// Every active key is compared with `key_hashes_match` rather than looked
+    /// up by hash, so the time taken is the same for any presented key.
+    pub async fn scope_of(pool: &SqlitePool, key: &str) -> Result<Option<ApiScope>, sqlx::Error> {
+        let key_hash = hash_api_key(key);
+        let active = sqlx::query!(
+            r#"SELECT key_hash, scope as "scope: ApiScope" FROM api_keys WHERE revoked_at IS NULL"#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut scope = None;
+        for row in active {
+            if key_hashes_match(&row.key_hash, &key_hash) {
+                scope = Some(row.scope);
+            }
+        }
+        Ok(scope)
+    }
+
+    /// Revokes a key; requests using it are rejected from then on.
+    ///
+    /// # Returns
+    ///
+    /// False if there is no such key or it was already revoked
+    pub async fn revoke(pool: &SqlitePool, id: i64) -> Result<bool, sqlx::Error> {
+        let revoked_at = Utc::now();
+        let result = sqlx::query!(
+            "UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+            revoked_at,
+            id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+impl RelayEvent {
+    /// Appends the event to the relay audit log
+    pub async fn insert(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO relay_events (timestamp, relay, state, reason)
+            VALUES (?, ?, ?, ?)
+            "#,
+            self.timestamp,
+            self.relay,
+            self.state,
+            self.reason,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the most recent relay state changes, newest first
+    pub async fn get_history(pool: &SqlitePool, limit: i64) -> Result<Vec<RelayEvent>, sqlx::Error> {
+        let events = sqlx::query_as!(
+            RelayEvent,
+            r#"
+            SELECT
+                timestamp as "timestamp: DateTime<Utc>",
+                relay,
+                state as "state: bool",
+                reason as "reason: RelayReason"
+            FROM relay_events
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Returns one relay's events in `[start, end)`, oldest first
+    pub async fn get_between(
+        pool: &SqlitePool,
+        relay: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<RelayEvent>, sqlx::Error> {
+        let events = sqlx::query_as!(
+            RelayEvent,
+            r#"
+            SELECT
+                timestamp as "timestamp: DateTime<Utc>",
+                relay,
+                state as "state: bool",
+                reason as "reason: RelayReason"
+            FROM relay_events
+            WHERE relay = ? AND timestamp >= ? AND timestamp < ?
+            ORDER BY id
+            "#,
+            relay,
+            start,
+            end
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Returns the last state written to a relay before `at`, if any
+    pub async fn state_before(pool: &SqlitePool, relay: &str, at: DateTime<Utc>) -> Result<Option<bool>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT state as "state: bool"
+            FROM relay_events
+            WHERE relay = ? AND timestamp < ?
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+            relay,
+            at
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+/// Last logged state of every relay that was ever switched, keyed by relay name.
+///
+/// The relay event log doubles as the persisted relay state used by
+/// `[main] boot_behavior = "resume"`.
+pub async fn last_relay_states(pool: &SqlitePool) -> Result<HashMap<String, bool>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT relay, state as "state: bool"
+        FROM relay_events
+        WHERE id IN (SELECT MAX(id) FROM relay_events GROUP BY relay)
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.relay, row.state)).collect())
+}
+
+/// Relays reported by `/api/duty`
+pub const DUTY_RELAYS: &[&str] = &["heat", "uv1", "uv2"];
+
+/// Works out how long a relay was on between `start` and `end` by pairing
+/// its on/off events.
+///
+/// # Arguments
+///
+/// * `relay` - Relay name, copied into the result
+/// * `initial` - State at `start`, i.e. the last event before it
+/// * `events` - The relay's events, oldest first; ones outside the period are ignored
+/// * `start` - Start of the period
+/// * `end` - End of the period
+pub fn duty_cycle(relay: &str, initial: bool, events: &[RelayEvent], start: DateTime<Utc>, end: DateTime<Utc>) -> DutyCycle {
+    let mut on = Duration::zero();
+    let mut state = initial;
+    let mut since = start;
+    for event in events.iter().filter(|e| e.timestamp >= start && e.timestamp < end) {
+        if state {
+            on = on + (event.timestamp - since);
+        }
+        state = event.state;
+        since = event.timestamp;
+    }
+    if state && end > since {
+        on = on + (end - since);
+    }
+
+    let period_ms = (end - start).num_milliseconds();
+    let on_ms = on.num_milliseconds();
+    DutyCycle {
+        relay: relay.to_string(),
+        on_minutes: on_ms as f64 / 60_000.0,
+        percent: if period_ms > 0 { on_ms as f64 * 100.0 / period_ms as f64 } else { 0.0 },
+    }
+}
+
+/// Computes the duty cycle of the heat and UV relays for a local calendar day.
+///
+/// A day still in progress is measured up to `now`, and a future day is
+/// all zeros, as is a day before the first relay event.
+pub async fn relay_duty_for_day(pool: &SqlitePool, date: NaiveDate, now: DateTime<Utc>) -> Result<Vec<DutyCycle>, sqlx::Error> {
+    let local_midnight = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+            .map(|midnight| midnight.with_timezone(&Utc))
+    };
+    let start = local_midnight(date).unwrap_or(now);
+    let end = date.succ_opt()
+        .and_then(local_midnight)
+        .unwrap_or(now)
+        .min(now)
+        .max(start);
+
+    let mut duty = Vec::with_capacity(DUTY_RELAYS.len());
+    for relay in DUTY_RELAYS {
+        let initial = RelayEvent::state_before(pool, relay, start).await?.unwrap_or(false);
+        let events = RelayEvent::get_between(pool, relay, start, end).await?;
+        duty.push(duty_cycle(relay, initial, &events, start, end));
+    }
+    Ok(duty)
+}
+
+/// Returns the newest `limit` log lines, relay events and overheat events
+/// before `before` (default: now), merged newest first, each with its
+/// `ActivityCursor`.
+///
+/// Each source is read up to `limit` rows, so the merged page is complete.
+/// Pass the last entry's cursor as `before` to get the next page; entries
+/// sharing its timestamp are ordered by cursor id, so none are skipped.
+pub async fn recent_activity(
+    pool: &SqlitePool,
+    before: Option<ActivityCursor>,
+    limit: i64,
+) -> Result<Vec<(ActivityCursor, ActivityEntry)>, sqlx::Error> {
+    let before = before.unwrap_or_else(|| ActivityCursor { timestamp: Utc::now(), id: i64::MAX });
+
+    let logs = sqlx::query!(
+        r#"
+        SELECT id * 3 as "cursor_id!: i64", timestamp as "timestamp: DateTime<Utc>", level, message
+        FROM logs
+        WHERE (timestamp, id * 3) < (?, ?)
+        ORDER BY timestamp DESC, id DESC
+        LIMIT ?
+        "#,
+        before.timestamp,
+        before.id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let relays = sqlx::query!(
+        r#"
+        SELECT
+            id * 3 + 1 as "cursor_id!: i64",
+            timestamp as "timestamp: DateTime<Utc>",
+            relay,
+            state as "state: bool",
+            reason as "reason: RelayReason"
+        FROM relay_events
+        WHERE (timestamp, id * 3 + 1) < (?, ?)
+        ORDER BY timestamp DESC, id DESC
+        LIMIT ?
+        "#,
+        before.timestamp,
+        before.id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let overheats = sqlx::query!(
+        r#"
+        SELECT
+            id * 3 + 2 as "cursor_id!: i64",
+            started_at as "started_at: DateTime<Utc>",
+            ended_at as "ended_at: DateTime<Utc>",
+            peak_temp as "peak_temp: f32",
+            duration_seconds,
+            zone
+        FROM overheat_events
+        WHERE (started_at, id * 3 + 2) < (?, ?)
+        ORDER BY started_at DESC, id DESC
+        LIMIT ?
+        "#,
+        before.timestamp,
+        before.id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let entries = logs.into_iter()
+        .map(|row| (row.cursor_id, ActivityEntry::Log { timestamp: row.timestamp, level: row.level, message: row.message }))
+        .chain(relays.into_iter().map(|row| (row.cursor_id, ActivityEntry::Relay(RelayEvent {
+            timestamp: row.timestamp,
+            relay: row.relay,
+            state: row.state,
+            reason: row.reason,
+        }))))
+        .chain(overheats.into_iter().map(|row| (row.cursor_id, ActivityEntry::Overheat(OverheatEvent {
+            started_at: row.started_at,
+            ended_at: row.ended_at,
+            peak_temp: row.peak_temp,
+            duration_seconds: row.duration_seconds,
+            zone: row.zone,
+        }))))
+        .map(|(id, entry)| (ActivityCursor { timestamp: entry.timestamp(), id }, entry))
+        .collect();
+    Ok(merge_activity(entries, limit))
+}
+
+/// Sorts entries from several sources newest first and keeps the first `limit`
+fn merge_activity(mut entries: Vec<(ActivityCursor, ActivityEntry)>, limit: i64) -> Vec<(ActivityCursor, ActivityEntry)> {
+    entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+    entries.truncate(limit.max(0) as usize);
+    entries
+}
+
+/// Writes relay events to the database as the controllers send them.
+///
+/// Runs until every `RelayEventLog` sender is dropped. A failed insert is
+/// logged and the event dropped, so the audit log never holds up switching.
+pub async fn write_relay_events(pool: SqlitePool, mut events: UnboundedReceiver<RelayEvent>) {
+    while let Some(event) = events.recv().await {
+        if let Err(e) = retry_busy(|| event.insert(&pool)).await {
+            error!("Failed to record {} relay change: {}", event.relay, e);
+        }
+    }
+}
+
+/// How often `maintain_sensor_history` rolls up and prunes `sensor_history`
+const HISTORY_MAINTENANCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Averages every complete hour of `sensor_history` before `until` that has
+/// no row in `sensor_history_hourly` yet, and likewise each probe's hours of
+/// `probe_history` into `probe_history_hourly`.
+///
+/// Each hour is averaged once, including hours older than the newest rolled-up
+/// one, e.g. backfilled or imported rows or rows from before a clock jump.
+/// Raw rows added to an hour that was already rolled up are not picked up.
+///
+/// # Returns
+///
+/// The number of `sensor_history_hourly` rows written
+pub async fn rollup_sensor_history(pool: &SqlitePool, until: NaiveDateTime) -> Result<u64, sqlx::Error> {
+    let until = until.format("%Y-%m-%d %H:00:00").to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO probe_history_hourly (hour, probe, temperature, samples)
+        SELECT strftime('%Y-%m-%d %H:00:00', timestamp) AS hour, probe, AVG(temperature), COUNT(*)
+        FROM probe_history
+        WHERE timestamp < ?
+          AND NOT EXISTS (
+              SELECT 1 FROM probe_history_hourly AS rolled
+              WHERE rolled.hour = strftime('%Y-%m-%d %H:00:00', probe_history.timestamp)
+                AND rolled.probe = probe_history.probe
+          )
+        GROUP BY hour, probe
+        "#,
+    )
+    .bind(&until)
+    .execute(pool)
+    .await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO sensor_history_hourly
+            (hour, basking_temp, control_temp, cool_temp, humidity, uv_1, uv_2, samples)
+        SELECT strftime('%Y-%m-%d %H:00:00', timestamp) AS hour,
+               AVG(basking_temp), AVG(control_temp), AVG(cool_temp), AVG(humidity), AVG(uv_1), AVG(uv_2),
+               COUNT(*)
+        FROM sensor_history
+        WHERE timestamp < ?
+          AND strftime('%Y-%m-%d %H:00:00', timestamp) NOT IN (SELECT hour FROM sensor_history_hourly)
+        GROUP BY hour
+        "#,
+    )
+    .bind(until)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Returns the newest `count` raw `sensor_history` rows, newest first, with
+/// every column as stored
+pub async fn recent_sensor_rows(pool: &SqlitePool, count: i64) -> Result<Vec<SensorHistoryRow>, sqlx::Error> {
+    sqlx::query_as!(
+        SensorHistoryRow,
+        r#"
+        SELECT
+            id as "id!",
+            timestamp as "timestamp: NaiveDateTime",
+            basking_temp, control_temp, cool_temp, humidity, uv_1, uv_2
+        FROM sensor_history
+        ORDER BY timestamp DESC, id DESC
+        LIMIT ?
+        "#,
+        count
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Extra DS18B20 probe readings between two `SENSOR_TIMESTAMP_FORMAT` times,
+/// keyed by the timestamp of their `sensor_history` row and then by probe name
+pub async fn probe_readings_between(
+    pool: &SqlitePool,
+    start: &str,
+    end: &str,
+) -> Result<HashMap<String, BTreeMap<String, f32>>, sqlx::Error> {
+    let rows: Vec<(String, String, Option<f64>)> = sqlx::query_as(
+        "SELECT timestamp, probe, temperature FROM probe_history WHERE timestamp BETWEEN ? AND ?"
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+    Ok(group_probe_rows(rows))
+}
+
+/// Like `probe_readings_between`, averaged per hour and keyed by the start of
+/// the hour as in `sensor_history_hourly`. Rolled-up hours come from
+/// `probe_history_hourly`, the others are averaged from the raw rows.
+pub async fn hourly_probe_readings_between(
+    pool: &SqlitePool,
+    start: &str,
+    end: &str,
+) -> Result<HashMap<String, BTreeMap<String, f32>>, sqlx::Error> {
+    let rows: Vec<(String, String, Option<f64>)> = sqlx::query_as(
+        r#"
+        SELECT hour, probe, temperature
+        FROM probe_history_hourly
+        WHERE hour BETWEEN ? AND ?
+        UNION ALL
+        SELECT strftime('%Y-%m-%d %H:00:00', timestamp) AS hour, probe, AVG(temperature)
+        FROM probe_history
+        WHERE timestamp BETWEEN ? AND ?
+          AND NOT EXISTS (
+              SELECT 1 FROM probe_history_hourly AS rolled
+              WHERE rolled.hour = strftime('%Y-%m-%d %H:00:00', probe_history.timestamp)
+                AND rolled.probe = probe_history.probe
+          )
+        GROUP BY 1, 2
+        "#
+    )
+    .bind(start)
+    .bind(end)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+    Ok(group_probe_rows(rows))
+}
+
+// A failed read is left out, so the graphs skip the probe at that point
+fn group_probe_rows(rows: Vec<(String, String, Option<f64>)>) -> HashMap<String, BTreeMap<String, f32>> {
+    let mut grouped: HashMap<String, BTreeMap<String, f32>> = HashMap::new();
+    for (timestamp, probe, temperature) in rows {
+        if let Some(temperature) = temperature {
+            grouped.entry(timestamp).or_default().insert(probe, temperature as f32);
+        }
+    }
+    grouped
+}
+
+/// Deletes raw `sensor_history` rows older than `before` whose hour is in
+/// `sensor_history_hourly`. Hours not yet rolled up are kept, wherever they
+/// fall, so the hourly averages never lose data. `probe_history` rows older
+/// than `before` are deleted the same way once in `probe_history_hourly`.
+///
+/// # Returns
+///
+/// The number of `sensor_history` rows deleted
+pub async fn prune_sensor_history(pool: &SqlitePool, before: NaiveDateTime) -> Result<u64, sqlx::Error> {
+    let before = before.format(SENSOR_TIMESTAMP_FORMAT).to_string();
+    sqlx::query(
+        r#"
+        DELETE FROM probe_history
+        WHERE timestamp < ?
+          AND EXISTS (
+              SELECT 1 FROM probe_history_hourly AS rolled
+              WHERE rolled.hour = strftime('%Y-%m-%d %H:00:00', probe_history.timestamp)
+                AND rolled.probe = probe_history.probe
+          )
+        "#,
+    )
+    .bind(&before)
+    .execute(pool)
+    .await?;
+
+    let result = sqlx::query(
+        r#"
+        DELETE FROM sensor_history
+        WHERE timestamp < ?
+          AND strftime('%Y-%m-%d %H:00:00', timestamp) IN (SELECT hour FROM sensor_history_hourly)
+        "#,
+    )
+    .bind(before)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Days of raw readings kept in the in-memory stand-in of a degraded start,
+/// which would otherwise grow until it runs out of memory
+pub const DEGRADED_STORAGE_DAYS: u32 = 1;
+
+/// Days of raw readings `maintain_sensor_history` keeps: `get_data.storage_days`,
+/// capped at `DEGRADED_STORAGE_DAYS` on the in-memory stand-in
+pub fn retained_days(storage_days: Option<u32>, degraded: bool) -> Option<u32> {
+    match (storage_days, degraded) {
+        (Some(days), true) => Some(days.min(DEGRADED_STORAGE_DAYS)),
+        (None, true) => Some(DEGRADED_STORAGE_DAYS),
+        (days, false) => days,
+    }
+}
+
+/// Background task keeping the hourly rollup current and deleting raw
+/// readings older than `retained_days`. Runs once at startup and then every hour.
+pub async fn maintain_sensor_history(pool: SqlitePool, storage_days: Option<u32>) {
+    let mut interval = tokio::time::interval(HISTORY_MAINTENANCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Local::now().naive_local();
+
+        if let Err(e) = retry_busy(|| rollup_sensor_history(&pool, now)).await {
+            error!("Failed to roll up sensor history: {}", e);
+            continue;
+        }
+        if let Some(days) = retained_days(storage_days, degraded().is_some()) {
+            match retry_busy(|| prune_sensor_history(&pool, now - Duration::days(days as i64))).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {} sensor readings older than {} days", deleted, days),
+                Err(e) => error!("Failed to prune sensor history: {}", e),
+            }
+        }
+    }
+}
+
 impl History {
     pub async fn get_history_for_month(
         pool: &SqlitePool,
@@ -157,7 +1401,7 @@ impl History {
         let history = sqlx::query_as!(
             History,
             r#"
-            SELECT * FROM history
+            SELECT * FROM sensor_history
             WHERE strftime('%Y-%m', timestamp) = ?
             ORDER BY timestamp
             "#,
@@ -169,3 +1413,562 @@ impl History {
         Ok(history)
     }
 }
+
+/// A database file in the temp dir for tests, deleted with its WAL files on drop
+#[cfg(test)]
+pub struct TempDbPath(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDbPath {
+    /// `terra_<name>_<pid>.db`, with any file left over from an aborted run removed
+    pub fn new(name: &str) -> Self {
+        let path = Self(std::env::temp_dir().join(format!("terra_{}_{}.db", name, std::process::id())));
+        path.remove();
+        path
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    pub fn exists(&self) -> bool {
+        self.0.exists()
+    }
+
+    fn remove(&self) {
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.as_str(), suffix));
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDbPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// A database initialized in the temp dir for tests, see `temp_db`
+#[cfg(test)]
+pub struct TempDb {
+    pub pool: SqlitePool,
+    pub path: TempDbPath,
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDb {
+    type Target = SqlitePool;
+
+    fn deref(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+/// Opens a fresh database through `initialize_db` at `TempDbPath::new(name)`.
+/// The file is removed when the returned `TempDb` is dropped.
+#[cfg(test)]
+pub async fn temp_db(name: &str) -> TempDb {
+    let path = TempDbPath::new(name);
+    let pool = initialize_db(path.as_str()).await.unwrap();
+    TempDb { pool, path }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_scope_of_matches_only_active_keys() {
+        let pool = temp_db("key_scope").await;
+        let (_, read_key) = ApiKey::create(&pool, "wall display", ApiScope::Read).await.unwrap();
+        let (phone, control_key) = ApiKey::create(&pool, "phone", ApiScope::Control).await.unwrap();
+
+        assert_eq!(ApiKey::scope_of(&pool, &read_key).await.unwrap(), Some(ApiScope::Read));
+        assert_eq!(ApiKey::scope_of(&pool, &control_key).await.unwrap(), Some(ApiScope::Control));
+        assert_eq!(ApiKey::scope_of(&pool, &control_key[1..]).await.unwrap(), None);
+
+        ApiKey::revoke(&pool, phone.id).await.unwrap();
+        assert_eq!(ApiKey::scope_of(&pool, &control_key).await.unwrap(), None);
+        assert!(key_hashes_match(&hash_api_key("a"), &hash_api_key("a")) && !key_hashes_match("ab", "abc"));
+    }
+
+    #[tokio::test]
+    async fn test_busy_write_retried_until_lock_released() {
+        let path = TempDbPath::new("busy");
+        let pool = initialize_db_with_busy_timeout(path.as_str(), std::time::Duration::ZERO).await.unwrap();
+
+        // Another connection holds the write lock for a moment
+        let mut blocker = pool.begin().await.unwrap();
+        sqlx::query("INSERT INTO logs (timestamp, level, message) VALUES (?, 'INFO', 'blocker')")
+            .bind(Utc::now())
+            .execute(&mut blocker)
+            .await
+            .unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(120)).await;
+            blocker.commit().await.unwrap();
+        });
+
+        let write = || sqlx::query("INSERT INTO logs (timestamp, level, message) VALUES (?, 'INFO', 'retried')")
+            .bind(Utc::now())
+            .execute(&pool);
+        let err = retry_busy_with(0, write).await.unwrap_err();
+        assert!(is_busy(&err), "{}", err);
+        retry_busy_with(5, write).await.unwrap();
+        release.await.unwrap();
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM logs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reset_restores_schedule_and_keeps_history() {
+        let pool = temp_db("reset").await;
+        let config = crate::modules::config::test_config();
+
+        let mut edited = Schedule::default_week(12, &config.db);
+        edited.heat_start = "03:00".to_string();
+        edited.uv1_level = 40;
+        edited.upsert(&pool).await.unwrap();
+        sqlx::query("UPDATE led_settings SET r = 1, override = 1 WHERE id = 1").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO sensor_history (timestamp, basking_temp) VALUES ('2024-03-01 10:00:00', 31.5)")
+            .execute(&pool).await.unwrap();
+
+        assert_eq!(reset_to_defaults(&pool, &config).await.unwrap(), 53);
+
+        let schedule = Schedule::get_schedule(&pool).await.unwrap();
+        let defaults: Vec<Schedule> = (1..=52).map(|week| Schedule::default_week(week, &config.db)).collect();
+        assert_eq!(schedule, defaults);
+
+        let (r, override_natural): (i64, i64) = sqlx::query_as("SELECT r, override FROM led_settings WHERE id = 1")
+            .fetch_one(&pool).await.unwrap();
+        assert_eq!((r, override_natural), (config.db.def_led_R as i64, 0));
+
+        let (history,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sensor_history").fetch_one(&pool).await.unwrap();
+        assert_eq!(history, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_seeded_with_52_weeks_once() {
+        let pool = temp_db("seed").await;
+        let config = crate::modules::config::test_config();
+        assert!(Schedule::get_schedule(&pool).await.unwrap().is_empty());
+
+        assert_eq!(seed_schedule(&pool, &config.db).await.unwrap(), 52);
+        let defaults: Vec<Schedule> = (1..=52).map(|week| Schedule::default_week(week, &config.db)).collect();
+        assert_eq!(Schedule::get_schedule(&pool).await.unwrap(), defaults);
+
+        // Later starts keep the edited schedule
+        let mut edited = Schedule::default_week(12, &config.db);
+        edited.heat_start = "03:00".to_string();
+        edited.upsert(&pool).await.unwrap();
+        assert_eq!(seed_schedule(&pool, &config.db).await.unwrap(), 0);
+        let schedule = Schedule::get_schedule(&pool).await.unwrap();
+        assert_eq!((schedule.len(), schedule[11].heat_start.as_str()), (52, "03:00"));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_profile_round_trip() {
+        let pool = temp_db("profiles").await;
+        let config = crate::modules::config::test_config();
+
+        reset_to_defaults(&pool, &config).await.unwrap();
+        let summer = Schedule::get_schedule(&pool).await.unwrap();
+        let saved = ScheduleProfile::save(&pool, "summer", Some("Summer"), Some("#ffaa00")).await.unwrap();
+        assert_eq!(saved.weeks, 52);
+
+        let mut winter_week = Schedule::default_week(12, &config.db);
+        winter_week.uv1_end = "16:00".to_string();
+        winter_week.heat_target = Some(28.0);
+        winter_week.upsert(&pool).await.unwrap();
+        ScheduleProfile::save(&pool, "winter", None, None).await.unwrap();
+
+        assert_eq!(ScheduleProfile::activate(&pool, "summer").await.unwrap(), Some(52));
+        assert_eq!(Schedule::get_schedule(&pool).await.unwrap(), summer);
+        assert_eq!(ScheduleProfile::activate(&pool, "winter").await.unwrap(), Some(52));
+        assert_eq!(Schedule::get_week(&pool, 12).await.unwrap(), Some(winter_week));
+
+        // A missing profile leaves the schedule alone
+        assert_eq!(ScheduleProfile::activate(&pool, "spring").await.unwrap(), None);
+        assert_eq!(Schedule::get_week(&pool, 12).await.unwrap().unwrap().uv1_end, "16:00");
+
+        let profiles = ScheduleProfile::list(&pool).await.unwrap();
+        assert_eq!(profiles.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["summer", "winter"]);
+        assert_eq!(profiles[0], saved);
+        assert!(ScheduleProfile::validate_name("Summer 2024").is_err());
+        assert!(ScheduleProfile::validate_label(None, Some("orange")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_upsert_all_is_all_or_nothing() {
+        let pool = temp_db("upsert_all").await;
+        let config = crate::modules::config::test_config();
+        reset_to_defaults(&pool, &config).await.unwrap();
+        let before = Schedule::get_schedule(&pool).await.unwrap();
+
+        // Writing week 3 fails, after weeks 1 and 2 went through
+        sqlx::query("CREATE TRIGGER fail_week_3 BEFORE UPDATE ON schedule WHEN NEW.week_number = 3 BEGIN SELECT RAISE(ABORT, 'disk I/O error'); END")
+            .execute(&*pool).await.unwrap();
+        let weeks: Vec<Schedule> = (1..=3).map(|week_number| Schedule {
+            uv1_end: "16:00".to_string(),
+            ..Schedule::default_week(week_number, &config.db)
+        }).collect();
+        assert!(Schedule::upsert_all(&pool, &weeks).await.is_err());
+        assert_eq!(Schedule::get_schedule(&pool).await.unwrap(), before);
+
+        sqlx::query("DROP TRIGGER fail_week_3").execute(&*pool).await.unwrap();
+        Schedule::upsert_all(&pool, &weeks).await.unwrap();
+        assert_eq!(Schedule::get_week(&pool, 3).await.unwrap().unwrap().uv1_end, "16:00");
+    }
+
+    #[tokio::test]
+    async fn test_hourly_rollup_matches_raw_average() {
+        let pool = temp_db("rollup").await;
+
+        for (timestamp, basking, humidity) in [
+            ("2024-03-01 10:00:00", 30.0, 50.0),
+            ("2024-03-01 10:20:30", 31.0, 55.0),
+            ("2024-03-01 10:59:59", 35.0, 60.0),
+            ("2024-03-01 11:00:00", 40.0, 70.0),
+        ] {
+            sqlx::query("INSERT INTO sensor_history (timestamp, basking_temp, humidity) VALUES (?, ?, ?)")
+                .bind(timestamp).bind(basking).bind(humidity)
+                .execute(&pool).await.unwrap();
+        }
+
+        // 11:00 is still in progress and stays raw-only
+        let until = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(11, 30, 0).unwrap();
+        assert_eq!(rollup_sensor_history(&pool, until).await.unwrap(), 1);
+
+        let hourly: (String, f64, f64, Option<f64>, i64) = sqlx::query_as(
+            "SELECT hour, basking_temp, humidity, uv_1, samples FROM sensor_history_hourly"
+        ).fetch_one(&pool).await.unwrap();
+        let raw: (f64, f64) = sqlx::query_as(
+            "SELECT AVG(basking_temp), AVG(humidity) FROM sensor_history WHERE timestamp LIKE '2024-03-01 10:%'"
+        ).fetch_one(&pool).await.unwrap();
+        assert_eq!(hourly, ("2024-03-01 10:00:00".to_string(), raw.0, raw.1, None, 3));
+        assert_eq!(raw.0, 32.0);
+
+        // Raw rows of the rolled-up hour can go, the open hour stays
+        assert_eq!(prune_sensor_history(&pool, until).await.unwrap(), 3);
+        let (left,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sensor_history").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 1);
+    }
+
+    #[tokio::test]
+    async fn test_probe_hours_outlive_pruned_readings() {
+        let pool = temp_db("rollup_probes").await;
+        for (timestamp, temperature) in [
+            ("2024-03-01 10:00:00", Some(24.0)),
+            ("2024-03-01 10:30:00", None),
+            ("2024-03-01 10:45:00", Some(26.0)),
+            ("2024-03-01 11:00:00", Some(30.0)),
+        ] {
+            sqlx::query("INSERT INTO probe_history (timestamp, probe, temperature) VALUES (?, 'hide', ?)")
+                .bind(timestamp).bind(temperature)
+                .execute(&pool).await.unwrap();
+        }
+
+        let until = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(11, 30, 0).unwrap();
+        rollup_sensor_history(&pool, until).await.unwrap();
+        prune_sensor_history(&pool, until).await.unwrap();
+        let (left,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM probe_history").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 1);
+
+        let hourly = hourly_probe_readings_between(&pool, "2024-03-01 00:00:00", "2024-03-01 23:59:59").await.unwrap();
+        assert_eq!(hourly["2024-03-01 10:00:00"]["hide"], 25.0);
+        assert_eq!(hourly["2024-03-01 11:00:00"]["hide"], 30.0);
+    }
+
+    #[tokio::test]
+    async fn test_backfilled_hours_kept_until_rolled_up() {
+        let pool = temp_db("rollup_backfill").await;
+        let insert = |timestamp: &'static str| sqlx::query("INSERT INTO sensor_history (timestamp, basking_temp) VALUES (?, 30.0)")
+            .bind(timestamp)
+            .execute(&pool.pool);
+        let count = || async {
+            let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sensor_history").fetch_one(&pool.pool).await.unwrap();
+            count
+        };
+
+        insert("2024-03-01 10:15:00").await.unwrap();
+        let until = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(rollup_sensor_history(&pool, until).await.unwrap(), 1);
+
+        // Imported after 10:00 was rolled up, older than it
+        insert("2024-03-01 08:15:00").await.unwrap();
+        insert("2024-03-01 08:45:00").await.unwrap();
+        assert_eq!(prune_sensor_history(&pool, until).await.unwrap(), 1);
+        assert_eq!(count().await, 2);
+
+        // Averaged on the next rollup, then prunable
+        assert_eq!(rollup_sensor_history(&pool, until).await.unwrap(), 1);
+        assert_eq!(prune_sensor_history(&pool, until).await.unwrap(), 2);
+        assert_eq!(count().await, 0);
+        let (hours,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sensor_history_hourly").fetch_one(&pool.pool).await.unwrap();
+        assert_eq!(hours, 2);
+    }
+
+    #[tokio::test]
+    async fn test_week_53_uses_week_52_schedule() {
+        let pool = temp_db("week53").await;
+        let config = crate::modules::config::test_config();
+
+        let mut last = Schedule::default_week(52, &config.db);
+        last.heat_start = "09:15".to_string();
+        last.upsert(&pool).await.unwrap();
+
+        // 2020-12-31 falls in ISO week 53
+        let date = NaiveDate::from_ymd_opt(2020, 12, 31).unwrap();
+        assert_eq!(chrono::Datelike::iso_week(&date).week(), 53);
+        assert_eq!(Schedule::week_for(&date), 52);
+
+        let week = Schedule::get_week(&pool, Schedule::week_for(&date)).await.unwrap();
+        assert_eq!(week.map(|w| w.heat_start), Some("09:15".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_unopenable_db_degrades_to_config_defaults() {
+        let path = TempDbPath::new("corrupt");
+        let garbage = b"not a sqlite database, e.g. after a half-written page".repeat(100);
+        std::fs::write(path.as_str(), &garbage).unwrap();
+        let mut config = crate::modules::config::test_config();
+        config.db.path = Some(path.as_str().to_string());
+
+        config.db.on_unavailable = DbUnavailable::Exit;
+        assert!(open_db(&config.db).await.is_err());
+
+        config.db.on_unavailable = DbUnavailable::Degraded;
+        let (pool, degraded) = open_db(&config.db).await.unwrap();
+        assert!(degraded.unwrap().contains(path.as_str()));
+
+        // No stored weeks, so the control loops fall back to the [db] defaults
+        let week = Schedule::get_week(&pool, 20).await.unwrap();
+        assert!(week.is_none());
+        let relays = crate::modules::lightControl::resolve_relay_states(&config.db.def_heat_start, week.as_ref(), &config);
+        assert!(relays.heat);
+
+        // The stand-in keeps its state across queries and leaves the file alone
+        Schedule::default_week(20, &config.db).upsert(&pool).await.unwrap();
+        assert!(Schedule::get_week(&pool, 20).await.unwrap().is_some());
+        assert_eq!(std::fs::read(path.as_str()).unwrap(), garbage);
+
+        // ...and only holds a day of raw readings, whatever `storage_days` says
+        assert_eq!(retained_days(None, true), Some(DEGRADED_STORAGE_DAYS));
+        assert_eq!(retained_days(Some(30), true), Some(DEGRADED_STORAGE_DAYS));
+        assert_eq!(retained_days(None, false), None);
+        assert_eq!(retained_days(Some(30), false), Some(30));
+    }
+
+    #[tokio::test]
+    async fn test_old_schedule_table_is_migrated() {
+        let path = TempDbPath::new("old_schema");
+
+        // Schedule table as created before the LED window and UV levels were stored
+        let options = SqliteConnectOptions::new().filename(path.as_str()).create_if_missing(true);
+        let old = SqlitePoolOptions::new().connect_with(options).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE schedule (week_number INTEGER PRIMARY KEY, uv1_start TEXT NOT NULL, uv1_end TEXT NOT NULL, \
+             uv2_start TEXT NOT NULL, uv2_end TEXT NOT NULL, heat_start TEXT NOT NULL, heat_end TEXT NOT NULL, \
+             led_r INTEGER NOT NULL, led_g INTEGER NOT NULL, led_b INTEGER NOT NULL, led_cw INTEGER NOT NULL, led_ww INTEGER NOT NULL)"
+        ).execute(&old).await.unwrap();
+        sqlx::query("INSERT INTO schedule VALUES (5, '06:30', '19:00', '07:00', '19:30', '10:00', '14:30', 1, 2, 3, 4, 5)")
+            .execute(&old).await.unwrap();
+        old.close().await;
+
+        let pool = initialize_db(path.as_str()).await.unwrap();
+        let week = Schedule::get_week(&pool, 5).await.unwrap().unwrap();
+        assert_eq!((week.led_start.as_str(), week.led_end.as_str()), ("07:00", "19:00"));
+        assert_eq!((week.led_r, week.led_ww, week.uv1_level, week.heat_target), (1, 5, 100, None));
+        assert!(audit_schema(&pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unmigratable_schema_fails_naming_the_columns() {
+        let path = TempDbPath::new("bad_schema");
+
+        let options = SqliteConnectOptions::new().filename(path.as_str()).create_if_missing(true);
+        let old = SqlitePoolOptions::new().connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE schedule (week INTEGER PRIMARY KEY, uv1_start TEXT, uv1_end TEXT)")
+            .execute(&old).await.unwrap();
+        old.close().await;
+
+        let err = initialize_db(path.as_str()).await.unwrap_err().to_string();
+        assert!(err.contains("schedule.week_number"), "{}", err);
+        assert!(err.contains("schedule.heat_end"), "{}", err);
+        assert!(!err.contains("led_start"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_db_at_custom_path() {
+        let path = TempDbPath::new("test");
+
+        let pool = initialize_db(path.as_str()).await.unwrap();
+        assert!(path.exists());
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM led_settings")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_validate_setting_uses_allowlist() {
+        assert!(validate_setting(AWAY_MODE_KEY, "true").is_ok());
+        assert!(validate_setting(AWAY_MODE_KEY, "yes").is_err());
+        assert!(validate_setting("db_path", "/tmp/x.db").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_settings_round_trip() {
+        let pool = temp_db("settings").await;
+
+        assert_eq!(get_setting(&pool, AWAY_MODE_KEY).await.unwrap(), None);
+        set_setting(&pool, AWAY_MODE_KEY, "true").await.unwrap();
+        set_setting(&pool, AWAY_MODE_KEY, "false").await.unwrap();
+        assert_eq!(get_setting(&pool, AWAY_MODE_KEY).await.unwrap(), Some("false".to_string()));
+        assert_eq!(load_settings(&pool).await.unwrap()[AWAY_MODE_KEY], "false");
+    }
+
+    #[tokio::test]
+    async fn test_load_settings_fills_defaults() {
+        let pool = temp_db("settings_defaults").await;
+
+        let settings = load_settings(&pool).await.unwrap();
+        assert_eq!(settings.len(), KNOWN_SETTINGS.len());
+        for (key, default) in KNOWN_SETTINGS {
+            assert_eq!(&settings[*key], default);
+        }
+
+    }
+
+    #[tokio::test]
+    async fn test_relay_events_written_newest_first() {
+        let pool = temp_db("relay_events").await;
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        for (relay, state, reason) in [("heat", true, RelayReason::Schedule), ("heat", false, RelayReason::Overheat)] {
+            sender.send(RelayEvent { timestamp: Utc::now(), relay: relay.to_string(), state, reason }).unwrap();
+        }
+        drop(sender);
+        write_relay_events(pool.clone(), receiver).await;
+
+        let history = RelayEvent::get_history(&pool, 10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[0].state, history[0].reason), (false, RelayReason::Overheat));
+        assert_eq!((history[1].state, history[1].reason), (true, RelayReason::Schedule));
+    }
+
+    #[tokio::test]
+    async fn test_recent_sensor_rows_newest_first() {
+        let pool = temp_db("recent_rows").await;
+
+        for minute in 0..5 {
+            sqlx::query("INSERT INTO sensor_history (timestamp, basking_temp, humidity) VALUES (?, ?, 60.125)")
+                .bind(format!("2024-03-01 10:0{}:30", minute))
+                .bind(30.0 + minute as f64 / 8.0)
+                .execute(&pool).await.unwrap();
+        }
+
+        let rows = recent_sensor_rows(&pool, 3).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        let times: Vec<String> = rows.iter().map(|row| row.timestamp.format(SENSOR_TIMESTAMP_FORMAT).to_string()).collect();
+        assert_eq!(times, ["2024-03-01 10:04:30", "2024-03-01 10:03:30", "2024-03-01 10:02:30"]);
+        assert_eq!((rows[0].basking_temp, rows[0].humidity, rows[0].cool_temp), (Some(30.5), Some(60.125), None));
+        assert_eq!(recent_sensor_rows(&pool, 10).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_activity_merges_sources_newest_first() {
+        let pool = temp_db("activity").await;
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+
+        sqlx::query("INSERT INTO logs (timestamp, level, message) VALUES (?, 'WARNING', 'first'), (?, 'INFO', 'last')")
+            .bind(start)
+            .bind(start + Duration::minutes(30))
+            .execute(&pool)
+            .await
+            .unwrap();
+        for (minutes, state) in [(10, true), (25, false)] {
+            RelayEvent { timestamp: start + Duration::minutes(minutes), relay: "heat".to_string(), state, reason: RelayReason::Overheat }
+                .insert(&pool).await.unwrap();
+        }
+        OverheatEvent { started_at: start + Duration::minutes(20), ended_at: None, peak_temp: 47.5, duration_seconds: None, zone: "basking".to_string() }
+            .insert(&pool).await.unwrap();
+
+        let kind = |entry: &ActivityEntry| match entry {
+            ActivityEntry::Log { .. } => "log",
+            ActivityEntry::Relay(_) => "relay",
+            ActivityEntry::Overheat(_) => "overheat",
+        };
+        let kinds = |page: &[(ActivityCursor, ActivityEntry)]| page.iter().map(|(_, entry)| kind(entry)).collect::<Vec<_>>();
+        let activity = recent_activity(&pool, None, 10).await.unwrap();
+        assert_eq!(kinds(&activity), ["log", "relay", "overheat", "relay", "log"]);
+        assert_eq!(activity[0].1.timestamp(), start + Duration::minutes(30));
+
+        // The next page continues below the last entry of a full one
+        let page = recent_activity(&pool, None, 2).await.unwrap();
+        assert_eq!(kinds(&page), ["log", "relay"]);
+        let next = recent_activity(&pool, Some(page[1].0), 2).await.unwrap();
+        assert_eq!(kinds(&next), ["overheat", "relay"]);
+        assert_eq!(next[1].1.timestamp(), start + Duration::minutes(10));
+
+        // Entries sharing the timestamp a page ends on carry over to the next page
+        let tied = start + Duration::minutes(40);
+        for message in ["a", "b", "c"] {
+            sqlx::query("INSERT INTO logs (timestamp, level, message) VALUES (?, 'INFO', ?)")
+                .bind(tied).bind(message).execute(&pool).await.unwrap();
+        }
+        RelayEvent { timestamp: tied, relay: "uv1".to_string(), state: true, reason: RelayReason::Schedule }
+            .insert(&pool).await.unwrap();
+        let first = recent_activity(&pool, None, 2).await.unwrap();
+        let second = recent_activity(&pool, Some(first[1].0), 2).await.unwrap();
+        let third = recent_activity(&pool, Some(second[1].0), 2).await.unwrap();
+        let paged: Vec<ActivityCursor> = first.iter().chain(&second).chain(&third).map(|(cursor, _)| *cursor).collect();
+        assert!(paged[..4].iter().all(|cursor| cursor.timestamp == tied), "{:?}", paged);
+        assert!(paged.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", paged);
+        assert_eq!(third[1].1.timestamp(), start + Duration::minutes(30));
+
+        let json = serde_json::to_value(&next[0].1).unwrap();
+        assert_eq!((json["type"].as_str(), json["zone"].as_str()), (Some("overheat"), Some("basking")));
+    }
+
+    #[test]
+    fn test_duty_cycle_pairs_on_off_events() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let end = start + Duration::days(1);
+        let event = |hour: i64, state: bool| RelayEvent {
+            timestamp: start + Duration::hours(hour),
+            relay: "heat".to_string(),
+            state,
+            reason: RelayReason::Schedule,
+        };
+
+        // On from midnight (carried over), off 2-8, on 8-20, overheat cut at 20
+        let events = [event(2, false), event(8, true), event(8, true), event(20, false)];
+        let duty = duty_cycle("heat", true, &events, start, end);
+        assert_eq!(duty.on_minutes, 14.0 * 60.0);
+        assert!((duty.percent - 14.0 * 100.0 / 24.0).abs() < 1e-9);
+
+        // Still on at the end of the period
+        let duty = duty_cycle("heat", false, &[event(18, true)], start, end);
+        assert_eq!(duty.on_minutes, 6.0 * 60.0);
+        assert_eq!(duty.percent, 25.0);
+    }
+
+    #[test]
+    fn test_duty_cycle_without_events_is_zero() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let duty = duty_cycle("uv1", false, &[], start, start + Duration::days(1));
+        assert_eq!((duty.on_minutes, duty.percent), (0.0, 0.0));
+
+        let duty = duty_cycle("uv1", true, &[], start, start);
+        assert_eq!((duty.on_minutes, duty.percent), (0.0, 0.0));
+    }
+}
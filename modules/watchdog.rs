@@ -0,0 +1,106 @@
+// modules/watchdog.rs
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex as StdMutex, OnceLock};
+use std::time::Duration;
+use log::error;
+use sqlx::SqlitePool;
+use crate::modules::logs;
+
+// Restarts of every supervised loop by name, kept for the process lifetime
+static RESTARTS: OnceLock<StdMutex<BTreeMap<&'static str, u32>>> = OnceLock::new();
+
+fn restarts() -> &'static StdMutex<BTreeMap<&'static str, u32>> {
+    RESTARTS.get_or_init(Default::default)
+}
+
+/// How often each supervised loop was restarted after a panic, by name.
+/// Loops that never panicked are listed with 0.
+pub fn restart_counts() -> BTreeMap<String, u32> {
+    let restarts = restarts().lock().unwrap_or_else(|e| e.into_inner());
+    restarts.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+}
+
+/// Runs the loop made by `make` on its own task and, whenever it panics,
+/// logs the panic and starts a fresh one after `backoff`.
+///
+/// A panic only takes down that one loop instead of the whole controller.
+/// `make` is called again for each restart, so the loop starts over with
+/// fresh local state; shared state behind a tokio `Mutex` isn't poisoned.
+///
+/// # Arguments
+///
+/// * `db_pool` - Database connection pool, for logging restarts
+/// * `name` - Name of the loop in logs and `restart_counts`
+/// * `backoff` - Wait before a restart, see `[main] restart_backoff_secs`
+/// * `make` - Builds the loop
+///
+/// # Returns
+///
+/// Once the loop ends without panicking, or its task is cancelled
+pub async fn supervise<F, Fut>(db_pool: Arc<SqlitePool>, name: &'static str, backoff: Duration, mut make: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    restarts().lock().unwrap_or_else(|e| e.into_inner()).entry(name).or_insert(0);
+    loop {
+        let panic = match tokio::spawn(make()).await {
+            Ok(()) => return,
+            Err(e) if e.is_panic() => e.into_panic(),
+            Err(_) => return,
+        };
+
+        let count = {
+            let mut restarts = restarts().lock().unwrap_or_else(|e| e.into_inner());
+            let count = restarts.entry(name).or_insert(0);
+            *count += 1;
+            *count
+        };
+        let message = format!(
+            "{} loop panicked: {}; restarting in {}s (restart {})",
+            name, panic_message(panic.as_ref()), backoff.as_secs(), count
+        );
+        error!("{}", message);
+        if let Err(e) = logs::log(&db_pool, "ERROR", &message).await {
+            eprintln!("Failed to log {} loop panic: {:?}", name, e);
+        }
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// The message a panic was raised with, if it has a printable one
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message,
+        _ => "no message",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_loop_panicking_once_is_respawned() {
+        let db_pool = Arc::new(SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap());
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let started = tokio::time::Instant::now();
+        supervise(db_pool, "flaky", Duration::from_millis(50), || {
+            let runs = Arc::clone(&runs);
+            async move {
+                if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("sensor bus gone");
+                }
+            }
+        }).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(restart_counts().get("flaky"), Some(&1));
+    }
+}
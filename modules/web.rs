@@ -1,27 +1,38 @@
 use axum::{
-    extract::{Json, State, Query, Path},
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Json, State, Query, Path},
+    routing::{delete, get, post, put},
     Router,
     response::{IntoResponse, Response},
-    http::{StatusCode, header},
-    body::Body,
+    http::{StatusCode, header, HeaderMap, HeaderValue, Method, Request, Uri},
+    middleware::{self, Next},
+    body::{Body, StreamBody},
 };
 use serde::{Deserialize, Serialize};
 use sqlx::{SqlitePool, SqlitePoolOptions};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use crate::modules::config::{WebConfig, Config};
-use crate::modules::models::Schedule;
-use crate::modules::gpio::{RelayController, RelayType, RGBWW};
-use crate::modules::lightControl::LightController;
-use crate::modules::ledStrip::LEDController;
-use crate::modules::getData::{CurrentReadings, get_current_readings, get_overheat_status};
+use futures::StreamExt;
+use crate::modules::config::{WebConfig, Config, RequestLogMode, SensorsConfig};
+use crate::modules::models::{ActivityCursor, ActivityEntry, ApiKey, ApiScope, DutyCycle, Schedule, OverheatEvent, OverheatStatus, RelayEvent, RelayReason, ScheduleProfile, SensorHistoryRow, SensorStatus, TimelineEvent, SCHEDULE_WEEKS};
+use crate::modules::gpio::{LedWiring, RelayController, RelayType, RGBWW, LED_SPI_BUS, LED_SPI_CLOCK_HZ, LED_SPI_MODE, LED_SPI_SLAVE_SELECT};
+use crate::modules::lightControl::{LightController, resolve_relay_states};
+use crate::modules::animations::AnimationKind;
+use crate::modules::ledStrip::{ChannelCaps, LEDController, LedAlert, LedSettings, NaturalLightInputs, disable_led_settings, estimate_cct, get_led_settings, natural_light_curve, resolve_led_color, update_leds};
+use crate::modules::getData::{self, CurrentReadings, get_current_readings, get_overheat_status};
+use crate::modules::alerts::{self, AlertStatus};
 use crate::modules::logs;
-use crate::modules::cam::{CameraService, CameraError};
-use chrono::{DateTime, Utc, NaiveDateTime, NaiveDate, NaiveTime};
+use crate::modules::simulation::{self, VirtualClock};
+use crate::modules::watchdog;
+use crate::modules::calendar;
+use crate::modules::openapi;
+use crate::modules::storage;
+use crate::modules::cam::{self, CameraService, CameraError};
+use chrono::{DateTime, Datelike, Utc, NaiveDateTime, NaiveDate, NaiveTime, Timelike};
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
 
 // ===== Utility Types =====
 
@@ -39,6 +50,10 @@ pub enum ApiError {
     BadRequest(String),
     /// Unauthorized access error (HTTP 401)
     Unauthorized(String),
+    /// Authenticated, but not allowed to do this (HTTP 403)
+    Forbidden(String),
+    /// Request rejected by rate limiting (HTTP 429)
+    TooManyRequests(String),
 }
 
 impl IntoResponse for ApiError {
@@ -48,6 +63,8 @@ impl IntoResponse for ApiError {
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
         };
         
         (status, Json(serde_json::json!({ "error": message }))).into_response()
@@ -67,6 +84,52 @@ pub fn map_db_error<E: std::fmt::Display>(err: E) -> ApiError {
     ApiError::InternalError(format!("Database error: {}", err))
 }
 
+/// Temperature unit used in API responses.
+///
+/// Temperatures are always stored in Celsius; this only affects how they are
+/// reported. Selected with the `units` query parameter (`c` or `f`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TemperatureUnit {
+    #[default]
+    #[serde(rename = "c", alias = "C")]
+    Celsius,
+    #[serde(rename = "f", alias = "F")]
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// Converts a Celsius value into this unit
+    pub fn convert(self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    /// Unit symbol shown next to temperatures
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+
+    /// Converts a Celsius temperature difference (or rate) into this unit
+    pub fn convert_delta(self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0,
+        }
+    }
+}
+
+/// Query parameter selecting the temperature unit of a response
+#[derive(Debug, Default, Deserialize)]
+pub struct UnitsQuery {
+    #[serde(default)]
+    pub units: TemperatureUnit,
+}
+
 // Shared application state
 /// Shared application state for all API handlers.
 ///
@@ -77,6 +140,8 @@ pub fn map_db_error<E: std::fmt::Display>(err: E) -> ApiError {
 /// - Current sensor readings
 /// - Application configuration
 /// - Camera service
+/// - Away mode flag, shared with the control loops
+/// - Persisted runtime settings (see `storage::KNOWN_SETTINGS`)
 ///
 /// It's used with Axum's State extractor to provide handlers access to these resources.
 pub struct AppState {
@@ -87,6 +152,10 @@ pub struct AppState {
     current_readings: Arc<Mutex<CurrentReadings>>,
     config: Arc<Config>,
     camera_service: Arc<CameraService>,
+    last_manual_read: Arc<Mutex<Option<Instant>>>,
+    away_mode: Arc<Mutex<bool>>,
+    boot_hold: Arc<Mutex<bool>>,
+    settings: Arc<Mutex<HashMap<String, String>>>,
 }
 
 // Helper methods for AppState
@@ -137,6 +206,20 @@ impl AppState {
         &self.config
     }
     
+    /// Validates and persists a setting, then applies it to the running system
+    pub async fn store_setting(&self, key: &str, value: &str) -> Result<(), ApiError> {
+        storage::validate_setting(key, value).map_err(ApiError::BadRequest)?;
+        storage::retry_busy(|| storage::set_setting(&self.db_pool, key, value))
+            .await
+            .map_err(map_db_error)?;
+        
+        if key == storage::AWAY_MODE_KEY {
+            *self.away_mode.lock().await = value == "true";
+        }
+        self.settings.lock().await.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+    
     /// Execute a database query and map the error to an ApiError
     pub async fn query<T, E, F>(&self, query_fn: F) -> Result<T, ApiError>
     where
@@ -194,6 +277,9 @@ use handlers::camera::*;
 /// * `current_readings` - Shared state for current sensor readings
 /// * `config` - Application configuration
 /// * `camera_service` - Camera service for snapshots and streaming
+/// * `away_mode` - Away mode flag, also read by the control loops
+/// * `boot_hold` - Set while `boot_behavior = "safe_off"` pauses the control loops
+/// * `settings` - Known settings as loaded by `storage::load_settings`
 ///
 /// # Returns
 ///
@@ -206,6 +292,9 @@ pub async fn create_router(
     current_readings: Arc<Mutex<CurrentReadings>>,
     config: Arc<Config>,
     camera_service: Arc<CameraService>,
+    away_mode: Arc<Mutex<bool>>,
+    boot_hold: Arc<Mutex<bool>>,
+    settings: HashMap<String, String>,
 ) -> Router {
     let state = AppState {
         db_pool: Arc::new(db_pool.clone()),
@@ -215,16 +304,241 @@ pub async fn create_router(
         current_readings,
         config,
         camera_service,
+        last_manual_read: Arc::new(Mutex::new(None)),
+        away_mode,
+        boot_hold,
+        settings: Arc::new(Mutex::new(settings)),
+    };
+
+    let request_log = RequestLogState {
+        db_pool: Arc::clone(&state.db_pool),
+        mode: state.config.web.request_log,
     };
 
+    // Applies to every extracted body; the sensor data import sets its own
+    let body_limit = state.config.web.max_body_bytes();
+
+    let auth = AuthState::new(&state.db_pool, &state.config);
+
     Router::new()
-        .merge(schedule_routes())
-        .merge(led_routes())
-        .merge(monitoring_routes())
-        .merge(system_routes())
-        .merge(camera_routes())
+        .merge(with_scope(schedule_routes(), &auth, ApiScope::Read, ApiScope::Admin))
+        .merge(with_scope(led_routes(), &auth, ApiScope::Read, ApiScope::Control))
+        .merge(with_scope(monitoring_routes(), &auth, ApiScope::Read, ApiScope::Control))
+        .merge(with_scope(data_routes(state.config.web.max_import_body_bytes()), &auth, ApiScope::Read, ApiScope::Control))
+        .merge(with_scope(system_routes(), &auth, ApiScope::Read, ApiScope::Control))
+        .merge(with_scope(settings_routes(), &auth, ApiScope::Read, ApiScope::Admin))
+        .merge(with_scope(api_key_routes(), &auth, ApiScope::Admin, ApiScope::Admin))
+        .merge(with_scope(camera_routes(), &auth, ApiScope::Read, ApiScope::Control))
         .fallback(handle_not_found)
         .with_state(state)
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(middleware::from_fn_with_state(request_log, log_requests))
+}
+
+// ===== Request Logging =====
+
+/// Response header carrying the id `log_requests` gave the request
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Counter behind the request ids, unique for the lifetime of the process
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// What `log_requests` needs; kept apart from `AppState` so the layer stays cheap to clone
+#[derive(Clone)]
+pub struct RequestLogState {
+    pub db_pool: Arc<SqlitePool>,
+    pub mode: RequestLogMode,
+}
+
+/// Returns a new request id: the startup second and a counter, in hex
+fn next_request_id() -> String {
+    static STARTED: std::sync::OnceLock<i64> = std::sync::OnceLock::new();
+    let started = *STARTED.get_or_init(|| Utc::now().timestamp());
+    format!("{:x}-{:x}", started, NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Middleware: tags every response with a request id and logs method, path,
+/// status and latency at DEBUG, as selected by `[web] request_log`.
+///
+/// Log lines are written on their own task so the response isn't held up by the database.
+async fn log_requests<B>(
+    State(log_state): State<RequestLogState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let request_id = next_request_id();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    let status = response.status().as_u16();
+    if log_state.mode.logs(status) {
+        let message = format!(
+            "{} {} -> {} in {} ms (request {})",
+            method, path, status, started.elapsed().as_millis(), request_id
+        );
+        tokio::spawn(async move {
+            if let Err(e) = logs::log(&log_state.db_pool, "DEBUG", &message).await {
+                eprintln!("Failed to log request {}: {}", request_id, e);
+            }
+        });
+    }
+
+    response
+}
+
+// ===== Server Timing =====
+
+/// Response header with the database and total handler time of a request
+pub const SERVER_TIMING_HEADER: &str = "server-timing";
+
+tokio::task_local! {
+    // Database time of the request `server_timing` is handling, added to by `timed_db`
+    static DB_TIME: std::cell::Cell<Duration>;
+}
+
+/// Awaits a database query, adding its duration to the `db` entry of the
+/// request's `Server-Timing` header. Outside `server_timing` it just awaits.
+pub async fn timed_db<F: std::future::Future>(query: F) -> F::Output {
+    let started = Instant::now();
+    let output = query.await;
+    let _ = DB_TIME.try_with(|time| time.set(time.get() + started.elapsed()));
+    output
+}
+
+/// `Server-Timing` value for `db` and `total`, in milliseconds
+pub fn server_timing_value(db: Duration, total: Duration) -> String {
+    format!("db;dur={:.1}, total;dur={:.1}", db.as_secs_f64() * 1000.0, total.as_secs_f64() * 1000.0)
+}
+
+/// Middleware: adds a `Server-Timing` header with the time spent in
+/// `timed_db` queries and in the whole handler, for the browser's dev tools
+async fn server_timing<B>(request: Request<B>, next: Next<B>) -> Response {
+    let started = Instant::now();
+    let (mut response, db) = DB_TIME.scope(std::cell::Cell::new(Duration::ZERO), async {
+        let response = next.run(request).await;
+        (response, DB_TIME.with(|time| time.get()))
+    }).await;
+
+    if let Ok(value) = HeaderValue::from_str(&server_timing_value(db, started.elapsed())) {
+        response.headers_mut().insert(SERVER_TIMING_HEADER, value);
+    }
+    response
+}
+
+// ===== API Keys =====
+
+/// Header an API key can be sent in instead of `Authorization: Bearer <key>`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// What `require_scope` needs to check the requests of one route group
+#[derive(Clone)]
+pub struct AuthState {
+    pub db_pool: Arc<SqlitePool>,
+    pub enabled: bool,                  // `[web] require_api_key`
+    pub admin_key_hash: Option<Arc<str>>,   // SHA-256 of `[web] admin_key`, see `storage::hash_api_key`
+    pub read_scope: ApiScope,           // Scope needed for GET and HEAD requests
+    pub write_scope: ApiScope,          // Scope needed for every other request
+    pub query_key: bool,                // Also take the key from `?key=`, see `with_stream_auth`
+}
+
+impl AuthState {
+    /// Read and control scopes, as set by `with_scope` per route group
+    pub fn new(db_pool: &Arc<SqlitePool>, config: &Config) -> Self {
+        Self {
+            db_pool: Arc::clone(db_pool),
+            enabled: config.web.require_api_key,
+            admin_key_hash: config.web.admin_key.as_deref().map(|key| Arc::from(storage::hash_api_key(key))),
+            read_scope: ApiScope::Read,
+            write_scope: ApiScope::Control,
+            query_key: false,
+        }
+    }
+}
+
+/// Whether `key` is `[web] admin_key`, compared in constant time like stored keys
+fn is_admin_key(auth: &AuthState, key: &str) -> bool {
+    match &auth.admin_key_hash {
+        Some(admin) => storage::key_hashes_match(admin, &storage::hash_api_key(key)),
+        None => false,
+    }
+}
+
+/// Applies `require_scope` to a route group with the scopes it needs
+fn with_scope(routes: Router, auth: &AuthState, read_scope: ApiScope, write_scope: ApiScope) -> Router {
+    let auth = AuthState { read_scope, write_scope, ..auth.clone() };
+    routes.route_layer(middleware::from_fn_with_state(auth, require_scope))
+}
+
+/// Applies `require_scope` to the camera stream server on `[web] camera_port`.
+///
+/// The stream and its static files need `read` scope. The key may also be
+/// passed as `?key=`, as the `<img>` showing the stream can't send headers.
+pub fn with_stream_auth(routes: Router, db_pool: &Arc<SqlitePool>, config: &Config) -> Router {
+    let auth = AuthState { query_key: true, ..AuthState::new(db_pool, config) };
+    routes.layer(middleware::from_fn_with_state(auth, require_scope))
+}
+
+/// The API key a request presents, from `Authorization: Bearer` or `x-api-key`
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()))
+        .map(str::trim)
+}
+
+/// The API key passed as `?key=`, only accepted where `AuthState.query_key` is set
+fn query_key(uri: &Uri) -> Option<String> {
+    let Query(params) = Query::<HashMap<String, String>>::try_from_uri(uri).ok()?;
+    params.get("key").map(|key| key.trim().to_string())
+}
+
+/// Middleware: with `[web] require_api_key` on, rejects requests without a
+/// valid API key (401) or whose key's scope is too low for the route (403).
+async fn require_scope<B>(
+    State(auth): State<AuthState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !auth.enabled {
+        return next.run(request).await;
+    }
+
+    let required = if request.method() == Method::GET || request.method() == Method::HEAD {
+        auth.read_scope
+    } else {
+        auth.write_scope
+    };
+
+    let key = match presented_key(request.headers()) {
+        Some(key) => Some(key.to_string()),
+        None if auth.query_key => query_key(request.uri()),
+        None => None,
+    };
+    let scope = match key.as_deref() {
+        None => return ApiError::Unauthorized("Missing API key".to_string()).into_response(),
+        Some(key) if is_admin_key(&auth, key) => ApiScope::Admin,
+        Some(key) => match ApiKey::scope_of(&auth.db_pool, key).await {
+            Ok(Some(scope)) => scope,
+            Ok(None) => return ApiError::Unauthorized("Invalid or revoked API key".to_string()).into_response(),
+            Err(e) => return map_db_error(e).into_response(),
+        },
+    };
+
+    if !scope.allows(required) {
+        return ApiError::Forbidden(format!(
+            "API key has '{}' scope, '{}' is required", scope.as_str(), required.as_str()
+        )).into_response();
+    }
+
+    next.run(request).await
 }
 
 // ===== Fallback Handler =====
@@ -246,15 +560,29 @@ async fn handle_not_found() -> impl IntoResponse {
 fn schedule_routes() -> Router {
     Router::new()
         .route("/api/schedule", get(get_schedule).post(update_schedule))
+        .route("/api/schedule/preview", get(preview_schedule))
+        .route("/api/schedule/timeline", get(get_schedule_timeline))
+        .route("/api/schedule/export.ics", get(export_schedule_ics))
+        .route("/api/schedule/profiles", get(list_schedule_profiles).post(save_schedule_profile))
+        .route("/api/schedule/profiles/:name/activate", post(activate_schedule_profile))
+        .route("/api/schedule/simulate", post(simulate_schedule))
+        .route("/api/schedule/simulate/cancel", post(cancel_simulation))
+        .route("/api/schedule/:week", get(get_schedule_week).put(put_schedule_week))
 }
 
 /// LED control routes
 fn led_routes() -> Router {
     Router::new()
         .route("/api/led/power", post(set_led_power))
+        .route("/api/led/off", post(force_led_off))
         .route("/api/led/color", post(set_led_color))
+        .route("/api/led/cct", post(set_led_cct))
         .route("/api/led/status", get(get_led_status))
+        .route("/api/led/reinit", post(reinit_led_strip))
+        .route("/api/led/animation", post(start_led_animation).delete(stop_led_animation))
+        .route("/api/led/alert/ack", post(acknowledge_led_alert))
         .route("/api/led/natural", post(set_natural_light_settings))
+        .route("/api/led/natural/curve", get(get_natural_light_curve))
         .route("/api/led/presets", 
             get(get_natural_light_presets)
             .post(set_natural_light_presets))
@@ -266,15 +594,61 @@ fn monitoring_routes() -> Router {
         .route("/api/values", get(get_current_values))
         .route("/api/graph/today", get(get_graph_data_today))
         .route("/api/graph/yesterday", get(get_graph_data_yesterday))
+        .route("/api/graph/last/:hours", get(get_graph_data_last_hours))
+        .route("/api/graph/compare", get(get_graph_compare))
+        .route("/api/overheat/history", get(get_overheat_history))
+        .route("/api/overheat/status", get(get_overheat_state))
+        .route("/api/overheat/resume", post(resume_heat))
+        .route("/api/duty", get(get_duty_cycle))
+        .route("/api/sensors/read", post(trigger_sensor_read))
+        .route("/api/sensors/health", get(get_sensor_health))
+        .route("/api/sensors/recent", get(get_recent_sensor_rows))
+        .route_layer(middleware::from_fn(server_timing))
+}
+
+/// Sensor data import and export.
+///
+/// The import has a body limit of its own for large CSV files. The exports
+/// stream their rows, so their `Server-Timing` `db` is the time to the first row.
+fn data_routes(max_body: usize) -> Router {
+    Router::new()
         .route("/api/data/download", get(download_sensor_data))
+        .route("/api/data/export.jsonl", get(export_sensor_data_jsonl))
+        .route_layer(middleware::from_fn(server_timing))
+        .route("/api/data/import", post(import_sensor_data))
+        .layer(DefaultBodyLimit::max(max_body))
 }
 
 /// System management routes
 fn system_routes() -> Router {
     Router::new()
         .route("/api/system/status", get(get_system_status))
+        .route("/api/system/hardware", get(get_hardware))
         .route("/api/logs", get(get_logs))
         .route("/api/logs/download", get(download_logs))
+        .route("/api/openapi.json", get(get_openapi_spec))
+        .route("/api/relay/pulse", post(pulse_relay))
+        .route("/api/relay/history", get(get_relay_history))
+        .route("/api/activity", get(get_activity))
+        .route("/api/alerts", get(get_alerts))
+        .route("/api/alerts/:id/ack", post(acknowledge_alert))
+        .route("/api/system/away", get(get_away_mode).post(set_away_mode))
+        .route("/api/system/resume", post(resume_control))
+}
+
+/// Persisted settings routes and the factory reset; changes need the admin scope
+fn settings_routes() -> Router {
+    Router::new()
+        .route("/api/settings", get(get_settings))
+        .route("/api/settings/:key", get(get_setting_value).put(put_setting_value))
+        .route("/api/system/reset", post(factory_reset))
+}
+
+/// API key management routes, admin scope only
+fn api_key_routes() -> Router {
+    Router::new()
+        .route("/api/keys", get(list_api_keys).post(create_api_key))
+        .route("/api/keys/:id", delete(revoke_api_key))
 }
 
 /// Camera streaming routes
@@ -282,6 +656,7 @@ fn camera_routes() -> Router {
     Router::new()
         .route("/api/camera/status", get(get_camera_status))
         .route("/api/camera/snapshot", get(get_camera_snapshot))
+        .route("/api/camera/burst", get(get_camera_burst))
         .route("/api/camera/stream", get(get_camera_stream_url))
 }
 
@@ -296,68 +671,327 @@ pub mod handlers {
         
         /// Handler: Fetch schedule as JSON
         pub async fn get_schedule(State(state): State<AppState>) -> ApiResult<Vec<Schedule>> {
-            let db_pool = &state.db_pool;
+            Schedule::get_schedule(&state.db_pool)
+                .await
+                .map_err(map_db_error)
+                .map(Json)
+        }
 
-            sqlx::query_as!(
-                Schedule,
-                r#"
-                SELECT week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end,
-                       led_r AS red, led_g AS green, led_b AS blue, led_cw, led_ww
-                FROM schedule
-                ORDER BY week_number
-                "#
-            )
-            .fetch_all(db_pool)
-            .await
-            .map_err(map_db_error)
-            .map(Json)
+        #[derive(Deserialize)]
+        pub struct ScheduleWriteQuery {
+            /// Store LED windows outside `[led] min_on_hours`/`max_on_hours`
+            #[serde(default)]
+            pub force: bool,
+        }
+
+        /// Validates a week, including the LED on-duration bounds unless `force` is set
+        fn validate_week(week: &Schedule, config: &Config, force: bool) -> Result<(), ApiError> {
+            week.validate().map_err(ApiError::BadRequest)?;
+            if !force {
+                let (min_hours, max_hours) = config.led.on_hours();
+                week.check_led_hours(min_hours, max_hours)
+                    .map_err(|e| ApiError::BadRequest(format!("{} Pass force=true to store it anyway.", e)))?;
+            }
+            Ok(())
         }
 
         /// Handler: Update schedule via JSON
+        ///
+        /// Every week is validated before anything is written, so a single bad
+        /// week rejects the whole request instead of leaving a partial update.
         pub async fn update_schedule(
-            Json(payload): Json<Vec<Schedule>>,
             State(state): State<AppState>,
+            Query(query): Query<ScheduleWriteQuery>,
+            Json(payload): Json<Vec<Schedule>>,
         ) -> ApiResult<&'static str> {
-            let db_pool = &state.db_pool;
+            for setting in &payload {
+                validate_week(setting, &state.config, query.force)?;
+            }
 
-            for setting in payload {
-                sqlx::query!(
-                    r#"
-                    INSERT INTO schedule (week_number, uv1_start, uv1_end, uv2_start, uv2_end, heat_start, heat_end, led_r, led_g, led_b, led_cw, led_ww)
-                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                    ON CONFLICT(week_number) DO UPDATE SET
-                        uv1_start = excluded.uv1_start,
-                        uv1_end = excluded.uv1_end,
-                        uv2_start = excluded.uv2_start,
-                        uv2_end = excluded.uv2_end,
-                        heat_start = excluded.heat_start,
-                        heat_end = excluded.heat_end,
-                        led_r = excluded.led_r,
-                        led_g = excluded.led_g,
-                        led_b = excluded.led_b,
-                        led_cw = excluded.led_cw,
-                        led_ww = excluded.led_ww
-                    "#,
-                    setting.week,
-                    setting.uv1_start,
-                    setting.uv1_end,
-                    setting.uv2_start,
-                    setting.uv2_end,
-                    setting.heat_start,
-                    setting.heat_end,
-                    setting.red,
-                    setting.green,
-                    setting.blue,
-                    setting.led_cw,
-                    setting.led_ww,
-                )
-                .execute(db_pool)
+            storage::retry_busy(|| Schedule::upsert_all(&state.db_pool, &payload))
                 .await
                 .map_err(map_db_error)?;
-            }
 
             success("Schedule updated successfully")
         }
+
+        /// Rejects week numbers outside 1-52 with a 404, as those weeks don't exist
+        fn check_week_in_range(week: i32) -> Result<(), ApiError> {
+            if !(1..=SCHEDULE_WEEKS).contains(&week) {
+                return Err(ApiError::NotFound(format!(
+                    "Schedule week {} does not exist, weeks are 1-{} (ISO week 53 uses week {})", week, SCHEDULE_WEEKS, SCHEDULE_WEEKS
+                )));
+            }
+            Ok(())
+        }
+
+        /// Handler: Fetch a single schedule week
+        pub async fn get_schedule_week(
+            State(state): State<AppState>,
+            Path(week): Path<i32>,
+        ) -> ApiResult<Schedule> {
+            check_week_in_range(week)?;
+
+            Schedule::get_week(&state.db_pool, week)
+                .await
+                .map_err(map_db_error)?
+                .ok_or_else(|| ApiError::NotFound(format!("No schedule stored for week {}", week)))
+                .map(Json)
+        }
+
+        #[derive(Deserialize)]
+        pub struct ScheduleTimelineQuery {
+            pub week: i32,
+        }
+
+        /// Handler: Fetch a stored week as its day's events, sorted by time
+        pub async fn get_schedule_timeline(
+            State(state): State<AppState>,
+            Query(query): Query<ScheduleTimelineQuery>,
+        ) -> ApiResult<Vec<TimelineEvent>> {
+            check_week_in_range(query.week)?;
+
+            let week = Schedule::get_week(&state.db_pool, query.week)
+                .await
+                .map_err(map_db_error)?
+                .ok_or_else(|| ApiError::NotFound(format!("No schedule stored for week {}", query.week)))?;
+
+            success(week.timeline())
+        }
+
+        /// Handler: Export the stored weeks of this year as an iCalendar file
+        ///
+        /// Each device's on-window is a VEVENT repeated daily through its week,
+        /// see `calendar::schedule_ics`.
+        pub async fn export_schedule_ics(State(state): State<AppState>) -> Result<Response, ApiError> {
+            let weeks = Schedule::get_schedule(&state.db_pool)
+                .await
+                .map_err(map_db_error)?;
+            let year = state.config.main.schedule_now().iso_week().year();
+            let ics = calendar::schedule_ics(&weeks, year, state.config.main.timezone.as_deref(), Utc::now());
+
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "text/calendar; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"schedule_{}.ics\"", year)),
+                ],
+                ics,
+            ).into_response())
+        }
+
+        #[derive(Deserialize)]
+        pub struct SaveProfileRequest {
+            pub name: String,
+            #[serde(default)]
+            pub display_name: Option<String>,
+            #[serde(default)]
+            pub color: Option<String>,
+        }
+
+        /// Handler: List the saved schedule profiles
+        pub async fn list_schedule_profiles(State(state): State<AppState>) -> ApiResult<Vec<ScheduleProfile>> {
+            ScheduleProfile::list(&state.db_pool)
+                .await
+                .map_err(map_db_error)
+                .map(Json)
+        }
+
+        /// Handler: Save the current schedule as a named profile, replacing one of the same name
+        pub async fn save_schedule_profile(
+            State(state): State<AppState>,
+            Json(payload): Json<SaveProfileRequest>,
+        ) -> ApiResult<ScheduleProfile> {
+            let display_name = payload.display_name.as_deref().map(str::trim);
+            ScheduleProfile::validate_name(&payload.name).map_err(ApiError::BadRequest)?;
+            ScheduleProfile::validate_label(display_name, payload.color.as_deref()).map_err(ApiError::BadRequest)?;
+
+            let profile = storage::retry_busy(|| ScheduleProfile::save(&state.db_pool, &payload.name, display_name, payload.color.as_deref()))
+                .await
+                .map_err(map_db_error)?;
+
+            if let Err(e) = logs::log(&state.db_pool, "INFO", &format!(
+                "Saved the schedule as profile {:?} ({} weeks)", profile.name, profile.weeks
+            )).await {
+                eprintln!("Failed to log schedule profile save: {:?}", e);
+            }
+
+            success(profile)
+        }
+
+        /// Handler: Load a saved profile into the active schedule
+        ///
+        /// Every stored week is replaced; weeks the profile lacks run on the
+        /// config defaults until they are stored again.
+        pub async fn activate_schedule_profile(
+            State(state): State<AppState>,
+            Path(name): Path<String>,
+        ) -> ApiResult<String> {
+            ScheduleProfile::validate_name(&name).map_err(ApiError::NotFound)?;
+
+            let weeks = storage::retry_busy(|| ScheduleProfile::activate(&state.db_pool, &name))
+                .await
+                .map_err(map_db_error)?
+                .ok_or_else(|| ApiError::NotFound(format!("No schedule profile named {:?}", name)))?;
+
+            let message = format!("Activated schedule profile {:?} ({} weeks)", name, weeks);
+            if let Err(e) = logs::log(&state.db_pool, "INFO", &message).await {
+                eprintln!("Failed to log schedule profile activation: {:?}", e);
+            }
+
+            success(message)
+        }
+
+        /// Handler: Create or replace a single schedule week
+        ///
+        /// The body must be a complete week whose `week_number` matches the path.
+        pub async fn put_schedule_week(
+            State(state): State<AppState>,
+            Path(week): Path<i32>,
+            Query(query): Query<ScheduleWriteQuery>,
+            Json(payload): Json<Schedule>,
+        ) -> ApiResult<Schedule> {
+            check_week_in_range(week)?;
+
+            if payload.week_number != week {
+                return Err(ApiError::BadRequest(format!(
+                    "week_number {} in the body does not match week {} in the path", payload.week_number, week
+                )));
+            }
+            validate_week(&payload, &state.config, query.force)?;
+
+            storage::retry_busy(|| payload.upsert(&state.db_pool))
+                .await
+                .map_err(map_db_error)?;
+
+            success(payload)
+        }
+
+        #[derive(Deserialize)]
+        pub struct SimulateQuery {
+            pub speed: u32,
+        }
+
+        #[derive(Serialize)]
+        pub struct SimulateResponse {
+            pub speed: u32,
+            pub duration_seconds: u64,  // Real time the simulated day takes
+        }
+
+        /// Handler: Replay today's schedule on the loads at `speed`× real time
+        ///
+        /// Only with `[main] simulation = true`, as it switches the real relays
+        /// and strip through a whole day. The control loops are held until it ends
+        /// or is cancelled through `/api/schedule/simulate/cancel`.
+        pub async fn simulate_schedule(
+            State(state): State<AppState>,
+            Query(query): Query<SimulateQuery>,
+        ) -> ApiResult<SimulateResponse> {
+            if !state.config.main.simulation {
+                return Err(ApiError::Forbidden(
+                    "Schedule simulation is disabled; set [main] simulation = true on demo setups only".to_string()
+                ));
+            }
+            if !(1..=simulation::MAX_SPEED).contains(&query.speed) {
+                return Err(ApiError::BadRequest(format!(
+                    "speed must be between 1 and {}, got: {}", simulation::MAX_SPEED, query.speed
+                )));
+            }
+            if !simulation::try_start() {
+                return Err(ApiError::BadRequest("A schedule simulation is already running".to_string()));
+            }
+
+            let midnight = state.config.main.schedule_now().date().and_hms_opt(0, 0, 0).unwrap();
+            let clock = VirtualClock::new(midnight, query.speed);
+            let duration = clock.real_duration(chrono::Duration::days(1));
+            tokio::spawn(simulation::run_day(
+                Arc::clone(&state.db_pool),
+                Arc::clone(&state.config),
+                Arc::clone(&state.light_controller),
+                Arc::clone(&state.led_controller),
+                clock,
+            ));
+
+            success(SimulateResponse { speed: query.speed, duration_seconds: duration.as_secs() })
+        }
+
+        #[derive(Serialize)]
+        pub struct CancelSimulationResponse {
+            pub cancelled: bool,
+        }
+
+        /// Handler: Stop the running schedule simulation
+        ///
+        /// The control loops take over again from their next tick.
+        pub async fn cancel_simulation() -> ApiResult<CancelSimulationResponse> {
+            if !simulation::cancel() {
+                return Err(ApiError::BadRequest("No schedule simulation is running".to_string()));
+            }
+            success(CancelSimulationResponse { cancelled: true })
+        }
+
+        #[derive(Deserialize)]
+        pub struct SchedulePreviewQuery {
+            pub at: String,
+        }
+
+        #[derive(Serialize)]
+        pub struct PreviewLedColor {
+            pub r: u8,
+            pub g: u8,
+            pub b: u8,
+            pub ww: u8,
+            pub cw: u8,
+        }
+
+        #[derive(Serialize)]
+        pub struct SchedulePreviewResponse {
+            pub at: String,
+            pub week: i32,
+            pub uv1_on: bool,
+            pub uv2_on: bool,
+            pub heat_on: bool,
+            pub led_on: bool,
+            pub led: Option<PreviewLedColor>,
+        }
+
+        /// Handler: Preview what the schedule does at a given instant
+        ///
+        /// Uses the same resolution as `update_lights` and `update_leds` without
+        /// touching hardware. Overheat protection is not simulated.
+        pub async fn preview_schedule(
+            State(state): State<AppState>,
+            Query(query): Query<SchedulePreviewQuery>,
+        ) -> ApiResult<SchedulePreviewResponse> {
+            let instant = DateTime::parse_from_rfc3339(&query.at)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid 'at' timestamp {:?}: {}", query.at, e)))?;
+            let at = state.config.main.schedule_time(instant.with_timezone(&Utc));
+            let current_time = at.format("%H:%M").to_string();
+            let week = Schedule::week_for(&at);
+
+            let schedule = Schedule::get_week(&state.db_pool, week)
+                .await
+                .map_err(map_db_error)?;
+            let mut settings = get_led_settings(&state.db_pool)
+                .await
+                .map_err(map_db_error)?;
+            if *state.away_mode.lock().await {
+                settings = settings.map(LedSettings::without_overrides);
+            }
+
+            let relays = resolve_relay_states(&current_time, schedule.as_ref(), &state.config);
+            let led = resolve_led_color(&current_time, at.date(), schedule.as_ref(), settings.as_ref(), &state.config)
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+
+            success(SchedulePreviewResponse {
+                at: instant.to_rfc3339(),
+                week,
+                uv1_on: relays.uv1,
+                uv2_on: relays.uv2,
+                heat_on: relays.heat,
+                led_on: led.is_some(),
+                led: led.map(|c| PreviewLedColor { r: c.r, g: c.g, b: c.b, ww: c.ww, cw: c.cw }),
+            })
+        }
     }
 
     // LED handlers module
@@ -376,19 +1010,27 @@ pub mod handlers {
         ) -> ApiResult<&'static str> {
             let result = if payload.power {
                 state.with_led_controller(|controller| {
-                    controller.power_on()
+                    controller.power_on(RelayReason::Manual)
                 }).await
             } else {
                 state.with_led_controller(|controller| {
-                    controller.power_off()
+                    controller.power_off(RelayReason::Manual)
                 }).await
             };
             
             result.map_err(|e| ApiError::InternalError(e.to_string()))?;
+            if payload.power {
+                end_forced_off(&state).await?;
+            }
             
             success("LED power state updated")
         }
 
+        /// Lifts `POST /api/led/off` once the strip is lit through the API again
+        async fn end_forced_off(state: &AppState) -> Result<(), ApiError> {
+            state.store_setting(storage::LED_FORCED_OFF_KEY, "false").await
+        }
+
         #[derive(Deserialize)]
         pub struct LEDColorRequest {
             pub r: u8,
@@ -415,24 +1057,54 @@ pub mod handlers {
             
             // Update the database with the new settings
             let db_pool = &state.db_pool;
-            sqlx::query!(
+            let (r, g, b, ww, cw) = (payload.r as i32, payload.g as i32, payload.b as i32, payload.ww as i32, payload.cw as i32);
+            storage::retry_busy(|| sqlx::query!(
                 r#"
                 INSERT OR REPLACE INTO led_settings (id, r, g, b, ww, cw, enabled)
                 VALUES (1, ?, ?, ?, ?, ?, true)
                 "#,
-                payload.r as i32,
-                payload.g as i32,
-                payload.b as i32,
-                payload.ww as i32,
-                payload.cw as i32,
+                r,
+                g,
+                b,
+                ww,
+                cw,
             )
-            .execute(db_pool)
+            .execute(db_pool))
             .await
             .map_err(map_db_error)?;
+            end_forced_off(&state).await?;
             
             success("LED color updated")
         }
 
+        #[derive(Deserialize)]
+        pub struct LEDCctRequest {
+            pub kelvin: u32,
+            pub brightness: u8,
+        }
+
+        /// Set a white color temperature, clamped to the range of the white LEDs
+        pub async fn set_led_cct(
+            State(state): State<AppState>,
+            Json(payload): Json<LEDCctRequest>,
+        ) -> ApiResult<&'static str> {
+            if payload.brightness > 100 {
+                return Err(ApiError::BadRequest(format!(
+                    "brightness must be between 0 and 100, got: {}", payload.brightness
+                )));
+            }
+            
+            let mut led_controller = state.led_controller.lock().await;
+            led_controller.set_color_temperature(
+                payload.kelvin,
+                payload.brightness,
+                state.config.led.white_range(),
+            ).await.map_err(|e| ApiError::InternalError(e.to_string()))?;
+            end_forced_off(&state).await?;
+            
+            success("LED color temperature updated")
+        }
+
         #[derive(Deserialize)]
         pub struct NaturalLightRequest {
             pub override_settings: bool,
@@ -454,6 +1126,53 @@ pub mod handlers {
             Ok(Json("Natural light settings updated"))
         }
 
+        #[derive(Deserialize)]
+        pub struct NaturalCurveQuery {
+            pub step_minutes: Option<u32>,
+        }
+
+        #[derive(Serialize)]
+        pub struct NaturalCurvePoint {
+            pub time: String,
+            pub r: u8,
+            pub g: u8,
+            pub b: u8,
+            pub ww: u8,
+            pub cw: u8,
+        }
+
+        /// Get the natural light color across today, sampled every `step_minutes` (default 15)
+        ///
+        /// Uses this week's LED window and season color and the stored season
+        /// weight, i.e. what natural mode shows, whether or not it is active.
+        pub async fn get_natural_light_curve(
+            State(state): State<AppState>,
+            Query(query): Query<NaturalCurveQuery>,
+        ) -> ApiResult<Vec<NaturalCurvePoint>> {
+            let step_minutes = query.step_minutes.unwrap_or(15);
+            if step_minutes == 0 || step_minutes > 24 * 60 {
+                return Err(ApiError::BadRequest(format!(
+                    "step_minutes must be between 1 and 1440, got: {}", step_minutes
+                )));
+            }
+            
+            let now = state.config.main.schedule_now();
+            let schedule = Schedule::get_week(&state.db_pool, Schedule::week_for(&now))
+                .await
+                .map_err(map_db_error)?;
+            let settings = get_led_settings(&state.db_pool)
+                .await
+                .map_err(map_db_error)?;
+            
+            let inputs = NaturalLightInputs::resolve(now.date(), schedule.as_ref(), settings.as_ref(), &state.config);
+            let curve = natural_light_curve(&inputs, step_minutes, &state.config)
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            
+            success(curve.into_iter().map(|(time, c)| NaturalCurvePoint {
+                time, r: c.r, g: c.g, b: c.b, ww: c.ww, cw: c.cw,
+            }).collect())
+        }
+
         #[derive(Serialize)]
         pub struct LEDStatus {
             pub power: bool,
@@ -464,15 +1183,34 @@ pub mod handlers {
             pub cw: u8,
             pub use_natural: bool,
             pub season_weight: f32,
+            pub cct: Option<u32>,   // Estimated color temperature when only the white channels are lit
+            pub available: bool,    // False once the strip is disabled after repeated init failures
+            pub animation: Option<AnimationKind>,
+            pub estimated_current_ma: f32,  // Estimated strip draw, see `[led.power]`
+            pub alert: Option<LedAlert>,    // Alert pulsing until acknowledged
+            pub caps: LEDChannelCaps,       // Per-channel maximums, see `[led] max_r`..`max_cw`
         }
 
-        /// Get LED status
-        pub async fn get_led_status(
+        #[derive(Serialize)]
+        pub struct LEDChannelCaps {
+            pub r: u8,
+            pub g: u8,
+            pub b: u8,
+            pub ww: u8,
+            pub cw: u8,
+        }
+
+        /// Get LED status
+        pub async fn get_led_status(
             State(state): State<AppState>,
         ) -> Result<Json<LEDStatus>, String> {
             let led_controller = state.led_controller.lock().await;
-            
-            let status = LEDStatus {
+            Ok(Json(led_status(&led_controller, &state.config)))
+        }
+
+        /// The state of `led_controller` as reported by `GET /api/led/status`
+        fn led_status(led_controller: &LEDController, config: &Config) -> LEDStatus {
+            LEDStatus {
                 power: led_controller.is_on(),
                 r: led_controller.get_red(),
                 g: led_controller.get_green(),
@@ -481,9 +1219,136 @@ pub mod handlers {
                 cw: led_controller.get_cool_white(),
                 use_natural: led_controller.is_natural_mode(),
                 season_weight: led_controller.get_season_weight(),
+                cct: estimate_cct(led_controller.current_color(), config.led.white_range()),
+                available: led_controller.is_available(),
+                animation: led_controller.animation(),
+                estimated_current_ma: led_controller.estimated_current_ma(),
+                alert: led_controller.alert(),
+                caps: {
+                    let ChannelCaps(max) = led_controller.channel_caps();
+                    LEDChannelCaps { r: max.r, g: max.g, b: max.b, ww: max.ww, cw: max.cw }
+                },
+            }
+        }
+
+        /// Make it dark: stop the alert pulse and any animation, power the strip
+        /// off, and disable it in the stored settings with the manual override cleared.
+        ///
+        /// The settings are written first, along with `storage::LED_FORCED_OFF_KEY`,
+        /// so the schedule keeps the strip off from its next tick on, whatever the
+        /// override precedence and in away mode, until a color is set again.
+        pub async fn force_led_off(
+            State(state): State<AppState>,
+        ) -> ApiResult<LEDStatus> {
+            state.store_setting(storage::LED_FORCED_OFF_KEY, "true").await?;
+            storage::retry_busy(|| disable_led_settings(&state.db_pool))
+                .await
+                .map_err(map_db_error)?;
+            
+            let mut led_controller = state.led_controller.lock().await;
+            let alert = led_controller.force_off(RelayReason::Manual)
+                .await
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            
+            let message = match alert {
+                Some(alert) => format!("LED strip forced off, {} alert acknowledged", alert.as_str()),
+                None => "LED strip forced off".to_string(),
+            };
+            if let Err(e) = logs::log(&state.db_pool, "INFO", &message).await {
+                eprintln!("Failed to log LED off: {:?}", e);
+            }
+            
+            success(led_status(&led_controller, &state.config))
+        }
+
+        /// Retry opening the LED strip after it was disabled, e.g. once SPI is enabled
+        pub async fn reinit_led_strip(
+            State(state): State<AppState>,
+        ) -> ApiResult<&'static str> {
+            let mut led_controller = state.led_controller.lock().await;
+            led_controller.reinit()
+                .await
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            
+            success("LED strip initialized")
+        }
+
+        #[derive(Deserialize)]
+        pub struct LEDAnimationRequest {
+            pub animation: AnimationKind,
+            pub color: Option<LEDColorRequest>,   // Base color (default: the current color, or warm white while off)
+            pub period_seconds: Option<f32>,      // Length of one breath (default: 6)
+        }
+
+        /// Start an ambient animation, replacing any running one
+        ///
+        /// Runs until stopped with `DELETE /api/led/animation` or preempted by a
+        /// manual color or power command. The schedule doesn't touch the strip meanwhile.
+        pub async fn start_led_animation(
+            State(state): State<AppState>,
+            Json(payload): Json<LEDAnimationRequest>,
+        ) -> ApiResult<&'static str> {
+            let period = payload.period_seconds.unwrap_or(6.0);
+            if !(1.0..=120.0).contains(&period) {
+                return Err(ApiError::BadRequest(format!(
+                    "period_seconds must be between 1 and 120, got: {}", period
+                )));
+            }
+            
+            let color = match payload.color {
+                Some(c) => RGBWW { r: c.r, g: c.g, b: c.b, ww: c.ww, cw: c.cw },
+                None => {
+                    let current = state.led_controller.lock().await.current_color();
+                    if current == RGBWW::off() { WARM_WHITE } else { current }
+                }
             };
             
-            Ok(Json(status))
+            let animation = payload.animation.build(color, Duration::from_secs_f32(period));
+            LEDController::start_animation(&state.led_controller, payload.animation, animation)
+                .await
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            end_forced_off(&state).await?;
+            
+            success("LED animation started")
+        }
+
+        /// Base color of an animation started while the strip is off
+        const WARM_WHITE: RGBWW = RGBWW { r: 255, g: 140, b: 40, ww: 255, cw: 0 };
+
+        /// Stop the running animation and restore the color and power from before it
+        pub async fn stop_led_animation(
+            State(state): State<AppState>,
+        ) -> ApiResult<&'static str> {
+            let stopped = state.led_controller.lock().await
+                .stop_animation()
+                .await
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            
+            success(if stopped { "LED animation stopped" } else { "No LED animation running" })
+        }
+
+        #[derive(Serialize)]
+        pub struct LEDAlertAckResponse {
+            pub acknowledged: LedAlert,
+        }
+
+        /// Stop the alert pulse and bring back the scheduled color.
+        ///
+        /// A condition that is still present doesn't raise the alert again until
+        /// it has cleared once.
+        pub async fn acknowledge_led_alert(
+            State(state): State<AppState>,
+        ) -> ApiResult<LEDAlertAckResponse> {
+            let acknowledged = state.led_controller.lock().await
+                .acknowledge_alert()
+                .ok_or_else(|| ApiError::BadRequest("No LED alert active".to_string()))?;
+            
+            let away = *state.away_mode.lock().await;
+            update_leds(&state.db_pool, &state.led_controller, &state.config, away)
+                .await
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            
+            success(LEDAlertAckResponse { acknowledged })
         }
 
         #[derive(Deserialize, Serialize)]
@@ -557,13 +1422,67 @@ pub mod handlers {
         
         #[derive(Serialize)]
         pub struct CurrentValuesResponse {
+            pub instance: String,           // `[main] name`
             pub timestamp: String,
+            pub unit: TemperatureUnit,
             pub baskingTemp: f32,
             pub controlTemp: f32,
             pub coolZoneTemp: f32,
+            pub tempTrend: Option<f32>,     // Basking temperature change per minute, in `unit`
             pub humidity: f32,
             pub uv1: f32,
             pub uv2: f32,
+            pub probes: std::collections::BTreeMap<String, Option<f32>>,   // Further DS18B20 probes by name, in `unit`; null after a failed read
+            pub uv1_on: bool,
+            pub uv2_on: bool,
+            pub heat_on: bool,
+            pub led_on: bool,
+            pub overheat: bool,
+        }
+
+        #[derive(Debug, Default, Deserialize)]
+        pub struct ValuesQuery {
+            #[serde(default)]
+            pub units: TemperatureUnit,
+            /// Report each reading with its unit, plausible range and staleness
+            #[serde(default)]
+            pub verbose: bool,
+        }
+
+        /// A reading with the metadata a generic gauge needs
+        #[derive(Serialize)]
+        pub struct SensorValue {
+            pub value: f32,
+            pub unit: &'static str,
+            pub min: f32,
+            pub max: f32,
+            pub stale: bool,    // No successful read of this sensor for three collection intervals
+        }
+
+        /// Whether `sensor` had no successful read in the `stale_after` before
+        /// `now`, going by its entry in `health`. A sensor that never answered
+        /// is stale.
+        pub fn sensor_stale(health: &[SensorStatus], sensor: &str, now: DateTime<Utc>, stale_after: Duration) -> bool {
+            health.iter()
+                .find(|status| status.name == sensor)
+                .and_then(|status| status.last_good)
+                .map_or(true, |last_good| (now - last_good).to_std().map_or(false, |age| age > stale_after))
+        }
+
+        /// `/api/values?verbose=true`: the compact response with every sensor reading expanded
+        #[derive(Serialize)]
+        pub struct VerboseValuesResponse {
+            pub instance: String,
+            pub timestamp: String,
+            pub unit: TemperatureUnit,
+            pub baskingTemp: SensorValue,
+            pub controlTemp: SensorValue,
+            pub coolZoneTemp: SensorValue,
+            pub tempTrend: Option<f32>,
+            pub humidity: SensorValue,
+            pub uv1: SensorValue,
+            pub uv2: SensorValue,
+            pub probes: std::collections::BTreeMap<String, Option<SensorValue>>,
             pub uv1_on: bool,
             pub uv2_on: bool,
             pub heat_on: bool,
@@ -571,73 +1490,505 @@ pub mod handlers {
             pub overheat: bool,
         }
 
+        impl VerboseValuesResponse {
+            /// Expands `values` with units and the plausible ranges from `[sensors]`,
+            /// marking each reading with `stale` of its sensor name
+            pub fn new(values: CurrentValuesResponse, sensors: &SensorsConfig, stale: impl Fn(&str) -> bool) -> Self {
+                let unit = values.unit;
+                let (temp_min, temp_max) = sensors.temp_range();
+                let temp = |value: f32, sensor: &str| SensorValue {
+                    value, unit: unit.symbol(), min: unit.convert(temp_min), max: unit.convert(temp_max), stale: stale(sensor),
+                };
+                let other = |value: f32, sensor: &str, unit: &'static str, (min, max): (f32, f32)| SensorValue {
+                    value, unit, min, max, stale: stale(sensor),
+                };
+                Self {
+                    instance: values.instance,
+                    timestamp: values.timestamp,
+                    unit,
+                    baskingTemp: temp(values.baskingTemp, "basking"),
+                    controlTemp: temp(values.controlTemp, "control"),
+                    coolZoneTemp: temp(values.coolZoneTemp, "cool"),
+                    tempTrend: values.tempTrend,
+                    humidity: other(values.humidity, "humidity", "%", sensors.humidity_range()),
+                    uv1: other(values.uv1, "uv1", "UVI", sensors.uv_range()),
+                    uv2: other(values.uv2, "uv2", "UVI", sensors.uv_range()),
+                    probes: values.probes.into_iter()
+                        .map(|(name, value)| {
+                            let value = value.map(|value| temp(value, &name));
+                            (name, value)
+                        })
+                        .collect(),
+                    uv1_on: values.uv1_on,
+                    uv2_on: values.uv2_on,
+                    heat_on: values.heat_on,
+                    led_on: values.led_on,
+                    overheat: values.overheat,
+                }
+            }
+        }
+
+        /// Compact bare numbers by default, `VerboseValuesResponse` on request
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        pub enum ValuesResponse {
+            Compact(CurrentValuesResponse),
+            Verbose(VerboseValuesResponse),
+        }
+
         /// Get current sensor values
         pub async fn get_current_values(
             State(state): State<AppState>,
-        ) -> Json<CurrentValuesResponse> {
+            Query(query): Query<ValuesQuery>,
+        ) -> Json<ValuesResponse> {
+            if !query.verbose {
+                return Json(ValuesResponse::Compact(current_values(&state, query.units).await));
+            }
+            
+            let health = getData::sensor_health();
+            let stale_after = state.config.get_data.interval() * 3;
+            let now = Utc::now();
+            let values = current_values(&state, query.units).await;
+            Json(ValuesResponse::Verbose(VerboseValuesResponse::new(values, &state.config.sensors, |sensor| {
+                sensor_stale(&health, sensor, now, stale_after)
+            })))
+        }
+
+        /// Get the read health of every sensor, so a failing sensor shows as a fault rather than a 0.0 reading
+        pub async fn get_sensor_health() -> ApiResult<Vec<SensorStatus>> {
+            success(getData::sensor_health())
+        }
+
+        /// Most rows `GET /api/sensors/recent` returns
+        pub const MAX_RECENT_ROWS: i64 = 500;
+
+        #[derive(Deserialize)]
+        pub struct RecentRowsQuery {
+            pub count: Option<i64>,
+        }
+
+        /// Get the newest raw `sensor_history` rows, newest first, e.g. to look at sensor jitter
+        ///
+        /// Unlike the graph endpoints the rows are neither formatted nor
+        /// converted: every column as stored. A failed read of a canonical
+        /// sensor is stored as 0.0, so check `GET /api/sensors/health` before
+        /// reading a 0.0 as a real value.
+        pub async fn get_recent_sensor_rows(
+            State(state): State<AppState>,
+            Query(query): Query<RecentRowsQuery>,
+        ) -> ApiResult<Vec<SensorHistoryRow>> {
+            let count = query.count.unwrap_or(20).clamp(1, MAX_RECENT_ROWS);
+            timed_db(storage::recent_sensor_rows(&state.db_pool, count))
+                .await
+                .map_err(map_db_error)
+                .map(Json)
+        }
+
+        /// Reads every sensor immediately, stores the row and returns the fresh values.
+        ///
+        /// Limited to one call per `sensor_read_cooldown` so it can't be used to hammer the sensors.
+        pub async fn trigger_sensor_read(
+            State(state): State<AppState>,
+            Query(query): Query<UnitsQuery>,
+        ) -> ApiResult<CurrentValuesResponse> {
+            {
+                let mut last_read = state.last_manual_read.lock().await;
+                let cooldown = state.config.web.sensor_read_cooldown();
+                if let Some(elapsed) = last_read.map(|at| at.elapsed()) {
+                    if elapsed < cooldown {
+                        return Err(ApiError::TooManyRequests(format!(
+                            "Sensors were read {}s ago, try again in {}s",
+                            elapsed.as_secs(),
+                            (cooldown - elapsed).as_secs() + 1
+                        )));
+                    }
+                }
+                *last_read = Some(Instant::now());
+            }
+
+            getData::read_sensors(
+                &state.db_pool,
+                &state.current_readings,
+                &state.config,
+                &state.light_controller,
+                *state.away_mode.lock().await,
+            ).await;
+
+            success(current_values(&state, query.units).await)
+        }
+
+        /// Builds the `/api/values` response from the shared readings and controller states
+        async fn current_values(state: &AppState, unit: TemperatureUnit) -> CurrentValuesResponse {
+            let overheat = get_overheat_status(&state.light_controller).await.overheating;
             let current_readings = state.current_readings.lock().await;
             let light_controller = state.light_controller.lock().await;
             let led_controller = state.led_controller.lock().await;
             
-            let (overheat, _) = get_overheat_status(&state.db_pool).await;
-            
-            let response = CurrentValuesResponse {
+            CurrentValuesResponse {
+                instance: state.config.main.name().to_string(),
                 timestamp: Utc::now().to_rfc3339(),
-                baskingTemp: current_readings.basking_temp,
-                controlTemp: current_readings.control_temp,
-                coolZoneTemp: current_readings.cool_zone_temp,
+                unit,
+                baskingTemp: unit.convert(current_readings.basking_temp),
+                controlTemp: unit.convert(current_readings.control_temp),
+                coolZoneTemp: unit.convert(current_readings.cool_temp),
+                tempTrend: light_controller.temperature_trend().map(|rate| unit.convert_delta(rate)),
                 humidity: current_readings.humidity,
-                uv1: current_readings.uv1_intensity,
-                uv2: current_readings.uv2_intensity,
+                uv1: current_readings.uv_1,
+                uv2: current_readings.uv_2,
+                probes: current_readings.probes.iter().map(|(name, &value)| (name.clone(), value.map(|value| unit.convert(value)))).collect(),
                 uv1_on: light_controller.is_uv1_on(),
                 uv2_on: light_controller.is_uv2_on(),
                 heat_on: light_controller.is_heat_on(),
                 led_on: led_controller.is_on(),
                 overheat,
-            };
-            
-            Json(response)
+            }
         }
 
-        #[derive(Serialize)]
+        #[derive(Clone, Serialize)]
         pub struct GraphDataPoint {
             pub time: String,
             pub temperature: f32,
             pub controlTemp: f32,
             pub coolZoneTemp: f32,
             pub humidity: f32,
+            /// Further DS18B20 probes by name, left out when none are configured
+            #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+            pub probes: std::collections::BTreeMap<String, f32>,
+        }
+
+        impl GraphDataPoint {
+            /// Converts the temperature fields of this point into `unit`
+            pub fn in_unit(self, unit: TemperatureUnit) -> Self {
+                Self {
+                    temperature: unit.convert(self.temperature),
+                    controlTemp: unit.convert(self.controlTemp),
+                    coolZoneTemp: unit.convert(self.coolZoneTemp),
+                    probes: self.probes.into_iter().map(|(name, value)| (name, unit.convert(value))).collect(),
+                    ..self
+                }
+            }
+
+            /// Mean of every series over `points`, labelled `time`. A probe is
+            /// averaged over the points that have a reading of it, and a
+            /// canonical series over its non-zero values, since a failed read
+            /// is stored as 0.0; it stays 0.0 if every read failed.
+            fn mean(time: String, points: &[GraphDataPoint]) -> Self {
+                let mean = |value: fn(&GraphDataPoint) -> f32| {
+                    let (sum, count) = points.iter().map(value).filter(|&value| value != 0.0)
+                        .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+                    if count == 0 { 0.0 } else { sum / count as f32 }
+                };
+                let mut probes: std::collections::BTreeMap<String, (f32, u32)> = std::collections::BTreeMap::new();
+                for (name, &value) in points.iter().flat_map(|p| &p.probes) {
+                    let (sum, count) = probes.entry(name.clone()).or_default();
+                    *sum += value;
+                    *count += 1;
+                }
+                GraphDataPoint {
+                    time,
+                    temperature: mean(|p| p.temperature),
+                    controlTemp: mean(|p| p.controlTemp),
+                    coolZoneTemp: mean(|p| p.coolZoneTemp),
+                    humidity: mean(|p| p.humidity),
+                    probes: probes.into_iter().map(|(name, (sum, count))| (name, sum / count as f32)).collect(),
+                }
+            }
+        }
+
+        #[derive(Serialize)]
+        pub struct GraphDataResponse {
+            pub unit: TemperatureUnit,
+            pub points: Vec<GraphDataPoint>,
+            pub bucket_size: usize,     // Readings averaged into each point, 1 for raw readings (about an hour's worth for hourly averages)
+        }
+
+        /// What the graph endpoints return: the bare Celsius points, as before
+        /// `units` existed, or the wrapped form once `units` is passed
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        pub enum GraphData {
+            Points(Vec<GraphDataPoint>),
+            Wrapped(GraphDataResponse),
+        }
+
+        impl GraphDataResponse {
+            /// Builds a response from Celsius points, converting them into `unit`
+            pub fn new(points: Vec<GraphDataPoint>, unit: TemperatureUnit, bucket_size: usize) -> Self {
+                Self {
+                    unit,
+                    points: points.into_iter().map(|p| p.in_unit(unit)).collect(),
+                    bucket_size,
+                }
+            }
+        }
+
+        /// Widest moving average the graph endpoints apply
+        pub const MAX_SMOOTH: u32 = 15;
+
+        #[derive(Deserialize)]
+        pub struct GraphQuery {
+            /// Temperature unit; when given, the points come wrapped with `unit` and `bucket_size`
+            pub units: Option<TemperatureUnit>,
+            /// Points in the centered moving average, odd; 0 or absent for raw data
+            pub smooth: Option<u32>,
+        }
+
+        impl GraphQuery {
+            /// The validated `smooth` window, None for raw data
+            pub fn smooth_window(&self) -> Result<Option<usize>, ApiError> {
+                match self.smooth.unwrap_or(0) {
+                    0 => Ok(None),
+                    window if window % 2 == 1 && window <= MAX_SMOOTH => Ok(Some(window as usize)),
+                    window => Err(ApiError::BadRequest(format!(
+                        "smooth must be 0 or an odd number up to {}, got: {}", MAX_SMOOTH, window
+                    ))),
+                }
+            }
+
+            /// Averages the points down to `max_points`, applies the moving
+            /// average, if any, then builds the response: the bare points
+            /// without `units`, a `GraphDataResponse` in `units` with it
+            ///
+            /// `readings_per_point` is how many readings each of `points` already
+            /// averages: 1 for raw rows, an hour's worth for hourly averages.
+            fn respond(&self, points: Vec<GraphDataPoint>, max_points: usize, readings_per_point: usize) -> ApiResult<GraphData> {
+                let smooth = self.smooth_window()?;
+                let (points, bucket_size) = downsample_points(points, max_points);
+                let points = match smooth {
+                    Some(window) => smooth_points(&points, window),
+                    None => points,
+                };
+                match self.units {
+                    Some(unit) => success(GraphData::Wrapped(GraphDataResponse::new(points, unit, bucket_size * readings_per_point))),
+                    None => success(GraphData::Points(points)),
+                }
+            }
+        }
+
+        /// Averages runs of consecutive points so at most `max_points` remain.
+        ///
+        /// Each returned point is the mean of `bucket_size` readings, the last
+        /// one possibly of fewer, labelled with the time of its first reading.
+        ///
+        /// # Returns
+        ///
+        /// The points and the bucket size, 1 if they already fit
+        pub fn downsample_points(points: Vec<GraphDataPoint>, max_points: usize) -> (Vec<GraphDataPoint>, usize) {
+            let max_points = max_points.max(1);
+            if points.len() <= max_points {
+                return (points, 1);
+            }
+
+            let bucket_size = (points.len() + max_points - 1) / max_points;
+            let buckets = points.chunks(bucket_size)
+                .map(|bucket| GraphDataPoint::mean(bucket[0].time.clone(), bucket))
+                .collect();
+            (buckets, bucket_size)
+        }
+
+        /// Centered `window`-point moving average of every series.
+        ///
+        /// Near the ends the window is cut short rather than padded, so the
+        /// first and last points average fewer neighbours. Times are kept.
+        pub fn smooth_points(points: &[GraphDataPoint], window: usize) -> Vec<GraphDataPoint> {
+            let half = window / 2;
+            (0..points.len())
+                .map(|i| {
+                    let neighbours = &points[i.saturating_sub(half)..(i + half + 1).min(points.len())];
+                    GraphDataPoint::mean(points[i].time.clone(), neighbours)
+                })
+                .collect()
         }
 
         /// Get today's graph data
         pub async fn get_graph_data_today(
             State(state): State<AppState>,
-        ) -> Json<Vec<GraphDataPoint>> {
+            Query(query): Query<GraphQuery>,
+        ) -> ApiResult<GraphData> {
             let today = chrono::Local::now().date_naive();
-            Json(get_graph_data_for_date(&state.db_pool, today).await)
+            let points = timed_db(get_graph_data_for_date(&state.db_pool, today)).await;
+            query.respond(points, state.config.get_data.max_graph_points(), 1)
         }
 
         /// Get yesterday's graph data
         pub async fn get_graph_data_yesterday(
             State(state): State<AppState>,
-        ) -> Json<Vec<GraphDataPoint>> {
+            Query(query): Query<GraphQuery>,
+        ) -> ApiResult<GraphData> {
             let yesterday = chrono::Local::now().date_naive() - chrono::Duration::days(1);
-            Json(get_graph_data_for_date(&state.db_pool, yesterday).await)
+            let points = timed_db(get_graph_data_for_date(&state.db_pool, yesterday)).await;
+            query.respond(points, state.config.get_data.max_graph_points(), 1)
+        }
+
+        /// Longest window served by `/api/graph/last/:hours`
+        const MAX_GRAPH_HOURS: u32 = 168;
+
+        /// Get graph data for the last `hours` hours, across midnight if needed
+        ///
+        /// Points are labelled with their date as well as the time, since the
+        /// window can span several days.
+        pub async fn get_graph_data_last_hours(
+            State(state): State<AppState>,
+            Path(hours): Path<u32>,
+            Query(query): Query<GraphQuery>,
+        ) -> ApiResult<GraphData> {
+            if hours == 0 || hours > MAX_GRAPH_HOURS {
+                return Err(ApiError::BadRequest(format!(
+                    "hours must be between 1 and {}, got: {}", MAX_GRAPH_HOURS, hours
+                )));
+            }
+            
+            let end = chrono::Local::now().naive_local();
+            let start = end - chrono::Duration::hours(hours as i64);
+            let (points, readings_per_point) = if end - start > state.config.get_data.rollup_after() {
+                let readings_per_hour = (3600 / state.config.get_data.interval().as_secs().max(1)).max(1) as usize;
+                (timed_db(get_hourly_graph_data_between(&state.db_pool, start, end, "%Y-%m-%d %H:%M")).await, readings_per_hour)
+            } else {
+                (timed_db(get_graph_data_between(&state.db_pool, start, end, "%Y-%m-%d %H:%M")).await, 1)
+            };
+            query.respond(points, state.config.get_data.max_graph_points(), readings_per_point)
+        }
+
+        /// Most days `/api/graph/compare` overlays in one request
+        const MAX_COMPARE_DATES: usize = 7;
+
+        #[derive(Deserialize)]
+        pub struct GraphCompareQuery {
+            /// Comma-separated dates, e.g. `2024-01-01,2024-01-02`
+            pub dates: String,
+            /// Width of the time-of-day buckets in minutes (default: 15)
+            pub bucket: Option<u32>,
+            #[serde(default)]
+            pub units: TemperatureUnit,
+            /// Moving average over each day's buckets, see `GraphQuery::smooth`
+            pub smooth: Option<u32>,
+        }
+
+        #[derive(Serialize)]
+        pub struct GraphCompareResponse {
+            pub unit: TemperatureUnit,
+            pub bucket_minutes: u32,
+            pub days: std::collections::BTreeMap<String, Vec<GraphDataPoint>>,
+        }
+
+        /// Overlay the readings of several days, aligned to time of day
+        ///
+        /// Each day's points are averaged into `bucket`-minute buckets labelled
+        /// `HH:MM`. A day without readings gets an empty series.
+        pub async fn get_graph_compare(
+            State(state): State<AppState>,
+            Query(query): Query<GraphCompareQuery>,
+        ) -> ApiResult<GraphCompareResponse> {
+            let dates = parse_compare_dates(&query.dates).map_err(ApiError::BadRequest)?;
+            let bucket_minutes = query.bucket.unwrap_or(15);
+            if bucket_minutes == 0 || bucket_minutes > 240 {
+                return Err(ApiError::BadRequest(format!("bucket must be between 1 and 240 minutes, got: {}", bucket_minutes)));
+            }
+
+            let smooth = GraphQuery { units: Some(query.units), smooth: query.smooth }.smooth_window()?;
+
+            let days = timed_db(compare_days(&state.db_pool, &dates, bucket_minutes)).await;
+            success(GraphCompareResponse {
+                unit: query.units,
+                bucket_minutes,
+                days: days.into_iter()
+                    .map(|(date, points)| {
+                        let points = match smooth {
+                            Some(window) => smooth_points(&points, window),
+                            None => points,
+                        };
+                        (date, points.into_iter().map(|p| p.in_unit(query.units)).collect())
+                    })
+                    .collect(),
+            })
+        }
+
+        /// Parses the `dates` list of `/api/graph/compare`, dropping duplicates
+        pub fn parse_compare_dates(dates: &str) -> Result<Vec<NaiveDate>, String> {
+            let mut parsed: Vec<NaiveDate> = Vec::new();
+            for date in dates.split(',').map(str::trim).filter(|date| !date.is_empty()) {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|e| format!("Invalid date {:?}: {}", date, e))?;
+                if !parsed.contains(&date) {
+                    parsed.push(date);
+                }
+            }
+            if parsed.is_empty() {
+                return Err("dates must list at least one YYYY-MM-DD date".to_string());
+            }
+            if parsed.len() > MAX_COMPARE_DATES {
+                return Err(format!("At most {} dates can be compared, got: {}", MAX_COMPARE_DATES, parsed.len()));
+            }
+            Ok(parsed)
+        }
+
+        /// Bucketed time-of-day series of every date, keyed by `YYYY-MM-DD`
+        pub async fn compare_days(
+            pool: &SqlitePool,
+            dates: &[NaiveDate],
+            bucket_minutes: u32,
+        ) -> std::collections::BTreeMap<String, Vec<GraphDataPoint>> {
+            let mut days = std::collections::BTreeMap::new();
+            for &date in dates {
+                let points = get_graph_data_for_date(pool, date).await;
+                days.insert(date.format("%Y-%m-%d").to_string(), bucket_points(points, bucket_minutes));
+            }
+            days
+        }
+
+        /// Averages `HH:MM`-labelled points into buckets of `minutes`, labelled
+        /// with the bucket start
+        fn bucket_points(points: Vec<GraphDataPoint>, minutes: u32) -> Vec<GraphDataPoint> {
+            let mut buckets: std::collections::BTreeMap<u32, Vec<GraphDataPoint>> = std::collections::BTreeMap::new();
+            for point in points {
+                let time = match NaiveTime::parse_from_str(&point.time, "%H:%M") {
+                    Ok(time) => time,
+                    Err(_) => continue,
+                };
+                let minute_of_day = time.hour() * 60 + time.minute();
+                buckets.entry(minute_of_day / minutes * minutes).or_default().push(point);
+            }
+
+            buckets.into_iter()
+                .map(|(start, bucket)| GraphDataPoint::mean(format!("{:02}:{:02}", start / 60, start % 60), &bucket))
+                .collect()
         }
 
         /// Helper function to get graph data for a specific date
         pub async fn get_graph_data_for_date(pool: &SqlitePool, date: NaiveDate) -> Vec<GraphDataPoint> {
             let start_of_day = date.and_hms_opt(0, 0, 0).unwrap();
             let end_of_day = date.and_hms_opt(23, 59, 59).unwrap();
+            get_graph_data_between(pool, start_of_day, end_of_day, "%H:%M").await
+        }
+
+        /// Helper function to get graph data between two local times, oldest first
+        ///
+        /// # Arguments
+        ///
+        /// * `pool` - Database connection pool
+        /// * `start` - First reading time included
+        /// * `end` - Last reading time included
+        /// * `time_format` - `chrono` format used for each point's `time` label
+        pub async fn get_graph_data_between(
+            pool: &SqlitePool,
+            start: NaiveDateTime,
+            end: NaiveDateTime,
+            time_format: &str,
+        ) -> Vec<GraphDataPoint> {
+            let start = start.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string();
+            let end = end.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string();
+            // Without probe readings the graph still shows the canonical ones
+            let mut probes = storage::probe_readings_between(pool, &start, &end).await.unwrap_or_default();
             
             let result = sqlx::query!(
                 r#"
-                SELECT timestamp, basking_temp, control_temp, cool_zone_temp, humidity
-                FROM readings
+                SELECT timestamp, basking_temp, control_temp, cool_temp, humidity
+                FROM sensor_history
                 WHERE timestamp BETWEEN ? AND ?
                 ORDER BY timestamp
                 "#,
-                start_of_day.to_string(),
-                end_of_day.to_string()
+                start,
+                end
             )
             .fetch_all(pool)
             .await;
@@ -645,15 +1996,18 @@ pub mod handlers {
             match result {
                 Ok(rows) => {
                     rows.into_iter().map(|row| {
-                        let dt = NaiveDateTime::parse_from_str(&row.timestamp, "%Y-%m-%d %H:%M:%S")
+                        let dt = NaiveDateTime::parse_from_str(&row.timestamp, storage::SENSOR_TIMESTAMP_FORMAT)
                             .unwrap_or_else(|_| NaiveDateTime::from_timestamp_opt(0, 0).unwrap());
                             
+                        // sensor_history columns are nullable REAL; a missing reading plots as 0
+                        let value = |reading: Option<f64>| reading.unwrap_or_default() as f32;
                         GraphDataPoint {
-                            time: dt.format("%H:%M").to_string(),
-                            temperature: row.basking_temp,
-                            controlTemp: row.control_temp,
-                            coolZoneTemp: row.cool_zone_temp,
-                            humidity: row.humidity,
+                            time: dt.format(time_format).to_string(),
+                            temperature: value(row.basking_temp),
+                            controlTemp: value(row.control_temp),
+                            coolZoneTemp: value(row.cool_temp),
+                            humidity: value(row.humidity),
+                            probes: probes.remove(&row.timestamp).unwrap_or_default(),
                         }
                     }).collect()
                 },
@@ -661,6 +2015,157 @@ pub mod handlers {
             }
         }
 
+        /// Like `get_graph_data_between`, but with one point per hour from the
+        /// `sensor_history_hourly` rollup. Hours not rolled up yet are averaged
+        /// from the raw rows, so the newest data is included.
+        pub async fn get_hourly_graph_data_between(
+            pool: &SqlitePool,
+            start: NaiveDateTime,
+            end: NaiveDateTime,
+            time_format: &str,
+        ) -> Vec<GraphDataPoint> {
+            let start = start.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string();
+            let end = end.format(storage::SENSOR_TIMESTAMP_FORMAT).to_string();
+            let mut probes = storage::hourly_probe_readings_between(pool, &start, &end).await.unwrap_or_default();
+            
+            let result: Result<Vec<(String, Option<f64>, Option<f64>, Option<f64>, Option<f64>)>, _> = sqlx::query_as(
+                r#"
+                SELECT hour, basking_temp, control_temp, cool_temp, humidity
+                FROM sensor_history_hourly
+                WHERE hour BETWEEN ? AND ?
+                UNION ALL
+                SELECT strftime('%Y-%m-%d %H:00:00', timestamp) AS hour,
+                       AVG(basking_temp), AVG(control_temp), AVG(cool_temp), AVG(humidity)
+                FROM sensor_history
+                WHERE timestamp BETWEEN ? AND ?
+                  AND strftime('%Y-%m-%d %H:00:00', timestamp) NOT IN (SELECT hour FROM sensor_history_hourly)
+                GROUP BY 1
+                ORDER BY 1
+                "#
+            )
+            .bind(&start)
+            .bind(&end)
+            .bind(&start)
+            .bind(&end)
+            .fetch_all(pool)
+            .await;
+            
+            match result {
+                Ok(rows) => {
+                    let value = |reading: Option<f64>| reading.unwrap_or_default() as f32;
+                    rows.into_iter().filter_map(|(hour, basking, control, cool, humidity)| {
+                        let dt = NaiveDateTime::parse_from_str(&hour, storage::SENSOR_TIMESTAMP_FORMAT).ok()?;
+                        Some(GraphDataPoint {
+                            time: dt.format(time_format).to_string(),
+                            temperature: value(basking),
+                            controlTemp: value(control),
+                            coolZoneTemp: value(cool),
+                            humidity: value(humidity),
+                            probes: probes.remove(&hour).unwrap_or_default(),
+                        })
+                    }).collect()
+                },
+                Err(_) => Vec::new(),
+            }
+        }
+
+        #[derive(Deserialize)]
+        pub struct OverheatHistoryQueryParams {
+            pub limit: Option<i64>,
+        }
+
+        /// Get recorded overheat events, newest first
+        pub async fn get_overheat_history(
+            State(state): State<AppState>,
+            Query(params): Query<OverheatHistoryQueryParams>,
+        ) -> ApiResult<Vec<OverheatEvent>> {
+            let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+            
+            timed_db(OverheatEvent::get_history(&state.db_pool, limit))
+                .await
+                .map_err(map_db_error)
+                .map(Json)
+        }
+
+        /// Get the overheat protection state with the remaining cooldown, for a live countdown
+        pub async fn get_overheat_state(
+            State(state): State<AppState>,
+        ) -> ApiResult<OverheatStatus> {
+            success(get_overheat_status(&state.light_controller).await)
+        }
+
+        /// Re-enable the heat lamp after the maximum on-time watchdog cut it
+        pub async fn resume_heat(
+            State(state): State<AppState>,
+        ) -> ApiResult<OverheatStatus> {
+            let mut controller = state.light_controller.lock().await;
+            if !controller.resume_heat() {
+                return Err(ApiError::BadRequest("Heat watchdog has not tripped".to_string()));
+            }
+            storage::retry_busy(|| storage::save_heat_watchdog_tripped(&state.db_pool, false))
+                .await
+                .map_err(map_db_error)?;
+            success(controller.overheat_status())
+        }
+
+        #[derive(Deserialize)]
+        pub struct DutyQueryParams {
+            pub date: Option<NaiveDate>,
+        }
+
+        #[derive(Serialize)]
+        pub struct DutyResponse {
+            pub date: NaiveDate,
+            pub relays: Vec<DutyCycle>,
+        }
+
+        /// Get how long the heat and UV relays were on during a day (local time, default today)
+        ///
+        /// Computed from the relay event log; today is measured up to now.
+        pub async fn get_duty_cycle(
+            State(state): State<AppState>,
+            Query(params): Query<DutyQueryParams>,
+        ) -> ApiResult<DutyResponse> {
+            let date = params.date.unwrap_or_else(|| chrono::Local::now().date_naive());
+            
+            let relays = timed_db(storage::relay_duty_for_day(&state.db_pool, date, Utc::now()))
+                .await
+                .map_err(map_db_error)?;
+            success(DutyResponse { date, relays })
+        }
+
+        #[derive(Deserialize)]
+        pub struct SensorDataImportParams {
+            #[serde(default)]
+            pub on_duplicate: logs::DuplicateMode,
+        }
+
+        /// Import historical readings from a CSV in the export format
+        ///
+        /// The whole file is checked first; any malformed row rejects the
+        /// import with the offending line numbers. Rows are written in one
+        /// transaction and matched to stored rows on their timestamp.
+        pub async fn import_sensor_data(
+            State(state): State<AppState>,
+            Query(params): Query<SensorDataImportParams>,
+            body: String,
+        ) -> ApiResult<logs::ImportSummary> {
+            let rows = logs::parse_sensor_data_csv(&body).map_err(ApiError::BadRequest)?;
+            
+            let summary = logs::import_sensor_data(&state.db_pool, &rows, params.on_duplicate)
+                .await
+                .map_err(map_db_error)?;
+            
+            if let Err(e) = logs::log(&state.db_pool, "INFO", &format!(
+                "Imported sensor history: {} inserted, {} updated, {} skipped",
+                summary.inserted, summary.updated, summary.skipped
+            )).await {
+                eprintln!("Failed to log import: {:?}", e);
+            }
+            
+            success(summary)
+        }
+
         #[derive(Deserialize)]
         pub struct SensorDataQueryParams {
             pub start: String,
@@ -672,7 +2177,8 @@ pub mod handlers {
             State(state): State<AppState>,
             Query(params): Query<SensorDataQueryParams>,
         ) -> Result<impl IntoResponse, (StatusCode, String)> {
-            // ... existing implementation ...
+            logs::parse_export_range(&params.start, &params.end)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
         
             // Placeholder for the actual implementation
             Ok(Response::builder()
@@ -685,6 +2191,31 @@ pub mod handlers {
                 .body(Body::from(String::new()))
                 .unwrap())
         }
+
+        /// Export sensor data as JSON Lines, one object per reading
+        ///
+        /// Takes the same `start`/`end` range as the CSV download. The body is
+        /// streamed as rows are read, so large ranges aren't buffered; the
+        /// handler waits for the first row, which is the query time
+        /// `Server-Timing` reports.
+        pub async fn export_sensor_data_jsonl(
+            State(state): State<AppState>,
+            Query(params): Query<SensorDataQueryParams>,
+        ) -> Result<Response, ApiError> {
+            let (start, end) = logs::parse_export_range(&params.start, &params.end)
+                .map_err(ApiError::BadRequest)?;
+            
+            let mut lines = logs::stream_sensor_data_jsonl((*state.db_pool).clone(), start, end);
+            let first = timed_db(lines.next()).await;
+            let lines = futures::stream::iter(first).chain(lines);
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"sensor_data_{}_{}.jsonl\"", start, end)),
+                ],
+                StreamBody::new(lines),
+            ).into_response())
+        }
     }
 
     // System handlers module
@@ -693,6 +2224,7 @@ pub mod handlers {
         
         #[derive(Serialize)]
         pub struct SystemStatusResponse {
+            pub instance: String,
             pub version: String,
             pub uptime_seconds: u64,
             pub overheat_detected: bool,
@@ -700,58 +2232,497 @@ pub mod handlers {
             pub cooldown_remaining: Option<u64>,
             pub data_collection_interval: u64,
             pub free_disk_space_mb: u64,
+            pub degraded: Option<String>,   // Why the database is unavailable while running on the config.toml defaults
+            pub task_restarts: std::collections::BTreeMap<String, u32>,   // Restarts after a panic per background loop, see watchdog::supervise
+            pub unavailable: Vec<&'static str>,   // Controllers whose GPIO couldn't be initialized: "relays", "lights"
         }
 
         /// Get system status
         pub async fn get_system_status(
             State(state): State<AppState>,
         ) -> Json<SystemStatusResponse> {
-            // ... existing implementation ...
-            
-            // Placeholder for the actual implementation
-            Json(SystemStatusResponse {
-                version: env!("CARGO_PKG_VERSION").to_string(),
-                uptime_seconds: 0,
-                overheat_detected: false,
-                last_overheat: None,
-                cooldown_remaining: None,
-                data_collection_interval: 60,
-                free_disk_space_mb: 0,
-            })
+            let overheat = get_overheat_status(&state.light_controller).await;
+            let mut status = SystemStatusResponse::new(&state.config, &overheat);
+            if !state.with_relay_controller(|relays| relays.is_available()).await {
+                status.unavailable.push("relays");
+            }
+            if !state.with_light_controller(|lights| lights.is_available()).await {
+                status.unavailable.push("lights");
+            }
+            Json(status)
         }
 
-        #[derive(Deserialize)]
-        pub struct LogQueryParams {
-            pub filter: Option<String>,
-            pub limit: Option<i64>,
+        impl SystemStatusResponse {
+            pub fn new(config: &Config, overheat: &OverheatStatus) -> Self {
+                // Placeholder for the actual implementation of uptime and disk space
+                Self {
+                    instance: config.main.name().to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    uptime_seconds: 0,
+                    overheat_detected: overheat.overheating,
+                    last_overheat: overheat.last_overheat.map(|at| at.to_rfc3339()),
+                    cooldown_remaining: overheat.cooldown_remaining_seconds,
+                    data_collection_interval: 60,
+                    free_disk_space_mb: 0,
+                    degraded: storage::degraded().map(str::to_string),
+                    task_restarts: watchdog::restart_counts(),
+                    unavailable: Vec::new(),
+                }
+            }
         }
 
-        /// Get system logs
-        pub async fn get_logs(
-            State(state): State<AppState>,
-            Query(params): Query<LogQueryParams>,
-        ) -> Result<Json<Vec<logs::LogEntry>>, (StatusCode, String)> {
-            // ... existing implementation ...
-            
-            // Placeholder for the actual implementation
+        #[derive(Debug, Serialize)]
+        pub struct RelayPins {
+            pub uv1: u8,
+            pub uv2: u8,
+            pub heat: u8,
+            pub led: u8,
+            pub fan: Option<u8>,
+            pub aux: Option<u8>,
+            pub active_low: bool,
+        }
+
+        #[derive(Debug, Serialize)]
+        pub struct UvDimming {
+            pub uv1_pwm_channel: Option<u8>,
+            pub uv2_pwm_channel: Option<u8>,
+            pub frequency_hz: f64,
+        }
+
+        #[derive(Debug, Serialize)]
+        pub struct I2cSensor {
+            pub bus: u8,
+            pub address: u8,
+        }
+
+        #[derive(Debug, Serialize)]
+        pub struct SensorWiring {
+            pub ds18b20_bus: Option<u8>,
+            pub ds18b20: HashMap<String, String>,  // Role -> 1-Wire device id
+            pub dht22_pin: u8,
+            pub veml6075_uv1: I2cSensor,
+            pub veml6075_uv2: I2cSensor,
+        }
+
+        #[derive(Debug, Serialize)]
+        pub struct LedStripWiring {
+            pub ic_count: usize,
+            pub spi_bus: u8,
+            pub spi_slave_select: u8,
+            pub spi_clock_hz: u32,
+            pub spi_mode: u8,
+            pub data_pin: Option<u8>,   // GPIO pin bit-banging the strip instead of SPI, see `[led] driver`
+        }
+
+        /// The hardware wiring the running config describes
+        #[derive(Debug, Serialize)]
+        pub struct HardwareResponse {
+            pub relays: RelayPins,
+            pub uv_dimming: UvDimming,
+            pub sensors: SensorWiring,
+            pub led_strip: LedStripWiring,
+        }
+
+        impl HardwareResponse {
+            /// Resolves every pin, bus and address from `config`, defaults applied
+            pub fn from_config(config: &Config) -> Self {
+                let gpio = &config.gpio;
+                let i2c = |(bus, address): (u8, u8)| I2cSensor { bus, address };
+                HardwareResponse {
+                    relays: RelayPins {
+                        uv1: gpio.uv_relay1,
+                        uv2: gpio.uv_relay2,
+                        heat: gpio.heat_relay,
+                        led: gpio.led_relay,
+                        fan: gpio.fan_relay,
+                        aux: gpio.aux_relay,
+                        active_low: gpio.active_low,
+                    },
+                    uv_dimming: UvDimming {
+                        uv1_pwm_channel: gpio.uv1_pwm_channel,
+                        uv2_pwm_channel: gpio.uv2_pwm_channel,
+                        frequency_hz: gpio.uv_pwm_frequency(),
+                    },
+                    sensors: SensorWiring {
+                        ds18b20_bus: gpio.ds18b20_bus,
+                        ds18b20: config.sensors.ds18b20.clone(),
+                        dht22_pin: gpio.dht22_pin(),
+                        veml6075_uv1: i2c(gpio.uv1_sensor()),
+                        veml6075_uv2: i2c(gpio.uv2_sensor()),
+                    },
+                    led_strip: LedStripWiring {
+                        ic_count: gpio.ic_count(),
+                        spi_bus: LED_SPI_BUS as u8,
+                        spi_slave_select: LED_SPI_SLAVE_SELECT as u8,
+                        spi_clock_hz: LED_SPI_CLOCK_HZ,
+                        spi_mode: LED_SPI_MODE as u8,
+                        data_pin: match config.led.wiring() {
+                            LedWiring::BitBang { pin } => Some(pin),
+                            LedWiring::Spi => None,
+                        },
+                    },
+                }
+            }
+        }
+
+        /// Get the relay pins, sensor buses and addresses and LED strip link
+        /// the running config uses, for spotting miswiring
+        pub async fn get_hardware(
+            State(state): State<AppState>,
+        ) -> ApiResult<HardwareResponse> {
+            success(HardwareResponse::from_config(&state.config))
+        }
+
+        #[derive(Deserialize)]
+        pub struct LogQueryParams {
+            pub filter: Option<String>,
+            pub limit: Option<i64>,
+        }
+
+        /// Get system logs
+        pub async fn get_logs(
+            State(state): State<AppState>,
+            Query(params): Query<LogQueryParams>,
+        ) -> Result<Json<Vec<logs::LogEntry>>, (StatusCode, String)> {
+            // ... existing implementation ...
+            
+            // Placeholder for the actual implementation
             Ok(Json(Vec::new()))
         }
 
-        /// Download logs as file
+        /// Get the OpenAPI description of this API
+        pub async fn get_openapi_spec() -> Json<serde_json::Value> {
+            Json(openapi::spec())
+        }
+
+        #[derive(Deserialize)]
+        pub struct RelayPulseRequest {
+            pub relay: RelayType,
+            pub seconds: u64,
+        }
+
+        /// Switch the aux or fan relay on for a few seconds, e.g. to run a feeder or pump.
+        ///
+        /// The pulse runs on its own task and the response returns once it has
+        /// started; a client disconnecting does not cut the pulse short.
+        pub async fn pulse_relay(
+            State(state): State<AppState>,
+            Json(payload): Json<RelayPulseRequest>,
+        ) -> ApiResult<&'static str> {
+            // Heat and UV are owned by the scheduler and overheat protection
+            if !matches!(payload.relay, RelayType::Aux | RelayType::Fan) {
+                return Err(ApiError::BadRequest(format!(
+                    "Only the aux and fan relays can be pulsed, got: {:?}", payload.relay
+                )));
+            }
+            
+            let max_pulse = state.config.gpio.max_pulse();
+            if payload.seconds == 0 || payload.seconds > max_pulse.as_secs() {
+                return Err(ApiError::BadRequest(format!(
+                    "seconds must be between 1 and {}, got: {}", max_pulse.as_secs(), payload.seconds
+                )));
+            }
+            
+            if !state.with_relay_controller(|relays| relays.has_relay(payload.relay)).await {
+                return Err(ApiError::BadRequest(format!(
+                    "No GPIO pin is configured for the {:?} relay", payload.relay
+                )));
+            }
+            
+            let relay_controller = Arc::clone(&state.relay_controller);
+            let duration = Duration::from_secs(payload.seconds);
+            tokio::spawn(async move {
+                RelayController::pulse(&relay_controller, payload.relay, duration).await;
+            });
+            
+            success("Relay pulse started")
+        }
+
+        #[derive(Deserialize)]
+        pub struct RelayHistoryQueryParams {
+            pub limit: Option<i64>,
+        }
+
+        /// Get recorded relay state changes and why they happened, newest first
+        pub async fn get_relay_history(
+            State(state): State<AppState>,
+            Query(params): Query<RelayHistoryQueryParams>,
+        ) -> ApiResult<Vec<RelayEvent>> {
+            let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+            
+            RelayEvent::get_history(&state.db_pool, limit)
+                .await
+                .map_err(map_db_error)
+                .map(Json)
+        }
+
+        /// Get the alert conditions present now, oldest first
+        pub async fn get_alerts() -> ApiResult<Vec<AlertStatus>> {
+            success(alerts::tracker().lock().unwrap_or_else(|e| e.into_inner()).active())
+        }
+
+        /// Silence an active alert until its condition clears; it notifies again if it comes back
+        pub async fn acknowledge_alert(
+            State(state): State<AppState>,
+            Path(id): Path<String>,
+        ) -> ApiResult<AlertStatus> {
+            let status = alerts::tracker().lock().unwrap_or_else(|e| e.into_inner()).acknowledge(&id)
+                .ok_or_else(|| ApiError::NotFound(format!("No active alert {:?}", id)))?;
+            
+            if let Err(e) = logs::log(&state.db_pool, "INFO", &format!("Alert acknowledged: {}", id)).await {
+                eprintln!("Failed to log alert acknowledgement: {:?}", e);
+            }
+            success(status)
+        }
+
+        /// Most entries `GET /api/activity` returns per page
+        pub const MAX_ACTIVITY_LIMIT: i64 = 200;
+
+        #[derive(Deserialize)]
+        pub struct ActivityQueryParams {
+            pub limit: Option<i64>,
+            pub before: Option<DateTime<Utc>>,  // Only entries older than this, for the next page
+            pub before_id: Option<i64>,         // With `before`, also entries at that time with a lower cursor id
+        }
+
+        #[derive(Serialize)]
+        pub struct ActivityPage {
+            pub entries: Vec<ActivityEntry>,
+            pub next_before: Option<DateTime<Utc>>,  // Pass as `before` for the next page; None on the last one
+            pub next_before_id: Option<i64>,         // Pass as `before_id` along with `next_before`
+        }
+
+        /// Get log lines, relay changes and overheat events merged into one feed, newest first
+        pub async fn get_activity(
+            State(state): State<AppState>,
+            Query(params): Query<ActivityQueryParams>,
+        ) -> ApiResult<ActivityPage> {
+            let limit = params.limit.unwrap_or(50).clamp(1, MAX_ACTIVITY_LIMIT);
+            
+            // Without `before_id` only entries strictly older than `before`, as cursor ids are never negative
+            let before = params.before.map(|timestamp| ActivityCursor { timestamp, id: params.before_id.unwrap_or(0) });
+            let entries = storage::recent_activity(&state.db_pool, before, limit)
+                .await
+                .map_err(map_db_error)?;
+            let next = match entries.last() {
+                Some((cursor, _)) if entries.len() as i64 == limit => Some(*cursor),
+                _ => None,
+            };
+            success(ActivityPage {
+                entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+                next_before: next.map(|cursor| cursor.timestamp),
+                next_before_id: next.map(|cursor| cursor.id),
+            })
+        }
+
+        #[derive(Deserialize)]
+        pub struct CreateApiKeyRequest {
+            pub name: String,
+            pub scope: ApiScope,
+        }
+
+        #[derive(Serialize)]
+        pub struct CreatedApiKey {
+            #[serde(flatten)]
+            pub api_key: ApiKey,
+            pub key: String,    // Only returned here, the database keeps a hash
+        }
+
+        /// List API keys, revoked ones included
+        pub async fn list_api_keys(
+            State(state): State<AppState>,
+        ) -> ApiResult<Vec<ApiKey>> {
+            ApiKey::list(&state.db_pool)
+                .await
+                .map_err(map_db_error)
+                .map(Json)
+        }
+
+        /// Create an API key; the key is only shown in this response
+        pub async fn create_api_key(
+            State(state): State<AppState>,
+            Json(payload): Json<CreateApiKeyRequest>,
+        ) -> ApiResult<CreatedApiKey> {
+            let name = payload.name.trim();
+            if name.is_empty() || name.len() > 64 {
+                return Err(ApiError::BadRequest("name must be 1-64 characters".to_string()));
+            }
+            
+            let (api_key, key) = storage::retry_busy(|| ApiKey::create(&state.db_pool, name, payload.scope))
+                .await
+                .map_err(map_db_error)?;
+            
+            if let Err(e) = logs::log(&state.db_pool, "INFO", &format!(
+                "Created API key {} ({:?}) with {} scope", api_key.id, api_key.name, api_key.scope.as_str()
+            )).await {
+                eprintln!("Failed to log API key creation: {:?}", e);
+            }
+            
+            success(CreatedApiKey { api_key, key })
+        }
+
+        /// Revoke an API key
+        pub async fn revoke_api_key(
+            State(state): State<AppState>,
+            Path(id): Path<i64>,
+        ) -> ApiResult<&'static str> {
+            let revoked = storage::retry_busy(|| ApiKey::revoke(&state.db_pool, id))
+                .await
+                .map_err(map_db_error)?;
+            if !revoked {
+                return Err(ApiError::NotFound(format!("No active API key with id {}", id)));
+            }
+            
+            success("API key revoked")
+        }
+
+        #[derive(Deserialize, Serialize)]
+        pub struct AwayModeRequest {
+            pub away: bool,
+        }
+
+        /// Get whether away mode is on
+        pub async fn get_away_mode(
+            State(state): State<AppState>,
+        ) -> ApiResult<AwayModeRequest> {
+            success(AwayModeRequest { away: *state.away_mode.lock().await })
+        }
+
+        /// Switch away mode on or off and persist it across restarts.
+        ///
+        /// While away mode is on:
+        /// - The LED loop ignores the manual color override and the manual
+        ///   LED disable, so the strip follows the schedule and natural light
+        /// - Temperature warnings fire `away.alert_margin` °C below `overheat_temp`
+        /// - A camera snapshot is saved to `snapshots/` every `away.snapshot_interval`
+        ///
+        /// Overheat protection, the UV/heat schedule and relay pulses are unchanged.
+        pub async fn set_away_mode(
+            State(state): State<AppState>,
+            Json(payload): Json<AwayModeRequest>,
+        ) -> ApiResult<AwayModeRequest> {
+            state.store_setting(storage::AWAY_MODE_KEY, &payload.away.to_string()).await?;
+            
+            let message = if payload.away { "Away mode switched on" } else { "Away mode switched off" };
+            if let Err(e) = logs::log(&state.db_pool, "INFO", message).await {
+                eprintln!("Failed to log away mode change: {:?}", e);
+            }
+            
+            success(payload)
+        }
+
+        #[derive(Serialize)]
+        pub struct ResumeResponse {
+            pub resumed: bool,
+        }
+
+        /// Start the control loops after a `boot_behavior = "safe_off"` start.
+        ///
+        /// The loads follow the schedule again from the loops' next tick.
+        pub async fn resume_control(
+            State(state): State<AppState>,
+        ) -> ApiResult<ResumeResponse> {
+            let mut hold = state.boot_hold.lock().await;
+            if !*hold {
+                return Err(ApiError::BadRequest("Control loops are not held".to_string()));
+            }
+            *hold = false;
+            
+            if let Err(e) = logs::log(&state.db_pool, "INFO", "Control loops resumed after safe_off boot").await {
+                eprintln!("Failed to log resume: {:?}", e);
+            }
+            
+            success(ResumeResponse { resumed: true })
+        }
+
+        #[derive(Serialize)]
+        pub struct SettingResponse {
+            pub key: String,
+            pub value: String,
+        }
+
+        #[derive(Deserialize)]
+        pub struct SettingRequest {
+            pub value: String,
+        }
+
+        /// Get every known setting
+        pub async fn get_settings(
+            State(state): State<AppState>,
+        ) -> ApiResult<HashMap<String, String>> {
+            success(state.settings.lock().await.clone())
+        }
+
+        /// Get a single setting; unknown keys are a 404
+        pub async fn get_setting_value(
+            State(state): State<AppState>,
+            Path(key): Path<String>,
+        ) -> ApiResult<SettingResponse> {
+            let value = state.settings.lock().await.get(&key).cloned()
+                .ok_or_else(|| ApiError::NotFound(format!("Unknown setting: {}", key)))?;
+            success(SettingResponse { key, value })
+        }
+
+        /// Set a single setting; unknown keys are a 404, invalid values a 400
+        pub async fn put_setting_value(
+            State(state): State<AppState>,
+            Path(key): Path<String>,
+            Json(payload): Json<SettingRequest>,
+        ) -> ApiResult<SettingResponse> {
+            if !storage::KNOWN_SETTINGS.iter().any(|(known, _)| *known == key) {
+                return Err(ApiError::NotFound(format!("Unknown setting: {}", key)));
+            }
+            state.store_setting(&key, &payload.value).await?;
+            success(SettingResponse { key, value: payload.value })
+        }
+
+        #[derive(Serialize)]
+        pub struct ResetResponse {
+            pub rows_reset: u64,
+        }
+
+        /// Put the schedule and the LED settings back to the config.toml defaults.
+        ///
+        /// All 52 weeks are rewritten and the manual LED color and override are
+        /// cleared in one transaction; sensor history and logs are kept.
+        pub async fn factory_reset(
+            State(state): State<AppState>,
+        ) -> ApiResult<ResetResponse> {
+            let rows_reset = storage::retry_busy(|| storage::reset_to_defaults(&state.db_pool, &state.config))
+                .await
+                .map_err(map_db_error)?;
+            
+            if let Err(e) = logs::log(&state.db_pool, "INFO", "Schedule and LED settings reset to defaults").await {
+                eprintln!("Failed to log factory reset: {:?}", e);
+            }
+            
+            success(ResetResponse { rows_reset })
+        }
+
+        /// Download logs as a zip archive
         pub async fn download_logs(
             State(state): State<AppState>,
         ) -> Result<impl IntoResponse, (StatusCode, String)> {
-            // ... existing implementation ...
+            let zip_path = logs::create_logs_zip(&state.db_pool)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create log archive: {}", e)))?;
+            
+            let data = tokio::fs::read(&zip_path)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read log archive: {}", e)));
+            
+            // The archive is single-use, remove it regardless of the read outcome
+            let _ = tokio::fs::remove_file(&zip_path).await;
+            let data = data?;
             
-            // Placeholder for the actual implementation
             Ok(Response::builder()
                 .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, "text/plain")
+                .header(header::CONTENT_TYPE, "application/zip")
                 .header(
                     header::CONTENT_DISPOSITION,
-                    "attachment; filename=\"system_logs.txt\""
+                    "attachment; filename=\"terrarium_logs.zip\""
                 )
-                .body(Body::from(String::new()))
+                .body(Body::from(data))
                 .unwrap())
         }
     }
@@ -784,13 +2755,12 @@ pub mod handlers {
                 camera.is_initialized()
             }).await;
             
-            // Build the stream URL only if camera is available and initialized
-            let stream_url = if camera_available && camera_initialized {
-                Some(format!("http://{}:{}/stream", 
-                    state.config().web.address, 
-                    state.config().web.camera_port.unwrap_or(3030)))
-            } else {
-                None
+            // Build the stream URL only if camera is available, initialized and streamed
+            let stream_url = match state.config().web.camera_port {
+                Some(port) if camera_available && camera_initialized => Some(format!("http://{}:{}/stream",
+                    state.config().web.address,
+                    port)),
+                _ => None,
             };
             
             success(CameraStatusResponse {
@@ -819,19 +2789,34 @@ pub mod handlers {
             }
             
             // Get the configured camera stream URL from config
-            let stream_url = format!("http://{}:{}/stream", 
-                state.config().web.address, 
-                state.config().web.camera_port.unwrap_or(3030));
+            let port = state.config().web.camera_port
+                .ok_or_else(|| ApiError::NotFound("Camera stream server is disabled (no web.camera_port)".to_string()))?;
+            let stream_url = format!("http://{}:{}/stream",
+                state.config().web.address,
+                port);
                 
             success(CameraStreamResponse {
                 stream_url,
             })
         }
         
+        /// Optional re-encoding of `GET /api/camera/snapshot`
+        #[derive(Deserialize)]
+        pub struct SnapshotQuery {
+            /// JPEG quality 1-100 (default: the captured quality)
+            pub quality: Option<u8>,
+            /// Scale down to at most this many pixels wide
+            pub max_width: Option<u32>,
+        }
+
         /// Get a snapshot from the camera
         pub async fn get_camera_snapshot(
             State(state): State<AppState>,
+            Query(query): Query<SnapshotQuery>,
         ) -> Result<impl IntoResponse, ApiError> {
+            let quality = query.quality.unwrap_or(cam::SNAPSHOT_QUALITY);
+            cam::check_variant(quality, query.max_width).map_err(ApiError::BadRequest)?;
+            
             // Check if camera is available
             if !CameraService::is_camera_available() {
                 return Err(ApiError::NotFound("Camera is not available".to_string()));
@@ -846,18 +2831,591 @@ pub mod handlers {
                 return Err(ApiError::InternalError("Camera is not initialized".to_string()));
             }
             
-            // Use the helper method to take a snapshot
-            let jpeg_data = state.with_camera(|camera| {
-                camera.take_snapshot()
-            }).await
-                .map_err(|e| ApiError::InternalError(format!("Failed to take camera snapshot: {}", e)))?;
+            // Concurrent requests share one capture, see CameraService::take_snapshot
+            let jpeg_data = match (query.quality, query.max_width) {
+                (None, None) => state.camera_service.take_snapshot().await,
+                _ => state.camera_service.take_snapshot_variant(quality, query.max_width).await,
+            }
+            .map_err(|e| ApiError::InternalError(format!("Failed to take camera snapshot: {}", e)))?;
             
             // Return the image data with correct MIME type
             Ok(Response::builder()
                 .status(StatusCode::OK)
                 .header(header::CONTENT_TYPE, "image/jpeg")
-                .body(Body::from(jpeg_data))
+                .body(Body::from(jpeg_data.as_ref().clone()))
                 .map_err(|e| ApiError::InternalError(format!("Failed to create response: {}", e)))?)
         }
+
+        #[derive(Deserialize)]
+        pub struct BurstQuery {
+            pub count: Option<u32>,       // Frames to capture (default: 5)
+            pub interval_ms: Option<u64>, // Spacing between frames (default: 200)
+        }
+
+        /// Capture a short burst of snapshots and return them as a zip of JPEGs
+        pub async fn get_camera_burst(
+            State(state): State<AppState>,
+            Query(query): Query<BurstQuery>,
+        ) -> Result<impl IntoResponse, ApiError> {
+            let count = query.count.unwrap_or(5);
+            if count == 0 || count > cam::MAX_BURST_FRAMES {
+                return Err(ApiError::BadRequest(format!("count must be between 1 and {}", cam::MAX_BURST_FRAMES)));
+            }
+            let interval = Duration::from_millis(query.interval_ms.unwrap_or(200));
+            if interval < cam::MIN_BURST_INTERVAL || interval > cam::MAX_BURST_INTERVAL {
+                return Err(ApiError::BadRequest(format!("interval_ms must be between {} and {}",
+                    cam::MIN_BURST_INTERVAL.as_millis(), cam::MAX_BURST_INTERVAL.as_millis())));
+            }
+            
+            if !CameraService::is_camera_available() {
+                return Err(ApiError::NotFound("Camera is not available".to_string()));
+            }
+            if !state.camera_service.is_initialized().await {
+                return Err(ApiError::InternalError("Camera is not initialized".to_string()));
+            }
+            
+            let frames = state.camera_service.take_burst(count, interval)
+                .await
+                .map_err(|e| ApiError::InternalError(format!("Failed to capture burst: {}", e)))?;
+            let archive = cam::burst_zip(&frames)
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/zip")
+                .header(header::CONTENT_DISPOSITION, "attachment; filename=\"camera_burst.zip\"")
+                .body(Body::from(archive))
+                .map_err(|e| ApiError::InternalError(format!("Failed to create response: {}", e)))?)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::HttpBody;
+    use tower::ServiceExt;
+
+    /// The full router over `db_pool`, with controllers that have no GPIO
+    async fn test_router(db_pool: &SqlitePool, config: Config) -> Router {
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let leds = Arc::new(Mutex::new(LEDController::new(Arc::clone(&relays))));
+        test_router_with_leds(db_pool, config, relays, leds).await
+    }
+
+    /// Like `test_router`, with `leds` as the LED controller
+    async fn test_router_with_leds(
+        db_pool: &SqlitePool,
+        config: Config,
+        relays: Arc<Mutex<RelayController>>,
+        leds: Arc<Mutex<LEDController>>,
+    ) -> Router {
+        create_router(
+            db_pool,
+            Arc::new(Mutex::new(LightController::unavailable(config.light_control.clone()))),
+            relays,
+            leds,
+            Arc::new(Mutex::new(CurrentReadings::new())),
+            Arc::new(config),
+            Arc::new(CameraService::new()),
+            Arc::new(Mutex::new(false)),
+            Arc::new(Mutex::new(false)),
+            storage::load_settings(db_pool).await.unwrap(),
+        ).await
+    }
+
+    /// Collects a response body
+    async fn body_bytes(response: Response) -> Vec<u8> {
+        let mut body = response.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        bytes
+    }
+
+    async fn send_get(app: &Router, uri: &str) -> Response {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_status_lists_controllers_without_gpio() {
+        let db_pool = storage::temp_db("unavailable").await;
+        let app = test_router(&db_pool, crate::modules::config::test_config()).await;
+
+        let response = send_get(&app, "/api/system/status").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["unavailable"], serde_json::json!(["relays", "lights"]));
+    }
+
+    #[tokio::test]
+    async fn test_led_off_stops_animation_and_alert() {
+        use crate::modules::ledStrip::RecordingStrip;
+
+        let db_pool = storage::temp_db("led_off_route").await;
+        let relays = Arc::new(Mutex::new(RelayController::unavailable()));
+        let strip = RecordingStrip::new();
+        let leds = Arc::new(Mutex::new(LEDController::with_driver(Arc::clone(&relays), strip.clone())));
+        let app = test_router_with_leds(&db_pool, crate::modules::config::test_config(), relays, Arc::clone(&leds)).await;
+        let color = RGBWW { r: 255, g: 120, b: 0, ww: 0, cw: 0 };
+        let led_off = || async {
+            let request = Request::builder().method(Method::POST).uri("/api/led/off").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            serde_json::from_slice::<serde_json::Value>(&body_bytes(response).await).unwrap()
+        };
+
+        LEDController::start_animation(&leds, AnimationKind::Candle, AnimationKind::Candle.build(color, Duration::from_secs(4)))
+            .await
+            .unwrap();
+        assert!(leds.lock().await.animation().is_some());
+        let status = led_off().await;
+        assert_eq!((status["power"].clone(), status["animation"].clone()), (serde_json::json!(false), serde_json::Value::Null));
+
+        LEDController::check_alerts(&leds, &[LedAlert::Overheat], color).await.unwrap();
+        assert!(leds.lock().await.alert().is_some());
+        let status = led_off().await;
+        assert_eq!(status["power"], false);
+        assert!(status["alert"].is_null() && status["animation"].is_null(), "{}", status);
+
+        let leds = leds.lock().await;
+        assert!(!leds.is_powered_on() && leds.animation().is_none() && leds.alert().is_none());
+        assert_eq!(strip.shown().last(), Some(&RGBWW::off()));
+        assert!(storage::led_forced_off(&db_pool).await.unwrap(), "led/off must hold over the schedule");
+    }
+
+    #[tokio::test]
+    async fn test_responses_carry_a_request_id() {
+        let db_pool = SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap();
+        let log_state = RequestLogState { db_pool: Arc::new(db_pool), mode: RequestLogMode::Off };
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .layer(middleware::from_fn_with_state(log_state, log_requests));
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let request = Request::builder().uri("/ping").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let id = response.headers().get(REQUEST_ID_HEADER).expect("x-request-id header missing");
+            ids.push(id.to_str().unwrap().to_string());
+        }
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_graph_today_has_parseable_server_timing() {
+        let db_pool = storage::temp_db("server_timing").await;
+        let app = test_router(&db_pool, crate::modules::config::test_config()).await;
+
+        let response = send_get(&app, "/api/graph/today").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = response.headers().get(SERVER_TIMING_HEADER).expect("server-timing header missing");
+
+        let metrics: HashMap<&str, f64> = header.to_str().unwrap().split(", ")
+            .map(|metric| {
+                let (name, duration) = metric.split_once(";dur=").unwrap();
+                (name, duration.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(metrics.len(), 2);
+        assert!(metrics["db"] >= 0.0 && metrics["total"] >= metrics["db"], "{:?}", metrics);
+
+        // A bare array unless `units` asks for the wrapped form
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json, serde_json::json!([]));
+        let response = send_get(&app, "/api/graph/today?units=f").await;
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!((json["unit"].clone(), json["points"].clone()), (serde_json::json!("f"), serde_json::json!([])));
+
+        // The exports report the time to their first row
+        let mut readings = CurrentReadings::new();
+        readings.basking_temp = 30.0;
+        getData::store_readings(&db_pool, &readings).await.unwrap();
+        readings.timestamp = readings.timestamp + chrono::Duration::seconds(1);
+        getData::store_readings(&db_pool, &readings).await.unwrap();
+        let today = readings.timestamp.with_timezone(&chrono::Local).date_naive();
+        let response = send_get(&app, &format!("/api/data/export.jsonl?start={}&end={}", today, today)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = response.headers().get(SERVER_TIMING_HEADER).expect("server-timing header missing on export");
+        assert!(header.to_str().unwrap().starts_with("db;dur="), "{:?}", header);
+        // The row waited for is still sent
+        let body = body_bytes(response).await;
+        assert_eq!(body.iter().filter(|&&b| b == b'\n').count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected_with_413() {
+        let db_pool = storage::temp_db("body_limit").await;
+        let mut config = crate::modules::config::test_config();
+        config.web.max_body_kb = Some(1);
+        config.web.max_import_body_kb = Some(8);
+        let app = test_router(&db_pool, config).await;
+        let status = |uri: &'static str, body: String| {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+        // An empty schedule list padded to `size` bytes
+        let schedule = |size: usize| format!("[{}]", " ".repeat(size - 2));
+        // A CSV import of `rows` readings, about 34 bytes each
+        let csv = |rows: usize| {
+            let readings: String = (0..rows)
+                .map(|i| format!("2024-03-01 {:02}:{:02}:00,31.5,62.0,2.1\n", i / 60, i % 60))
+                .collect();
+            format!("Timestamp,Temperature,Humidity,UV Index\n{}", readings)
+        };
+
+        assert_eq!(status("/api/schedule", schedule(1024)).await, StatusCode::OK);
+        assert_eq!(status("/api/schedule", schedule(1025)).await, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // The import has its own, larger limit
+        assert!(csv(120).len() > 1024 && csv(120).len() < 8 * 1024);
+        assert_eq!(status("/api/data/import", csv(120)).await, StatusCode::OK);
+        assert!(csv(300).len() > 8 * 1024);
+        assert_eq!(status("/api/data/import", csv(300)).await, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_read_key_rejected_on_control_route() {
+        let db_pool = storage::temp_db("keys").await;
+        let (_, read_key) = ApiKey::create(&db_pool, "wall display", ApiScope::Read).await.unwrap();
+        let (control, control_key) = ApiKey::create(&db_pool, "phone", ApiScope::Control).await.unwrap();
+
+        let auth = AuthState {
+            db_pool: Arc::new(db_pool.clone()),
+            enabled: true,
+            admin_key_hash: Some(Arc::from(storage::hash_api_key("admin-key-0123456789"))),
+            read_scope: ApiScope::Read,
+            write_scope: ApiScope::Control,
+            query_key: false,
+        };
+        let app = with_scope(
+            Router::new().route("/api/led/power", get(|| async { "off" }).post(|| async { "on" })),
+            &auth, ApiScope::Read, ApiScope::Control,
+        );
+        let status = |method: Method, key: Option<&str>| {
+            let mut request = Request::builder().method(method).uri("/api/led/power");
+            if let Some(key) = key {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+            }
+            let app = app.clone();
+            async move { app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status() }
+        };
+
+        assert_eq!(status(Method::POST, Some(&read_key)).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Method::GET, Some(&read_key)).await, StatusCode::OK);
+        assert_eq!(status(Method::POST, Some(&control_key)).await, StatusCode::OK);
+        assert_eq!(status(Method::GET, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Method::POST, Some("admin-key-0123456789")).await, StatusCode::OK);
+        assert_eq!(status(Method::POST, Some("admin-key-012345678")).await, StatusCode::UNAUTHORIZED);
+
+        ApiKey::revoke(&db_pool, control.id).await.unwrap();
+        assert_eq!(status(Method::POST, Some(&control_key)).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_camera_stream_requires_key() {
+        let db_pool = storage::temp_db("stream_keys").await;
+        let (_, read_key) = ApiKey::create(&db_pool, "wall display", ApiScope::Read).await.unwrap();
+        let mut config = crate::modules::config::test_config();
+        config.web.require_api_key = true;
+        let app = with_stream_auth(Router::new().route("/stream", get(|| async { "frames" })), &Arc::new(db_pool.clone()), &config);
+        let status = |uri: String, header: Option<&str>| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(key) = header {
+                request = request.header(API_KEY_HEADER, key);
+            }
+            let app = app.clone();
+            async move { app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status() }
+        };
+
+        assert_eq!(status("/stream".to_string(), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/stream?key=wrong".to_string(), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(format!("/stream?key={}", read_key), None).await, StatusCode::OK);
+        assert_eq!(status("/stream".to_string(), Some(&read_key)).await, StatusCode::OK);
+
+        // The main API doesn't take keys from the query string
+        let auth = AuthState::new(&Arc::new(db_pool.clone()), &config);
+        let api = with_scope(Router::new().route("/api/led/status", get(|| async { "off" })), &auth, ApiScope::Read, ApiScope::Control);
+        let request = Request::builder().uri(format!("/api/led/status?key={}", read_key)).body(Body::empty()).unwrap();
+        assert_eq!(api.oneshot(request).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_request_log_mode_filters_by_status() {
+        assert!(RequestLogMode::All.logs(200));
+        assert!(!RequestLogMode::Errors.logs(204));
+        assert!(RequestLogMode::Errors.logs(404));
+        assert!(!RequestLogMode::Off.logs(500));
+    }
+
+    #[tokio::test]
+    async fn test_imported_rows_appear_in_graph() {
+        use handlers::monitoring::get_graph_data_for_date;
+
+        let db_pool = storage::temp_db("import_graph").await;
+        let app = test_router(&db_pool, crate::modules::config::test_config()).await;
+        let csv = "Timestamp,Temperature,Humidity,UV Index\n\
+                   2024-03-01 10:00:00,31.5,62.0,2.1\n\
+                   2024-03-01 10:05:00,32.0,,2.3\n";
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/data/import")
+            .body(Body::from(csv))
+            .unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let points = get_graph_data_for_date(&db_pool, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()).await;
+        let plotted: Vec<(&str, f32, f32)> = points.iter().map(|p| (p.time.as_str(), p.temperature, p.humidity)).collect();
+        assert_eq!(plotted, [("10:00", 31.5, 62.0), ("10:05", 32.0, 0.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_compare_two_seeded_days() {
+        let db_pool = SqlitePoolOptions::new().connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE sensor_history (timestamp TEXT, basking_temp REAL, control_temp REAL, cool_temp REAL, humidity REAL)")
+            .execute(&db_pool).await.unwrap();
+        for (timestamp, basking) in [
+            ("2024-01-01 10:00:00", 30.0),
+            ("2024-01-01 10:10:00", 32.0),
+            ("2024-01-01 10:20:00", 34.0),
+            ("2024-01-02 10:05:00", 28.0),
+        ] {
+            sqlx::query("INSERT INTO sensor_history VALUES (?, ?, 25.0, 22.0, 60.0)")
+                .bind(timestamp).bind(basking)
+                .execute(&db_pool).await.unwrap();
+        }
+
+        let dates = handlers::monitoring::parse_compare_dates("2024-01-01, 2024-01-02,2024-01-03,2024-01-01").unwrap();
+        let days = handlers::monitoring::compare_days(&db_pool, &dates, 15).await;
+
+        assert_eq!(days.keys().collect::<Vec<_>>(), ["2024-01-01", "2024-01-02", "2024-01-03"]);
+        let first: Vec<(&str, f32)> = days["2024-01-01"].iter().map(|p| (p.time.as_str(), p.temperature)).collect();
+        assert_eq!(first, [("10:00", 31.0), ("10:15", 34.0)]);
+        assert_eq!(days["2024-01-02"][0].time, "10:00");
+        assert!(days["2024-01-03"].is_empty());
+
+        let eight = "2024-01-01,2024-01-02,2024-01-03,2024-01-04,2024-01-05,2024-01-06,2024-01-07,2024-01-08";
+        assert!(handlers::monitoring::parse_compare_dates(eight).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_graph_over_cap_is_bucketed() {
+        use handlers::monitoring::{downsample_points, get_graph_data_for_date};
+
+        let db_pool = storage::temp_db("graph_cap").await;
+        // A reading every minute from 10:00 to 11:39
+        for minute in 0..100 {
+            sqlx::query("INSERT INTO sensor_history (timestamp, basking_temp, control_temp, cool_temp, humidity) VALUES (?, ?, 25.0, 22.0, 60.0)")
+                .bind(format!("2024-01-01 {:02}:{:02}:00", 10 + minute / 60, minute % 60))
+                .bind(30.0 + minute as f64)
+                .execute(&db_pool).await.unwrap();
+        }
+
+        let raw = get_graph_data_for_date(&db_pool, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()).await;
+        assert_eq!(raw.len(), 100);
+
+        let (points, bucket_size) = downsample_points(raw, 30);
+        assert_eq!((points.len(), bucket_size), (25, 4));
+        assert_eq!((points[0].time.as_str(), points[0].temperature), ("10:00", 31.5));
+        assert_eq!((points[24].time.as_str(), points[24].temperature), ("11:36", 127.5));
+
+        // Under the cap the readings are returned as they are
+        let few = get_graph_data_for_date(&db_pool, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()).await;
+        assert_eq!(downsample_points(few, 720).1, 1);
+
+        // Each hourly average stands for an hour of readings
+        let mut config = crate::modules::config::test_config();
+        config.get_data.interval = Some(60);
+        config.get_data.rollup_after_hours = Some(1);
+        let app = test_router(&db_pool, config).await;
+        for (hours, bucket_size) in [(1, 1), (3, 60)] {
+            let response = send_get(&app, &format!("/api/graph/last/{}?units=c", hours)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert_eq!(json["bucket_size"], bucket_size, "last {} hours", hours);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fourth_probe_is_stored_and_graphed() {
+        use handlers::monitoring::{GraphDataResponse, downsample_points, get_graph_data_for_date};
+
+        let pool = storage::temp_db("probes").await;
+
+        let mut config = crate::modules::config::test_config();
+        for (name, id) in [("hide", "28-000000000004"), ("basking", "28-000000000001"), ("control", "28-000000000002"), ("cool", "28-000000000003")] {
+            config.sensors.ds18b20.insert(name.to_string(), id.to_string());
+        }
+        config.sensors.validate().unwrap();
+        assert_eq!(config.sensors.probe_names(), ["basking", "control", "cool", "hide"]);
+        assert_eq!(getData::staggered_sensors(&config.sensors), ["basking", "control", "cool", "hide", "humidity", "uv1", "uv2"]);
+
+        let mut readings = CurrentReadings::new();
+        readings.basking_temp = 32.0;
+        readings.probes.insert("hide".to_string(), Some(24.5));
+        getData::store_readings(&pool, &readings).await.unwrap();
+        readings.probes.insert("hide".to_string(), Some(25.5));
+        readings.timestamp = readings.timestamp + chrono::Duration::seconds(1);
+        getData::store_readings(&pool, &readings).await.unwrap();
+        // A failed read is stored as NULL and left out of the graph, not plotted as 0
+        readings.probes.insert("hide".to_string(), None);
+        readings.timestamp = readings.timestamp + chrono::Duration::seconds(1);
+        getData::store_readings(&pool, &readings).await.unwrap();
+        assert_eq!(readings.probes.get("hide"), Some(&None));
+        let (nulls,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM probe_history WHERE temperature IS NULL")
+            .fetch_one(&pool.pool).await.unwrap();
+        assert_eq!(nulls, 1);
+
+        let today = readings.timestamp.with_timezone(&chrono::Local).date_naive();
+        let points = get_graph_data_for_date(&pool, today).await;
+        assert_eq!(points.iter().map(|p| p.probes.get("hide").copied()).collect::<Vec<_>>(), [Some(24.5), Some(25.5), None]);
+        let (points, _) = downsample_points(points, 1);
+        let json = serde_json::to_value(GraphDataResponse::new(points, TemperatureUnit::default(), 2)).unwrap();
+        assert_eq!(json["points"][0]["probes"], serde_json::json!({ "hide": 25.0 }));
+        assert_eq!(json["points"][0]["temperature"], serde_json::json!(32.0));
+
+        // The other sensors' names can't be reused for a probe
+        config.sensors.ds18b20.insert("uv1".to_string(), "28-000000000005".to_string());
+        assert!(config.sensors.validate().is_err());
+    }
+
+    #[test]
+    fn test_smoothed_graph_is_moving_average_of_raw() {
+        use handlers::monitoring::{GraphDataPoint, GraphQuery, smooth_points};
+
+        let series = [30.0, 33.0, 36.0, 33.0, 30.0, 30.0];
+        let raw: Vec<GraphDataPoint> = series.iter().enumerate()
+            .map(|(i, &temperature)| GraphDataPoint {
+                time: format!("10:{:02}", i * 5),
+                temperature,
+                controlTemp: 25.0,
+                coolZoneTemp: 22.0,
+                humidity: 60.0 + i as f32,
+                probes: Default::default(),
+            })
+            .collect();
+
+        let smoothed = smooth_points(&raw, 3);
+        let temperatures: Vec<f32> = smoothed.iter().map(|p| p.temperature).collect();
+        assert_eq!(temperatures, [31.5, 33.0, 34.0, 33.0, 31.0, 30.0]);
+        assert_eq!(smoothed.iter().map(|p| p.time.as_str()).collect::<Vec<_>>(), raw.iter().map(|p| p.time.as_str()).collect::<Vec<_>>());
+        assert_eq!((smoothed[2].controlTemp, smoothed[2].humidity), (25.0, 62.0));
+
+        // A 1-point window is the raw series
+        let unchanged: Vec<f32> = smooth_points(&raw, 1).iter().map(|p| p.temperature).collect();
+        assert_eq!(unchanged, series);
+
+        // A failed read's 0.0 is left out of its neighbours' averages, and out of a bucket's
+        let mut failed = raw.clone();
+        failed[2].temperature = 0.0;
+        failed[2].humidity = 0.0;
+        let temperatures: Vec<f32> = smooth_points(&failed, 3).iter().map(|p| p.temperature).collect();
+        assert_eq!(temperatures, [31.5, 31.5, 33.0, 31.5, 31.0, 30.0]);
+        assert_eq!(smooth_points(&failed, 3)[2].humidity, 62.0);
+        let (buckets, _) = handlers::monitoring::downsample_points(failed, 2);
+        assert_eq!(buckets[0].temperature, 31.5);
+        assert_eq!(buckets[1].temperature, 31.0);
+
+        let query = |smooth| GraphQuery { units: None, smooth };
+        assert_eq!(query(None).smooth_window().unwrap(), None);
+        assert_eq!(query(Some(0)).smooth_window().unwrap(), None);
+        assert_eq!(query(Some(5)).smooth_window().unwrap(), Some(5));
+        assert!(query(Some(4)).smooth_window().is_err());
+        assert!(query(Some(17)).smooth_window().is_err());
+    }
+
+    #[test]
+    fn test_verbose_values_expand_compact_readings() {
+        use handlers::monitoring::{CurrentValuesResponse, ValuesResponse, VerboseValuesResponse};
+
+        let values = || CurrentValuesResponse {
+            instance: "terrarium".to_string(),
+            timestamp: "2024-03-01T10:00:00+00:00".to_string(),
+            unit: TemperatureUnit::Fahrenheit,
+            baskingTemp: 95.0,
+            controlTemp: 80.6,
+            coolZoneTemp: 75.2,
+            tempTrend: None,
+            humidity: 55.0,
+            uv1: 3.5,
+            uv2: 2.0,
+            probes: [("hide".to_string(), Some(78.8)), ("shelf".to_string(), None)].into(),
+            uv1_on: true,
+            uv2_on: true,
+            heat_on: false,
+            led_on: true,
+            overheat: false,
+        };
+        let config = crate::modules::config::test_config();
+
+        let compact = serde_json::to_value(ValuesResponse::Compact(values())).unwrap();
+        let verbose = serde_json::to_value(ValuesResponse::Verbose(
+            VerboseValuesResponse::new(values(), &config.sensors, |_| true)
+        )).unwrap();
+
+        assert_eq!(compact["baskingTemp"], serde_json::json!(95.0));
+        assert_eq!(verbose["baskingTemp"], serde_json::json!({ "value": 95.0, "unit": "°F", "min": 14.0, "max": 140.0, "stale": true }));
+        assert_eq!(verbose["humidity"]["unit"], "%");
+        assert_eq!(verbose["uv1"]["max"], serde_json::json!(15.0));
+        assert_eq!(verbose["probes"]["hide"]["unit"], "°F");
+        assert!(compact["probes"]["shelf"].is_null() && verbose["probes"]["shelf"].is_null());
+        // Everything but the readings is identical
+        for key in ["instance", "timestamp", "unit", "tempTrend", "uv1_on", "heat_on", "overheat"] {
+            assert_eq!(compact[key], verbose[key], "{}", key);
+        }
+
+        // Staleness is per sensor: only the one that stopped answering is stale
+        let start = Utc::now();
+        let minutes = |n: i64| start + chrono::Duration::minutes(n);
+        let mut health = getData::SensorHealth::default();
+        for sensor in ["basking", "control", "cool", "hide", "humidity", "uv1", "uv2"] {
+            health.record(sensor, true, start, 3);
+            health.record(sensor, sensor != "humidity", minutes(2), 3);
+        }
+        let stale_after = Duration::from_secs(3 * 60);
+        let verbose = serde_json::to_value(VerboseValuesResponse::new(values(), &config.sensors, |sensor| {
+            handlers::monitoring::sensor_stale(health.statuses(), sensor, minutes(4), stale_after)
+        })).unwrap();
+        assert_eq!(verbose["humidity"]["stale"], true);
+        for key in ["baskingTemp", "controlTemp", "coolZoneTemp", "uv1", "uv2"] {
+            assert_eq!(verbose[key]["stale"], false, "{}", key);
+        }
+        assert_eq!(verbose["probes"]["hide"]["stale"], false);
+    }
+
+    #[test]
+    fn test_status_reports_instance_name() {
+        let mut config = crate::modules::config::test_config();
+        config.main.name = Some("gecko-2".to_string());
+
+        let status = handlers::system::SystemStatusResponse::new(&config, &OverheatStatus::default());
+        let json = serde_json::to_value(status).unwrap();
+
+        assert_eq!(json["instance"], "gecko-2");
+        assert!(json["task_restarts"].is_object());
+    }
+
+    #[test]
+    fn test_hardware_map_matches_config() {
+        let config = crate::modules::config::test_config();
+        let hardware = handlers::system::HardwareResponse::from_config(&config);
+
+        assert_eq!(hardware.relays.uv1, config.gpio.uv_relay1);
+        assert_eq!(hardware.relays.uv2, config.gpio.uv_relay2);
+        assert_eq!(hardware.relays.heat, config.gpio.heat_relay);
+        assert_eq!(hardware.relays.led, config.gpio.led_relay);
+        assert_eq!(hardware.relays.fan, config.gpio.fan_relay);
+        assert_eq!(hardware.led_strip.ic_count, config.gpio.ic_count());
+        assert_eq!(hardware.sensors.dht22_pin, config.gpio.dht22_pin());
     }
 }